@@ -0,0 +1,292 @@
+//! Audit log of the requests handled by the server
+//!
+//! With `--audit-log <PATH>`, every request appends one JSON line with its method,
+//! the tool and arguments of tool calls, how long it took and how it ended, for
+//! debugging agent behaviour and for compliance records. Unlike the ledger, it also
+//! records failed and cancelled requests.
+//!
+//! Arguments are redacted before they are written: likely credentials are masked
+//! like with `--secret-filter mask`, further [`Redactor`]s can be plugged in, and
+//! long strings are truncated.
+
+use crate::ledger;
+use crate::secrets;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Strings in logged arguments and messages are cut to this many characters
+const MAX_STRING_CHARS: usize = 256;
+
+/// Rewrites tool arguments before they are logged
+pub trait Redactor: Send + Sync {
+    fn redact(&self, tool: &str, arguments: &mut Value);
+}
+
+/// Masks likely credentials, like `--secret-filter mask`
+struct MaskSecrets;
+
+impl Redactor for MaskSecrets {
+    fn redact(&self, _tool: &str, arguments: &mut Value) {
+        secrets::filter_args(arguments, true);
+    }
+}
+
+/// How a request ended
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "outcome", rename_all = "lowercase")]
+pub enum Outcome {
+    Ok,
+    /// Answered with a JSON-RPC error
+    Error {
+        code: i32,
+        message: String,
+    },
+    /// A tool ran and returned an `isError` result
+    Failed {
+        message: String,
+    },
+    /// Cancelled by the client, so never answered
+    Cancelled,
+}
+
+impl Outcome {
+    /// The outcome of a request answered with `error` or `result`
+    pub fn of(error: Option<(i32, &str)>, result: Option<&Value>) -> Self {
+        match (error, result) {
+            (Some((code, message)), _) => Self::Error {
+                code,
+                message: message.to_string(),
+            },
+            (None, Some(result)) if result["isError"] == true => Self::Failed {
+                message: result
+                    .pointer("/content/0/text")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+            },
+            (None, _) => Self::Ok,
+        }
+    }
+}
+
+/// A line of the audit log
+#[derive(Debug, Serialize)]
+struct Entry {
+    /// Unix timestamp in seconds of when the request was received
+    timestamp: u64,
+    method: String,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    arguments: Option<Value>,
+    duration_ms: u64,
+    #[serde(flatten)]
+    outcome: Outcome,
+}
+
+/// A request being handled, to be recorded once it ends
+pub struct Pending {
+    entry: Entry,
+    started: Instant,
+}
+
+/// Append-only audit log
+pub struct AuditLog {
+    sink: Mutex<Box<dyn Write + Send>>,
+    redactors: Vec<Arc<dyn Redactor>>,
+}
+
+impl AuditLog {
+    /// Open the log at `path` for appending, creating it if needed; `-` logs to stderr
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if path == Path::new("-") {
+            return Ok(Self::new(Box::new(io::stderr())));
+        }
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(Box::new(file)))
+    }
+
+    /// A log written to `sink`, masking likely credentials
+    pub fn new(sink: Box<dyn Write + Send>) -> Self {
+        Self {
+            sink: Mutex::new(sink),
+            redactors: vec![Arc::new(MaskSecrets)],
+        }
+    }
+
+    /// Also redact arguments with `redactor`, after the redactors added before
+    // The binary only masks credentials, embedders add their own
+    #[allow(dead_code)]
+    pub fn with_redactor(mut self, redactor: Arc<dyn Redactor>) -> Self {
+        self.redactors.push(redactor);
+        self
+    }
+
+    /// Start recording a request with `params`
+    pub fn start(&self, method: &str, id: &Value, params: Option<&Value>) -> Pending {
+        let tool = (method == "tools/call")
+            .then(|| params?.get("name")?.as_str())
+            .flatten();
+        let arguments = tool.map(|tool| {
+            let mut arguments = params
+                .and_then(|params| params.get("arguments"))
+                .cloned()
+                .unwrap_or(Value::Null);
+            for redactor in &self.redactors {
+                redactor.redact(tool, &mut arguments);
+            }
+            truncate_strings(&mut arguments);
+            arguments
+        });
+        Pending {
+            entry: Entry {
+                timestamp: ledger::now(),
+                method: method.to_string(),
+                id: id.clone(),
+                tool: tool.map(str::to_string),
+                arguments,
+                duration_ms: 0,
+                outcome: Outcome::Ok,
+            },
+            started: Instant::now(),
+        }
+    }
+
+    /// Record how a request ended; failures are reported on stderr rather than
+    /// failing the request
+    pub fn finish(&self, pending: Pending, mut outcome: Outcome) {
+        if let Outcome::Error { message, .. } | Outcome::Failed { message } = &mut outcome {
+            truncate(message);
+        }
+        let entry = Entry {
+            duration_ms: u64::try_from(pending.started.elapsed().as_millis()).unwrap_or(u64::MAX),
+            outcome,
+            ..pending.entry
+        };
+        let result = serde_json::to_string(&entry)
+            .map_err(io::Error::other)
+            .and_then(|line| {
+                let mut sink = self.sink.lock().unwrap_or_else(|e| e.into_inner());
+                writeln!(sink, "{line}").and_then(|()| sink.flush())
+            });
+        if let Err(e) = result {
+            tracing::warn!("Failed to write to the audit log: {e}");
+        }
+    }
+}
+
+fn truncate_strings(value: &mut Value) {
+    match value {
+        Value::String(text) => truncate(text),
+        Value::Array(values) => values.iter_mut().for_each(truncate_strings),
+        Value::Object(map) => map.values_mut().for_each(truncate_strings),
+        _ => {}
+    }
+}
+
+fn truncate(text: &mut String) {
+    if let Some((end, _)) = text.char_indices().nth(MAX_STRING_CHARS) {
+        text.truncate(end);
+        text.push('…');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestClient;
+    use crate::{strict, KagiMcpServer, ServerOptions};
+    use kagiapi::testing::MockKagi;
+    use serde_json::json;
+
+    /// Hides the queries of searches
+    struct HideQueries;
+
+    impl Redactor for HideQueries {
+        fn redact(&self, tool: &str, arguments: &mut Value) {
+            if tool == "kagi_search_fetch" {
+                arguments["queries"] = json!("[hidden]");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_audit_log() {
+        let path =
+            std::env::temp_dir().join(format!("kagi-mcp-audit-{}/audit.jsonl", std::process::id()));
+        let audit = AuditLog::open(&path)
+            .unwrap()
+            .with_redactor(Arc::new(HideQueries));
+        let mock = MockKagi::start().await;
+        let server = Arc::new(KagiMcpServer::new(
+            mock.client(),
+            ServerOptions {
+                strict: strict::StrictMode::Panic,
+                audit: Some(audit),
+                ..ServerOptions::default()
+            },
+        ));
+        let mut client = TestClient::start(server);
+        client.initialize().await.unwrap();
+        let long_question = format!("my key is sk-{} {}", "a".repeat(40), "why ".repeat(100));
+        client
+            .call_tool("kagi_fastgpt", json!({"query": long_question}))
+            .await
+            .unwrap();
+        client
+            .call_tool("kagi_search_fetch", json!({"queries": ["rust"]}))
+            .await
+            .unwrap();
+        client
+            .call_tool("kagi_fastgpt", json!({}))
+            .await
+            .unwrap_err();
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        let entries: Vec<Value> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0]["method"], "initialize");
+        assert_eq!(entries[0]["outcome"], "ok");
+        assert!(entries[0].get("tool").is_none());
+
+        assert_eq!(entries[1]["tool"], "kagi_fastgpt");
+        let query = entries[1]["arguments"]["query"].as_str().unwrap();
+        assert!(!query.contains("sk-aaaa"), "{query}");
+        assert!(query.ends_with('…'));
+        assert!(entries[1]["duration_ms"].is_u64());
+
+        assert_eq!(entries[2]["arguments"], json!({"queries": "[hidden]"}));
+        assert_eq!(entries[3]["outcome"], "error");
+        assert_eq!(entries[3]["code"], -32602);
+    }
+
+    #[test]
+    fn test_outcome() {
+        let failed =
+            json!({"content": [{"type": "text", "text": "FastGPT failed"}], "isError": true});
+        assert_eq!(
+            Outcome::of(None, Some(&failed)),
+            Outcome::Failed {
+                message: "FastGPT failed".to_string()
+            }
+        );
+        assert_eq!(Outcome::of(None, Some(&json!({}))), Outcome::Ok);
+        assert_eq!(
+            serde_json::to_value(Outcome::of(Some((-32001, "timed out")), None)).unwrap(),
+            json!({"outcome": "error", "code": -32001, "message": "timed out"})
+        );
+    }
+}
@@ -0,0 +1,539 @@
+//! Authentication of HTTP clients
+//!
+//! By default the HTTP transports trust every client that can reach them, which is
+//! fine on the loopback address they listen on. When exposed further, clients can
+//! be required to send `Authorization: Bearer <token>` with every request,
+//! following the MCP authorization specification:
+//!
+//! - Static tokens from `--auth-token` name the client they were handed to.
+//! - OAuth access tokens are validated with the authorization server's token
+//!   introspection endpoint (RFC 7662), and must have been issued for this server,
+//!   named by `--oauth-resource`. The protected resource metadata (RFC 9728) served
+//!   at `/.well-known/oauth-protected-resource` tells clients where to get them.
+//! - Requests without a valid token are answered with `401 Unauthorized` and a
+//!   `WWW-Authenticate` challenge pointing at that metadata.
+//!
+//! Other validators plug in through [`TokenValidator`]. The [`Principal`] a token
+//! belongs to owns the sessions it opens, and is available to tool handlers
+//! through their [`ToolContext`](crate::context::ToolContext).
+
+use async_trait::async_trait;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// Path of the protected resource metadata, before the resource's own path
+const METADATA_PATH: &str = "/.well-known/oauth-protected-resource";
+
+/// Who an HTTP client authenticated as
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    /// The name of a static token, or the subject of an OAuth access token
+    pub subject: String,
+    /// Scopes granted to an OAuth access token
+    pub scopes: Vec<String>,
+}
+
+/// Checks bearer tokens sent by HTTP clients
+#[async_trait]
+pub trait TokenValidator: Send + Sync {
+    /// The principal `token` belongs to, `None` if it is not valid, or an error if
+    /// the token could not be checked
+    async fn validate(&self, token: &str) -> Result<Option<Principal>, String>;
+}
+
+/// Command-line options of HTTP client authentication
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct AuthArgs {
+    /// Bearer token HTTP clients must send, optionally as `name:token` to name the
+    /// client. Repeatable, or comma-separated in the environment
+    #[arg(
+        long = "auth-token",
+        env = "KAGI_AUTH_TOKENS",
+        value_delimiter = ',',
+        hide_env_values = true
+    )]
+    auth_tokens: Vec<String>,
+
+    /// Canonical URL of this server's MCP endpoint, e.g. `https://mcp.example.com/mcp`;
+    /// OAuth access tokens must have been issued for it
+    #[arg(long, env = "KAGI_OAUTH_RESOURCE")]
+    oauth_resource: Option<String>,
+
+    /// Authorization server clients get access tokens from, advertised in the
+    /// protected resource metadata. Repeatable
+    #[arg(long, env = "KAGI_OAUTH_AUTHORIZATION_SERVER", value_delimiter = ',')]
+    oauth_authorization_server: Vec<String>,
+
+    /// Token introspection endpoint (RFC 7662) OAuth access tokens are validated with
+    #[arg(long, env = "KAGI_OAUTH_INTROSPECTION_URL")]
+    oauth_introspection_url: Option<String>,
+
+    /// Client id the server authenticates to the introspection endpoint with
+    #[arg(long, env = "KAGI_OAUTH_CLIENT_ID")]
+    oauth_client_id: Option<String>,
+
+    /// Client secret the server authenticates to the introspection endpoint with
+    #[arg(long, env = "KAGI_OAUTH_CLIENT_SECRET", hide_env_values = true)]
+    oauth_client_secret: Option<String>,
+}
+
+impl AuthArgs {
+    /// The configured authentication, `None` if HTTP clients need none
+    pub fn auth(&self, http: &reqwest::Client) -> Result<Option<Auth>, String> {
+        let mut validators: Vec<Arc<dyn TokenValidator>> = Vec::new();
+        if !self.auth_tokens.is_empty() {
+            validators.push(Arc::new(StaticTokens::parse(&self.auth_tokens)?));
+        }
+        let resource = match &self.oauth_resource {
+            Some(resource) => Some(ProtectedResource::new(
+                resource,
+                self.oauth_authorization_server.clone(),
+            )?),
+            None if self.oauth_introspection_url.is_some() => {
+                return Err("--oauth-introspection-url requires --oauth-resource".to_string())
+            }
+            None => None,
+        };
+        if let (Some(endpoint), Some(resource)) = (&self.oauth_introspection_url, &resource) {
+            validators.push(Arc::new(Introspection {
+                http: http.clone(),
+                endpoint: endpoint.clone(),
+                client_id: self.oauth_client_id.clone(),
+                client_secret: self.oauth_client_secret.clone(),
+                resource: resource.resource.clone(),
+            }));
+        }
+        if validators.is_empty() {
+            return match resource {
+                Some(_) => Err("--oauth-resource requires --oauth-introspection-url".to_string()),
+                None => Ok(None),
+            };
+        }
+        Ok(Some(Auth {
+            validators,
+            resource,
+        }))
+    }
+}
+
+/// Where clients learn how to get access tokens for this server (RFC 9728)
+#[derive(Debug, Clone)]
+struct ProtectedResource {
+    resource: String,
+    authorization_servers: Vec<String>,
+    /// Absolute URL the metadata is served at
+    metadata_url: String,
+}
+
+impl ProtectedResource {
+    fn new(resource: &str, authorization_servers: Vec<String>) -> Result<Self, String> {
+        let url = reqwest::Url::parse(resource)
+            .map_err(|e| format!("invalid --oauth-resource {resource}: {e}"))?;
+        let path = url.path().trim_end_matches('/');
+        Ok(Self {
+            resource: resource.to_string(),
+            authorization_servers,
+            metadata_url: format!(
+                "{}{METADATA_PATH}{path}",
+                url.origin().ascii_serialization()
+            ),
+        })
+    }
+
+    /// Path the metadata is served at
+    fn metadata_path(&self) -> &str {
+        let start = self.metadata_url.find(METADATA_PATH).unwrap_or_default();
+        &self.metadata_url[start..]
+    }
+
+    fn metadata(&self) -> Value {
+        json!({
+            "resource": self.resource,
+            "authorization_servers": self.authorization_servers,
+            "bearer_methods_supported": ["header"],
+        })
+    }
+}
+
+/// Bearer token checks in front of the HTTP transports
+pub struct Auth {
+    /// Tried in order until one accepts the token
+    validators: Vec<Arc<dyn TokenValidator>>,
+    resource: Option<ProtectedResource>,
+}
+
+impl Auth {
+    // Embedders validate tokens their own way; the server builds `Auth` from `AuthArgs`
+    #[allow(dead_code)]
+    pub fn new(validators: Vec<Arc<dyn TokenValidator>>) -> Self {
+        Self {
+            validators,
+            resource: None,
+        }
+    }
+
+    /// Require a valid bearer token for every route of `router`
+    ///
+    /// The principal of the token is added to the request's extensions. The
+    /// protected resource metadata, if any, stays public.
+    pub fn protect(self, router: Router) -> Router {
+        let metadata = self.resource.as_ref().map(|resource| {
+            let metadata = resource.metadata();
+            (resource.metadata_path().to_string(), metadata)
+        });
+        let router = router.layer(middleware::from_fn_with_state(Arc::new(self), authenticate));
+        match metadata {
+            Some((path, metadata)) => {
+                router.route(&path, get(move || async move { Json(metadata) }))
+            }
+            None => router,
+        }
+    }
+
+    async fn validate(&self, token: &str) -> Result<Option<Principal>, String> {
+        let mut error = None;
+        for validator in &self.validators {
+            match validator.validate(token).await {
+                Ok(Some(principal)) => return Ok(Some(principal)),
+                Ok(None) => {}
+                Err(e) => error = Some(e),
+            }
+        }
+        error.map_or(Ok(None), Err)
+    }
+
+    /// `401 Unauthorized` with a challenge for a bearer token
+    fn challenge(&self, invalid_token: bool) -> Response {
+        let mut challenge = match &self.resource {
+            Some(resource) => format!("Bearer resource_metadata=\"{}\"", resource.metadata_url),
+            None => "Bearer realm=\"kagi-mcp-server\"".to_string(),
+        };
+        if invalid_token {
+            challenge.push_str(", error=\"invalid_token\"");
+        }
+        let message = if invalid_token {
+            "Invalid bearer token"
+        } else {
+            "Missing bearer token"
+        };
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, challenge)],
+            message,
+        )
+            .into_response()
+    }
+}
+
+async fn authenticate(State(auth): State<Arc<Auth>>, mut request: Request, next: Next) -> Response {
+    let Some(token) = bearer_token(request.headers()) else {
+        return auth.challenge(false);
+    };
+    match auth.validate(token).await {
+        Ok(Some(principal)) => {
+            request.extensions_mut().insert(principal);
+            next.run(request).await
+        }
+        Ok(None) => auth.challenge(true),
+        Err(e) => {
+            tracing::error!("Failed to validate a bearer token: {e}");
+            (StatusCode::SERVICE_UNAVAILABLE, "Cannot validate tokens").into_response()
+        }
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty()).then_some(token)
+}
+
+/// Tokens handed out by the operator, by client name
+struct StaticTokens(Vec<(String, String)>);
+
+impl StaticTokens {
+    fn parse(specs: &[String]) -> Result<Self, String> {
+        specs
+            .iter()
+            .map(|spec| {
+                let (name, token) = spec.split_once(':').unwrap_or(("client", spec));
+                if token.is_empty() {
+                    return Err(format!("empty --auth-token for {name}"));
+                }
+                Ok((name.to_string(), token.to_string()))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+#[async_trait]
+impl TokenValidator for StaticTokens {
+    async fn validate(&self, token: &str) -> Result<Option<Principal>, String> {
+        // Every token is compared, in constant time, so timing reveals none of them
+        let mut subject = None;
+        for (name, expected) in &self.0 {
+            if constant_time_eq(token.as_bytes(), expected.as_bytes()) {
+                subject = Some(name);
+            }
+        }
+        Ok(subject.map(|subject| Principal {
+            subject: subject.clone(),
+            scopes: Vec::new(),
+        }))
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// OAuth access tokens checked with the authorization server (RFC 7662)
+struct Introspection {
+    http: reqwest::Client,
+    endpoint: String,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    /// Audience the tokens must have been issued for
+    resource: String,
+}
+
+#[derive(Deserialize)]
+struct IntrospectionResponse {
+    active: bool,
+    #[serde(default)]
+    sub: Option<String>,
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    client_id: Option<String>,
+    /// Space-separated scopes
+    #[serde(default)]
+    scope: Option<String>,
+    #[serde(default)]
+    aud: Option<Audience>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, resource: &str) -> bool {
+        match self {
+            Self::One(audience) => audience == resource,
+            Self::Many(audiences) => audiences.iter().any(|audience| audience == resource),
+        }
+    }
+}
+
+#[async_trait]
+impl TokenValidator for Introspection {
+    async fn validate(&self, token: &str) -> Result<Option<Principal>, String> {
+        let mut request = self
+            .http
+            .post(&self.endpoint)
+            .form(&[("token", token), ("token_type_hint", "access_token")]);
+        if let Some(client_id) = &self.client_id {
+            request = request.basic_auth(client_id, self.client_secret.as_ref());
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("token introspection failed: {e}"))?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "token introspection failed with status {}",
+                response.status()
+            ));
+        }
+        let body = response
+            .text()
+            .await
+            .map_err(|e| format!("token introspection failed: {e}"))?;
+        let info: IntrospectionResponse = serde_json::from_str(&body)
+            .map_err(|e| format!("invalid token introspection response: {e}"))?;
+        // Tokens issued for other servers must not be accepted, even if active
+        let for_this_server = info
+            .aud
+            .as_ref()
+            .is_some_and(|audience| audience.contains(&self.resource));
+        if !info.active || !for_this_server {
+            return Ok(None);
+        }
+        Ok(Some(Principal {
+            subject: info
+                .sub
+                .or(info.username)
+                .or(info.client_id)
+                .unwrap_or_default(),
+            scopes: info
+                .scope
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http;
+    use crate::tests::test_server;
+    use kagiapi::testing::MockKagi;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn start(mock: &MockKagi, auth: Auth) -> String {
+        let router = auth.protect(http::router(test_server(mock)));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        url
+    }
+
+    fn initialize() -> Value {
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}})
+    }
+
+    #[tokio::test]
+    async fn test_static_tokens() {
+        let mock = MockKagi::start().await;
+        let args = AuthArgs {
+            auth_tokens: vec!["zed:secret-1".to_string(), "cli:secret-2".to_string()],
+            ..AuthArgs::default()
+        };
+        let auth = args.auth(&reqwest::Client::new()).unwrap().unwrap();
+        let url = format!("{}{}", start(&mock, auth).await, http::ENDPOINT);
+        let client = reqwest::Client::new();
+
+        let response = client.post(&url).json(&initialize()).send().await.unwrap();
+        assert_eq!(response.status(), 401);
+        assert_eq!(
+            response.headers()[header::WWW_AUTHENTICATE],
+            "Bearer realm=\"kagi-mcp-server\""
+        );
+        let response = client
+            .post(&url)
+            .bearer_auth("secret-3")
+            .json(&initialize())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+
+        let response = client
+            .post(&url)
+            .bearer_auth("secret-1")
+            .json(&initialize())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let session = response.headers()["mcp-session-id"].clone();
+        let list = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"});
+        let response = client
+            .post(&url)
+            .bearer_auth("secret-1")
+            .header("mcp-session-id", &session)
+            .json(&list)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        // Sessions belong to the client that opened them
+        let response = client
+            .post(&url)
+            .bearer_auth("secret-2")
+            .header("mcp-session-id", &session)
+            .json(&list)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_oauth_introspection() {
+        let mock = MockKagi::start().await;
+        let authorization_server = MockServer::start().await;
+        let introspect = |token: &str, info: Value| {
+            Mock::given(method("POST"))
+                .and(path("/introspect"))
+                .and(body_string_contains(format!("token={token}")))
+                .respond_with(ResponseTemplate::new(200).set_body_json(info))
+        };
+        introspect(
+            "valid",
+            json!({"active": true, "sub": "alice", "scope": "mcp read", "aud": "https://mcp.example.com/mcp"}),
+        )
+        .mount(&authorization_server)
+        .await;
+        introspect(
+            "other",
+            json!({"active": true, "sub": "alice", "aud": ["https://other.example.com"]}),
+        )
+        .mount(&authorization_server)
+        .await;
+        introspect("expired", json!({"active": false}))
+            .mount(&authorization_server)
+            .await;
+
+        let args = AuthArgs {
+            oauth_resource: Some("https://mcp.example.com/mcp".to_string()),
+            oauth_authorization_server: vec![authorization_server.uri()],
+            oauth_introspection_url: Some(format!("{}/introspect", authorization_server.uri())),
+            oauth_client_id: Some("kagi-mcp-server".to_string()),
+            ..AuthArgs::default()
+        };
+        let auth = args.auth(&reqwest::Client::new()).unwrap().unwrap();
+        let principal = auth.validate("valid").await.unwrap().unwrap();
+        assert_eq!(principal.subject, "alice");
+        assert_eq!(principal.scopes, ["mcp", "read"]);
+        assert_eq!(auth.validate("other").await, Ok(None));
+        assert_eq!(auth.validate("expired").await, Ok(None));
+
+        let base = start(&mock, auth).await;
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{base}{}", http::ENDPOINT))
+            .bearer_auth("expired")
+            .json(&initialize())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+        assert_eq!(
+            response.headers()[header::WWW_AUTHENTICATE],
+            "Bearer resource_metadata=\"https://mcp.example.com/.well-known/oauth-protected-resource/mcp\", error=\"invalid_token\""
+        );
+
+        // The metadata is public
+        let metadata: Value = client
+            .get(format!("{base}{METADATA_PATH}/mcp"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(metadata["resource"], "https://mcp.example.com/mcp");
+        assert_eq!(
+            metadata["authorization_servers"],
+            json!([authorization_server.uri()])
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! Size limits of search and enrichment results
+//!
+//! Long snippets and many results can fill a good part of the assistant's context
+//! window. With `--max-snippet-chars`, snippets are cut at a word boundary, and
+//! with `--max-response-chars`, the last results are dropped until the result
+//! fits, with a marker saying how many were left out. Both can be overridden per
+//! call.
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// Per-call overrides of the server's size limits
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct BudgetArgs {
+    /// Maximum number of characters of each result snippet; longer snippets are cut. Defaults to the server's limit.
+    #[schemars(range(min = 1))]
+    pub max_snippet_chars: Option<usize>,
+    /// Maximum number of characters of the result; the last results are left out to stay within it. Defaults to the server's limit.
+    #[schemars(range(min = 1))]
+    pub max_response_chars: Option<usize>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    pub max_snippet_chars: Option<usize>,
+    pub max_response_chars: Option<usize>,
+}
+
+impl Budget {
+    /// The limits of this budget, with those given by the call taking precedence
+    pub fn with_overrides(self, args: &BudgetArgs) -> Self {
+        Self {
+            max_snippet_chars: args.max_snippet_chars.or(self.max_snippet_chars),
+            max_response_chars: args.max_response_chars.or(self.max_response_chars),
+        }
+    }
+
+    /// Cut `snippet` to the snippet limit, at a word boundary where there is one
+    pub fn cut_snippet(&self, snippet: &mut Option<String>) {
+        let (Some(max), Some(text)) = (self.max_snippet_chars, snippet.as_mut()) else {
+            return;
+        };
+        let Some((end, _)) = text.char_indices().nth(max) else {
+            return;
+        };
+        let cut = &text[..end];
+        let cut = match cut.rfind(char::is_whitespace) {
+            Some(space) if space > end / 2 => &cut[..space],
+            _ => cut,
+        };
+        *text = format!("{}…", cut.trim_end());
+    }
+
+    /// Drop results from `results` with `drop_last` until `render` fits the
+    /// response limit, and return how many were dropped
+    pub fn fit<T: ?Sized>(
+        &self,
+        results: &mut T,
+        render: impl Fn(&T) -> String,
+        mut drop_last: impl FnMut(&mut T) -> bool,
+    ) -> usize {
+        let Some(max) = self.max_response_chars else {
+            return 0;
+        };
+        let mut dropped = 0;
+        while render(results).chars().count() > max && drop_last(results) {
+            dropped += 1;
+        }
+        dropped
+    }
+}
+
+/// The line telling the assistant that `dropped` results were left out
+pub fn truncation_marker(dropped: usize) -> String {
+    match dropped {
+        1 => "… 1 more result truncated\n".to_string(),
+        _ => format!("… {dropped} more results truncated\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget() {
+        let budget = Budget {
+            max_snippet_chars: Some(12),
+            max_response_chars: Some(10),
+        };
+        let mut snippet = Some("Tokio is an asynchronous runtime".to_string());
+        budget.cut_snippet(&mut snippet);
+        assert_eq!(snippet.as_deref(), Some("Tokio is an…"));
+        let mut short = Some("Tokio".to_string());
+        budget.cut_snippet(&mut short);
+        assert_eq!(short.as_deref(), Some("Tokio"));
+
+        let mut results = vec!["four", "five", "six"];
+        let dropped = budget.fit(
+            &mut results,
+            |results| results.concat(),
+            |results| results.pop().is_some(),
+        );
+        assert_eq!((dropped, results), (1, vec!["four", "five"]));
+        assert_eq!(truncation_marker(1), "… 1 more result truncated\n");
+
+        let budget = budget.with_overrides(&BudgetArgs {
+            max_snippet_chars: None,
+            max_response_chars: Some(100),
+        });
+        assert_eq!(budget.max_snippet_chars, Some(12));
+        assert_eq!(budget.max_response_chars, Some(100));
+    }
+
+    #[tokio::test]
+    async fn test_budgeted_results() {
+        let mock = kagiapi::testing::MockKagi::start().await;
+        let mut client = crate::testing::TestClient::start(crate::tests::test_server(&mock));
+        let result = client
+            .call_tool(
+                "kagi_search_fetch",
+                serde_json::json!({"queries": ["rust"], "max_snippet_chars": 22, "max_response_chars": 300}),
+            )
+            .await
+            .unwrap();
+        let text = result.text();
+        assert!(text.contains("A language empowering…"), "{text}");
+        assert!(!text.contains("The Book"), "{text}");
+        assert!(text.ends_with("… 1 more result truncated\n"), "{text}");
+        assert_eq!(result.structured_content.unwrap()["truncated"], 1);
+
+        let result = client
+            .call_tool(
+                "kagi_enrich_web",
+                serde_json::json!({"query": "rust", "max_snippet_chars": 30}),
+            )
+            .await
+            .unwrap();
+        assert!(result.text().contains("In order to increase fluency…"));
+    }
+}
@@ -0,0 +1,224 @@
+//! Cache of Kagi tool results
+//!
+//! With `--cache-ttl`, successful results of the paid Kagi tools are kept for that
+//! long, keyed by the tool and its normalized arguments, so an assistant repeating
+//! a call within an editing session does not spend API credits again. Arguments
+//! are serialized like [`kagiapi::canonical`] requests, with object keys sorted,
+//! after nulls are dropped and whitespace in queries is collapsed. Free text, such
+//! as the `text` of `kagi_summarizer_text`, is kept as given, since its line breaks
+//! and indentation can change the result. Cached results carry no new ledger
+//! entry, as they cost nothing.
+
+use crate::output::ToolOutput;
+use kagiapi::canonical;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Tools whose results only depend on their arguments, and are worth caching
+const CACHEABLE_TOOLS: &[&str] = &[
+    "kagi_search_fetch",
+    "kagi_research",
+    "kagi_summarizer",
+    "kagi_summarizer_text",
+    "kagi_fastgpt",
+    "kagi_enrich",
+];
+
+/// Arguments holding search queries, whose whitespace does not change the result
+const QUERY_ARGUMENTS: &[&str] = &["query", "queries"];
+
+/// Number of entries kept when no maximum is configured
+pub const DEFAULT_MAX_ENTRIES: usize = 256;
+
+#[derive(Default)]
+pub struct ResultCache {
+    /// Time results are kept; zero disables the cache
+    ttl: Duration,
+    max_entries: usize,
+    /// Results by key, with the time they were stored
+    entries: Mutex<HashMap<String, (Instant, ToolOutput)>>,
+}
+
+impl ResultCache {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            ttl,
+            max_entries,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// The cache key of a call of `tool` with `args`, if its result may be cached
+    pub fn key(&self, tool: &str, args: &Value) -> Option<String> {
+        if self.ttl.is_zero() || self.max_entries == 0 || !CACHEABLE_TOOLS.contains(&tool) {
+            return None;
+        }
+        let mut key = format!("{tool}:");
+        canonical::write_canonical(&normalize(args), &mut key);
+        Some(key)
+    }
+
+    /// The result stored under `key`, unless it expired
+    pub fn get(&self, key: &str) -> Option<ToolOutput> {
+        let mut entries = self.entries();
+        match entries.get(key) {
+            Some((stored, output)) if stored.elapsed() < self.ttl => Some(output.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Store a successful `output` under `key`, evicting the oldest result if full
+    pub fn insert(&self, key: String, output: &ToolOutput) {
+        if output.is_error {
+            return;
+        }
+        let mut entries = self.entries();
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            entries.retain(|_, (stored, _)| stored.elapsed() < self.ttl);
+            if entries.len() >= self.max_entries {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, (stored, _))| *stored)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        entries.insert(key, (Instant::now(), output.clone()));
+    }
+
+    fn entries(&self) -> MutexGuard<'_, HashMap<String, (Instant, ToolOutput)>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Arguments without nulls and with whitespace in queries collapsed
+fn normalize(args: &Value) -> Value {
+    let Value::Object(args) = args else {
+        return args.clone();
+    };
+    let normalized = args
+        .iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(name, value)| {
+            let value = match value {
+                Value::String(query) if QUERY_ARGUMENTS.contains(&name.as_str()) => {
+                    Value::String(canonical::normalize_query(query))
+                }
+                Value::Array(queries) if QUERY_ARGUMENTS.contains(&name.as_str()) => queries
+                    .iter()
+                    .map(|query| match query {
+                        Value::String(query) => Value::String(canonical::normalize_query(query)),
+                        other => other.clone(),
+                    })
+                    .collect(),
+                other => other.clone(),
+            };
+            (name.clone(), value)
+        })
+        .collect();
+    Value::Object(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestClient;
+    use crate::{strict, KagiMcpServer, ServerOptions};
+    use kagiapi::testing::MockKagi;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_cache() {
+        let cache = ResultCache::new(Duration::from_secs(60), 2);
+        let key = cache
+            .key(
+                "kagi_fastgpt",
+                &json!({"query": " What is  Rust? ", "cache": null}),
+            )
+            .unwrap();
+        assert_eq!(
+            Some(key.clone()),
+            cache.key("kagi_fastgpt", &json!({"query": "What is Rust?"}))
+        );
+        assert_eq!(cache.key("kagi_unfurl", &json!({"url": "x"})), None);
+        assert_ne!(
+            cache.key(
+                "kagi_summarizer_text",
+                &json!({"text": "fn main() {\n    run();\n}"})
+            ),
+            cache.key(
+                "kagi_summarizer_text",
+                &json!({"text": "fn main() { run(); }"})
+            )
+        );
+        assert_eq!(
+            ResultCache::default().key("kagi_fastgpt", &json!({"query": "x"})),
+            None
+        );
+
+        cache.insert(key.clone(), &ToolOutput::error("failed"));
+        assert_eq!(cache.get(&key), None);
+        cache.insert(key.clone(), &ToolOutput::from("Rust is...".to_string()));
+        assert_eq!(
+            cache.get(&key),
+            Some(ToolOutput::from("Rust is...".to_string()))
+        );
+
+        // The oldest result makes room for new ones
+        cache.insert("b".to_string(), &ToolOutput::default());
+        cache.insert("c".to_string(), &ToolOutput::default());
+        assert_eq!(cache.get(&key), None);
+        assert!(cache.get("b").is_some());
+
+        let expired = ResultCache::new(Duration::from_nanos(1), 2);
+        expired.insert(key.clone(), &ToolOutput::default());
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(expired.get(&key), None);
+    }
+
+    #[tokio::test]
+    async fn test_cached_calls() {
+        let mock = MockKagi::start().await;
+        let server = KagiMcpServer::new(
+            mock.client(),
+            ServerOptions {
+                strict: strict::StrictMode::Panic,
+                cache: ResultCache::new(Duration::from_secs(60), DEFAULT_MAX_ENTRIES),
+                ..ServerOptions::default()
+            },
+        );
+        let mut client = TestClient::start(Arc::new(server));
+        for queries in [json!(["rust"]), json!([" rust "]), json!(["rust"])] {
+            let result = client
+                .call_tool("kagi_search_fetch", json!({ "queries": queries }))
+                .await
+                .unwrap();
+            assert!(result.text().contains("https://www.rust-lang.org/"));
+        }
+        for _ in 0..2 {
+            client
+                .call_tool("kagi_fastgpt", json!({"query": "rust", "cache": false}))
+                .await
+                .unwrap();
+        }
+
+        let requests = mock.server().received_requests().await.unwrap();
+        let count = |path: &str| {
+            requests
+                .iter()
+                .filter(|request| request.url.path().ends_with(path))
+                .count()
+        };
+        assert_eq!(count("/search"), 1);
+        assert_eq!(count("/fastgpt"), 2);
+    }
+}
@@ -0,0 +1,130 @@
+//! Cancellation of in-flight requests
+//!
+//! Every request is registered by its JSON-RPC id while it is being handled. A
+//! `notifications/cancelled` (or LSP-style `$/cancelRequest`) notification for
+//! that id cancels its [`CancellationToken`]; the request task then stops, which
+//! drops any outstanding Kagi requests, and no response is sent.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio_util::sync::CancellationToken;
+
+/// Tokens of the requests currently being handled, keyed by request id
+#[derive(Debug, Default)]
+pub struct InFlightRequests {
+    requests: Mutex<HashMap<String, (u64, CancellationToken)>>,
+    next_generation: AtomicU64,
+}
+
+impl InFlightRequests {
+    /// Register a request; it stays cancellable until the returned guard is dropped
+    pub fn register(self: &Arc<Self>, id: &Value) -> InFlightGuard {
+        let key = id.to_string();
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        let token = CancellationToken::new();
+        self.lock().insert(key.clone(), (generation, token.clone()));
+        InFlightGuard {
+            requests: Arc::clone(self),
+            key,
+            generation,
+            token,
+        }
+    }
+
+    /// Cancel the request with `id`, returning whether it was in flight
+    pub fn cancel(&self, id: &Value) -> bool {
+        match self.lock().get(&id.to_string()) {
+            Some((_, token)) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, (u64, CancellationToken)>> {
+        self.requests.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Keeps a request registered while it is being handled
+pub struct InFlightGuard {
+    requests: Arc<InFlightRequests>,
+    key: String,
+    generation: u64,
+    token: CancellationToken,
+}
+
+impl InFlightGuard {
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let mut requests = self.requests.lock();
+        // A reused id may have replaced this registration; leave the newer one alone
+        if requests
+            .get(&self.key)
+            .is_some_and(|(generation, _)| *generation == self.generation)
+        {
+            requests.remove(&self.key);
+        }
+    }
+}
+
+/// Key of request `id` of `session` when one server serves several sessions
+///
+/// Request ids are only unique within a session, so in-flight requests are keyed by both.
+pub fn scoped_id(session: &str, id: &Value) -> Value {
+    json!([session, id])
+}
+
+/// The id of the request a cancellation notification refers to
+pub fn cancelled_request_id<'a>(method: &str, params: Option<&'a Value>) -> Option<&'a Value> {
+    let params = params?;
+    match method {
+        "notifications/cancelled" => params.get("requestId"),
+        "$/cancelRequest" => params.get("id"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_in_flight_request() {
+        let requests = Arc::new(InFlightRequests::default());
+        let guard = requests.register(&json!(7));
+        assert!(!requests.cancel(&json!("7")));
+        assert!(requests.cancel(&json!(7)));
+        assert!(guard.token().is_cancelled());
+
+        // A reused id replaces the registration and outlives the first guard
+        let reused = requests.register(&json!(7));
+        drop(guard);
+        assert!(requests.cancel(&json!(7)));
+        assert!(reused.token().is_cancelled());
+        drop(reused);
+        assert!(!requests.cancel(&json!(7)));
+
+        let params = json!({"requestId": 3, "reason": "user aborted"});
+        assert_eq!(
+            cancelled_request_id("notifications/cancelled", Some(&params)),
+            Some(&json!(3))
+        );
+        assert_eq!(
+            cancelled_request_id("$/cancelRequest", Some(&json!({"id": "a"}))),
+            Some(&json!("a"))
+        );
+        assert_eq!(
+            cancelled_request_id("notifications/initialized", None),
+            None
+        );
+    }
+}
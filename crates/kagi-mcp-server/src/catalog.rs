@@ -0,0 +1,124 @@
+//! Tool catalog printed by the `list-tools` and `describe-tool` commands
+//!
+//! Prints the tools the server would offer with the given options, including those
+//! of sub-servers, without starting a session, to document and debug client
+//! configurations. With `--json`, tools are printed as in a `tools/list` result.
+
+use crate::Tool;
+use serde_json::Value;
+use std::fmt::Write;
+
+/// Each tool's name and title, with the first sentence of its description
+pub fn list(tools: &[Tool], json: bool) -> String {
+    if json {
+        return format!("{}\n", to_json(&tools));
+    }
+    let mut output = String::new();
+    for tool in tools {
+        let _ = writeln!(output, "{}", heading(tool));
+        let _ = writeln!(output, "    {}", first_sentence(&tool.description));
+    }
+    output
+}
+
+/// The full description of a tool, with its arguments and schemas
+pub fn describe(tool: &Tool, json: bool) -> String {
+    if json {
+        return format!("{}\n", to_json(tool));
+    }
+    let mut output = format!("{}\n\n{}\n", heading(tool), tool.description);
+    let properties = tool
+        .input_schema
+        .get("properties")
+        .and_then(Value::as_object);
+    let required: Vec<&str> = tool
+        .input_schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|names| names.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    if let Some(properties) = properties.filter(|properties| !properties.is_empty()) {
+        output.push_str("\nArguments:\n");
+        for (name, schema) in properties {
+            let required = if required.contains(&name.as_str()) {
+                ", required"
+            } else {
+                ""
+            };
+            let _ = writeln!(output, "  {name} ({}{required})", value_type(schema));
+            if let Some(description) = schema.get("description").and_then(Value::as_str) {
+                let _ = writeln!(output, "      {description}");
+            }
+        }
+    }
+    let _ = write!(output, "\nInput schema:\n{}\n", to_json(&tool.input_schema));
+    if let Some(output_schema) = &tool.output_schema {
+        let _ = write!(output, "\nOutput schema:\n{}\n", to_json(output_schema));
+    }
+    output
+}
+
+fn heading(tool: &Tool) -> String {
+    match &tool.title {
+        Some(title) => format!("{} ({title})", tool.name),
+        None => tool.name.clone(),
+    }
+}
+
+fn first_sentence(description: &str) -> &str {
+    match description.find(". ") {
+        Some(end) => &description[..=end],
+        None => description,
+    }
+}
+
+/// The type of an argument, or its allowed values
+fn value_type(schema: &Value) -> String {
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        let values: Vec<String> = values.iter().map(ToString::to_string).collect();
+        return format!("one of {}", values.join(", "));
+    }
+    match schema.get("type") {
+        Some(Value::String(name)) => name.clone(),
+        Some(Value::Array(names)) => names
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" or "),
+        _ => "any".to_string(),
+    }
+}
+
+fn to_json(value: &impl serde::Serialize) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kagiapi::testing::MockKagi;
+
+    #[tokio::test]
+    async fn test_catalog() {
+        let mock = MockKagi::start().await;
+        let tools = crate::tests::test_server(&mock).registry.tools();
+
+        let listed = list(&tools, false);
+        assert!(listed.starts_with(
+            "kagi_search_fetch (Kagi Search)\n    Fetch web results based on one or more queries using the Kagi Search API.\n"
+        ));
+        let listed: Vec<Tool> = serde_json::from_str(&list(&tools, true)).unwrap();
+        assert_eq!(listed, tools);
+
+        let search = tools
+            .iter()
+            .find(|tool| tool.name == "kagi_search_fetch")
+            .unwrap();
+        let described = describe(search, false);
+        assert!(described.contains("\nArguments:\n  debug (boolean)\n"));
+        assert!(described.contains("\n  queries (array, required)\n      One or more"));
+        assert!(described.contains("\nOutput schema:\n{"));
+        let described: Tool = serde_json::from_str(&describe(search, true)).unwrap();
+        assert_eq!(&described, search);
+    }
+}
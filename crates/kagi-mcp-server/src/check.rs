@@ -0,0 +1,95 @@
+//! Self-test run by the `check` command
+//!
+//! Troubleshoots "the extension doesn't work" reports without an MCP client. Every
+//! Kagi endpoint is probed with a request without the API key, which is not
+//! billed, to report whether it can be reached with the configured API version and
+//! how fast. The API key is then validated with one Web Enrichment query, at most
+//! $0.002, whose response carries the remaining balance.
+
+use kagiapi::{Endpoint, EnrichType, KagiClient};
+use std::fmt::Write;
+use std::time::Instant;
+
+/// Query sent to validate the API key
+const CHECK_QUERY: &str = "kagi";
+
+/// The report of the check, and whether every step passed
+pub async fn run(client: &KagiClient) -> (String, bool) {
+    let mut report = String::from("Endpoints:\n");
+    let mut passed = true;
+    for endpoint in Endpoint::ALL {
+        let url = client.endpoint_url(endpoint);
+        let started = Instant::now();
+        let outcome = client.probe(endpoint).await;
+        let elapsed = started.elapsed().as_millis();
+        let status = match outcome {
+            Ok(404) => {
+                passed = false;
+                format!("FAILED: not found ({elapsed} ms), check the API version")
+            }
+            Ok(status) => format!("reachable (HTTP {status} without API key, {elapsed} ms)"),
+            Err(e) => {
+                passed = false;
+                format!("FAILED: {e}")
+            }
+        };
+        let _ = writeln!(
+            report,
+            "  {:<16} {url}\n  {:<16} {status}",
+            endpoint.name(),
+            ""
+        );
+    }
+
+    let started = Instant::now();
+    match client.enrich(CHECK_QUERY, EnrichType::Web).await {
+        Ok(response) => {
+            let _ = writeln!(
+                report,
+                "\nAPI key: valid ({} ms, {} ms at Kagi)",
+                started.elapsed().as_millis(),
+                response.meta.ms
+            );
+            match response.meta.api_balance {
+                Some(balance) => {
+                    let _ = writeln!(report, "Balance: ${balance:.3}");
+                }
+                None => report.push_str("Balance: not reported\n"),
+            }
+        }
+        Err(e) => {
+            passed = false;
+            let _ = writeln!(report, "\nAPI key: FAILED: {e}");
+        }
+    }
+    (report, passed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kagiapi::testing::MockKagi;
+    use wiremock::matchers::path;
+    use wiremock::{Mock, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_check() {
+        let mock = MockKagi::start().await;
+        let (report, passed) = run(&mock.client()).await;
+        assert!(passed, "{report}");
+        assert!(report.contains(&format!(
+            "  Search           {}/v0/search\n",
+            mock.base_url_prefix()
+        )));
+        assert!(report.contains("\nAPI key: valid ("));
+        assert!(report.ends_with("Balance: $9.928\n"), "{report}");
+
+        Mock::given(path("/api/v0/enrich/web"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(mock.server())
+            .await;
+        let (report, passed) = run(&mock.client()).await;
+        assert!(!passed);
+        assert!(report.contains("API key: FAILED: Unauthorized"), "{report}");
+    }
+}
@@ -0,0 +1,528 @@
+//! Client side of the protocol
+//!
+//! [`McpClient`] opens an MCP session with another server and lists and calls its
+//! tools. The server is either a child process spoken to over stdio, any other byte
+//! stream of newline-delimited messages, or a Streamable HTTP endpoint. The hub
+//! fronts its sub-servers with it, and tests use it to drive this server end to end.
+//!
+//! The client offers no client features: requests from the server other than
+//! `ping` are answered with a method-not-found error, and notifications other than
+//! `notifications/tools/list_changed` are dropped.
+
+use crate::error_code::ErrorCode;
+use crate::tools::ToolCallError;
+use crate::{strict, Tool};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::{oneshot, Notify};
+
+/// Header carrying the session id of Streamable HTTP sessions
+const SESSION_ID_HEADER: &str = "mcp-session-id";
+
+type Responder = oneshot::Sender<Result<Value, ToolCallError>>;
+/// Requests awaiting a response by JSON-RPC id, or `None` once the server is gone
+type Pending = Arc<Mutex<Option<HashMap<u64, Responder>>>>;
+type Writer = Arc<tokio::sync::Mutex<Box<dyn AsyncWrite + Send + Unpin>>>;
+
+/// How messages reach the server
+enum Connection {
+    /// Newline-delimited messages over a byte stream, answered by a reader task
+    Stream { writer: Writer, pending: Pending },
+    /// One `POST` per message, answered in the response body
+    Http {
+        http: reqwest::Client,
+        url: String,
+        /// Assigned by the server in its response to `initialize`
+        session_id: Arc<Mutex<Option<String>>>,
+    },
+}
+
+/// An MCP session with a server
+pub struct McpClient {
+    /// Names the server in error messages, e.g. `Sub-server 'notes'`
+    label: String,
+    connection: Connection,
+    next_id: AtomicU64,
+    /// Signalled when the server sends `notifications/tools/list_changed`
+    tools_changed: Arc<Notify>,
+    /// The server process, killed when the client is dropped
+    _child: Option<Child>,
+}
+
+impl McpClient {
+    /// Spawn `program` with `args` and speak to it over its stdio
+    ///
+    /// The server's stderr is passed through to ours.
+    pub fn spawn(label: String, program: &str, args: &[String]) -> Result<Self, String> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("failed to start {label}: {e}"))?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(format!("{label} has no stdio pipes"));
+        };
+        let mut client = Self::connect(label, stdout, stdin);
+        client._child = Some(child);
+        Ok(client)
+    }
+
+    /// Speak to a server over `reader` and `writer`
+    pub fn connect(
+        label: String,
+        reader: impl AsyncRead + Send + Unpin + 'static,
+        writer: impl AsyncWrite + Send + Unpin + 'static,
+    ) -> Self {
+        let writer: Writer = Arc::new(tokio::sync::Mutex::new(Box::new(writer)));
+        let pending: Pending = Arc::new(Mutex::new(Some(HashMap::new())));
+        let tools_changed = Arc::new(Notify::new());
+        tokio::spawn(read_messages(
+            label.clone(),
+            reader,
+            Arc::downgrade(&writer),
+            Arc::clone(&pending),
+            Arc::clone(&tools_changed),
+        ));
+        Self {
+            label,
+            connection: Connection::Stream { writer, pending },
+            next_id: AtomicU64::new(1),
+            tools_changed,
+            _child: None,
+        }
+    }
+
+    /// Speak to the Streamable HTTP endpoint at `url`
+    pub fn connect_http(label: String, url: String) -> Self {
+        Self {
+            label,
+            connection: Connection::Http {
+                http: reqwest::Client::builder()
+                    .user_agent(concat!("kagi-mcp-server/", env!("CARGO_PKG_VERSION")))
+                    .build()
+                    .unwrap_or_default(),
+                url,
+                session_id: Arc::default(),
+            },
+            next_id: AtomicU64::new(1),
+            tools_changed: Arc::default(),
+            _child: None,
+        }
+    }
+
+    /// Initialize the session and return the server's `initialize` result
+    pub async fn initialize(&self) -> Result<Value, ToolCallError> {
+        let result = self
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": strict::PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {
+                        "name": "kagi-mcp-server",
+                        "version": env!("CARGO_PKG_VERSION")
+                    }
+                }),
+            )
+            .await?;
+        self.notify("notifications/initialized").await;
+        Ok(result)
+    }
+
+    /// List all of the server's tools, following `nextCursor` across pages
+    pub async fn list_tools(&self) -> Result<Vec<Tool>, ToolCallError> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = cursor.map_or_else(|| json!({}), |cursor| json!({"cursor": cursor}));
+            let result = self.request("tools/list", params).await?;
+            for tool in result["tools"].as_array().into_iter().flatten() {
+                let Some(name) = tool["name"].as_str() else {
+                    continue;
+                };
+                tools.push(Tool {
+                    name: name.to_string(),
+                    title: tool["title"].as_str().map(str::to_string),
+                    description: tool["description"].as_str().unwrap_or_default().to_string(),
+                    input_schema: tool
+                        .get("inputSchema")
+                        .cloned()
+                        .unwrap_or_else(|| json!({"type": "object"})),
+                    output_schema: tool.get("outputSchema").cloned(),
+                    // Hints that cannot be parsed are dropped rather than failing the listing
+                    annotations: tool
+                        .get("annotations")
+                        .and_then(|annotations| serde_json::from_value(annotations.clone()).ok()),
+                });
+            }
+            match result["nextCursor"].as_str() {
+                Some(next) => cursor = Some(next.to_string()),
+                None => break,
+            }
+        }
+        Ok(tools)
+    }
+
+    /// Call `tool` with `arguments` and return its `tools/call` result
+    pub async fn call_tool(&self, tool: &str, arguments: Value) -> Result<Value, ToolCallError> {
+        self.request("tools/call", json!({"name": tool, "arguments": arguments}))
+            .await
+    }
+
+    /// Signalled when the server reports that its tools changed
+    pub fn tools_changed(&self) -> &Notify {
+        &self.tools_changed
+    }
+
+    /// Send a request and wait for its result
+    ///
+    /// If the returned future is dropped first, e.g. because our own client
+    /// cancelled the call, the server is told to cancel the request too.
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, ToolCallError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let message = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        let mut in_flight = InFlight {
+            client: self,
+            id,
+            done: false,
+        };
+        let response = match &self.connection {
+            Connection::Stream { writer, pending } => {
+                let (tx, rx) = oneshot::channel();
+                match lock(pending).as_mut() {
+                    Some(pending) => pending.insert(id, tx),
+                    None => {
+                        in_flight.done = true;
+                        return Err(self.exited());
+                    }
+                };
+                write_line(writer, &message).await;
+                rx.await.unwrap_or_else(|_| Err(self.exited()))
+            }
+            Connection::Http { .. } => self.exchange(id, &message).await,
+        };
+        in_flight.done = true;
+        response
+    }
+
+    /// Send a notification without parameters
+    async fn notify(&self, method: &str) {
+        let message = json!({"jsonrpc": "2.0", "method": method});
+        match &self.connection {
+            Connection::Stream { writer, .. } => write_line(writer, &message).await,
+            Connection::Http {
+                http,
+                url,
+                session_id,
+            } => {
+                // The server only acknowledges notifications
+                let _ = post(http, url, session_id, &message).await;
+            }
+        }
+    }
+
+    /// Post request `id` over HTTP and read its response, handling the server's
+    /// messages sent before it on an event stream
+    async fn exchange(&self, id: u64, message: &Value) -> Result<Value, ToolCallError> {
+        let Connection::Http {
+            http,
+            url,
+            session_id,
+        } = &self.connection
+        else {
+            unreachable!("only HTTP requests are exchanged");
+        };
+        let failed = |e: String| {
+            ToolCallError::new(ErrorCode::InternalError, format!("{}: {e}", self.label))
+        };
+        let mut response = post(http, url, session_id, message).await.map_err(failed)?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(failed(format!("HTTP {status}: {body}")));
+        }
+        let event_stream = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        if !event_stream {
+            let body = response.text().await.map_err(|e| failed(e.to_string()))?;
+            let message = serde_json::from_str(&body).map_err(|e| failed(e.to_string()))?;
+            return outcome(&self.label, &message);
+        }
+        let mut buffer = String::new();
+        while let Some(chunk) = response.chunk().await.map_err(|e| failed(e.to_string()))? {
+            buffer.push_str(&String::from_utf8_lossy(&chunk));
+            while let Some(end) = buffer.find("\n\n") {
+                let event: String = buffer.drain(..end + 2).collect();
+                let data: Vec<&str> = event
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("data:"))
+                    .map(str::trim_start)
+                    .collect();
+                let Ok(message) = serde_json::from_str::<Value>(&data.join("\n")) else {
+                    continue;
+                };
+                if message.get("method").is_none() && message["id"] == id {
+                    return outcome(&self.label, &message);
+                }
+                if let Some(reply) = handle_server_message(&message, &self.tools_changed) {
+                    self.send_detached(reply);
+                }
+            }
+        }
+        Err(failed(
+            "the event stream ended without a response".to_string(),
+        ))
+    }
+
+    /// Send `message` in the background
+    fn send_detached(&self, message: Value) {
+        match &self.connection {
+            Connection::Stream { writer, .. } => {
+                let writer = Arc::clone(writer);
+                tokio::spawn(async move { write_line(&writer, &message).await });
+            }
+            Connection::Http {
+                http,
+                url,
+                session_id,
+            } => {
+                let (http, url, session_id) = (http.clone(), url.clone(), Arc::clone(session_id));
+                tokio::spawn(async move { post(&http, &url, &session_id, &message).await });
+            }
+        }
+    }
+
+    fn exited(&self) -> ToolCallError {
+        ToolCallError::new(ErrorCode::InternalError, format!("{} exited", self.label))
+    }
+}
+
+/// Cancels a request on the server if the caller stops waiting for its response
+struct InFlight<'a> {
+    client: &'a McpClient,
+    id: u64,
+    done: bool,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        if let Connection::Stream { pending, .. } = &self.client.connection {
+            if let Some(pending) = lock(pending).as_mut() {
+                pending.remove(&self.id);
+            }
+        }
+        self.client.send_detached(json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/cancelled",
+            "params": {"requestId": self.id, "reason": "Cancelled by the client"}
+        }));
+    }
+}
+
+fn lock(pending: &Pending) -> MutexGuard<'_, Option<HashMap<u64, Responder>>> {
+    pending.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The result of a response from the server, or its error
+fn outcome(label: &str, message: &Value) -> Result<Value, ToolCallError> {
+    match message.get("error") {
+        Some(error) => Err(ToolCallError {
+            code: error["code"]
+                .as_i64()
+                .and_then(|code| i32::try_from(code).ok())
+                .unwrap_or(ErrorCode::InternalError.code()),
+            message: format!(
+                "{label}: {}",
+                error["message"].as_str().unwrap_or("unknown error")
+            ),
+            data: error.get("data").cloned(),
+        }),
+        None => Ok(message.get("result").cloned().unwrap_or_default()),
+    }
+}
+
+/// Handle a notification or request from the server, returning the reply to a
+/// request
+fn handle_server_message(message: &Value, tools_changed: &Notify) -> Option<Value> {
+    let Some(id) = message.get("id") else {
+        if message["method"] == "notifications/tools/list_changed" {
+            tools_changed.notify_one();
+        }
+        return None;
+    };
+    let method = &message["method"];
+    Some(if method == "ping" {
+        json!({"jsonrpc": "2.0", "id": id, "result": {}})
+    } else {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {
+                "code": ErrorCode::MethodNotFound.code(),
+                "message": format!("Unsupported method: {method}")
+            }
+        })
+    })
+}
+
+/// Post one JSON-RPC message to a Streamable HTTP endpoint, remembering the
+/// session id the server assigns
+async fn post(
+    http: &reqwest::Client,
+    url: &str,
+    session_id: &Mutex<Option<String>>,
+    message: &Value,
+) -> Result<reqwest::Response, String> {
+    let mut request = http
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(
+            reqwest::header::ACCEPT,
+            "application/json, text/event-stream",
+        )
+        .body(message.to_string());
+    let known = session_id.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Some(id) = known {
+        request = request.header(SESSION_ID_HEADER, id);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    if let Some(id) = response
+        .headers()
+        .get(SESSION_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        *session_id.lock().unwrap_or_else(|e| e.into_inner()) = Some(id.to_string());
+    }
+    Ok(response)
+}
+
+/// Write one JSON-RPC message to a byte stream
+///
+/// Write errors are ignored: a server that went away is reported by its reader.
+async fn write_line(writer: &Writer, message: &Value) {
+    let mut line = message.to_string();
+    line.push('\n');
+    let mut writer = writer.lock().await;
+    if writer.write_all(line.as_bytes()).await.is_ok() {
+        let _ = writer.flush().await;
+    }
+}
+
+/// Route a server's responses to the pending requests until it closes the stream
+///
+/// Only the client holds the writer, so dropping the client closes the stream.
+async fn read_messages(
+    label: String,
+    reader: impl AsyncRead + Unpin,
+    writer: Weak<tokio::sync::Mutex<Box<dyn AsyncWrite + Send + Unpin>>>,
+    pending: Pending,
+    tools_changed: Arc<Notify>,
+) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            tracing::warn!("Ignoring invalid JSON from {label}");
+            continue;
+        };
+        if message.get("method").is_some() {
+            let reply = handle_server_message(&message, &tools_changed);
+            if let Some((reply, writer)) = reply.zip(writer.upgrade()) {
+                write_line(&writer, &reply).await;
+            }
+            continue;
+        }
+        let sender = message["id"]
+            .as_u64()
+            .and_then(|id| lock(&pending).as_mut()?.remove(&id));
+        if let Some(sender) = sender {
+            let _ = sender.send(outcome(&label, &message));
+        }
+    }
+    // Fail the requests still waiting on a server that went away
+    lock(&pending).take();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_server;
+    use crate::transport::StreamTransport;
+    use kagiapi::testing::MockKagi;
+
+    #[tokio::test]
+    async fn test_stream_client() {
+        let mock = MockKagi::start().await;
+        let server = test_server(&mock);
+        let (ours, theirs) = tokio::io::duplex(64 * 1024);
+        let (input, output) = tokio::io::split(theirs);
+        let session = tokio::spawn(server.serve(StreamTransport::new(input, output), None));
+        let (reader, writer) = tokio::io::split(ours);
+        let client = McpClient::connect("Kagi".to_string(), reader, writer);
+
+        let initialized = client.initialize().await.unwrap();
+        assert_eq!(initialized["serverInfo"]["name"], "kagi-mcp-server");
+        let tools = client.list_tools().await.unwrap();
+        assert!(tools.iter().any(|tool| tool.name == "kagi_search_fetch"));
+        let result = client
+            .call_tool("kagi_search_fetch", json!({"queries": ["rust"]}))
+            .await
+            .unwrap();
+        assert!(result["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("The Rust Programming Language"));
+        let error = client
+            .call_tool("kagi_fastgpt", json!({}))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidParams.code());
+        assert!(error.message.starts_with("Kagi: "), "{}", error.message);
+
+        session.abort();
+    }
+
+    #[cfg(feature = "http")]
+    #[tokio::test]
+    async fn test_http_client() {
+        let mock = MockKagi::start().await;
+        let router = crate::http::router(test_server(&mock));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}{}",
+            listener.local_addr().unwrap(),
+            crate::http::ENDPOINT
+        );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        let client = McpClient::connect_http("Kagi".to_string(), url);
+
+        client.initialize().await.unwrap();
+        assert!(client
+            .list_tools()
+            .await
+            .unwrap()
+            .iter()
+            .any(|tool| tool.name == "kagi_search_fetch"));
+        // Answered on an event stream, after the call's progress notifications
+        let result = client
+            .call_tool("kagi_search_fetch", json!({"queries": ["rust"]}))
+            .await
+            .unwrap();
+        assert!(result["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("The Rust Programming Language"));
+    }
+}
@@ -0,0 +1,167 @@
+//! Requests from the server to the client
+//!
+//! A [`ClientHandle`] sends requests such as `roots/list`, `sampling/createMessage`
+//! and `elicitation/create` to the client of a session, and matches the client's
+//! responses to the requests awaiting them by id. It works the same over every
+//! transport, as it only needs the session's [`Notifier`].
+//!
+//! A client that never answers would otherwise hold a tool call forever, so every
+//! request has a timeout, after which the client is told to cancel it. Requests
+//! that wait on the user get longer.
+
+use crate::notifier::Notifier;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Time the client gets to answer a request
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Time the client gets to answer a request it may show to the user first
+const USER_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Answer to a request sent to the client
+type Reply = Result<Value, String>;
+
+pub struct ClientHandle {
+    notifier: Notifier,
+    /// Requests sent to the client that await a response, by id
+    pending: Mutex<HashMap<String, oneshot::Sender<Reply>>>,
+    next_id: AtomicU64,
+}
+
+impl ClientHandle {
+    pub fn new(notifier: Notifier) -> Self {
+        Self {
+            notifier,
+            pending: Mutex::default(),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    #[cfg(feature = "http")]
+    pub fn notifier(&self) -> &Notifier {
+        &self.notifier
+    }
+
+    /// Whether requests can reach the client, which they cannot outside of a
+    /// request over Streamable HTTP
+    pub fn is_connected(&self) -> bool {
+        !self.notifier.is_closed()
+    }
+
+    /// Send a request to the client and wait for its result, for as long as
+    /// `method` allows
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let timeout = match method {
+            "sampling/createMessage" | "elicitation/create" => USER_TIMEOUT,
+            _ => DEFAULT_TIMEOUT,
+        };
+        self.request_with_timeout(method, params, timeout).await
+    }
+
+    /// Send a request to the client and wait up to `timeout` for its result
+    pub async fn request_with_timeout(
+        &self,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<Value, String> {
+        if !self.is_connected() {
+            return Err(format!(
+                "{method} cannot be sent to the client outside of a request"
+            ));
+        }
+        let id = format!("server-{}", self.next_id.fetch_add(1, Ordering::Relaxed));
+        let (tx, rx) = oneshot::channel();
+        self.pending().insert(id.clone(), tx);
+        self.notifier.send(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }));
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(reply) => reply.unwrap_or_else(|_| {
+                Err(format!(
+                    "the session ended before the client answered {method}"
+                ))
+            }),
+            Err(_) => {
+                self.pending().remove(&id);
+                self.notifier.notify(
+                    "notifications/cancelled",
+                    json!({"requestId": id, "reason": "Timed out"}),
+                );
+                Err(format!(
+                    "the client did not answer {method} within {}s",
+                    timeout.as_secs()
+                ))
+            }
+        }
+    }
+
+    /// Deliver a response from the client to the request awaiting it
+    ///
+    /// Returns whether a request was waiting for it; responses to keepalive pings
+    /// and to timed out requests are not.
+    pub fn handle_response(&self, response: &Value) -> bool {
+        let Some(tx) = response["id"]
+            .as_str()
+            .and_then(|id| self.pending().remove(id))
+        else {
+            return false;
+        };
+        let reply = match response.get("error") {
+            Some(error) => Err(format!(
+                "the client answered with error {}: {}",
+                error["code"],
+                error["message"].as_str().unwrap_or_default()
+            )),
+            None => Ok(response.get("result").cloned().unwrap_or(Value::Null)),
+        };
+        // The request may have been abandoned in the meantime
+        let _ = tx.send(reply);
+        true
+    }
+
+    /// Fail the requests still waiting for the client, as the session has ended
+    pub fn close(&self) {
+        self.pending().clear();
+    }
+
+    fn pending(&self) -> MutexGuard<'_, HashMap<String, oneshot::Sender<Reply>>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_request_timeout() {
+        let (notifier, mut outgoing) = Notifier::channel();
+        let client = ClientHandle::new(notifier);
+
+        let error = client.request("roots/list", json!({})).await.unwrap_err();
+        assert!(error.contains("within 60s"), "{error}");
+        let request: Value = serde_json::from_str(&outgoing.recv().await.unwrap()).unwrap();
+        let cancelled: Value = serde_json::from_str(&outgoing.recv().await.unwrap()).unwrap();
+        assert_eq!(cancelled["method"], "notifications/cancelled");
+        assert_eq!(cancelled["params"]["requestId"], request["id"]);
+        // A late answer finds nothing waiting
+        assert!(!client.handle_response(&json!({"id": request["id"], "result": {}})));
+
+        let answered = client.request_with_timeout("ping", json!({}), Duration::from_secs(5));
+        let answer = async {
+            let request: Value = serde_json::from_str(&outgoing.recv().await.unwrap()).unwrap();
+            assert!(client.handle_response(&json!({"id": request["id"], "result": {"ok": 1}})));
+        };
+        let (result, ()) = tokio::join!(answered, answer);
+        assert_eq!(result.unwrap(), json!({"ok": 1}));
+    }
+}
@@ -0,0 +1,126 @@
+//! Per-tool concurrency limits
+//!
+//! Tools can be given a maximum number of concurrent executions. Calls beyond the
+//! limit wait in a FIFO queue, and callers that supplied a progress token receive
+//! periodic progress notifications while they wait so the host knows the call is
+//! queued rather than stuck.
+
+use crate::notifier::Notifier;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Interval between progress notifications sent to queued calls
+const QUEUE_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Default)]
+pub struct ToolLimits {
+    limits: HashMap<String, (usize, Arc<Semaphore>)>,
+}
+
+impl ToolLimits {
+    /// Parse a limit specification such as `kagi_summarizer=2,kagi_search_fetch=8`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut limits = HashMap::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (tool, limit) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("invalid tool concurrency '{entry}', expected tool=N"))?;
+            let limit: usize = limit
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&limit| limit > 0)
+                .ok_or_else(|| {
+                    format!("invalid tool concurrency '{entry}', N must be a positive integer")
+                })?;
+            limits.insert(
+                tool.trim().to_string(),
+                (limit, Arc::new(Semaphore::new(limit))),
+            );
+        }
+        Ok(Self { limits })
+    }
+
+    /// Wait for an execution slot for `tool`
+    ///
+    /// Returns `None` for tools without a limit. The returned permit frees the slot
+    /// when dropped.
+    pub async fn acquire(
+        &self,
+        tool: &str,
+        progress_token: Option<&Value>,
+        notifier: &Notifier,
+    ) -> Option<OwnedSemaphorePermit> {
+        let (limit, semaphore) = self.limits.get(tool)?;
+
+        if let Ok(permit) = Arc::clone(semaphore).try_acquire_owned() {
+            return Some(permit);
+        }
+
+        let queued_at = Instant::now();
+        let acquire = Arc::clone(semaphore).acquire_owned();
+        tokio::pin!(acquire);
+        let mut updates = 0u32;
+        loop {
+            if let Some(token) = progress_token {
+                let message = format!(
+                    "Queued: waiting for a free {tool} slot ({limit} concurrent calls allowed), waited {}s",
+                    queued_at.elapsed().as_secs()
+                );
+                notifier.progress(token, f64::from(updates), &message);
+            }
+            updates += 1;
+
+            match tokio::time::timeout(QUEUE_PROGRESS_INTERVAL, &mut acquire).await {
+                // The semaphore is never closed, so acquiring can only fail on shutdown
+                Ok(permit) => return permit.ok(),
+                Err(_) => continue,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_limits() {
+        let limits = ToolLimits::parse("kagi_summarizer=2, kagi_search_fetch = 8,").unwrap();
+        assert_eq!(limits.limits["kagi_summarizer"].0, 2);
+        assert_eq!(limits.limits["kagi_search_fetch"].0, 8);
+
+        assert!(ToolLimits::parse("kagi_summarizer").is_err());
+        assert!(ToolLimits::parse("kagi_summarizer=0").is_err());
+        assert!(ToolLimits::parse("").unwrap().limits.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_excess_calls_wait() {
+        let limits = ToolLimits::parse("kagi_summarizer=1").unwrap();
+        let (notifier, mut rx) = Notifier::channel();
+        let token = Value::from("token-1");
+
+        let first = limits.acquire("kagi_summarizer", None, &notifier).await;
+        assert!(first.is_some());
+        assert!(limits
+            .acquire("kagi_fastgpt", None, &notifier)
+            .await
+            .is_none());
+
+        let waiting = limits.acquire("kagi_summarizer", Some(&token), &notifier);
+        tokio::pin!(waiting);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), &mut waiting)
+                .await
+                .is_err()
+        );
+        assert!(rx.recv().await.unwrap().contains("notifications/progress"));
+
+        drop(first);
+        assert!(waiting.await.is_some());
+    }
+}
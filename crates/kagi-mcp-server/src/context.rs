@@ -0,0 +1,113 @@
+//! Context of a tool call
+//!
+//! Tool handlers get a [`ToolContext`] with what they may need to know about the
+//! call and the session it belongs to: the request, the client, progress
+//! reporting, cancellation and logging to the client.
+
+use crate::logging::LogLevel;
+use crate::notifier::{Notifier, Progress};
+use crate::session::{ClientInfo, Session};
+use crate::timeouts::CallTimeout;
+use crate::tools::RequestMeta;
+use serde_json::{json, Value};
+use tokio_util::sync::CancellationToken;
+
+pub struct ToolContext<'a> {
+    /// Name of the called tool
+    pub tool: &'a str,
+    /// JSON-RPC id of the `tools/call` request
+    pub request_id: &'a Value,
+    /// `_meta` of the `tools/call` request, if any
+    pub meta: Option<&'a RequestMeta>,
+    pub session: &'a Session,
+    /// Where messages about this call go, which is not always the session's
+    /// notifier: over Streamable HTTP each request has its own response stream
+    pub notifier: &'a Notifier,
+    /// Progress reporting, enabled when the client supplied a progress token
+    pub progress: Progress<'a>,
+    /// Cancelled when the client cancels the call, after which its result is
+    /// discarded
+    pub cancellation: CancellationToken,
+    /// Timeout of the call, which handlers extend when the caller asks for longer
+    pub(crate) timeout: &'a CallTimeout,
+    /// What the call cost in USD, when its handler knows it to be less than the
+    /// estimate the session's cost ceiling reserved
+    pub(crate) billed: Option<f64>,
+}
+
+impl ToolContext<'_> {
+    /// The client's name and version, once initialized
+    pub fn client_info(&self) -> Option<&ClientInfo> {
+        self.session.client_info()
+    }
+
+    /// The negotiated protocol version, once initialized
+    pub fn protocol_version(&self) -> Option<&'static str> {
+        self.session.protocol_version()
+    }
+
+    /// The authenticated client, over HTTP transports that require authentication
+    #[cfg(feature = "http")]
+    pub fn principal(&self) -> Option<&crate::auth::Principal> {
+        self.session.principal()
+    }
+
+    /// Send a log message about this call to the client
+    ///
+    /// Messages below the level the client asked for are dropped.
+    pub fn log(&self, level: LogLevel, message: &str) {
+        if level < self.session.log_level() {
+            return;
+        }
+        self.notifier.notify(
+            "notifications/message",
+            json!({"level": level, "logger": self.tool, "data": message}),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifier::Notifier;
+    use crate::timeouts::ToolTimeouts;
+
+    #[tokio::test]
+    async fn test_tool_context() {
+        let (notifier, mut outgoing) = Notifier::channel();
+        let session = Session::new(notifier.clone());
+        session.initialize(
+            Some(&json!({"capabilities": {}, "clientInfo": {"name": "zed", "version": "0.200.0"}})),
+            crate::strict::PROTOCOL_VERSION,
+        );
+        let timeout = ToolTimeouts::default().for_call("kagi_summarizer");
+        let context = ToolContext {
+            tool: "kagi_summarizer",
+            request_id: &json!(7),
+            meta: None,
+            session: &session,
+            notifier: &notifier,
+            progress: Progress::new(None, &notifier),
+            cancellation: CancellationToken::new(),
+            timeout: &timeout,
+            billed: None,
+        };
+        assert_eq!(context.client_info().unwrap().name, "zed");
+        assert_eq!(
+            context.protocol_version(),
+            Some(crate::strict::PROTOCOL_VERSION)
+        );
+
+        context.log(LogLevel::Debug, "Not sent below the default level");
+        context.log(LogLevel::Warning, "Retrying with muriel");
+        session.set_log_level(LogLevel::Error);
+        context.log(LogLevel::Warning, "Not sent below the requested level");
+
+        let message: Value = serde_json::from_str(&outgoing.recv().await.unwrap()).unwrap();
+        assert_eq!(
+            message["params"],
+            json!({"level": "warning", "logger": "kagi_summarizer", "data": "Retrying with muriel"})
+        );
+        assert!(outgoing.try_recv().is_err());
+    }
+}
@@ -0,0 +1,139 @@
+//! FastGPT conversation history for follow-up questions
+//!
+//! FastGPT answers each query on its own. The recent questions and answers of a
+//! session are kept, and `kagi_fastgpt_followup` prepends them to its query, so an
+//! assistant can ask a clarifying question about an earlier answer, and its
+//! references, without repeating them.
+
+use kagiapi::FastGptData;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+/// Number of recent questions and answers kept
+const MAX_EXCHANGES: usize = 3;
+
+/// Maximum number of characters of an earlier answer sent with a follow-up
+const MAX_ANSWER_CHARS: usize = 1500;
+
+/// Maximum number of references of an earlier answer sent with a follow-up
+const MAX_REFERENCES: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Exchange {
+    query: String,
+    answer: String,
+    /// Titles and URLs of the references of the answer
+    references: Vec<(String, String)>,
+}
+
+/// Recent FastGPT questions and answers in this session, most recent last
+#[derive(Debug, Default)]
+pub struct FastGptHistory {
+    exchanges: Mutex<VecDeque<Exchange>>,
+}
+
+impl FastGptHistory {
+    /// Remember the answer to `query`
+    pub fn record(&self, query: &str, data: &FastGptData) {
+        let mut exchanges = self.exchanges.lock().unwrap_or_else(|e| e.into_inner());
+        if exchanges.len() == MAX_EXCHANGES {
+            exchanges.pop_front();
+        }
+        exchanges.push_back(Exchange {
+            query: query.to_string(),
+            answer: data.output.chars().take(MAX_ANSWER_CHARS).collect(),
+            references: data
+                .references
+                .iter()
+                .take(MAX_REFERENCES)
+                .map(|reference| (reference.title.clone(), reference.url.clone()))
+                .collect(),
+        });
+    }
+
+    /// `query` preceded by the recent questions and answers, if there are any
+    pub fn contextualize(&self, query: &str) -> String {
+        let exchanges = self.exchanges.lock().unwrap_or_else(|e| e.into_inner());
+        if exchanges.is_empty() {
+            return query.to_string();
+        }
+        let mut context = String::from("Earlier in this conversation:\n\n");
+        for exchange in exchanges.iter() {
+            let _ = writeln!(context, "Question: {}", exchange.query);
+            let _ = writeln!(context, "Answer: {}", exchange.answer.trim());
+            if !exchange.references.is_empty() {
+                context.push_str("References:\n");
+                for (index, (title, url)) in exchange.references.iter().enumerate() {
+                    let _ = writeln!(context, "[{}] {title} - {url}", index + 1);
+                }
+            }
+            context.push('\n');
+        }
+        let _ = write!(context, "Follow-up question: {query}");
+        context
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kagiapi::FastGptReference;
+
+    fn data(output: &str) -> FastGptData {
+        FastGptData {
+            output: output.to_string(),
+            tokens: 10,
+            references: vec![FastGptReference {
+                title: "Tokio".to_string(),
+                snippet: String::new(),
+                url: "https://tokio.rs/".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_contextualize() {
+        let history = FastGptHistory::default();
+        assert_eq!(history.contextualize("why?"), "why?");
+
+        for index in 0..=MAX_EXCHANGES {
+            history.record(&format!("question {index}"), &data("An answer."));
+        }
+        let query = history.contextualize("How does it schedule tasks?");
+        assert!(!query.contains("question 0"), "{query}");
+        assert!(query.contains("Question: question 1\nAnswer: An answer.\n"));
+        assert!(query.contains("[1] Tokio - https://tokio.rs/"));
+        assert!(query.ends_with("Follow-up question: How does it schedule tasks?"));
+    }
+
+    #[tokio::test]
+    async fn test_followup() {
+        let mock = kagiapi::testing::MockKagi::start().await;
+        let mut client = crate::testing::TestClient::start(crate::tests::test_server(&mock));
+        client
+            .call_tool(
+                "kagi_fastgpt",
+                serde_json::json!({"query": "What is Rust?"}),
+            )
+            .await
+            .unwrap();
+        client
+            .call_tool(
+                "kagi_fastgpt_followup",
+                serde_json::json!({"query": "Who created it?"}),
+            )
+            .await
+            .unwrap();
+
+        let requests = mock.server().received_requests().await.unwrap();
+        let bodies: Vec<serde_json::Value> = requests
+            .iter()
+            .map(|request| serde_json::from_slice(&request.body).unwrap())
+            .collect();
+        assert_eq!(bodies[0]["query"], "What is Rust?");
+        let followup = bodies[1]["query"].as_str().unwrap();
+        assert!(followup.starts_with("Earlier in this conversation:\n\nQuestion: What is Rust?\n"));
+        assert!(followup.ends_with("Follow-up question: Who created it?"));
+    }
+}
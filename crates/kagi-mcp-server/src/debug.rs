@@ -0,0 +1,94 @@
+//! Kagi response metadata appended to tool results in debug mode
+//!
+//! When a tool is called with `debug: true`, or the server runs with `--verbose`,
+//! each Kagi request made for the call adds one line to a compact block at the end
+//! of the result. Including the block in a bug report lets the Kagi request be
+//! traced without reproducing it.
+
+use std::fmt::Write;
+
+/// Metadata of a single Kagi API request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KagiMeta {
+    /// What the request was for, e.g. the search query
+    pub label: Option<String>,
+    pub id: String,
+    pub node: String,
+    pub ms: u64,
+    pub tokens: Option<u32>,
+}
+
+impl KagiMeta {
+    pub fn new(id: &str, node: &str, ms: u64) -> Self {
+        Self {
+            label: None,
+            id: id.to_string(),
+            node: node.to_string(),
+            ms,
+            tokens: None,
+        }
+    }
+
+    #[must_use]
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    #[must_use]
+    pub fn tokens(mut self, tokens: Option<u32>) -> Self {
+        self.tokens = tokens;
+        self
+    }
+}
+
+/// Append the debug block for `entries` to a tool result
+pub fn append(output: &mut String, entries: &[KagiMeta]) {
+    if entries.is_empty() {
+        return;
+    }
+
+    output.push_str("\n\n-----\nDebug:\n");
+    for meta in entries {
+        output.push_str("- ");
+        if let Some(label) = &meta.label {
+            let _ = write!(output, "[{label}] ");
+        }
+        let _ = write!(
+            output,
+            "request_id={} node={} ms={}",
+            meta.id, meta.node, meta.ms
+        );
+        if let Some(tokens) = meta.tokens {
+            let _ = write!(output, " tokens={tokens}");
+        }
+        output.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_block() {
+        let mut output = "result".to_string();
+        append(
+            &mut output,
+            &[
+                KagiMeta::new("abc", "us-east", 120).label("rust"),
+                KagiMeta::new("def", "eu-west", 80).tokens(Some(42)),
+            ],
+        );
+        assert_eq!(
+            output,
+            "result\n\n-----\nDebug:\n\
+             - [rust] request_id=abc node=us-east ms=120\n\
+             - request_id=def node=eu-west ms=80 tokens=42\n"
+        );
+
+        let mut output = "result".to_string();
+        append(&mut output, &[]);
+        assert_eq!(output, "result");
+    }
+}
@@ -0,0 +1,48 @@
+//! Diagnostic logs of the server process
+//!
+//! Warnings and lifecycle events are emitted as `tracing` events and written to
+//! stderr, or appended to `--log-file`, never to stdout, which carries the protocol
+//! on the stdio transport. `--log-level` sets the least severe level written and
+//! `--log-format json` writes one JSON object per event for log collectors. These
+//! logs are separate from the messages sent to the client (see `logging`).
+
+use std::fs::OpenOptions;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+
+/// How log events are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// One human-readable line per event
+    #[default]
+    Pretty,
+    /// One JSON object per line, with the level, target and fields of the event
+    Json,
+}
+
+/// Install the process-wide subscriber writing events from `level` up to `file`,
+/// or to stderr without one
+pub fn init(level: LevelFilter, format: LogFormat, file: Option<&Path>) -> std::io::Result<()> {
+    let (writer, ansi) = match file {
+        Some(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            (BoxMakeWriter::new(Mutex::new(file)), false)
+        }
+        None => (
+            BoxMakeWriter::new(std::io::stderr),
+            std::io::stderr().is_terminal(),
+        ),
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(writer)
+        .with_ansi(ansi);
+    let installed = match format {
+        LogFormat::Pretty => builder.try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    };
+    installed.map_err(std::io::Error::other)
+}
@@ -0,0 +1,196 @@
+//! Markdown digest of the research recorded in the ledger
+//!
+//! Summarizes a period of [`ledger`](crate::ledger) entries into the topics
+//! researched, the most used sources and the estimated spend per tool, e.g. for
+//! reporting research effort to a client.
+
+use crate::ledger::Entry;
+use std::fmt::Write;
+
+/// Maximum number of topics listed
+const MAX_TOPICS: usize = 20;
+
+/// Maximum number of source domains listed
+const MAX_SOURCES: usize = 10;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Render a digest of the entries recorded between `since` and `until` (Unix seconds)
+pub fn render(entries: &[Entry], since: u64, until: u64) -> String {
+    let entries: Vec<&Entry> = entries
+        .iter()
+        .filter(|entry| (since..=until).contains(&entry.timestamp))
+        .collect();
+
+    let mut output = format!(
+        "# Research digest: {} to {}\n\n",
+        format_date(since),
+        format_date(until)
+    );
+    if entries.is_empty() {
+        output.push_str("No research was recorded in this period.\n");
+        return output;
+    }
+    let total_cost: f64 = entries.iter().map(|entry| entry.cost).sum();
+    let _ = writeln!(
+        output,
+        "{} tool calls, ${total_cost:.3} estimated spend\n",
+        entries.len()
+    );
+
+    // Most researched first, ties broken by most recent
+    let mut topics: Vec<(&str, usize, u64)> = Vec::new();
+    for entry in &entries {
+        let subject = entry.subject.trim();
+        match topics
+            .iter_mut()
+            .find(|(topic, ..)| topic.eq_ignore_ascii_case(subject))
+        {
+            Some((_, count, last)) => {
+                *count += 1;
+                *last = (*last).max(entry.timestamp);
+            }
+            None => topics.push((subject, 1, entry.timestamp)),
+        }
+    }
+    topics.sort_by(|a, b| b.1.cmp(&a.1).then(b.2.cmp(&a.2)));
+    output.push_str("## Topics researched\n\n");
+    for (topic, count, _) in topics.iter().take(MAX_TOPICS) {
+        match count {
+            1 => {
+                let _ = writeln!(output, "- {topic}");
+            }
+            _ => {
+                let _ = writeln!(output, "- {topic} ({count}×)");
+            }
+        }
+    }
+    if topics.len() > MAX_TOPICS {
+        let _ = writeln!(output, "- …and {} more", topics.len() - MAX_TOPICS);
+    }
+
+    let mut sources: Vec<(String, usize)> = Vec::new();
+    for url in entries.iter().flat_map(|entry| &entry.sources) {
+        let Some(domain) = domain(url) else {
+            continue;
+        };
+        match sources.iter_mut().find(|(seen, _)| *seen == domain) {
+            Some((_, count)) => *count += 1,
+            None => sources.push((domain, 1)),
+        }
+    }
+    if !sources.is_empty() {
+        sources.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        output.push_str("\n## Top sources\n\n");
+        for (domain, count) in sources.iter().take(MAX_SOURCES) {
+            let _ = writeln!(output, "- {domain} ({count})");
+        }
+    }
+
+    let mut tools: Vec<(&str, usize, f64)> = Vec::new();
+    for entry in &entries {
+        match tools.iter_mut().find(|(tool, ..)| *tool == entry.tool) {
+            Some((_, calls, cost)) => {
+                *calls += 1;
+                *cost += entry.cost;
+            }
+            None => tools.push((&entry.tool, 1, entry.cost)),
+        }
+    }
+    tools.sort_by(|a, b| b.2.total_cmp(&a.2).then_with(|| a.0.cmp(b.0)));
+    output.push_str("\n## Spend by tool\n\n| Tool | Calls | Estimated spend |\n|---|---:|---:|\n");
+    for (tool, calls, cost) in tools {
+        let _ = writeln!(output, "| {tool} | {calls} | ${cost:.3} |");
+    }
+    output
+}
+
+fn domain(url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(url).ok()?;
+    let host = url.host_str()?.to_ascii_lowercase();
+    Some(host.trim_start_matches("www.").to_string())
+}
+
+/// `YYYY-MM-DD` of a Unix timestamp, in UTC
+pub fn format_date(timestamp: u64) -> String {
+    // Civil-from-days conversion for the proleptic Gregorian calendar
+    let days = i64::try_from(timestamp / SECONDS_PER_DAY).unwrap_or(i64::MAX) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: u64, tool: &str, subject: &str, sources: &[&str], cost: f64) -> Entry {
+        Entry {
+            timestamp,
+            tool: tool.to_string(),
+            subject: subject.to_string(),
+            sources: sources.iter().map(ToString::to_string).collect(),
+            cost,
+        }
+    }
+
+    #[test]
+    fn test_render_digest() {
+        // 2024-03-01 00:00:00 UTC
+        let start = 1_709_251_200;
+        let entries = [
+            entry(start - 10, "kagi_search_fetch", "too early", &[], 0.025),
+            entry(
+                start + 100,
+                "kagi_search_fetch",
+                "rust async runtimes",
+                &["https://tokio.rs/", "https://www.tokio.rs/blog"],
+                0.025,
+            ),
+            entry(
+                start + 200,
+                "kagi_summarizer",
+                "https://tokio.rs/tokio/tutorial",
+                &["https://tokio.rs/tokio/tutorial"],
+                0.3,
+            ),
+            entry(
+                start + 300,
+                "kagi_search_fetch",
+                "Rust async runtimes",
+                &[],
+                0.025,
+            ),
+        ];
+
+        assert_eq!(
+            render(&entries, start, start + 7 * SECONDS_PER_DAY),
+            "# Research digest: 2024-03-01 to 2024-03-08\n\n\
+             3 tool calls, $0.350 estimated spend\n\n\
+             ## Topics researched\n\n\
+             - rust async runtimes (2×)\n\
+             - https://tokio.rs/tokio/tutorial\n\n\
+             ## Top sources\n\n\
+             - tokio.rs (3)\n\n\
+             ## Spend by tool\n\n\
+             | Tool | Calls | Estimated spend |\n\
+             |---|---:|---:|\n\
+             | kagi_summarizer | 1 | $0.300 |\n\
+             | kagi_search_fetch | 2 | $0.050 |\n"
+        );
+        assert!(render(&[], start, start).ends_with("No research was recorded in this period.\n"));
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+    }
+}
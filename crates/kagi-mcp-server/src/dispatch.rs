@@ -0,0 +1,128 @@
+//! Response ordering for concurrently dispatched requests
+//!
+//! Requests are always handled concurrently. In [`DispatchMode::Concurrent`] each
+//! response is written as soon as its handler finishes; in
+//! [`DispatchMode::Ordered`] responses are buffered and written in the order the
+//! requests were received, for hosts that assume in-order replies.
+//! Notifications are never delayed.
+
+use crate::notifier::Notifier;
+use serde::Serialize;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DispatchMode {
+    /// Write each response as soon as it is ready
+    #[default]
+    Concurrent,
+    /// Write responses in request order
+    Ordered,
+}
+
+/// Hands out response slots in request order
+pub struct ResponseOrder<T> {
+    notifier: Notifier,
+    queue: Option<mpsc::UnboundedSender<oneshot::Receiver<T>>>,
+    sequencer: Option<JoinHandle<()>>,
+}
+
+impl<T: Serialize + Send + 'static> ResponseOrder<T> {
+    pub fn new(mode: DispatchMode, notifier: &Notifier) -> Self {
+        let (queue, sequencer) = match mode {
+            DispatchMode::Concurrent => (None, None),
+            DispatchMode::Ordered => {
+                let (tx, mut rx) = mpsc::unbounded_channel::<oneshot::Receiver<T>>();
+                let notifier = notifier.clone();
+                let sequencer = tokio::spawn(async move {
+                    while let Some(slot) = rx.recv().await {
+                        // A dropped slot means the handler panicked; skip it rather than stall
+                        if let Ok(response) = slot.await {
+                            notifier.send(&response);
+                        }
+                    }
+                });
+                (Some(tx), Some(sequencer))
+            }
+        };
+
+        Self {
+            notifier: notifier.clone(),
+            queue,
+            sequencer,
+        }
+    }
+
+    /// Reserve the slot for the next request read from the client
+    pub fn reserve(&self) -> ResponseSlot<T> {
+        let slot = self.queue.as_ref().map(|queue| {
+            let (tx, rx) = oneshot::channel();
+            let _ = queue.send(rx);
+            tx
+        });
+        ResponseSlot {
+            notifier: self.notifier.clone(),
+            slot,
+        }
+    }
+
+    /// Wait until every reserved slot has been written
+    pub async fn finish(mut self) {
+        self.queue.take();
+        if let Some(sequencer) = self.sequencer.take() {
+            let _ = sequencer.await;
+        }
+    }
+}
+
+/// Where a single response is delivered
+pub struct ResponseSlot<T> {
+    notifier: Notifier,
+    slot: Option<oneshot::Sender<T>>,
+}
+
+impl<T: Serialize> ResponseSlot<T> {
+    pub fn send(self, response: T) {
+        match self.slot {
+            Some(slot) => {
+                let _ = slot.send(response);
+            }
+            None => self.notifier.send(&response),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_ordered_responses() {
+        let (notifier, mut rx) = Notifier::channel();
+        let order = ResponseOrder::new(DispatchMode::Ordered, &notifier);
+
+        let first = order.reserve();
+        let second = order.reserve();
+        second.send(2);
+        first.send(1);
+        order.finish().await;
+
+        assert_eq!(rx.recv().await.as_deref(), Some("1"));
+        assert_eq!(rx.recv().await.as_deref(), Some("2"));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_responses() {
+        let (notifier, mut rx) = Notifier::channel();
+        let order = ResponseOrder::new(DispatchMode::Concurrent, &notifier);
+
+        let first = order.reserve();
+        let second = order.reserve();
+        second.send(2);
+        first.send(1);
+        order.finish().await;
+
+        assert_eq!(rx.recv().await.as_deref(), Some("2"));
+        assert_eq!(rx.recv().await.as_deref(), Some("1"));
+    }
+}
@@ -0,0 +1,130 @@
+//! Input requested from the user in the middle of a tool call
+//!
+//! Clients that declare the `elicitation` capability let the server ask the user
+//! for input with `elicitation/create`, e.g. to approve a call that passes the
+//! session's cost ceiling, instead of the tool guessing. The client shows a form
+//! built from a flat JSON schema of primitive fields, and the user can accept,
+//! decline or dismiss it.
+
+use crate::session::Session;
+use crate::tools;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+/// The user's answer to an elicitation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Elicitation<T> {
+    /// The user submitted the form
+    Accept(T),
+    /// The user explicitly refused to answer
+    Decline,
+    /// The user dismissed the form without choosing
+    Cancel,
+}
+
+impl Session {
+    /// Ask the user to fill in a form described by `requested_schema`
+    ///
+    /// Fails if the client does not support elicitation, or fails the request.
+    pub async fn elicit_with_schema(
+        &self,
+        message: &str,
+        requested_schema: Value,
+    ) -> Result<Elicitation<Value>, String> {
+        if !self.client_supports("elicitation") {
+            return Err("the client does not support elicitation".to_string());
+        }
+        let mut result = self
+            .request(
+                "elicitation/create",
+                json!({"message": message, "requestedSchema": requested_schema}),
+            )
+            .await?;
+        match result["action"].as_str() {
+            Some("accept") => Ok(Elicitation::Accept(result["content"].take())),
+            Some("decline") => Ok(Elicitation::Decline),
+            Some("cancel") => Ok(Elicitation::Cancel),
+            _ => Err(format!(
+                "invalid elicitation/create action: {}",
+                result["action"]
+            )),
+        }
+    }
+
+    /// Ask the user to fill in the fields of `T`, which must all be primitive
+    pub async fn elicit<T: DeserializeOwned + JsonSchema>(
+        &self,
+        message: &str,
+    ) -> Result<Elicitation<T>, String> {
+        match self
+            .elicit_with_schema(message, tools::input_schema::<T>())
+            .await?
+        {
+            Elicitation::Accept(content) => serde_json::from_value(content)
+                .map(Elicitation::Accept)
+                .map_err(|e| format!("invalid elicitation/create content: {e}")),
+            Elicitation::Decline => Ok(Elicitation::Decline),
+            Elicitation::Cancel => Ok(Elicitation::Cancel),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifier::Notifier;
+    use serde::Deserialize;
+    use std::sync::Arc;
+
+    #[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+    struct Language {
+        /// Language code, e.g. 'EN'
+        code: String,
+    }
+
+    #[tokio::test]
+    async fn test_elicitation() {
+        let (notifier, mut outgoing) = Notifier::channel();
+        let session = Arc::new(Session::new(notifier));
+
+        let error = session.elicit::<Language>("Which language?").await;
+        assert_eq!(
+            error,
+            Err("the client does not support elicitation".to_string())
+        );
+
+        session.initialize(
+            Some(&json!({"capabilities": {"elicitation": {}}})),
+            crate::strict::PROTOCOL_VERSION,
+        );
+        let mut answer = async |result: Value| {
+            let request: Value = serde_json::from_str(&outgoing.recv().await.unwrap()).unwrap();
+            assert_eq!(request["method"], "elicitation/create");
+            session
+                .handle_response(&json!({"jsonrpc": "2.0", "id": request["id"], "result": result}));
+            request["params"].clone()
+        };
+
+        let language = tokio::spawn({
+            let session = Arc::clone(&session);
+            async move { session.elicit::<Language>("Which language?").await }
+        });
+        let params = answer(json!({"action": "accept", "content": {"code": "DE"}})).await;
+        assert_eq!(params["message"], "Which language?");
+        assert_eq!(params["requestedSchema"]["required"], json!(["code"]));
+        assert_eq!(
+            language.await.unwrap(),
+            Ok(Elicitation::Accept(Language {
+                code: "DE".to_string()
+            }))
+        );
+
+        let declined = tokio::spawn({
+            let session = Arc::clone(&session);
+            async move { session.elicit::<Language>("Which language?").await }
+        });
+        answer(json!({"action": "decline"})).await;
+        assert_eq!(declined.await.unwrap(), Ok(Elicitation::Decline));
+    }
+}
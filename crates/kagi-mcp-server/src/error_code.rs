@@ -0,0 +1,79 @@
+//! JSON-RPC error codes
+//!
+//! Protocol errors only: a tool that ran and failed answers with an `isError`
+//! result instead, so the model sees what went wrong.
+
+/// Code of a JSON-RPC error response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// The message is not valid JSON
+    ParseError,
+    /// The message is not a valid JSON-RPC request
+    InvalidRequest,
+    /// Unknown method, or a tool that does not exist or is disabled
+    MethodNotFound,
+    /// Missing or invalid parameters, including tool arguments
+    InvalidParams,
+    /// The server failed to handle the request
+    InternalError,
+    /// A tool call exceeded its timeout
+    ToolTimedOut,
+    /// No resource handler lists the URI passed to `resources/read`
+    ResourceNotFound,
+    /// The session called a tool more often than its rate limit allows
+    RateLimited,
+    /// The call would take the session's estimated spend past `--max-session-cost`
+    CostCeilingReached,
+}
+
+impl ErrorCode {
+    pub const fn code(self) -> i32 {
+        match self {
+            Self::ParseError => -32700,
+            Self::InvalidRequest => -32600,
+            Self::MethodNotFound => -32601,
+            Self::InvalidParams => -32602,
+            Self::InternalError => -32603,
+            // Implementation-defined server errors use -32000 to -32099
+            Self::ToolTimedOut => -32001,
+            // Set by the MCP specification
+            Self::ResourceNotFound => -32002,
+            Self::RateLimited => -32003,
+            Self::CostCeilingReached => -32004,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestClient;
+    use crate::tests::test_server;
+    use kagiapi::testing::MockKagi;
+    use serde_json::json;
+    use wiremock::matchers::path;
+    use wiremock::{Mock, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_tool_failures_are_results() {
+        let mock = MockKagi::start().await;
+        Mock::given(path("/api/v0/fastgpt"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(mock.server())
+            .await;
+        let mut client = TestClient::start(test_server(&mock));
+
+        let result = client
+            .call_tool("kagi_fastgpt", json!({"query": "rust"}))
+            .await
+            .unwrap();
+        assert!(result.is_error);
+        assert!(result.text().starts_with("FastGPT"), "{}", result.text());
+
+        let error = client
+            .call_tool("kagi_fastgpt", json!({}))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidParams.code());
+    }
+}
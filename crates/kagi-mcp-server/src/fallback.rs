@@ -0,0 +1,59 @@
+//! Retrying suspiciously short summaries with a stronger engine
+//!
+//! Engines occasionally answer a long document with a single sentence, or with
+//! nothing at all. When a fallback engine is configured, such summaries are
+//! regenerated once with it, and the result tells the assistant that it did.
+
+use kagiapi::SummarizerEngine;
+
+/// Documents of at least this many tokens deserve more than a sentence or two
+const LONG_DOCUMENT_TOKENS: u32 = 2_000;
+
+/// Summaries of long documents with fewer words than this are retried
+const MIN_SUMMARY_WORDS: usize = 40;
+
+/// Whether a summary `output` of a document of `tokens` tokens should be retried
+pub fn is_suspiciously_short(output: &str, tokens: Option<u32>) -> bool {
+    let words = output.split_whitespace().count();
+    words == 0
+        || (words < MIN_SUMMARY_WORDS
+            && tokens.is_some_and(|tokens| tokens >= LONG_DOCUMENT_TOKENS))
+}
+
+/// Line put in front of a summary produced by the fallback engine
+pub fn note(engine: SummarizerEngine, fallback: SummarizerEngine, tokens: Option<u32>) -> String {
+    let document = match tokens {
+        Some(tokens) => format!("a {tokens}-token document"),
+        None => "the document".to_string(),
+    };
+    format!(
+        "Note: the {} summary of {document} was empty or unusually short, so it was regenerated with {}.",
+        engine_name(engine),
+        engine_name(fallback)
+    )
+}
+
+/// The engine's name as accepted in arguments, e.g. `muriel`
+pub fn engine_name(engine: SummarizerEngine) -> String {
+    format!("{engine:?}").to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_suspiciously_short() {
+        assert!(is_suspiciously_short("", None));
+        assert!(is_suspiciously_short("  \n", Some(100)));
+        assert!(is_suspiciously_short("It is about Rust.", Some(12_000)));
+        assert!(!is_suspiciously_short("It is about Rust.", Some(300)));
+        assert!(!is_suspiciously_short("It is about Rust.", None));
+        assert!(!is_suspiciously_short(&"word ".repeat(60), Some(12_000)));
+
+        assert_eq!(
+            note(SummarizerEngine::Cecil, SummarizerEngine::Muriel, Some(12_000)),
+            "Note: the cecil summary of a 12000-token document was empty or unusually short, so it was regenerated with muriel."
+        );
+    }
+}
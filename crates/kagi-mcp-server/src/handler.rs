@@ -0,0 +1,370 @@
+//! Handlers that add tools, resources and prompts to the server
+//!
+//! Besides the built-in Kagi tools, a server built with
+//! [`KagiMcpServer::builder`](crate::KagiMcpServer::builder) serves the tools,
+//! resources and prompts of any number of [`ToolHandler`]s, [`ResourceHandler`]s
+//! and [`PromptHandler`]s, so one server can combine handlers from several crates.
+//! Their listings are merged, and each call is routed to the handler that listed
+//! the tool, resource or prompt.
+
+use crate::context::ToolContext;
+use crate::error_code::ErrorCode;
+use crate::output::ToolOutput;
+use crate::sampling::SamplingMessage;
+use crate::tools::ToolCallError;
+use crate::Tool;
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Provides tools
+#[async_trait]
+pub trait ToolHandler: Send + Sync {
+    /// The tools of the handler, listed once when the server is built
+    fn tools(&self) -> Vec<Tool>;
+
+    /// Run tool `name` with `arguments`, which match its input schema
+    async fn call(
+        &self,
+        name: &str,
+        arguments: Value,
+        context: &mut ToolContext<'_>,
+    ) -> Result<ToolOutput, ToolCallError>;
+}
+
+/// A resource clients can read
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resource {
+    pub uri: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// The text of a read resource
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceContents {
+    pub uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    pub text: String,
+}
+
+/// Provides resources
+#[async_trait]
+pub trait ResourceHandler: Send + Sync {
+    /// The resources of the handler, listed on every `resources/list`
+    fn resources(&self) -> Vec<Resource>;
+
+    /// Read the resource at `uri`, one of those listed
+    async fn read(&self, uri: &str) -> Result<Vec<ResourceContents>, ToolCallError>;
+}
+
+/// A prompt template clients can offer to users
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Prompt {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub arguments: Vec<PromptArgument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PromptArgument {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub required: bool,
+}
+
+/// A prompt filled in with its arguments
+///
+/// Prompt messages have the same shape as sampling messages.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PromptMessages {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub messages: Vec<SamplingMessage>,
+}
+
+/// Provides prompts
+#[async_trait]
+pub trait PromptHandler: Send + Sync {
+    /// The prompts of the handler, listed on every `prompts/list`
+    fn prompts(&self) -> Vec<Prompt>;
+
+    /// Fill in prompt `name`, one of those listed, with `arguments`
+    async fn get(
+        &self,
+        name: &str,
+        arguments: HashMap<String, String>,
+    ) -> Result<PromptMessages, ToolCallError>;
+}
+
+/// The handlers of a server, with the tools each one provides
+#[derive(Default)]
+pub struct Handlers {
+    tools: Vec<(Arc<dyn ToolHandler>, Vec<Tool>)>,
+    resources: Vec<Arc<dyn ResourceHandler>>,
+    prompts: Vec<Arc<dyn PromptHandler>>,
+}
+
+impl Handlers {
+    pub fn add_tools(&mut self, handler: Arc<dyn ToolHandler>) {
+        let tools = handler.tools();
+        self.tools.push((handler, tools));
+    }
+
+    pub fn add_resources(&mut self, handler: Arc<dyn ResourceHandler>) {
+        self.resources.push(handler);
+    }
+
+    pub fn add_prompts(&mut self, handler: Arc<dyn PromptHandler>) {
+        self.prompts.push(handler);
+    }
+
+    /// The tools of all handlers
+    pub fn tools(&self) -> impl Iterator<Item = &Tool> {
+        self.tools.iter().flat_map(|(_, tools)| tools)
+    }
+
+    /// The handler of tool `name`
+    pub fn tool(&self, name: &str) -> Option<&Arc<dyn ToolHandler>> {
+        self.tools
+            .iter()
+            .find(|(_, tools)| tools.iter().any(|tool| tool.name == name))
+            .map(|(handler, _)| handler)
+    }
+
+    pub fn has_resources(&self) -> bool {
+        !self.resources.is_empty()
+    }
+
+    pub fn has_prompts(&self) -> bool {
+        !self.prompts.is_empty()
+    }
+
+    /// The resources of all handlers
+    pub fn resources(&self) -> Vec<Resource> {
+        self.resources
+            .iter()
+            .flat_map(|handler| handler.resources())
+            .collect()
+    }
+
+    /// Read the resource at `uri` from the handler that lists it
+    pub async fn read_resource(&self, uri: &str) -> Result<Vec<ResourceContents>, ToolCallError> {
+        let handler = self
+            .resources
+            .iter()
+            .find(|handler| handler.resources().iter().any(|r| r.uri == uri))
+            .ok_or_else(|| {
+                ToolCallError::new(
+                    ErrorCode::ResourceNotFound,
+                    format!("Resource '{uri}' not found"),
+                )
+                .with_data(serde_json::json!({ "uri": uri }))
+            })?;
+        handler.read(uri).await
+    }
+
+    /// The prompts of all handlers
+    pub fn prompts(&self) -> Vec<Prompt> {
+        self.prompts
+            .iter()
+            .flat_map(|handler| handler.prompts())
+            .collect()
+    }
+
+    /// Fill in prompt `name` with the handler that lists it
+    pub async fn get_prompt(
+        &self,
+        name: &str,
+        arguments: HashMap<String, String>,
+    ) -> Result<PromptMessages, ToolCallError> {
+        let handler = self
+            .prompts
+            .iter()
+            .find(|handler| handler.prompts().iter().any(|p| p.name == name))
+            .ok_or_else(|| ToolCallError::invalid_params(format!("Prompt '{name}' not found")))?;
+        handler.get(name, arguments).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestClient;
+    use crate::{strict, KagiMcpServer, ServerOptions};
+    use kagiapi::testing::MockKagi;
+    use serde_json::json;
+
+    /// Echoes its text argument
+    struct Echo;
+
+    #[async_trait]
+    impl ToolHandler for Echo {
+        fn tools(&self) -> Vec<Tool> {
+            vec![Tool {
+                name: "echo".to_string(),
+                description: "Echo the text".to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {"text": {"type": "string"}},
+                    "required": ["text"]
+                }),
+                ..Tool::default()
+            }]
+        }
+
+        async fn call(
+            &self,
+            _name: &str,
+            arguments: Value,
+            _context: &mut ToolContext<'_>,
+        ) -> Result<ToolOutput, ToolCallError> {
+            Ok(ToolOutput::from(
+                arguments["text"].as_str().unwrap().to_string(),
+            ))
+        }
+    }
+
+    struct Notes;
+
+    #[async_trait]
+    impl ResourceHandler for Notes {
+        fn resources(&self) -> Vec<Resource> {
+            vec![Resource {
+                uri: "notes://today".to_string(),
+                name: "Today's notes".to_string(),
+                description: None,
+                mime_type: Some("text/markdown".to_string()),
+            }]
+        }
+
+        async fn read(&self, uri: &str) -> Result<Vec<ResourceContents>, ToolCallError> {
+            Ok(vec![ResourceContents {
+                uri: uri.to_string(),
+                mime_type: Some("text/markdown".to_string()),
+                text: "# Today".to_string(),
+            }])
+        }
+    }
+
+    struct Research;
+
+    #[async_trait]
+    impl PromptHandler for Research {
+        fn prompts(&self) -> Vec<Prompt> {
+            vec![Prompt {
+                name: "research".to_string(),
+                description: Some("Research a topic with Kagi".to_string()),
+                arguments: vec![PromptArgument {
+                    name: "topic".to_string(),
+                    description: None,
+                    required: true,
+                }],
+            }]
+        }
+
+        async fn get(
+            &self,
+            _name: &str,
+            arguments: HashMap<String, String>,
+        ) -> Result<PromptMessages, ToolCallError> {
+            Ok(PromptMessages {
+                description: None,
+                messages: vec![SamplingMessage::user(format!(
+                    "Research {} with kagi_search_fetch",
+                    arguments["topic"]
+                ))],
+            })
+        }
+    }
+
+    fn options() -> ServerOptions {
+        ServerOptions {
+            strict: strict::StrictMode::Panic,
+            ..ServerOptions::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handlers() {
+        let mock = MockKagi::start().await;
+        let server = KagiMcpServer::builder(mock.client(), options())
+            .tools(Echo)
+            .resources(Notes)
+            .prompts(Research)
+            .build()
+            .unwrap();
+        let mut client = TestClient::start(Arc::new(server));
+        let initialized = client.initialize().await.unwrap();
+        assert_eq!(initialized.capabilities["resources"], json!({}));
+        assert_eq!(initialized.capabilities["prompts"], json!({}));
+
+        let tools = client.list_tools().await.unwrap();
+        assert!(tools.iter().any(|tool| tool.name == "kagi_search_fetch"));
+        assert!(tools.iter().any(|tool| tool.name == "echo"));
+        let result = client
+            .call_tool("echo", json!({"text": "hello"}))
+            .await
+            .unwrap();
+        assert_eq!(result.text(), "hello");
+        let error = client.call_tool("echo", json!({})).await.unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidParams.code());
+
+        let resources = client.request("resources/list", json!({})).await.unwrap();
+        assert_eq!(resources["resources"][0]["mimeType"], "text/markdown");
+        let read = client
+            .request("resources/read", json!({"uri": "notes://today"}))
+            .await
+            .unwrap();
+        assert_eq!(read["contents"][0]["text"], "# Today");
+        let error = client
+            .request("resources/read", json!({"uri": "notes://tomorrow"}))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, ErrorCode::ResourceNotFound.code());
+
+        let prompt = client
+            .request(
+                "prompts/get",
+                json!({"name": "research", "arguments": {"topic": "rust"}}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            prompt["messages"][0]["content"]["text"],
+            "Research rust with kagi_search_fetch"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handler_conflicts() {
+        let mock = MockKagi::start().await;
+        assert!(KagiMcpServer::builder(mock.client(), options())
+            .tools(Echo)
+            .tools(Echo)
+            .build()
+            .is_err());
+
+        // Without handlers of a kind, its methods are unknown
+        let server = KagiMcpServer::builder(mock.client(), options())
+            .tools(Echo)
+            .build()
+            .unwrap();
+        let mut client = TestClient::start(Arc::new(server));
+        let initialized = client.initialize().await.unwrap();
+        assert!(initialized.capabilities.get("prompts").is_none());
+        let error = client.request("prompts/list", json!({})).await.unwrap_err();
+        assert_eq!(error.code, ErrorCode::MethodNotFound.code());
+    }
+}
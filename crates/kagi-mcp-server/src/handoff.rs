@@ -0,0 +1,150 @@
+//! Zero-downtime upgrades of the daemon transports
+//!
+//! Sending `SIGUSR2` to a server listening on TCP, a Unix socket or HTTP starts the
+//! binary again, from the path it was started from and with the same arguments,
+//! handing it the listening socket. The new server accepts connections from then on,
+//! while the old one stops accepting, finishes the sessions of its open connections
+//! and exits once they close. Installing an upgraded binary over the old one and
+//! signalling the server therefore replaces it without dropping editor sessions.
+//!
+//! The listener is passed as file descriptor 3, named by `KAGI_LISTEN_FD`, so a
+//! Unix socket keeps its path and nothing is unbound in between. Streamable HTTP
+//! sessions live in the process that opened them: clients reconnecting after the
+//! handoff start a new one, as the specification has them do when a session is not
+//! found. Handoffs are only supported on Unix.
+
+use std::io;
+use std::net::SocketAddr;
+
+/// Environment variable naming the descriptor of an inherited listener
+#[cfg(unix)]
+const LISTEN_FD_ENV: &str = "KAGI_LISTEN_FD";
+
+/// Descriptor the listener is passed to the new server as
+#[cfg(unix)]
+const INHERITED_FD: std::os::fd::RawFd = 3;
+
+/// Listen on `addr`, or on the listener inherited from the server this one replaces
+pub async fn tcp_listener(addr: SocketAddr) -> io::Result<tokio::net::TcpListener> {
+    #[cfg(unix)]
+    if let Some(fd) = inherited() {
+        let listener = std::net::TcpListener::from(fd);
+        listener.set_nonblocking(true)?;
+        return tokio::net::TcpListener::from_std(listener);
+    }
+    tokio::net::TcpListener::bind(addr).await
+}
+
+/// Listen on a Unix socket at `path`, or on the listener inherited from the server
+/// this one replaces
+///
+/// A socket left behind by a previous run is replaced; any other file at `path` is
+/// an error.
+#[cfg(unix)]
+pub fn unix_listener(path: &std::path::Path) -> io::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if let Some(fd) = inherited() {
+        let listener = std::os::unix::net::UnixListener::from(fd);
+        listener.set_nonblocking(true)?;
+        return tokio::net::UnixListener::from_std(listener);
+    }
+    if std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+        std::fs::remove_file(path)?;
+    }
+    tokio::net::UnixListener::bind(path)
+}
+
+/// The listener passed on by the server this one replaces, the first time it is asked
+/// for
+#[cfg(unix)]
+fn inherited() -> Option<std::os::fd::OwnedFd> {
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static TAKEN: AtomicBool = AtomicBool::new(false);
+    let fd = std::env::var(LISTEN_FD_ENV).ok()?.parse().ok()?;
+    if TAKEN.swap(true, Ordering::Relaxed) {
+        return None;
+    }
+    // SAFETY: the previous server passed this descriptor to this process, and it is
+    // taken only once
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    // Keep it from leaking into sub-servers
+    // SAFETY: `fd` is an open descriptor owned by this process
+    unsafe {
+        libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC);
+    }
+    tracing::info!("Took over the listener of the previous server");
+    Some(fd)
+}
+
+/// Resolve once a new server took over `listener` after `SIGUSR2` asked for an
+/// upgrade
+///
+/// A new server that cannot be started is logged, and the next signal tries again.
+#[cfg(unix)]
+pub async fn hand_off(listener: &impl std::os::fd::AsFd) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut upgrade = match signal(SignalKind::user_defined2()) {
+        Ok(upgrade) => upgrade,
+        Err(e) => {
+            tracing::warn!("Cannot listen for upgrade requests: {e}");
+            return std::future::pending().await;
+        }
+    };
+    while upgrade.recv().await.is_some() {
+        match spawn_successor(listener.as_fd()) {
+            Ok(pid) => {
+                tracing::info!(
+                    "Handed the listener over to process {pid}; finishing the open sessions"
+                );
+                return;
+            }
+            Err(e) => tracing::warn!("Cannot start the upgraded server: {e}"),
+        }
+    }
+    std::future::pending().await
+}
+
+/// Never resolves, as handoffs need Unix descriptor passing
+#[cfg(not(unix))]
+pub async fn hand_off<L>(_listener: &L) {
+    std::future::pending().await
+}
+
+/// Start this binary again with the same arguments, passing it `listener`
+#[cfg(unix)]
+fn spawn_successor(listener: std::os::fd::BorrowedFd<'_>) -> io::Result<u32> {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::process::CommandExt;
+
+    let mut args = std::env::args_os();
+    let program = args
+        .next()
+        .ok_or_else(|| io::Error::other("the program name is unknown"))?;
+    let fd = listener.as_raw_fd();
+    let mut command = std::process::Command::new(program);
+    command
+        .args(args)
+        .env(LISTEN_FD_ENV, INHERITED_FD.to_string())
+        .stdin(std::process::Stdio::null());
+    // SAFETY: only async-signal-safe calls run between fork and exec
+    unsafe {
+        command.pre_exec(move || {
+            // `dup2` clears close-on-exec on the copy, but does nothing when the
+            // listener already is descriptor 3
+            let result = if fd == INHERITED_FD {
+                libc::fcntl(fd, libc::F_SETFD, 0)
+            } else {
+                libc::dup2(fd, INHERITED_FD)
+            };
+            if result == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    Ok(command.spawn()?.id())
+}
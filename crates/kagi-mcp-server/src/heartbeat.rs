@@ -0,0 +1,76 @@
+//! Periodic stderr heartbeat for process supervisors
+//!
+//! When enabled, a background task writes a single `key=value` line to stderr on
+//! every interval so that watchdogs can detect a hung server and restart it.
+
+use kagiapi::KagiClient;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Runtime statistics shared between the request loop and the heartbeat task
+#[derive(Debug)]
+pub struct ServerStats {
+    started: Instant,
+    in_flight: AtomicUsize,
+    requests: AtomicU64,
+}
+
+impl Default for ServerStats {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            in_flight: AtomicUsize::new(0),
+            requests: AtomicU64::new(0),
+        }
+    }
+}
+
+impl ServerStats {
+    /// Mark a request as started; the returned guard marks it finished when dropped
+    pub fn begin_request(&self) -> InFlightGuard<'_> {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlightGuard { stats: self }
+    }
+
+    fn heartbeat_line(&self, client: &KagiClient) -> String {
+        let usage = client.usage();
+        let mut line = format!(
+            "heartbeat uptime={}s in_flight={} requests={} last_kagi_ms={} kagi_requests={}",
+            self.started.elapsed().as_secs(),
+            self.in_flight.load(Ordering::Relaxed),
+            self.requests.load(Ordering::Relaxed),
+            usage.last_latency.map_or(0, |latency| latency.as_millis()),
+            usage.total_requests(),
+        );
+        if let Some(balance) = usage.api_balance {
+            let _ = write!(line, " api_balance={balance}");
+        }
+        line
+    }
+}
+
+pub struct InFlightGuard<'a> {
+    stats: &'a ServerStats,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.stats.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Spawn the heartbeat task, writing one line to stderr every `interval`
+pub fn spawn(interval: Duration, stats: Arc<ServerStats>, client: KagiClient) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick completes immediately; skip it so the first line is one interval in
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            tracing::info!("{}", stats.heartbeat_line(&client));
+        }
+    });
+}
@@ -0,0 +1,139 @@
+//! Request lifecycle hooks
+//!
+//! A [`ServerHook`] observes every request and tool call without changes to the
+//! dispatch code, for auditing, metrics or redaction. Hooks run inline on the
+//! request's task in registration order, so they should return quickly.
+
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Callbacks around requests and tool calls; every method does nothing by default
+pub trait ServerHook: Send + Sync {
+    /// A request was received, before it is handled
+    fn on_request(&self, _method: &str, _id: &Value) {}
+
+    /// A tool is about to be called with `arguments`, which the hook may rewrite
+    fn on_tool_call_start(&self, _tool: &str, _arguments: &mut Value) {}
+
+    /// A tool call finished after `elapsed`, with the `error` message if it failed,
+    /// either with a JSON-RPC error or an `isError` result
+    fn on_tool_call_end(&self, _tool: &str, _elapsed: Duration, _error: Option<&str>) {}
+
+    /// A request is answered with a JSON-RPC error
+    fn on_error(&self, _method: &str, _code: i32, _message: &str) {}
+}
+
+/// The hooks registered on a server
+#[derive(Clone, Default)]
+pub struct Hooks(Vec<Arc<dyn ServerHook>>);
+
+impl Hooks {
+    // The binary only registers hooks for the metrics of the HTTP transports
+    #[cfg_attr(not(feature = "http"), allow(dead_code))]
+    pub fn new(hooks: Vec<Arc<dyn ServerHook>>) -> Self {
+        Self(hooks)
+    }
+
+    pub fn request(&self, method: &str, id: &Value) {
+        self.0.iter().for_each(|hook| hook.on_request(method, id));
+    }
+
+    pub fn tool_call_start(&self, tool: &str, arguments: &mut Value) {
+        self.0
+            .iter()
+            .for_each(|hook| hook.on_tool_call_start(tool, arguments));
+    }
+
+    pub fn tool_call_end(&self, tool: &str, elapsed: Duration, error: Option<&str>) {
+        self.0
+            .iter()
+            .for_each(|hook| hook.on_tool_call_end(tool, elapsed, error));
+    }
+
+    pub fn error(&self, method: &str, code: i32, message: &str) {
+        self.0
+            .iter()
+            .for_each(|hook| hook.on_error(method, code, message));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestClient;
+    use crate::{strict, KagiMcpServer, ServerOptions};
+    use kagiapi::testing::MockKagi;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// Records every callback and redacts queries mentioning "secret"
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl ServerHook for Recorder {
+        fn on_request(&self, method: &str, _id: &Value) {
+            self.0.lock().unwrap().push(format!("request {method}"));
+        }
+
+        fn on_tool_call_start(&self, tool: &str, arguments: &mut Value) {
+            self.0.lock().unwrap().push(format!("start {tool}"));
+            for query in arguments["queries"].as_array_mut().into_iter().flatten() {
+                if query.as_str().is_some_and(|q| q.contains("secret")) {
+                    *query = json!("redacted");
+                }
+            }
+        }
+
+        fn on_tool_call_end(&self, tool: &str, _elapsed: Duration, error: Option<&str>) {
+            let outcome = if error.is_some() { "failed" } else { "ok" };
+            self.0.lock().unwrap().push(format!("end {tool} {outcome}"));
+        }
+
+        fn on_error(&self, method: &str, code: i32, _message: &str) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("error {method} {code}"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hooks() {
+        let mock = MockKagi::start().await;
+        let recorder = Arc::new(Recorder::default());
+        let server = Arc::new(KagiMcpServer::new(
+            mock.client(),
+            ServerOptions {
+                strict: strict::StrictMode::Panic,
+                hooks: Hooks::new(vec![recorder.clone()]),
+                ..ServerOptions::default()
+            },
+        ));
+        let mut client = TestClient::start(server);
+
+        let result = client
+            .call_tool("kagi_search_fetch", json!({"queries": ["secret project"]}))
+            .await
+            .unwrap();
+        assert!(result.text().contains("\"redacted\""));
+        assert!(!result.text().contains("secret"));
+        client
+            .call_tool("kagi_fastgpt", json!({"query": 5}))
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [
+                "request tools/call",
+                "start kagi_search_fetch",
+                "end kagi_search_fetch ok",
+                "request tools/call",
+                "start kagi_fastgpt",
+                "end kagi_fastgpt failed",
+                "error tools/call -32602",
+            ]
+        );
+    }
+}
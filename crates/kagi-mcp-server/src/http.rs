@@ -0,0 +1,456 @@
+//! Streamable HTTP transport
+//!
+//! Serves the MCP endpoint at `/mcp` following the Streamable HTTP transport of
+//! the 2025-03-26 specification, so remote clients can use the server without
+//! spawning it over stdio:
+//!
+//! - `POST` carries one JSON-RPC message. Requests are answered with a JSON body,
+//!   or with a `text/event-stream` of the request's progress notifications followed
+//!   by its response when the client accepts event streams. Notifications and
+//!   responses are acknowledged with `202 Accepted`.
+//! - `initialize` opens a session whose id is returned in the `Mcp-Session-Id`
+//!   header. Every later request must send it back; `DELETE` ends the session.
+//!   Each session keeps its own initialization state and data.
+//! - `GET` is answered with `405 Method Not Allowed`, as the server never sends
+//!   messages outside of a request.
+//!
+//! Browser requests from non-local origins are rejected to prevent DNS rebinding.
+//! When clients authenticate, a session can only be used by the client that
+//! opened it; see [`auth`](crate::auth).
+
+use crate::auth::Principal;
+use crate::error_code::ErrorCode;
+use crate::handoff;
+use crate::notification;
+use crate::notifier::Notifier;
+use crate::session::Session;
+use crate::{cancellation, strict, KagiMcpServer, McpRequest, McpResponse};
+use axum::extract::{Extension, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use futures::stream::{self, StreamExt};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+/// Path of the MCP endpoint
+pub const ENDPOINT: &str = "/mcp";
+
+/// Header carrying the session id
+const SESSION_ID_HEADER: &str = "mcp-session-id";
+
+/// A request refused before it reaches the server
+pub type Rejection = (StatusCode, &'static str);
+
+/// State shared by the Streamable HTTP handlers
+struct StreamableHttp {
+    server: Arc<KagiMcpServer>,
+    /// Open sessions by id
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+    validator: Option<strict::Validator>,
+}
+
+/// Serve `router` on `addr` until the process exits, announcing `endpoint`
+pub async fn serve(router: Router, addr: SocketAddr, endpoint: &str) -> std::io::Result<()> {
+    let listener = handoff::tcp_listener(addr).await?;
+    tracing::info!(
+        "Serving MCP over HTTP at http://{}{endpoint}",
+        listener.local_addr()?
+    );
+    // Once an upgraded server took over, finish the open connections and exit
+    #[cfg(unix)]
+    let handle = std::os::fd::AsFd::as_fd(&listener).try_clone_to_owned()?;
+    #[cfg(not(unix))]
+    let handle = ();
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async move { handoff::hand_off(&handle).await })
+        .await
+}
+
+/// Routes of the Streamable HTTP transport
+pub fn router(server: Arc<KagiMcpServer>) -> Router {
+    let transport = StreamableHttp {
+        validator: strict::Validator::new(server.strict, strict::PROTOCOL_VERSION),
+        server,
+        sessions: Mutex::default(),
+    };
+    Router::new()
+        .route(
+            ENDPOINT,
+            post(handle_post).delete(handle_delete).get(|| async {
+                (
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    [(header::ALLOW, "POST, DELETE")],
+                )
+            }),
+        )
+        .with_state(Arc::new(transport))
+}
+
+impl StreamableHttp {
+    fn sessions(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<Session>>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Check the session id sent with a request, returning it and its session when
+    /// it is valid and belongs to the client authenticated as `principal`
+    fn session(
+        &self,
+        headers: &HeaderMap,
+        principal: Option<&Principal>,
+    ) -> Result<(String, Arc<Session>), Rejection> {
+        let Some(id) = headers
+            .get(SESSION_ID_HEADER)
+            .and_then(|id| id.to_str().ok())
+        else {
+            return Err((StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header"));
+        };
+        match self.sessions().get(id) {
+            Some(session) if session.is_owned_by(principal) => {
+                Ok((id.to_string(), Arc::clone(session)))
+            }
+            // Other clients' sessions are as unknown as expired ones
+            _ => Err((StatusCode::NOT_FOUND, "Unknown or expired session")),
+        }
+    }
+
+    /// Serialize an outgoing message, checking it in strict mode
+    fn serialize(&self, message: &impl serde::Serialize) -> Option<String> {
+        serde_json::to_string(message)
+            .ok()
+            .map(|line| self.checked(line))
+    }
+
+    fn checked(&self, line: String) -> String {
+        if let Some(validator) = &self.validator {
+            validator.check(&line);
+        }
+        line
+    }
+}
+
+async fn handle_post(
+    State(transport): State<Arc<StreamableHttp>>,
+    principal: Option<Extension<Principal>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    if let Err(rejection) = check_origin(&headers) {
+        return rejection.into_response();
+    }
+    let principal = principal.map(|Extension(principal)| principal);
+    let message: Value = match serde_json::from_str(&body) {
+        Ok(message) => message,
+        Err(e) => {
+            return rpc_error(
+                StatusCode::BAD_REQUEST,
+                ErrorCode::ParseError,
+                format!("Parse error: {e}"),
+            )
+        }
+    };
+
+    // Notifications and responses to server requests need no answer
+    if message.get("method").is_none() || message.get("id").is_none() {
+        let (session_id, session) = match transport.session(&headers, principal.as_ref()) {
+            Ok(session) => session,
+            Err(rejection) => return rejection.into_response(),
+        };
+        if let Some(notification) = notification::parse(&body) {
+            session.handle_notification(&notification);
+            transport
+                .server
+                .handle_session_notification(&session_id, &notification);
+        } else {
+            session.handle_response(&message);
+        }
+        return StatusCode::ACCEPTED.into_response();
+    }
+
+    let request: McpRequest = match serde_json::from_value(message) {
+        Ok(request) => request,
+        Err(e) => {
+            return rpc_error(
+                StatusCode::BAD_REQUEST,
+                ErrorCode::InvalidRequest,
+                format!("Invalid request: {e}"),
+            )
+        }
+    };
+    let (session_id, session, new_session) = if request.method == "initialize" {
+        // Without a standalone event stream, the client is only reachable in responses
+        let session_id = new_session_id();
+        let session = Arc::new(Session::detached());
+        session.set_principal(principal);
+        transport
+            .sessions()
+            .insert(session_id.clone(), Arc::clone(&session));
+        (session_id, session, true)
+    } else {
+        match transport.session(&headers, principal.as_ref()) {
+            Ok((session_id, session)) => (session_id, session, false),
+            Err(rejection) => return rejection.into_response(),
+        }
+    };
+
+    let (notifier, mut notifications) = Notifier::channel();
+    let key = cancellation::scoped_id(&session_id, &request.id);
+    let task = tokio::spawn(transport.server.process(request, &key, session, notifier));
+
+    let mut response = if accepts_event_stream(&headers) {
+        // Progress notifications first, then the response once the request finishes
+        let notifications = stream::poll_fn(move |cx| notifications.poll_recv(cx));
+        let response = stream::once(task).filter_map(|response| async move {
+            let response = response.ok().flatten()?;
+            serde_json::to_string(&response).ok()
+        });
+        let events_transport = Arc::clone(&transport);
+        let events = notifications.chain(response).map(move |line| {
+            Ok::<_, Infallible>(Event::default().data(events_transport.checked(line)))
+        });
+        Sse::new(events)
+            .keep_alive(KeepAlive::default())
+            .into_response()
+    } else {
+        match task.await.ok().flatten() {
+            Some(response) => match transport.serialize(&response) {
+                Some(line) => ([(header::CONTENT_TYPE, "application/json")], line).into_response(),
+                None => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            },
+            // Cancelled requests get no response
+            None => StatusCode::NO_CONTENT.into_response(),
+        }
+    };
+    if new_session {
+        if let Ok(value) = HeaderValue::from_str(&session_id) {
+            response.headers_mut().insert(SESSION_ID_HEADER, value);
+        }
+    }
+    response
+}
+
+async fn handle_delete(
+    State(transport): State<Arc<StreamableHttp>>,
+    principal: Option<Extension<Principal>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(rejection) = check_origin(&headers) {
+        return rejection.into_response();
+    }
+    let principal = principal.map(|Extension(principal)| principal);
+    match transport.session(&headers, principal.as_ref()) {
+        Ok((session_id, session)) => {
+            transport.sessions().remove(&session_id);
+            session.close();
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+/// Reject browser requests from origins other than this machine
+pub fn check_origin(headers: &HeaderMap) -> Result<(), Rejection> {
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        return Ok(());
+    };
+    let local = origin
+        .to_str()
+        .ok()
+        .and_then(|origin| reqwest::Url::parse(origin).ok())
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+        .is_some_and(|host| matches!(host.as_str(), "localhost" | "127.0.0.1" | "[::1]"));
+    if local {
+        Ok(())
+    } else {
+        Err((StatusCode::FORBIDDEN, "Origin not allowed"))
+    }
+}
+
+fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.contains("text/event-stream"))
+}
+
+/// A random, unguessable session id
+pub fn new_session_id() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).expect("the operating system provides random numbers");
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// A JSON-RPC error that is not tied to a request id
+pub fn rpc_error(status: StatusCode, code: ErrorCode, message: String) -> Response {
+    let response = McpResponse::error(Value::Null, code, message);
+    (status, Json(response)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_server;
+    use kagiapi::testing::MockKagi;
+    use serde_json::json;
+
+    async fn start() -> (MockKagi, String) {
+        let mock = MockKagi::start().await;
+        let server = test_server(&mock);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}{ENDPOINT}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router(server)).await });
+        (mock, url)
+    }
+
+    #[tokio::test]
+    async fn test_streamable_http_session() {
+        let (_mock, url) = start().await;
+        let client = reqwest::Client::new();
+        let post = |body: Value| client.post(&url).json(&body);
+
+        let response = post(json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        let response =
+            post(json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}))
+                .send()
+                .await
+                .unwrap();
+        assert_eq!(response.status(), 200);
+        let session = response.headers()[SESSION_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["result"]["protocolVersion"], strict::PROTOCOL_VERSION);
+
+        let response = post(json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+            .header(SESSION_ID_HEADER, &session)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 202);
+
+        let response = post(json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}))
+            .header(SESSION_ID_HEADER, &session)
+            .send()
+            .await
+            .unwrap();
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["id"], 2);
+        assert!(body["result"]["tools"].as_array().unwrap().len() > 1);
+
+        let response = post(json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "tools/call",
+            "params": {
+                "name": "kagi_search_fetch",
+                "arguments": {"queries": ["rust", "rust async"]},
+                "_meta": {"progressToken": "t"}
+            }
+        }))
+        .header(SESSION_ID_HEADER, &session)
+        .header(header::ACCEPT, "application/json, text/event-stream")
+        .send()
+        .await
+        .unwrap();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+        let events: Vec<Value> = response
+            .text()
+            .await
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .map(|data| serde_json::from_str(data).unwrap())
+            .collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0]["method"], "notifications/progress");
+        assert_eq!(events[2]["id"], 3);
+        assert!(events[2]["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("The Rust Programming Language"));
+
+        let response = client
+            .delete(&url)
+            .header(SESSION_ID_HEADER, &session)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 204);
+        let response = post(json!({"jsonrpc": "2.0", "id": 4, "method": "tools/list"}))
+            .header(SESSION_ID_HEADER, &session)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        let response = client
+            .post(&url)
+            .header(header::ORIGIN, "https://evil.example")
+            .json(&json!({"jsonrpc": "2.0", "id": 1, "method": "initialize"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 403);
+        assert_eq!(client.get(&url).send().await.unwrap().status(), 405);
+    }
+
+    #[tokio::test]
+    async fn test_sessions_are_independent() {
+        let (_mock, url) = start().await;
+        let client = reqwest::Client::new();
+        let initialize = async |capabilities: Value| {
+            let response = client
+                .post(&url)
+                .json(&json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "method": "initialize",
+                    "params": {"capabilities": capabilities}
+                }))
+                .send()
+                .await
+                .unwrap();
+            response.headers()[SESSION_ID_HEADER]
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+        let search = async |session: &str| -> Value {
+            let response = client
+                .post(&url)
+                .header(SESSION_ID_HEADER, session)
+                .json(&json!({
+                    "jsonrpc": "2.0",
+                    "id": 2,
+                    "method": "tools/call",
+                    "params": {"name": "kagi_search_fetch", "arguments": {"queries": ["rust"]}}
+                }))
+                .send()
+                .await
+                .unwrap();
+            response.json().await.unwrap()
+        };
+
+        // The second client's initialization must not change the first one's
+        let opted_in = initialize(json!({"experimental": {"suggestedCalls": {}}})).await;
+        let plain = initialize(json!({})).await;
+        assert_ne!(opted_in, plain);
+        assert!(search(&opted_in).await["result"]["_meta"]["suggestedCalls"].is_array());
+        assert!(search(&plain).await["result"].get("_meta").is_none());
+    }
+}
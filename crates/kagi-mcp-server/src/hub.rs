@@ -0,0 +1,350 @@
+//! Sub-servers fronted by this server
+//!
+//! Hosts often limit how many MCP servers they spawn. With `--sub-server`, this server
+//! spawns other stdio MCP servers, or connects to Streamable HTTP ones, and acts as
+//! a small hub: their tools are listed
+//! next to the Kagi tools as `<name>__<tool>`, and calls to them are forwarded to the
+//! sub-server under their original name, with the result relayed unchanged. When a
+//! sub-server reports that its tools changed, the server lists them again.
+//!
+//! With `--proxy`, the server only fronts its sub-servers: the Kagi tools are not
+//! served, so it can bundle other servers behind a single host entry.
+//!
+//! Each sub-server has its own [`McpClient`] session, so sub-servers share no state
+//! with each other or with the Kagi tools.
+
+use crate::client::McpClient;
+use crate::tools::ToolCallError;
+use crate::Tool;
+use serde_json::Value;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Separates the sub-server name from the tool name in forwarded tools
+pub const SEPARATOR: &str = "__";
+
+/// Time a sub-server gets to complete `initialize` and `tools/list`
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A `--sub-server` option: `name=command [args…]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubServerSpec {
+    pub name: String,
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl FromStr for SubServerSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, command) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME=COMMAND, got '{s}'"))?;
+        let name = name.trim();
+        let valid_name = !name.is_empty()
+            && !name.contains(SEPARATOR)
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_name {
+            return Err(format!(
+                "invalid sub-server name '{name}': use letters, digits, '-' and single '_'"
+            ));
+        }
+        let mut words = command.split_whitespace().map(str::to_string);
+        let program = words
+            .next()
+            .ok_or_else(|| format!("missing command for sub-server '{name}'"))?;
+        Ok(Self {
+            name: name.to_string(),
+            program,
+            args: words.collect(),
+        })
+    }
+}
+
+/// An MCP session with one sub-server
+pub struct SubServer {
+    name: String,
+    client: McpClient,
+    /// The sub-server's tools at startup, with namespaced names
+    tools: Vec<Tool>,
+}
+
+impl SubServer {
+    /// Start the sub-server described by `spec` and open a session with it
+    ///
+    /// Commands that are an `http://` or `https://` URL connect to a Streamable HTTP
+    /// endpoint instead of spawning a process.
+    pub async fn spawn(spec: &SubServerSpec) -> Result<Self, String> {
+        let label = format!("Sub-server '{}'", spec.name);
+        let is_url = spec.program.starts_with("http://") || spec.program.starts_with("https://");
+        let client = if is_url && spec.args.is_empty() {
+            McpClient::connect_http(label, spec.program.clone())
+        } else {
+            McpClient::spawn(label, &spec.program, &spec.args)?
+        };
+        Self::start(spec.name.clone(), client).await
+    }
+
+    /// Open a session over `reader` and `writer` and list the sub-server's tools
+    // Sub-servers are spawned, but tests talk to them over in-memory streams
+    #[allow(dead_code)]
+    pub async fn connect(
+        name: String,
+        reader: impl AsyncRead + Send + Unpin + 'static,
+        writer: impl AsyncWrite + Send + Unpin + 'static,
+    ) -> Result<Self, String> {
+        let client = McpClient::connect(format!("Sub-server '{name}'"), reader, writer);
+        Self::start(name, client).await
+    }
+
+    /// Initialize the session and collect the namespaced tools
+    async fn start(name: String, client: McpClient) -> Result<Self, String> {
+        let mut server = Self {
+            name,
+            client,
+            tools: Vec::new(),
+        };
+        let startup = async {
+            server.client.initialize().await?;
+            server.list_tools().await
+        };
+        let tools = tokio::time::timeout(STARTUP_TIMEOUT, startup)
+            .await
+            .map_err(|_| format!("sub-server '{}' did not start in time", server.name))?
+            .map_err(|e| e.message)?;
+        server.tools = tools;
+        Ok(server)
+    }
+
+    /// List the sub-server's tools, with namespaced names
+    pub async fn list_tools(&self) -> Result<Vec<Tool>, ToolCallError> {
+        let mut tools = self.client.list_tools().await?;
+        for tool in &mut tools {
+            tool.name = format!("{}{SEPARATOR}{}", self.name, tool.name);
+        }
+        Ok(tools)
+    }
+
+    /// Call `tool` by its name on the sub-server and return its `tools/call` result
+    ///
+    /// If the returned future is dropped first, e.g. because the client cancelled the
+    /// call, the sub-server is told to cancel the request too.
+    pub async fn call(&self, tool: &str, arguments: Value) -> Result<Value, ToolCallError> {
+        self.client.call_tool(tool, arguments).await
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// The sub-servers fronted by this server
+#[derive(Default)]
+pub struct Hub {
+    servers: Vec<SubServer>,
+}
+
+impl Hub {
+    /// Spawn every sub-server and list their tools
+    pub async fn start(specs: &[SubServerSpec]) -> Result<Self, String> {
+        let servers = futures::future::join_all(specs.iter().map(SubServer::spawn))
+            .await
+            .into_iter()
+            .collect::<Result<_, _>>()?;
+        Ok(Self { servers })
+    }
+
+    /// The forwarded tools of all sub-servers
+    pub fn tools(&self) -> impl Iterator<Item = &Tool> {
+        self.servers.iter().flat_map(|server| &server.tools)
+    }
+
+    /// Wait until a sub-server reports that its tools changed
+    ///
+    /// Never resolves without sub-servers.
+    pub async fn tools_changed(&self) -> &SubServer {
+        if self.servers.is_empty() {
+            return std::future::pending().await;
+        }
+        let changes = self.servers.iter().map(|server| {
+            Box::pin(async move {
+                server.client.tools_changed().notified().await;
+                server
+            })
+        });
+        futures::future::select_all(changes).await.0
+    }
+
+    /// The sub-server and original tool name of forwarded tool `name`
+    pub fn route<'a>(&self, name: &'a str) -> Option<(&SubServer, &'a str)> {
+        let (server, tool) = name.split_once(SEPARATOR)?;
+        self.servers
+            .iter()
+            .find(|candidate| candidate.name == server)
+            .map(|server| (server, tool))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strict;
+    use serde_json::json;
+    use tokio::io::{split, AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream};
+
+    /// A sub-server with an `echo` tool and a `fail` tool, listed on two pages
+    ///
+    /// Calling the unlisted `announce` tool makes it report a change of its tools.
+    async fn fake_sub_server(stream: DuplexStream) {
+        let (reader, mut writer) = split(stream);
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let request: Value = serde_json::from_str(&line).unwrap();
+            let id = &request["id"];
+            let params = &request["params"];
+            let response = match request["method"].as_str().unwrap() {
+                "initialize" => json!({"protocolVersion": strict::PROTOCOL_VERSION}),
+                "tools/list" if params.get("cursor").is_none() => json!({
+                    "tools": [{
+                        "name": "echo",
+                        "description": "Echo text",
+                        "inputSchema": {"type": "object"},
+                        "annotations": {"readOnlyHint": true}
+                    }],
+                    "nextCursor": "2"
+                }),
+                "tools/list" => {
+                    json!({"tools": [{"name": "fail", "inputSchema": {"type": "object"}}]})
+                }
+                "tools/call" if params["name"] == "announce" => {
+                    let changed =
+                        json!({"jsonrpc": "2.0", "method": "notifications/tools/list_changed"});
+                    writer
+                        .write_all(format!("{changed}\n").as_bytes())
+                        .await
+                        .unwrap();
+                    json!({"content": []})
+                }
+                "tools/call" if params["name"] == "echo" => json!({
+                    "content": [{"type": "text", "text": params["arguments"]["text"]}]
+                }),
+                "tools/call" => {
+                    let error = json!({"jsonrpc": "2.0", "id": id, "error": {"code": -32000, "message": "boom"}});
+                    writer
+                        .write_all(format!("{error}\n").as_bytes())
+                        .await
+                        .unwrap();
+                    continue;
+                }
+                _ => continue,
+            };
+            let response = json!({"jsonrpc": "2.0", "id": id, "result": response});
+            writer
+                .write_all(format!("{response}\n").as_bytes())
+                .await
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_parse_spec() {
+        let spec: SubServerSpec = "notes=notes-mcp --dir /tmp/notes".parse().unwrap();
+        assert_eq!(spec.name, "notes");
+        assert_eq!(spec.program, "notes-mcp");
+        assert_eq!(spec.args, ["--dir", "/tmp/notes"]);
+
+        assert!("notes".parse::<SubServerSpec>().is_err());
+        assert!("notes=".parse::<SubServerSpec>().is_err());
+        assert!("my__notes=notes-mcp".parse::<SubServerSpec>().is_err());
+        assert!("no tes=notes-mcp".parse::<SubServerSpec>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sub_server() {
+        let (ours, theirs) = tokio::io::duplex(4096);
+        let fake = tokio::spawn(fake_sub_server(theirs));
+        let (reader, writer) = split(ours);
+        let server = SubServer::connect("local".to_string(), reader, writer)
+            .await
+            .unwrap();
+        let hub = Hub {
+            servers: vec![server],
+        };
+
+        let names: Vec<&str> = hub.tools().map(|tool| tool.name.as_str()).collect();
+        assert_eq!(names, ["local__echo", "local__fail"]);
+        let echo = hub.tools().next().unwrap();
+        assert_eq!(
+            echo.annotations.as_ref().unwrap().read_only_hint,
+            Some(true)
+        );
+        assert!(hub.route("kagi_search_fetch").is_none());
+        assert!(hub.route("other__echo").is_none());
+
+        let (server, tool) = hub.route("local__echo").unwrap();
+        assert_eq!(tool, "echo");
+        let result = server.call(tool, json!({"text": "hi"})).await.unwrap();
+        assert_eq!(result["content"][0]["text"], "hi");
+
+        let error = server.call("fail", json!({})).await.unwrap_err();
+        assert_eq!(error.code, -32000);
+        assert_eq!(error.message, "Sub-server 'local': boom");
+
+        server.call("announce", json!({})).await.unwrap();
+        let changed = tokio::time::timeout(Duration::from_secs(5), hub.tools_changed())
+            .await
+            .unwrap();
+        assert_eq!(changed.name(), "local");
+        assert_eq!(changed.list_tools().await.unwrap().len(), 2);
+
+        fake.abort();
+        let _ = fake.await;
+        let error = server
+            .call("echo", json!({"text": "hi"}))
+            .await
+            .unwrap_err();
+        assert!(error.message.contains("exited"));
+    }
+
+    #[tokio::test]
+    async fn test_proxy_only() {
+        let (ours, theirs) = tokio::io::duplex(4096);
+        tokio::spawn(fake_sub_server(theirs));
+        let (reader, writer) = split(ours);
+        let server = SubServer::connect("local".to_string(), reader, writer)
+            .await
+            .unwrap();
+        let mock = kagiapi::testing::MockKagi::start().await;
+        let proxy = crate::KagiMcpServer::new(
+            mock.client(),
+            crate::ServerOptions {
+                strict: strict::StrictMode::Panic,
+                hub: Hub {
+                    servers: vec![server],
+                },
+                kagi_tools: false,
+                ..crate::ServerOptions::default()
+            },
+        );
+        let mut client = crate::testing::TestClient::start(std::sync::Arc::new(proxy));
+        client.initialize().await.unwrap();
+
+        let tools = client.list_tools().await.unwrap();
+        let names: Vec<&str> = tools.iter().map(|tool| tool.name.as_str()).collect();
+        assert_eq!(names, ["local__echo", "local__fail"]);
+        let result = client
+            .call_tool("local__echo", json!({"text": "hi"}))
+            .await
+            .unwrap();
+        assert_eq!(result.text(), "hi");
+        let error = client
+            .call_tool("kagi_search_fetch", json!({"queries": ["rust"]}))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, crate::ErrorCode::MethodNotFound.code());
+    }
+}
@@ -0,0 +1,119 @@
+//! Local ledger of the research done through the server
+//!
+//! With `--ledger <PATH>`, every successful tool call appends one JSON line with
+//! what was researched, the sources returned and the estimated cost. The ledger
+//! stays on this machine; the `digest` subcommand summarizes it.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A single tool call recorded in the ledger
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Unix timestamp in seconds
+    pub timestamp: u64,
+    pub tool: String,
+    /// Search query, question or summarized URL
+    pub subject: String,
+    /// URLs returned or used by the call
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// Estimated cost in USD
+    #[serde(default)]
+    pub cost: f64,
+}
+
+/// Append-only ledger file
+#[derive(Debug)]
+pub struct Ledger {
+    file: Mutex<File>,
+}
+
+impl Ledger {
+    /// Open the ledger at `path` for appending, creating it if needed
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Record a tool call; failures are reported on stderr rather than failing the call
+    pub fn record(&self, tool: &str, subject: &str, sources: Vec<String>, cost: f64) {
+        let entry = Entry {
+            timestamp: now(),
+            tool: tool.to_string(),
+            subject: subject.to_string(),
+            sources,
+            cost,
+        };
+        let result = serde_json::to_string(&entry)
+            .map_err(io::Error::other)
+            .and_then(|line| {
+                let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+                writeln!(file, "{line}")
+            });
+        if let Err(e) = result {
+            tracing::warn!("Failed to write to the ledger: {e}");
+        }
+    }
+}
+
+/// Read every entry of the ledger at `path`, skipping lines that cannot be parsed
+pub fn read(path: &Path) -> io::Result<Vec<Entry>> {
+    let file = File::open(path)?;
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        if let Ok(entry) = serde_json::from_str(&line?) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Current Unix timestamp in seconds
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_read() {
+        let path = std::env::temp_dir().join(format!(
+            "kagi-mcp-ledger-{}/ledger.jsonl",
+            std::process::id()
+        ));
+        let ledger = Ledger::open(&path).unwrap();
+        ledger.record(
+            "kagi_search_fetch",
+            "rust async",
+            vec!["https://tokio.rs/".to_string()],
+            0.025,
+        );
+        ledger.record("kagi_fastgpt", "what is tokio", Vec::new(), 0.015);
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| writeln!(file, "not json"))
+            .unwrap();
+
+        let entries = read(&path).unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].subject, "rust async");
+        assert_eq!(entries[0].sources, vec!["https://tokio.rs/"]);
+        assert_eq!(entries[1].tool, "kagi_fastgpt");
+    }
+}
@@ -0,0 +1,3093 @@
+//! Kagi MCP Server - Provides Kagi search and summarization tools for AI assistants
+//!
+//! This server implements the Model Context Protocol (MCP) to provide AI assistants
+//! with access to Kagi's search and Universal Summarizer APIs.
+//!
+//! The `kagi-mcp-server` binary only calls [`run`]. Other crates can build a
+//! [`KagiMcpServer`] with their own [`handler`]s besides the built-in tools with
+//! [`KagiMcpServer::builder`], serve it over any [`transport::Transport`], and test
+//! it with the `testing::TestClient` of the `testing` feature.
+
+use clap::{Parser, Subcommand};
+use futures::future::OptionFuture;
+use futures::stream::{FuturesUnordered, StreamExt};
+use kagiapi::{
+    DocumentKind, FastGptOptions, KagiClient, SummarizeOptions, SummarizerEngine, SummaryEvent,
+    SummaryType,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::env;
+use std::fmt::Write;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+
+mod audit;
+#[cfg(feature = "http")]
+mod auth;
+mod budget;
+mod cache;
+mod cancellation;
+mod catalog;
+mod check;
+mod client;
+mod client_handle;
+mod concurrency;
+pub mod context;
+mod conversation;
+mod debug;
+mod diagnostics;
+mod digest;
+mod dispatch;
+mod elicitation;
+mod error_code;
+mod fallback;
+pub mod handler;
+mod handoff;
+mod heartbeat;
+mod hooks;
+#[cfg(feature = "http")]
+mod http;
+mod hub;
+mod ledger;
+mod local;
+mod logging;
+mod markdown;
+#[cfg(feature = "http")]
+mod metrics;
+mod notification;
+mod notifier;
+pub mod output;
+mod pagination;
+mod prompts;
+mod ratelimit;
+mod recency;
+mod references;
+mod registry;
+mod repl;
+mod retry;
+pub mod sampling;
+mod search;
+mod secrets;
+mod session;
+mod socket;
+mod spend;
+#[cfg(feature = "http")]
+mod sse;
+mod strict;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod timeouts;
+pub mod tools;
+mod topics;
+pub mod transport;
+mod unfurl;
+mod urls;
+#[cfg(feature = "http")]
+mod ws;
+
+use context::ToolContext;
+use error_code::ErrorCode;
+use notification::{Notification, NotificationHandler};
+use notifier::{Notifier, Progress};
+use session::Session;
+
+/// Number of results per query when the caller gives no limit
+const SEARCH_DEFAULT_LIMIT: u32 = 10;
+
+/// Number of search results summarized by `kagi_research` when the caller gives no limit
+const RESEARCH_DEFAULT_SOURCES: usize = 3;
+
+/// Upper bound on the number of search results summarized by `kagi_research`
+const RESEARCH_MAX_SOURCES: usize = 5;
+
+/// Maximum number of summarizer calls suggested after a search
+const MAX_SUGGESTED_SUMMARIES: usize = 3;
+
+/// Interval between progress notifications while a summary is being generated
+const SUMMARY_PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Seconds in-flight requests get to finish once a session ends, unless configured
+const DEFAULT_SHUTDOWN_TIMEOUT: u64 = 10;
+
+/// Number of Small Web posts summarized when the caller gives no limit
+const SMALLWEB_DIGEST_DEFAULT_LIMIT: usize = 3;
+
+/// Upper bound on the number of Small Web posts summarized in one call
+const SMALLWEB_DIGEST_MAX_LIMIT: usize = 10;
+
+#[derive(Error, Debug)]
+pub enum McpError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Tool error: {0}")]
+    Tool(String),
+    #[error("Kagi API error: {0}")]
+    KagiApi(#[from] kagiapi::Error),
+}
+
+pub type McpResult<T> = Result<T, McpError>;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct McpRequest {
+    jsonrpc: String,
+    id: Value,
+    method: String,
+    params: Option<Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct McpResponse {
+    jsonrpc: String,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<McpErrorResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct McpErrorResponse {
+    code: i32,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+impl McpResponse {
+    fn result(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn error(id: Value, code: ErrorCode, message: impl Into<String>) -> Self {
+        Self::from_outcome(id, Err(tools::ToolCallError::new(code, message)))
+    }
+
+    /// The response to a request that produced `outcome`
+    fn from_outcome(id: Value, outcome: Result<Value, tools::ToolCallError>) -> Self {
+        match outcome {
+            Ok(result) => Self::result(id, result),
+            Err(e) => Self {
+                jsonrpc: "2.0".to_string(),
+                id,
+                result: None,
+                error: Some(McpErrorResponse {
+                    code: e.code,
+                    message: e.message,
+                    data: e.data,
+                }),
+            },
+        }
+    }
+}
+
+/// Instructions for the client's model writing the overview of a research briefing
+const RESEARCH_OVERVIEW_PROMPT: &str = "You write the overview at the top of a research briefing. \
+Answer the question in at most 120 words, using only the numbered source summaries given, and \
+cite them as [1], [2] and so on. Say where the sources disagree or leave the question open. \
+Reply with the overview only, without a heading.";
+
+/// An overview of the research `sections` on `query`, written by the client's model
+///
+/// Only clients that support sampling are asked; the briefing goes without an
+/// overview when they do not, or reject or fail the request.
+async fn research_overview(session: &Session, query: &str, sections: &str) -> Option<String> {
+    if !session.client_supports("sampling") {
+        return None;
+    }
+    let params = sampling::CreateMessageParams {
+        messages: vec![sampling::SamplingMessage::user(format!(
+            "Question: {query}\n\n{sections}"
+        ))],
+        system_prompt: Some(RESEARCH_OVERVIEW_PROMPT.to_string()),
+        max_tokens: 400,
+        ..sampling::CreateMessageParams::default()
+    };
+    match session.create_message(params).await {
+        Ok(result) => result
+            .text()
+            .map(str::trim)
+            .filter(|overview| !overview.is_empty())
+            .map(str::to_string),
+        Err(e) => {
+            tracing::debug!("Research briefing on '{query}' goes without an overview: {e}");
+            None
+        }
+    }
+}
+
+/// Number of search results `kagi_research` summarizes when the caller asks for `sources`
+fn research_sources(sources: Option<usize>) -> usize {
+    sources
+        .unwrap_or(RESEARCH_DEFAULT_SOURCES)
+        .clamp(1, RESEARCH_MAX_SOURCES)
+}
+
+/// The failure message of a tool call, whether it failed with a JSON-RPC error or
+/// an `isError` result
+fn tool_failure(outcome: &Result<Value, tools::ToolCallError>) -> Option<&str> {
+    match outcome {
+        Ok(result) if result["isError"] == true => Some(
+            result
+                .pointer("/content/0/text")
+                .and_then(Value::as_str)
+                .unwrap_or_default(),
+        ),
+        Ok(_) => None,
+        Err(e) => Some(&e.message),
+    }
+}
+
+/// A tool as listed in `tools/list`
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Tool {
+    pub name: String,
+    /// Display name for clients
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub description: String,
+    #[serde(rename = "inputSchema")]
+    pub input_schema: Value,
+    /// Schema of the tool's `structuredContent`, if it returns any
+    #[serde(
+        rename = "outputSchema",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub output_schema: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<tools::ToolAnnotations>,
+}
+
+impl Tool {
+    /// A tool that only reads from the Kagi API
+    fn kagi(name: &str, title: &str, description: impl Into<String>, input_schema: Value) -> Self {
+        Self {
+            name: name.to_string(),
+            title: Some(title.to_string()),
+            description: description.into(),
+            input_schema,
+            output_schema: None,
+            annotations: Some(tools::ToolAnnotations {
+                title: Some(title.to_string()),
+                read_only_hint: Some(true),
+                open_world_hint: Some(true),
+                ..tools::ToolAnnotations::default()
+            }),
+        }
+    }
+}
+
+/// Drop the first `offset` standard results, keeping related searches
+fn skip_search_results(data: &mut Vec<kagiapi::SearchResult>, offset: usize) {
+    let mut skipped = 0;
+    data.retain(|result| {
+        if result.result_type != 0 || skipped == offset {
+            return true;
+        }
+        skipped += 1;
+        false
+    });
+}
+
+/// The text returned for `summary`, saying what was summarized when it isn't a
+/// plain page
+fn format_summary(
+    summary: kagiapi::SummaryResponse,
+    fallback_note: Option<String>,
+    debug: bool,
+) -> String {
+    let mut result = match summary.data.kind {
+        Some(DocumentKind::Video) => {
+            format!(
+                "Summary of the video transcript:\n\n{}",
+                summary.data.output
+            )
+        }
+        Some(DocumentKind::Audio) => {
+            format!(
+                "Summary of the audio transcript:\n\n{}",
+                summary.data.output
+            )
+        }
+        Some(DocumentKind::Pdf) => {
+            format!("Summary of the PDF document:\n\n{}", summary.data.output)
+        }
+        _ => summary.data.output,
+    };
+    if let Some(note) = fallback_note {
+        result = format!("{note}\n\n{result}");
+    }
+    if debug {
+        debug::append(
+            &mut result,
+            &[
+                debug::KagiMeta::new(&summary.meta.id, &summary.meta.node, summary.meta.ms)
+                    .tokens(summary.data.tokens),
+            ],
+        );
+    }
+    result
+}
+
+/// Enrichment results as a numbered plain text list
+fn format_enrich_results(
+    type_name: &str,
+    query: &str,
+    results: &[kagiapi::EnrichResult],
+) -> String {
+    let mut formatted_results =
+        format!("Kagi {type_name} enrichment results for query: {query}\n\n");
+
+    // Format the results
+    for (i, result) in results.iter().enumerate() {
+        if result.result_type == 0 {
+            // Only include actual search results
+            if let Some(title) = &result.title {
+                let _ = writeln!(formatted_results, "{}. {}", i + 1, title);
+            } else {
+                let _ = writeln!(formatted_results, "{}. [No Title]", i + 1);
+            }
+
+            if let Some(url) = &result.url {
+                let _ = writeln!(formatted_results, "   URL: {url}");
+            }
+
+            if let Some(snippet) = &result.snippet {
+                if !snippet.is_empty() {
+                    let _ = writeln!(formatted_results, "   {snippet}");
+                }
+            }
+
+            if let Some(published) = &result.published {
+                if !published.is_empty() {
+                    let _ = writeln!(formatted_results, "   Published: {published}");
+                }
+            }
+
+            formatted_results.push('\n');
+        }
+    }
+    formatted_results
+}
+
+/// Billed cost of a summary that used `tokens`
+fn summary_cost(engine: SummarizerEngine, tokens: Option<u32>) -> f64 {
+    kagiapi::pricing::summary_cost(engine, tokens.map_or(0, u64::from))
+}
+
+/// Put a warning line in front of the text content of a tool result
+fn prepend_warning(result: &mut Value, warning: &str) {
+    if let Some(Value::String(text)) = result.pointer_mut("/content/0/text") {
+        *text = format!("{warning}\n\n{text}");
+    }
+}
+
+#[derive(Parser)]
+#[command(name = "kagi-mcp-server")]
+#[command(version = env!("CARGO_PKG_VERSION"))]
+#[command(about = "Kagi MCP Server for AI assistants")]
+struct Args {
+    /// Kagi API key (can also be set via `KAGI_API_KEY` environment variable)
+    #[arg(long, env = "KAGI_API_KEY")]
+    api_key: Option<String>,
+
+    /// Default summarizer engine
+    #[arg(long, env = "KAGI_SUMMARIZER_ENGINE", default_value = "cecil")]
+    summarizer_engine: String,
+
+    /// API version for search endpoint
+    #[arg(long, env = "KAGI_SEARCH_API_VERSION", default_value = "v0")]
+    search_api_version: String,
+
+    /// API version for summarizer endpoint
+    #[arg(long, env = "KAGI_SUMMARIZER_API_VERSION", default_value = "v0")]
+    summarizer_api_version: String,
+
+    /// API version for `FastGPT` endpoint
+    #[arg(long, env = "KAGI_FASTGPT_API_VERSION", default_value = "v0")]
+    fastgpt_api_version: String,
+
+    /// API version for enrichment endpoint
+    #[arg(long, env = "KAGI_ENRICH_API_VERSION", default_value = "v0")]
+    enrich_api_version: String,
+
+    /// Maximum concurrent calls per tool, e.g. `kagi_summarizer=2,kagi_search_fetch=8`
+    #[arg(long, env = "KAGI_TOOL_CONCURRENCY")]
+    tool_concurrency: Option<String>,
+
+    /// Seconds a tool call may run before it fails, with per-tool overrides, e.g.
+    /// `60,kagi_summarizer=180` (no limit by default)
+    #[arg(long, env = "KAGI_TOOL_TIMEOUT")]
+    tool_timeout: Option<String>,
+
+    /// Longest `timeout_seconds` callers may pass to `kagi_summarizer` and
+    /// `kagi_fastgpt`; longer ones are capped
+    #[arg(long, env = "KAGI_MAX_CALL_TIMEOUT", default_value_t = timeouts::DEFAULT_MAX_CALL_TIMEOUT)]
+    max_call_timeout: u64,
+
+    /// Calls per minute each session may make to a tool, with an optional burst size
+    /// and per-tool overrides, e.g. `60,kagi_summarizer=10:3` (no limit by default)
+    #[arg(long, env = "KAGI_TOOL_RATE_LIMIT")]
+    tool_rate_limit: Option<String>,
+
+    /// Whether responses are written as soon as they are ready or in request order
+    #[arg(long, env = "KAGI_DISPATCH_MODE", value_enum, default_value_t)]
+    dispatch_mode: dispatch::DispatchMode,
+
+    /// Interval in seconds between stderr heartbeat lines (0 disables the heartbeat)
+    #[arg(long, env = "KAGI_HEARTBEAT_INTERVAL", default_value_t = 0)]
+    heartbeat_interval: u64,
+
+    /// Interval in seconds between `ping` requests sent to idle clients on session
+    /// transports (stdio, TCP, Unix socket and WebSocket); 0 disables them
+    #[arg(long, env = "KAGI_KEEPALIVE_INTERVAL", default_value_t = 0)]
+    keepalive_interval: u64,
+
+    /// Append Kagi request metadata to every tool result, as if `debug: true` were passed
+    #[arg(long, env = "KAGI_VERBOSE")]
+    verbose: bool,
+
+    /// Least severe diagnostic log level written: off, error, warn, info, debug or
+    /// trace
+    #[arg(long, env = "KAGI_LOG_LEVEL", default_value = "info")]
+    log_level: tracing_subscriber::filter::LevelFilter,
+
+    /// Format of diagnostic logs
+    #[arg(long, env = "KAGI_LOG_FORMAT", value_enum, default_value_t)]
+    log_format: diagnostics::LogFormat,
+
+    /// File to append diagnostic logs to instead of stderr
+    #[arg(long, env = "KAGI_LOG_FILE")]
+    log_file: Option<PathBuf>,
+
+    /// Default output language for summaries and `FastGPT` answers, e.g. `DE`
+    #[arg(long, env = "KAGI_OUTPUT_LANGUAGE")]
+    output_language: Option<String>,
+
+    /// How search and enrichment results are written: plain text blocks or
+    /// Markdown with headings, lists and links
+    #[arg(long, env = "KAGI_FORMAT", value_enum, default_value_t)]
+    format: markdown::TextStyle,
+
+    /// Maximum number of characters of each search and enrichment snippet; longer
+    /// snippets are cut at a word boundary. Unlimited if unset
+    #[arg(long, env = "KAGI_MAX_SNIPPET_CHARS")]
+    max_snippet_chars: Option<std::num::NonZeroUsize>,
+
+    /// Maximum number of characters of a search or enrichment result; the last
+    /// results are left out to stay within it. Unlimited if unset
+    #[arg(long, env = "KAGI_MAX_RESPONSE_CHARS")]
+    max_response_chars: Option<std::num::NonZeroUsize>,
+
+    /// Times a Kagi request failing with a rate limit, server error or timeout is
+    /// retried, with exponential backoff (0 disables retrying)
+    #[arg(long, env = "KAGI_RETRIES", default_value_t = retry::DEFAULT_RETRIES)]
+    retries: u32,
+
+    /// Seconds in-flight requests get to finish when stdin or a connection closes or
+    /// on SIGINT or SIGTERM, before they are cancelled and answered with an error
+    #[arg(long, env = "KAGI_SHUTDOWN_TIMEOUT", default_value_t = DEFAULT_SHUTDOWN_TIMEOUT)]
+    shutdown_timeout: u64,
+
+    /// Comma-separated tools to hide from the client, e.g. `kagi_fastgpt,kagi_enrich_news`
+    #[arg(long, env = "KAGI_DISABLED_TOOLS", value_delimiter = ',')]
+    disabled_tools: Vec<String>,
+
+    /// How to handle likely credentials (API keys, tokens, private keys) in tool arguments
+    #[arg(long, env = "KAGI_SECRET_FILTER", value_enum, default_value_t)]
+    secret_filter: secrets::SecretFilter,
+
+    /// Add the previous topic to vague follow-up queries such as "its performance"
+    #[arg(long, env = "KAGI_TOPIC_CONTEXT")]
+    topic_context: bool,
+
+    /// Comma-separated rewrites applied to URLs in tool results, e.g. `strip-tracking,force-https`
+    #[arg(long, env = "KAGI_URL_POLICY", value_enum, value_delimiter = ',')]
+    url_policy: Vec<urls::UrlRule>,
+
+    /// Append a JSON line per successful tool call to this file, for `digest`
+    #[arg(long, env = "KAGI_LEDGER", global = true)]
+    ledger: Option<PathBuf>,
+
+    /// Append a JSON line per handled request to this file (`-` for stderr), with
+    /// redacted tool arguments, duration and outcome
+    #[arg(long, env = "KAGI_AUDIT_LOG")]
+    audit_log: Option<PathBuf>,
+
+    /// Validate every outgoing message against the MCP schema, for debugging and CI
+    #[arg(long, env = "KAGI_STRICT", value_enum, default_value_t)]
+    strict: strict::StrictMode,
+
+    /// Maximum estimated spend in USD of a single `kagi_smallweb_digest` call
+    #[arg(long, env = "KAGI_SMALLWEB_BUDGET", default_value_t = 1.0)]
+    smallweb_budget: f64,
+
+    /// Maximum estimated spend in USD of each session; paid tools are refused once a
+    /// call would exceed it (no limit by default)
+    #[arg(long, env = "KAGI_MAX_SESSION_COST")]
+    max_session_cost: Option<f64>,
+
+    /// How clients connect to the server
+    #[arg(long, env = "KAGI_TRANSPORT", value_enum, default_value_t)]
+    transport: Transport,
+
+    /// Engine to regenerate empty or unusually short summaries of long documents with,
+    /// e.g. `muriel` (off by default)
+    #[arg(long, env = "KAGI_SUMMARY_FALLBACK_ENGINE", value_enum)]
+    summary_fallback_engine: Option<tools::Engine>,
+
+    /// How messages are delimited on stdio: newline-delimited JSON, LSP-style
+    /// `Content-Length` headers, or whichever the client's first message uses
+    #[arg(long, env = "KAGI_STDIO_FRAMING", value_enum, default_value_t)]
+    stdio_framing: transport::Framing,
+
+    /// Maximum size in bytes of a message on stdio, TCP and Unix socket connections;
+    /// larger messages are skipped and answered with a parse error
+    #[arg(long, env = "KAGI_MAX_MESSAGE_SIZE", default_value_t = transport::DEFAULT_MAX_MESSAGE_SIZE)]
+    max_message_size: usize,
+
+    /// Maximum number of tools, resources or prompts per page of a list result;
+    /// further pages are fetched with the returned cursor. Unpaginated if unset
+    #[arg(long, env = "KAGI_LIST_PAGE_SIZE")]
+    list_page_size: Option<std::num::NonZeroUsize>,
+
+    /// Seconds successful results of the paid Kagi tools are cached for, keyed by
+    /// their normalized arguments (0 disables the cache)
+    #[arg(long, env = "KAGI_CACHE_TTL", default_value_t = 0)]
+    cache_ttl: u64,
+
+    /// Maximum number of cached tool results; the oldest is evicted first
+    #[arg(long, env = "KAGI_CACHE_MAX_ENTRIES", default_value_t = cache::DEFAULT_MAX_ENTRIES)]
+    cache_max_entries: usize,
+
+    /// Address the HTTP transports listen on
+    #[arg(
+        long,
+        visible_alias = "listen",
+        env = "KAGI_HTTP_ADDR",
+        default_value = "127.0.0.1:8787"
+    )]
+    http_addr: SocketAddr,
+
+    #[cfg(feature = "http")]
+    #[command(flatten)]
+    auth: auth::AuthArgs,
+
+    /// Serve Prometheus metrics at `/metrics` on the HTTP transports
+    #[cfg(feature = "http")]
+    #[arg(long, env = "KAGI_METRICS")]
+    metrics: bool,
+
+    /// Address the TCP transport listens on
+    #[arg(long, env = "KAGI_TCP_ADDR", default_value = "127.0.0.1:8788")]
+    tcp_addr: SocketAddr,
+
+    /// Path of the socket the Unix socket transport listens on
+    #[arg(long, env = "KAGI_SOCKET_PATH")]
+    socket_path: Option<PathBuf>,
+
+    /// Stdio MCP server to spawn and front, as `name=command [args]`, or Streamable
+    /// HTTP server to connect to, as `name=URL`; its tools are listed as
+    /// `name__tool`. Repeatable, or `;`-separated in the environment
+    #[arg(long = "sub-server", env = "KAGI_SUB_SERVERS", value_delimiter = ';')]
+    sub_servers: Vec<hub::SubServerSpec>,
+
+    /// Only front the sub-servers: the Kagi tools are not served and no API key is
+    /// needed
+    #[arg(long, env = "KAGI_PROXY")]
+    proxy: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum Transport {
+    /// JSON-RPC over stdin and stdout, for hosts that spawn the server
+    #[default]
+    Stdio,
+    /// MCP Streamable HTTP at `/mcp`, for remote clients and editors sharing one
+    /// server (requires the `http` feature)
+    #[value(alias = "http")]
+    StreamableHttp,
+    /// The older HTTP with Server-Sent Events transport at `/sse` and `/messages`,
+    /// for clients without Streamable HTTP support (requires the `http` feature)
+    Sse,
+    /// JSON-RPC messages as WebSocket text frames at `/ws`, for gateways without
+    /// stdio (requires the `http` feature)
+    #[value(name = "websocket")]
+    WebSocket,
+    /// Newline-delimited JSON-RPC over TCP connections, one session each, for
+    /// supervisors sharing a long-lived server
+    Tcp,
+    /// Newline-delimited JSON-RPC over Unix socket connections, one session each
+    Unix,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print a markdown digest of the research recorded in the ledger
+    Digest {
+        /// Number of days to cover, ending now
+        #[arg(long, default_value_t = 7)]
+        days: u64,
+    },
+    /// Check the API key and that the Kagi endpoints can be reached, reporting the
+    /// balance and latency; exits with an error if anything fails
+    Check,
+    /// Print the tools the server offers with the given options, without serving them
+    ListTools {
+        /// Print the tools as JSON, as in a `tools/list` result
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the description, arguments and schemas of a tool, without serving it
+    DescribeTool {
+        /// Name of the tool, as printed by `list-tools`
+        name: String,
+        /// Print the tool as JSON, as in a `tools/list` result
+        #[arg(long)]
+        json: bool,
+    },
+    /// Call tools interactively, e.g. `search rust async`, printing the results the
+    /// assistant would receive
+    Repl,
+}
+
+/// Server behaviour that is independent of the Kagi API client
+///
+/// The `kagi-mcp-server` command line sets these; other crates start from the
+/// defaults.
+pub struct ServerOptions {
+    default_engine: SummarizerEngine,
+    tool_limits: concurrency::ToolLimits,
+    tool_timeouts: timeouts::ToolTimeouts,
+    rate_limits: ratelimit::RateLimits,
+    hooks: hooks::Hooks,
+    dispatch_mode: dispatch::DispatchMode,
+    verbose: bool,
+    secret_filter: secrets::SecretFilter,
+    disabled_tools: Vec<String>,
+    output_language: Option<String>,
+    topic_context: bool,
+    url_policy: urls::UrlPolicy,
+    ledger: Option<ledger::Ledger>,
+    audit: Option<audit::AuditLog>,
+    strict: strict::StrictMode,
+    smallweb_budget: f64,
+    summary_fallback_engine: Option<SummarizerEngine>,
+    hub: hub::Hub,
+    /// Whether the Kagi tools are served, rather than only those of sub-servers
+    kagi_tools: bool,
+    keepalive_interval: Option<Duration>,
+    max_message_size: usize,
+    list_page_size: Option<usize>,
+    cache: cache::ResultCache,
+    text_style: markdown::TextStyle,
+    budget: budget::Budget,
+    retry: retry::RetryPolicy,
+    shutdown_deadline: Duration,
+    cost_ceiling: spend::CostCeiling,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            default_engine: SummarizerEngine::Cecil,
+            tool_limits: concurrency::ToolLimits::default(),
+            tool_timeouts: timeouts::ToolTimeouts::default(),
+            rate_limits: ratelimit::RateLimits::default(),
+            hooks: hooks::Hooks::default(),
+            dispatch_mode: dispatch::DispatchMode::default(),
+            verbose: false,
+            secret_filter: secrets::SecretFilter::default(),
+            disabled_tools: Vec::new(),
+            output_language: None,
+            topic_context: false,
+            url_policy: urls::UrlPolicy::default(),
+            ledger: None,
+            audit: None,
+            strict: strict::StrictMode::default(),
+            smallweb_budget: 1.0,
+            summary_fallback_engine: None,
+            hub: hub::Hub::default(),
+            kagi_tools: true,
+            keepalive_interval: None,
+            max_message_size: transport::DEFAULT_MAX_MESSAGE_SIZE,
+            list_page_size: None,
+            cache: cache::ResultCache::default(),
+            text_style: markdown::TextStyle::default(),
+            budget: budget::Budget::default(),
+            retry: retry::RetryPolicy::default(),
+            shutdown_deadline: Duration::from_secs(DEFAULT_SHUTDOWN_TIMEOUT),
+            cost_ceiling: spend::CostCeiling::default(),
+        }
+    }
+}
+
+/// An MCP server offering the Kagi tools, and those of any added handlers
+pub struct KagiMcpServer {
+    client: KagiClient,
+    http: reqwest::Client,
+    default_engine: SummarizerEngine,
+    stats: Arc<heartbeat::ServerStats>,
+    tool_limits: concurrency::ToolLimits,
+    tool_timeouts: timeouts::ToolTimeouts,
+    rate_limits: ratelimit::RateLimits,
+    hooks: hooks::Hooks,
+    dispatch_mode: dispatch::DispatchMode,
+    verbose: bool,
+    secret_filter: secrets::SecretFilter,
+    disabled_tools: Vec<String>,
+    output_language: Option<String>,
+    /// Whether vague queries are extended with the topics of earlier ones in the
+    /// session
+    topic_context: bool,
+    in_flight_requests: Arc<cancellation::InFlightRequests>,
+    url_policy: urls::UrlPolicy,
+    ledger: Option<ledger::Ledger>,
+    audit: Option<audit::AuditLog>,
+    strict: strict::StrictMode,
+    /// Maximum estimated spend of a Small Web digest, in USD
+    smallweb_budget: f64,
+    /// Engine that retries suspiciously short summaries, if any
+    summary_fallback_engine: Option<SummarizerEngine>,
+    /// Compiled tool input schemas, built on the first tool call
+    args_validators: OnceLock<tools::ArgsValidators>,
+    /// Sub-servers whose tools are forwarded
+    hub: hub::Hub,
+    /// Handlers adding tools, resources and prompts
+    handlers: handler::Handlers,
+    /// Tools listed to clients
+    registry: registry::ToolRegistry,
+    /// Time between keepalive pings to idle session clients, if enabled
+    keepalive_interval: Option<Duration>,
+    /// Maximum size of a message on byte stream transports
+    max_message_size: usize,
+    /// Maximum number of items per page of list results, if they are paginated
+    list_page_size: Option<usize>,
+    /// Recent results of the paid Kagi tools
+    cache: cache::ResultCache,
+    /// How search and enrichment results are written
+    text_style: markdown::TextStyle,
+    /// Size limits of search and enrichment results
+    budget: budget::Budget,
+    /// Retries of Kagi requests failing with transient errors
+    retry: retry::RetryPolicy,
+    /// Time in-flight requests get to finish once their session ends or the server
+    /// shuts down
+    shutdown_deadline: Duration,
+    /// Most a session may spend on Kagi calls, by their estimated cost
+    cost_ceiling: spend::CostCeiling,
+    /// Cancelled to stop sessions from taking new requests
+    shutdown: CancellationToken,
+}
+
+/// Builds a server serving the tools, resources and prompts of handlers besides
+/// the built-in tools
+pub struct ServerBuilder {
+    client: KagiClient,
+    options: ServerOptions,
+    handlers: handler::Handlers,
+}
+
+impl ServerBuilder {
+    /// Add the tools of `handler`
+    #[must_use]
+    pub fn tools(mut self, handler: impl handler::ToolHandler + 'static) -> Self {
+        self.handlers.add_tools(Arc::new(handler));
+        self
+    }
+
+    /// Add the resources of `handler`
+    #[must_use]
+    pub fn resources(mut self, handler: impl handler::ResourceHandler + 'static) -> Self {
+        self.handlers.add_resources(Arc::new(handler));
+        self
+    }
+
+    /// Add the prompts of `handler`
+    #[must_use]
+    pub fn prompts(mut self, handler: impl handler::PromptHandler + 'static) -> Self {
+        self.handlers.add_prompts(Arc::new(handler));
+        self
+    }
+
+    /// Build the server, failing if two handlers, or a handler and the built-in
+    /// tools or a sub-server, provide tools of the same name
+    ///
+    /// # Errors
+    ///
+    /// Returns a message naming the tool that is provided twice.
+    pub fn build(self) -> Result<KagiMcpServer, String> {
+        let server = KagiMcpServer::with_handlers(self.client, self.options, self.handlers);
+        let mut names = std::collections::HashSet::new();
+        let builtin = server.get_tools();
+        for tool in builtin
+            .iter()
+            .chain(server.hub.tools())
+            .chain(server.handlers.tools())
+        {
+            if !names.insert(tool.name.as_str()) {
+                return Err(format!("tool '{}' is provided twice", tool.name));
+            }
+        }
+        Ok(server)
+    }
+}
+
+impl KagiMcpServer {
+    /// A server offering only the built-in tools
+    pub fn new(client: KagiClient, options: ServerOptions) -> Self {
+        Self::with_handlers(client, options, handler::Handlers::default())
+    }
+
+    /// Start building a server with handlers adding tools, resources and prompts
+    pub fn builder(client: KagiClient, options: ServerOptions) -> ServerBuilder {
+        ServerBuilder {
+            client,
+            options,
+            handlers: handler::Handlers::default(),
+        }
+    }
+
+    fn with_handlers(
+        client: KagiClient,
+        options: ServerOptions,
+        handlers: handler::Handlers,
+    ) -> Self {
+        let server = Self {
+            client,
+            http: reqwest::Client::builder()
+                .user_agent(concat!("kagi-mcp-server/", env!("CARGO_PKG_VERSION")))
+                .build()
+                .unwrap_or_default(),
+            default_engine: options.default_engine,
+            stats: Arc::default(),
+            tool_limits: options.tool_limits,
+            tool_timeouts: options.tool_timeouts,
+            rate_limits: options.rate_limits,
+            hooks: options.hooks,
+            dispatch_mode: options.dispatch_mode,
+            verbose: options.verbose,
+            secret_filter: options.secret_filter,
+            disabled_tools: options.disabled_tools,
+            output_language: options.output_language,
+            topic_context: options.topic_context,
+            in_flight_requests: Arc::default(),
+            url_policy: options.url_policy,
+            ledger: options.ledger,
+            audit: options.audit,
+            strict: options.strict,
+            smallweb_budget: options.smallweb_budget,
+            summary_fallback_engine: options.summary_fallback_engine,
+            args_validators: OnceLock::new(),
+            hub: options.hub,
+            handlers,
+            registry: registry::ToolRegistry::default(),
+            keepalive_interval: options.keepalive_interval,
+            max_message_size: options.max_message_size,
+            list_page_size: options.list_page_size,
+            cache: options.cache,
+            text_style: options.text_style,
+            budget: options.budget,
+            retry: options.retry,
+            shutdown_deadline: options.shutdown_deadline,
+            cost_ceiling: options.cost_ceiling,
+            shutdown: CancellationToken::new(),
+        };
+        let mut tools = if options.kagi_tools {
+            server.get_tools()
+        } else {
+            Vec::new()
+        };
+        tools.extend(server.hub.tools().cloned());
+        tools.extend(server.handlers.tools().cloned());
+        server.register_tools(tools);
+        server
+    }
+
+    /// Register `tools`, except those disabled by the configuration
+    fn register_tools(&self, tools: impl IntoIterator<Item = Tool>) {
+        for tool in tools {
+            if !self.disabled_tools.contains(&tool.name) {
+                self.registry.register(tool);
+            }
+        }
+    }
+
+    /// Record a successful tool call in the ledger, if one is configured
+    fn record(&self, tool: &str, subject: &str, sources: Vec<String>, cost: f64) {
+        if let Some(ledger) = &self.ledger {
+            ledger.record(tool, subject, sources, cost);
+        }
+    }
+
+    /// Add the most recent topic to a vague follow-up query and remember the query
+    ///
+    /// Returns the query unchanged unless topic context is enabled.
+    fn with_topic_context(&self, session: &Session, query: &str) -> String {
+        if !self.topic_context {
+            return query.to_string();
+        }
+        let topics = session.data::<topics::TopicCache>();
+        let query_with_topic = topics.contextualize(query);
+        topics.record(query);
+        query_with_topic
+    }
+
+    /// Run all queries concurrently and return their results in query order
+    ///
+    /// `queries` replace those of `args`, from which the other arguments are taken.
+    /// Each query returns up to `limit` results, after skipping the first `offset`.
+    /// The Search API has no offset, so the skipped results are fetched and dropped.
+    /// A page returned for several queries is only listed under the first of them,
+    /// with the snippets of all of them. Snippets and the whole result are kept
+    /// within the size budget by cutting snippets and dropping the last results.
+    ///
+    /// The results are returned as structured content too, and with
+    /// [`OutputFormat::Json`](tools::OutputFormat::Json) as the text content as well.
+    ///
+    /// When the caller supplied a progress token and there is more than one query,
+    /// each query's formatted results are also sent as a progress notification as
+    /// soon as that query completes.
+    async fn handle_search(
+        &self,
+        queries: &[String],
+        args: &tools::SearchArgs,
+        progress: &mut Progress<'_>,
+    ) -> Result<output::ToolOutput, String> {
+        let debug = args.debug.unwrap_or(self.verbose);
+        let limit = args.limit.unwrap_or(SEARCH_DEFAULT_LIMIT);
+        let offset = args.offset.unwrap_or(0);
+        let format = args.output_format.unwrap_or(match self.text_style {
+            markdown::TextStyle::Plain => tools::OutputFormat::Text,
+            markdown::TextStyle::Markdown => tools::OutputFormat::Markdown,
+        });
+        let budget = self.budget.with_overrides(&args.budget);
+        let progress = &*progress;
+        let mut searches: FuturesUnordered<_> = queries
+            .iter()
+            .enumerate()
+            .map(|(index, query)| async move {
+                (
+                    index,
+                    query.as_str(),
+                    self.retry
+                        .run(progress, || self.client.search(query, limit + offset))
+                        .await,
+                )
+            })
+            .collect();
+
+        let format_results =
+            |query: &str, response: &kagiapi::SearchResponse, first_number| match format {
+                tools::OutputFormat::Markdown => {
+                    markdown::search_results(query, response, first_number)
+                }
+                _ => self.format_search_results(query, response, first_number),
+            };
+        let total = queries.len();
+        let mut responses = vec![None; total];
+        let mut debug_meta = vec![None; total];
+        while let Some((index, query, result)) = searches.next().await {
+            let mut response =
+                result.map_err(|e| format!("Search failed for query '{query}': {e}"))?;
+            skip_search_results(&mut response.data, offset as usize);
+            self.record(
+                "kagi_search_fetch",
+                query,
+                response
+                    .data
+                    .iter()
+                    .filter(|result| result.result_type == 0)
+                    .filter_map(|result| result.url.clone())
+                    .collect(),
+                kagiapi::pricing::SEARCH_COST_PER_QUERY,
+            );
+
+            if total > 1 {
+                progress.report(
+                    Some(total),
+                    &format_results(query, &response, offset as usize + 1),
+                );
+            }
+
+            debug_meta[index] = Some(
+                debug::KagiMeta::new(&response.meta.id, &response.meta.node, response.meta.ms)
+                    .label(query),
+            );
+            responses[index] = Some(response);
+        }
+
+        // Number the results continuously, listing each page once
+        let mut responses: Vec<_> = responses.into_iter().flatten().collect();
+        search::merge_duplicates(&mut responses);
+        for result in responses.iter_mut().flat_map(|response| &mut response.data) {
+            budget.cut_snippet(&mut result.snippet);
+        }
+        let render = |responses: &[kagiapi::SearchResponse]| {
+            let mut formatted = Vec::with_capacity(total);
+            let mut structured = search::SearchResults {
+                queries: Vec::with_capacity(total),
+                truncated: 0,
+            };
+            let mut first_number = offset as usize + 1;
+            for (query, response) in queries.iter().zip(responses) {
+                formatted.push(format_results(query, response, first_number));
+                structured
+                    .queries
+                    .push(search::QueryResults::new(query, response, first_number));
+                first_number += search::numbered_count(response);
+            }
+            let text = match format {
+                tools::OutputFormat::Text | tools::OutputFormat::Markdown => formatted.join("\n"),
+                tools::OutputFormat::Json => {
+                    serde_json::to_string_pretty(&structured).unwrap_or_default()
+                }
+            };
+            (text, structured)
+        };
+        let truncated = budget.fit(
+            responses.as_mut_slice(),
+            |responses| render(responses).0,
+            search::drop_last_result,
+        );
+        let (mut text, mut structured) = render(&responses);
+        if truncated > 0 {
+            structured.truncated = truncated;
+            text = match format {
+                tools::OutputFormat::Json => {
+                    serde_json::to_string_pretty(&structured).map_err(|e| e.to_string())?
+                }
+                _ => format!("{text}\n{}", budget::truncation_marker(truncated)),
+            };
+        }
+        let top_urls = responses.iter().filter_map(|response| {
+            response
+                .data
+                .iter()
+                .find(|result| result.result_type == 0)
+                .and_then(|result| result.url.clone())
+        });
+        let mut content = vec![output::Content::Text { text }];
+        if debug {
+            let debug_meta: Vec<_> = debug_meta.into_iter().flatten().collect();
+            let mut debug_text = String::new();
+            debug::append(&mut debug_text, &debug_meta);
+            match (&mut content[0], format) {
+                // Keep the JSON parseable
+                (_, tools::OutputFormat::Json) => content.push(output::Content::Text {
+                    text: debug_text.trim_start().to_string(),
+                }),
+                (output::Content::Text { text }, _) => text.push_str(&debug_text),
+                _ => content.push(output::Content::Text {
+                    text: debug_text.trim_start().to_string(),
+                }),
+            }
+        }
+        let suggested_calls = if self.disabled_tools.iter().any(|t| t == "kagi_summarizer") {
+            Vec::new()
+        } else {
+            top_urls
+                .take(MAX_SUGGESTED_SUMMARIES)
+                .map(|url| output::SuggestedCall {
+                    name: "kagi_summarizer".to_string(),
+                    reason: format!("Summarize the top result {url}"),
+                    arguments: json!({ "url": url }),
+                })
+                .collect()
+        };
+        Ok(output::ToolOutput {
+            content,
+            is_error: false,
+            structured_content: serde_json::to_value(&structured).ok(),
+            suggested_calls,
+        })
+    }
+
+    /// Answer `query` with FastGPT and remember the answer in the session's `history`
+    ///
+    /// A `followup` query is sent with the questions and answers in `history`.
+    async fn handle_fastgpt(
+        &self,
+        query: &str,
+        options: FastGptOptions,
+        max_references: Option<usize>,
+        followup: bool,
+        debug: bool,
+        context: &ToolContext<'_>,
+    ) -> Result<output::ToolOutput, String> {
+        let history = context.session.data::<conversation::FastGptHistory>();
+        let mut api_query = if followup {
+            history.contextualize(query)
+        } else {
+            query.to_string()
+        };
+        // FastGPT has no language parameter, so ask for the language in the query
+        if let Some(language) = &self.output_language {
+            let _ = write!(api_query, "\n\nAnswer in language: {language}");
+        }
+
+        match self
+            .retry
+            .run(&context.progress, || {
+                self.client.fastgpt(&api_query, options)
+            })
+            .await
+        {
+            Ok(response) => {
+                history.record(query, &response.data);
+                self.record(
+                    if followup {
+                        "kagi_fastgpt_followup"
+                    } else {
+                        "kagi_fastgpt"
+                    },
+                    query,
+                    response
+                        .data
+                        .references
+                        .iter()
+                        .map(|reference| reference.url.clone())
+                        .collect(),
+                    kagiapi::pricing::FASTGPT_COST_PER_QUERY,
+                );
+                let answer = references::Answer::new(
+                    &response.data.output,
+                    &response.data.references,
+                    max_references,
+                );
+                let mut result = answer.text();
+
+                if debug {
+                    debug::append(
+                        &mut result,
+                        &[debug::KagiMeta::new(
+                            &response.meta.id,
+                            &response.meta.node,
+                            response.meta.ms,
+                        )
+                        .tokens(Some(response.data.tokens))],
+                    );
+                }
+                Ok(answer.into_output(result))
+            }
+            Err(e) => Err(format!("FastGPT failed for query '{query}': {e}")),
+        }
+    }
+
+    /// Look up enrichment results published since `recency`, keeping them within `budget`
+    async fn handle_enrich(
+        &self,
+        query: &str,
+        enrich_type: kagiapi::EnrichType,
+        recency: Option<recency::Recency>,
+        budget: budget::Budget,
+        debug: bool,
+        progress: &Progress<'_>,
+    ) -> Result<String, String> {
+        match self
+            .retry
+            .run(progress, || self.client.enrich(query, enrich_type))
+            .await
+        {
+            Ok(mut response) => {
+                let type_name = match enrich_type {
+                    kagiapi::EnrichType::Web => "web",
+                    kagiapi::EnrichType::News => "news",
+                };
+                let sources: Vec<String> = response
+                    .data
+                    .iter()
+                    .filter(|result| result.result_type == 0)
+                    .filter_map(|result| result.url.clone())
+                    .collect();
+                // Enrichment queries without results are not billed
+                let cost = if sources.is_empty() {
+                    0.0
+                } else {
+                    kagiapi::pricing::ENRICH_COST_PER_QUERY
+                };
+                self.record(&format!("kagi_enrich_{type_name}"), query, sources, cost);
+
+                let outdated = recency.map_or(0, |recency| recency.retain(&mut response.data));
+                for result in &mut response.data {
+                    budget.cut_snippet(&mut result.snippet);
+                }
+                let render = |results: &[kagiapi::EnrichResult]| match self.text_style {
+                    markdown::TextStyle::Plain => format_enrich_results(type_name, query, results),
+                    markdown::TextStyle::Markdown => {
+                        markdown::enrich_results(type_name, query, results)
+                    }
+                };
+                let truncated = budget.fit(
+                    &mut response.data,
+                    |results| render(results),
+                    |results| match results.iter().rposition(|result| result.result_type == 0) {
+                        Some(index) => {
+                            results.remove(index);
+                            true
+                        }
+                        None => false,
+                    },
+                );
+                let mut formatted_results = render(&response.data);
+                if truncated > 0 {
+                    formatted_results.push_str(&budget::truncation_marker(truncated));
+                }
+                if let Some(recency) = recency.filter(|_| outdated > 0) {
+                    formatted_results.push_str(&recency.marker(outdated));
+                }
+
+                if debug {
+                    debug::append(
+                        &mut formatted_results,
+                        &[debug::KagiMeta::new(
+                            &response.meta.id,
+                            &response.meta.node,
+                            response.meta.ms,
+                        )],
+                    );
+                }
+                Ok(formatted_results)
+            }
+            Err(e) => Err(format!("Enrichment failed for query '{query}': {e}")),
+        }
+    }
+
+    /// Format the results of a search, numbering them from `first_number`
+    #[allow(clippy::unused_self)]
+    fn format_search_results(
+        &self,
+        query: &str,
+        response: &kagiapi::SearchResponse,
+        first_number: usize,
+    ) -> String {
+        let mut output = format!("-----\nResults for search query \"{query}\":\n-----\n");
+        let mut result_number = first_number;
+
+        for result in &response.data {
+            match result.result_type {
+                0 => {
+                    // Standard search result type
+                    if let (Some(title), Some(url)) = (&result.title, &result.url) {
+                        let _ = writeln!(output, "{result_number}: {title}\n{url}");
+
+                        // Add published date if available
+                        let _ = writeln!(
+                            output,
+                            "Published Date: {}",
+                            result.published.as_deref().unwrap_or("Not Available")
+                        );
+
+                        // Add snippet if available
+                        if let Some(snippet) = &result.snippet {
+                            let _ = writeln!(output, "{snippet}");
+                        }
+
+                        output.push('\n');
+                        result_number += 1;
+                    }
+                }
+                1 => {
+                    // Related searches type
+                    if let Some(list) = &result.list {
+                        output.push_str("Related searches:\n");
+                        for item in list {
+                            let _ = writeln!(output, "- {item}");
+                        }
+                        output.push('\n');
+                    }
+                }
+                _ => {
+                    // Unknown result type - try to extract what we can
+                    if let Some(title) = &result.title {
+                        let _ = writeln!(output, "{result_number}: {title}");
+                        if let Some(url) = &result.url {
+                            let _ = writeln!(output, "{url}");
+                        }
+                        if let Some(snippet) = &result.snippet {
+                            let _ = writeln!(output, "{snippet}");
+                        }
+                        output.push('\n');
+                        result_number += 1;
+                    }
+                }
+            }
+        }
+
+        output
+    }
+
+    async fn handle_summarize(
+        &self,
+        args: tools::SummarizerArgs,
+        context: &mut ToolContext<'_>,
+    ) -> Result<String, String> {
+        let url = args.url.as_str();
+        let debug = args.debug.unwrap_or(self.verbose);
+        let engine = args.engine.map_or(self.default_engine, Into::into);
+        let options = SummarizeOptions {
+            engine: Some(engine),
+            summary_type: Some(args.summary_type.into()),
+            target_language: args
+                .target_language
+                .or_else(|| self.output_language.clone()),
+        };
+
+        // Kagi can't reach local files and private hosts, so upload their text instead
+        let local_text = if local::is_file_url(url) {
+            Some(
+                local::read_file(url, &context.session.roots().await)
+                    .await
+                    .map_err(|e| format!("Summarization failed: {e}"))?,
+            )
+        } else if local::is_private_url(url) {
+            Some(
+                local::fetch_text(&self.http, url)
+                    .await
+                    .map_err(|e| format!("Summarization failed: {e}"))?,
+            )
+        } else {
+            None
+        };
+
+        let (summary, cost, fallback_note) = self
+            .summarize(url, local_text.as_deref(), options, context)
+            .await?;
+        self.record("kagi_summarizer", url, vec![url.to_string()], cost);
+        Ok(format_summary(summary, fallback_note, debug))
+    }
+
+    async fn handle_summarize_text(
+        &self,
+        args: tools::SummarizeTextArgs,
+        context: &mut ToolContext<'_>,
+    ) -> Result<String, String> {
+        if args.text.trim().is_empty() {
+            return Err("Summarization failed: the text is empty".to_string());
+        }
+        let options = SummarizeOptions {
+            engine: Some(args.engine.map_or(self.default_engine, Into::into)),
+            summary_type: Some(args.summary_type.into()),
+            target_language: args
+                .target_language
+                .or_else(|| self.output_language.clone()),
+        };
+        let (summary, cost, fallback_note) = self
+            .summarize("", Some(&args.text), options, context)
+            .await?;
+        let subject: String = args
+            .text
+            .split_whitespace()
+            .take(8)
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.record("kagi_summarizer_text", &subject, Vec::new(), cost);
+        Ok(format_summary(
+            summary,
+            fallback_note,
+            args.debug.unwrap_or(self.verbose),
+        ))
+    }
+
+    /// Summarize `url`, or `local_text` when given, retrying short summaries with
+    /// the fallback engine
+    ///
+    /// Returns the summary with its total cost, and a note for the assistant if
+    /// the fallback engine was used.
+    async fn summarize(
+        &self,
+        url: &str,
+        local_text: Option<&str>,
+        options: SummarizeOptions,
+        context: &mut ToolContext<'_>,
+    ) -> Result<(kagiapi::SummaryResponse, f64, Option<String>), String> {
+        let engine = options.engine.unwrap_or(self.default_engine);
+        let mut summary = self
+            .stream_summary(url, local_text, options.clone(), &mut context.progress)
+            .await?;
+        let mut cost = summary_cost(engine, summary.data.tokens);
+        let mut fallback_note = None;
+        if let Some(fallback) = self.summary_fallback_engine.filter(|f| *f != engine) {
+            if fallback::is_suspiciously_short(&summary.data.output, summary.data.tokens) {
+                context.progress.report(
+                    None,
+                    &format!("The summary is unusually short, retrying with {fallback:?}"),
+                );
+                let options = SummarizeOptions {
+                    engine: Some(fallback),
+                    ..options
+                };
+                match self
+                    .stream_summary(url, local_text, options, &mut context.progress)
+                    .await
+                {
+                    Ok(retry) => {
+                        cost += summary_cost(fallback, retry.data.tokens);
+                        fallback_note = Some(fallback::note(engine, fallback, summary.data.tokens));
+                        summary = retry;
+                    }
+                    // The first summary is still better than none
+                    Err(e) => {
+                        let message = format!("Fallback summary with {fallback:?} failed: {e}");
+                        tracing::warn!("{message}");
+                        context.log(logging::LogLevel::Warning, &message);
+                    }
+                }
+            }
+        }
+
+        Ok((summary, cost, fallback_note))
+    }
+
+    /// Stream a summary of `url`, or of `local_text` when given, reporting progress
+    ///
+    /// A stream failing before any part was summarized is retried like other requests.
+    async fn stream_summary(
+        &self,
+        url: &str,
+        local_text: Option<&str>,
+        options: SummarizeOptions,
+        progress: &mut Progress<'_>,
+    ) -> Result<kagiapi::SummaryResponse, String> {
+        let engine = options.engine.unwrap_or(self.default_engine);
+        let start = || match local_text {
+            Some(text) => self
+                .client
+                .summarize_text_stream(text, options.clone())
+                .boxed(),
+            None => self.client.summarize_stream(url, options.clone()).boxed(),
+        };
+        let mut events = start();
+        let mut summarized_parts = false;
+        let mut retry = 0;
+
+        // Slow engines can take half a minute, so keep the client informed
+        let started = Instant::now();
+        let mut ticker = tokio::time::interval(SUMMARY_PROGRESS_INTERVAL);
+        ticker.tick().await;
+        loop {
+            tokio::select! {
+                event = events.next() => match event {
+                    Some(Ok(SummaryEvent::Partial { index, total, output })) => {
+                        summarized_parts = true;
+                        progress.report(
+                            None,
+                            &format!("Summarized part {} of {total}:\n{output}", index + 1),
+                        );
+                    }
+                    Some(Ok(SummaryEvent::Done(summary))) => return Ok(summary),
+                    Some(Err(e)) => match self.retry.delay(retry, &e).filter(|_| !summarized_parts) {
+                        Some(delay) => {
+                            progress.report(None, &retry::message(&e, delay));
+                            tokio::time::sleep(delay).await;
+                            retry += 1;
+                            events = start();
+                        }
+                        None => return Err(format!("Summarization failed: {e}")),
+                    },
+                    None => return Err("Summarization failed: no summary returned".to_string()),
+                },
+                _ = ticker.tick(), if progress.is_enabled() => {
+                    progress.report(
+                        None,
+                        &format!(
+                            "Summarizing with {engine:?}, {}s elapsed",
+                            started.elapsed().as_secs()
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Summarize the latest Small Web posts matching `keyword` as key takeaways
+    ///
+    /// Summaries run concurrently. Only as many are started as the budget covers at
+    /// their worst-case cost; the remaining matches are listed without a summary.
+    async fn handle_smallweb_digest(
+        &self,
+        keyword: &str,
+        limit: usize,
+        debug: bool,
+        progress: &Progress<'_>,
+    ) -> Result<String, String> {
+        let entries: Vec<kagiapi::SmallWebEntry> = self
+            .client
+            .smallweb_feed(None)
+            .await
+            .map_err(|e| format!("Fetching the Small Web feed failed: {e}"))?
+            .into_iter()
+            .filter(|entry| entry.matches(keyword))
+            .take(limit)
+            .collect();
+        if entries.is_empty() {
+            return Ok(format!("No recent Small Web posts mention '{keyword}'."));
+        }
+
+        let engine = self.default_engine;
+        let estimate = kagiapi::pricing::estimate_cost(&kagiapi::SummarizeRequest {
+            url: Some(entries[0].url.clone()),
+            engine: Some(engine),
+            ..kagiapi::SummarizeRequest::default()
+        });
+        let affordable = (1..=entries.len())
+            .take_while(|&count| count as f64 * estimate <= self.smallweb_budget)
+            .count();
+        let summaries = futures::future::join_all(entries[..affordable].iter().map(|entry| {
+            self.retry.run(progress, || {
+                self.client.summarize(
+                    &entry.url,
+                    SummarizeOptions {
+                        engine: Some(engine),
+                        summary_type: Some(SummaryType::Takeaway),
+                        target_language: self.output_language.clone(),
+                    },
+                )
+            })
+        }))
+        .await;
+
+        let mut output = format!(
+            "Small Web posts mentioning '{keyword}' ({affordable} of {} summarized):\n\n",
+            entries.len()
+        );
+        let mut cost = 0.0;
+        let mut debug_meta = Vec::new();
+        for (index, entry) in entries.iter().enumerate() {
+            let _ = writeln!(output, "{}. {}", index + 1, entry.title);
+            let _ = writeln!(output, "   URL: {}", entry.url);
+            if let Some(author) = &entry.author {
+                let _ = writeln!(output, "   By: {author}");
+            }
+            if let Some(published) = &entry.published {
+                let _ = writeln!(output, "   Published: {published}");
+            }
+            match summaries.get(index) {
+                Some(Ok(summary)) => {
+                    cost += summary_cost(engine, summary.data.tokens);
+                    debug_meta.push(
+                        debug::KagiMeta::new(&summary.meta.id, &summary.meta.node, summary.meta.ms)
+                            .label(&entry.url)
+                            .tokens(summary.data.tokens),
+                    );
+                    let _ = writeln!(output, "   Takeaways:\n{}", summary.data.output.trim());
+                }
+                Some(Err(e)) => {
+                    let _ = writeln!(output, "   Summary failed: {e}");
+                }
+                None => {
+                    let _ = writeln!(
+                        output,
+                        "   Not summarized: the digest budget of ${:.2} is spent.",
+                        self.smallweb_budget
+                    );
+                }
+            }
+            output.push('\n');
+        }
+
+        self.record(
+            "kagi_smallweb_digest",
+            keyword,
+            entries.iter().map(|entry| entry.url.clone()).collect(),
+            cost,
+        );
+        if debug {
+            debug::append(&mut output, &debug_meta);
+        }
+        Ok(output)
+    }
+
+    /// Search `query` and summarize the top `sources` results concurrently into one
+    /// briefing
+    ///
+    /// Each finished summary is reported as progress, as the briefing takes as long
+    /// as the slowest summary.
+    async fn handle_research(
+        &self,
+        query: &str,
+        sources: usize,
+        options: SummarizeOptions,
+        debug: bool,
+        session: &Session,
+        progress: &mut Progress<'_>,
+    ) -> Result<String, String> {
+        let progress = &*progress;
+        let response = self
+            .retry
+            .run(progress, || self.client.search(query, SEARCH_DEFAULT_LIMIT))
+            .await
+            .map_err(|e| format!("Search failed for query '{query}': {e}"))?;
+        let results: Vec<(&str, &str)> = response
+            .data
+            .iter()
+            .filter(|result| result.result_type == 0)
+            .filter_map(|result| Some((result.title.as_deref()?, result.url.as_deref()?)))
+            .take(sources)
+            .collect();
+        if results.is_empty() {
+            return Ok(format!("No search results found for '{query}'."));
+        }
+
+        let mut summaries: FuturesUnordered<_> = results
+            .iter()
+            .enumerate()
+            .map(|(index, (_, url))| {
+                let options = options.clone();
+                async move {
+                    let summary = self
+                        .retry
+                        .run(progress, || self.client.summarize(url, options.clone()))
+                        .await;
+                    (index, summary)
+                }
+            })
+            .collect();
+        let total = results.len();
+        let mut briefs: Vec<_> = std::iter::repeat_with(|| None).take(total).collect();
+        while let Some((index, summary)) = summaries.next().await {
+            progress.report(Some(total), &format!("Summarized {}", results[index].1));
+            briefs[index] = Some(summary);
+        }
+
+        let engine = options.engine.unwrap_or(self.default_engine);
+        let mut cost = kagiapi::pricing::SEARCH_COST_PER_QUERY;
+        let mut debug_meta =
+            vec![
+                debug::KagiMeta::new(&response.meta.id, &response.meta.node, response.meta.ms)
+                    .label(query),
+            ];
+        let mut sections = String::new();
+        for (index, ((title, url), summary)) in results.iter().zip(briefs).enumerate() {
+            let _ = writeln!(sections, "## {}. {title}\n{url}\n", index + 1);
+            match summary {
+                Some(Ok(summary)) => {
+                    cost += summary_cost(engine, summary.data.tokens);
+                    debug_meta.push(
+                        debug::KagiMeta::new(&summary.meta.id, &summary.meta.node, summary.meta.ms)
+                            .label(*url)
+                            .tokens(summary.data.tokens),
+                    );
+                    let _ = writeln!(sections, "{}\n", summary.data.output.trim());
+                }
+                Some(Err(e)) => {
+                    let _ = writeln!(sections, "Summary failed: {e}\n");
+                }
+                None => {}
+            }
+        }
+        let mut output = format!("# Research briefing: {query}\n\n");
+        if let Some(overview) = research_overview(session, query, &sections).await {
+            let _ = writeln!(output, "## Overview\n\n{overview}\n");
+        }
+        output.push_str(&sections);
+        output.push_str("## Sources\n\n");
+        for (index, (title, url)) in results.iter().enumerate() {
+            let _ = writeln!(output, "[{}] {title} - {url}", index + 1);
+        }
+
+        self.record(
+            "kagi_research",
+            query,
+            results.iter().map(|(_, url)| url.to_string()).collect(),
+            cost,
+        );
+        if debug {
+            debug::append(&mut output, &debug_meta);
+        }
+        Ok(output)
+    }
+
+    /// Unfurl `url`, returning the result and what it cost
+    async fn handle_unfurl(&self, url: &str) -> Result<(output::ToolOutput, f64), String> {
+        let unfurled = |meta| unfurl::Unfurled {
+            url: url.to_string(),
+            meta,
+        };
+        // Prefer the page's own metadata, which costs nothing
+        if let Ok(meta) = unfurl::fetch_meta(&self.http, url).await {
+            if meta.title.is_some() || meta.description.is_some() {
+                return Ok((unfurled(meta).into_output(), 0.0));
+            }
+        }
+
+        // Fall back to the cheapest summarizer configuration for a one-line description
+        match self
+            .client
+            .summarize(
+                url,
+                SummarizeOptions {
+                    engine: Some(SummarizerEngine::Cecil),
+                    summary_type: Some(SummaryType::Takeaway),
+                    target_language: None,
+                },
+            )
+            .await
+        {
+            Ok(summary) => {
+                let description = summary
+                    .data
+                    .output
+                    .lines()
+                    .map(|line| line.trim_start_matches(['-', '*', ' ']).trim())
+                    .find(|line| !line.is_empty())
+                    .map(|line| unfurl::first_sentence(line).to_string());
+                let meta = unfurl::PageMeta {
+                    description,
+                    ..unfurl::PageMeta::default()
+                };
+                let cost = summary_cost(SummarizerEngine::Cecil, summary.data.tokens);
+                self.record("kagi_unfurl", url, vec![url.to_string()], cost);
+                Ok((unfurled(meta).into_output(), cost))
+            }
+            Err(e) => Err(format!("Unfurl failed for '{url}': {e}")),
+        }
+    }
+
+    /// Call tool `name`, or return its cached result for the same arguments
+    ///
+    /// Only calls reaching Kagi count towards the session's cost ceiling.
+    async fn call_tool(
+        &self,
+        name: &str,
+        args: Value,
+        context: &mut ToolContext<'_>,
+    ) -> Result<output::ToolOutput, tools::ToolCallError> {
+        let key = self.cache_key(name, &args);
+        if let Some(output) = key.as_deref().and_then(|key| self.cache.get(key)) {
+            return Ok(output);
+        }
+        let estimate = spend::estimate(name, &args, self.default_engine, self.smallweb_budget);
+        let reservation = self
+            .cost_ceiling
+            .reserve(name, estimate, context.session)
+            .await?;
+        let output = self.run_tool(name, args, context).await?;
+        if !output.is_error {
+            match context.billed {
+                Some(cost) => reservation.settle(cost),
+                None => reservation.commit(),
+            }
+        }
+        if let Some(key) = key {
+            self.cache.insert(key, &output);
+        }
+        Ok(output)
+    }
+
+    /// The key the result of a call is cached under, if it may be cached
+    ///
+    /// Results that depend on more than the arguments are not: queries extended
+    /// with the session's topics, uncached `FastGPT` answers and summaries of local
+    /// pages, which may change at any time.
+    fn cache_key(&self, name: &str, args: &Value) -> Option<String> {
+        let key = self.cache.key(name, args)?;
+        let depends_on_session = self.topic_context
+            && matches!(name, "kagi_search_fetch" | "kagi_research" | "kagi_fastgpt");
+        let uncached = args["cache"] == Value::Bool(false);
+        let local = args["url"]
+            .as_str()
+            .is_some_and(|url| local::is_private_url(url) || local::is_file_url(url));
+        (!depends_on_session && !uncached && !local).then_some(key)
+    }
+
+    /// Validate and deserialize the arguments of tool `name`, then run it
+    async fn run_tool(
+        &self,
+        name: &str,
+        args: Value,
+        context: &mut ToolContext<'_>,
+    ) -> Result<output::ToolOutput, tools::ToolCallError> {
+        self.args_validators
+            .get_or_init(|| {
+                let tools = self.get_tools();
+                tools::ArgsValidators::new(
+                    tools
+                        .iter()
+                        .chain(self.handlers.tools())
+                        .map(|tool| (tool.name.as_str(), &tool.input_schema)),
+                )
+            })
+            .validate(name, &args)?;
+
+        let result = match name {
+            "kagi_search_fetch" => {
+                let args: tools::SearchArgs = tools::parse_args(args)?;
+                let queries: Vec<String> = args
+                    .queries
+                    .iter()
+                    .map(|query| self.with_topic_context(context.session, query))
+                    .collect();
+                return Ok(self
+                    .handle_search(&queries, &args, &mut context.progress)
+                    .await
+                    .unwrap_or_else(output::ToolOutput::error));
+            }
+            "kagi_summarizer" => {
+                let args: tools::SummarizerArgs = tools::parse_args(args)?;
+                context.timeout.request(&args);
+                self.handle_summarize(args, context).await
+            }
+            "kagi_summarizer_text" => {
+                let args: tools::SummarizeTextArgs = tools::parse_args(args)?;
+                self.handle_summarize_text(args, context).await
+            }
+            "kagi_research" => {
+                let args: tools::ResearchArgs = tools::parse_args(args)?;
+                let sources = research_sources(args.sources);
+                let options = SummarizeOptions {
+                    engine: Some(args.engine.map_or(self.default_engine, Into::into)),
+                    summary_type: Some(args.summary_type.into()),
+                    target_language: self.output_language.clone(),
+                };
+                self.handle_research(
+                    &self.with_topic_context(context.session, &args.query),
+                    sources,
+                    options,
+                    args.debug.unwrap_or(self.verbose),
+                    context.session,
+                    &mut context.progress,
+                )
+                .await
+            }
+            "kagi_unfurl" => {
+                let args: tools::UnfurlArgs = tools::parse_args(args)?;
+                return Ok(match self.handle_unfurl(&args.url).await {
+                    Ok((output, cost)) => {
+                        context.billed = Some(cost);
+                        output
+                    }
+                    Err(e) => output::ToolOutput::error(e),
+                });
+            }
+            "kagi_fastgpt" => {
+                let args: tools::FastGptArgs = tools::parse_args(args)?;
+                context.timeout.request(&args);
+                return Ok(self
+                    .handle_fastgpt(
+                        &self.with_topic_context(context.session, &args.query),
+                        FastGptOptions {
+                            cache: args.cache,
+                            web_search: args.web_search,
+                        },
+                        args.max_references,
+                        false,
+                        args.debug.unwrap_or(self.verbose),
+                        context,
+                    )
+                    .await
+                    .unwrap_or_else(output::ToolOutput::error));
+            }
+            "kagi_fastgpt_followup" => {
+                let args: tools::FastGptFollowupArgs = tools::parse_args(args)?;
+                return Ok(self
+                    .handle_fastgpt(
+                        &args.query,
+                        FastGptOptions::default(),
+                        args.max_references,
+                        true,
+                        args.debug.unwrap_or(self.verbose),
+                        context,
+                    )
+                    .await
+                    .unwrap_or_else(output::ToolOutput::error));
+            }
+            "kagi_enrich" => {
+                let args: tools::EnrichArgs = tools::parse_args(args)?;
+                let debug = args.debug.unwrap_or(self.verbose);
+                let budget = self.budget.with_overrides(&args.budget);
+                let recency = match args.enrich_type {
+                    tools::EnrichType::Web
+                        if args.since.is_some() || args.max_age_days.is_some() =>
+                    {
+                        return Err(tools::ToolCallError::invalid_params(
+                            "since and max_age_days only apply to enrich_type 'news'".to_string(),
+                        ))
+                    }
+                    tools::EnrichType::Web => None,
+                    tools::EnrichType::News => recency::Recency::new(
+                        args.since.as_deref(),
+                        args.max_age_days,
+                        ledger::now(),
+                    )
+                    .map_err(tools::ToolCallError::invalid_params)?,
+                };
+                self.handle_enrich(
+                    &args.query,
+                    args.enrich_type.into(),
+                    recency,
+                    budget,
+                    debug,
+                    &context.progress,
+                )
+                .await
+            }
+            "kagi_smallweb_digest" => {
+                let args: tools::SmallWebDigestArgs = tools::parse_args(args)?;
+                let limit = args
+                    .limit
+                    .unwrap_or(SMALLWEB_DIGEST_DEFAULT_LIMIT)
+                    .clamp(1, SMALLWEB_DIGEST_MAX_LIMIT);
+                self.handle_smallweb_digest(
+                    &args.keyword,
+                    limit,
+                    args.debug.unwrap_or(self.verbose),
+                    &context.progress,
+                )
+                .await
+            }
+            _ => {
+                return match self.handlers.tool(name) {
+                    Some(handler) => handler.call(name, args, context).await,
+                    None => Err(tools::ToolCallError::not_found(name)),
+                }
+            }
+        };
+        Ok(result.map_or_else(output::ToolOutput::error, output::ToolOutput::from))
+    }
+
+    fn get_tools(&self) -> Vec<Tool> {
+        let mut summarizer_schema = tools::input_schema::<tools::SummarizerArgs>();
+        let mut summarize_text_schema = tools::input_schema::<tools::SummarizeTextArgs>();
+        for schema in [&mut summarizer_schema, &mut summarize_text_schema] {
+            tools::set_description(
+                schema,
+                "engine",
+                format!(
+                    "Summarization engine to use. Defaults to configured engine. 'muriel' is billed at a flat ${:.2} per summary, other engines cost at most ${:.2}; only use 'muriel' when the user asks for it.",
+                    kagiapi::pricing::MURIEL_COST_PER_SUMMARY,
+                    kagiapi::pricing::estimate_cost(&kagiapi::SummarizeRequest::default()),
+                ),
+            );
+            tools::set_description(
+                schema,
+                "target_language",
+                if self.output_language.is_some() {
+                    "Desired output language using language codes (e.g., 'EN' for English). If not specified, the user's configured language is used."
+                } else {
+                    "Desired output language using language codes (e.g., 'EN' for English). If not specified, the document's original language influences the output."
+                },
+            );
+        }
+
+        // Disabling a former name of kagi_enrich takes its type out of the tool
+        let enrich_types: Vec<&str> = tools::ENRICH_ALIASES
+            .iter()
+            .filter(|(alias, _)| !self.disabled_tools.iter().any(|tool| tool == alias))
+            .map(|(_, enrich_type)| *enrich_type)
+            .collect();
+        let mut enrich_schema = tools::input_schema::<tools::EnrichArgs>();
+        enrich_schema["properties"]["enrich_type"]["enum"] = json!(enrich_types);
+
+        let tools = vec![
+            Tool {
+                output_schema: Some(tools::output_schema::<search::SearchResults>()),
+                ..Tool::kagi(
+                    "kagi_search_fetch",
+                    "Kagi Search",
+                    "Fetch web results based on one or more queries using the Kagi Search API. Use for general search and when the user explicitly tells you to 'fetch' results/information. Results are from all queries given. They are numbered continuously, so that a user may be able to refer to a result by a specific number.",
+                    tools::input_schema::<tools::SearchArgs>(),
+                )
+            },
+            Tool::kagi(
+                "kagi_summarizer",
+                "Kagi Summarizer",
+                "Summarize content from a URL using the Kagi Summarizer API. The Summarizer can summarize any document type (text webpage, video, audio, etc.)",
+                summarizer_schema,
+            ),
+            Tool::kagi(
+                "kagi_summarizer_text",
+                "Kagi Text Summarizer",
+                "Summarize text using the Kagi Summarizer API. Use for pasted content, editor selections or other text that has no public URL; use kagi_summarizer for URLs.",
+                summarize_text_schema,
+            ),
+            Tool {
+                output_schema: Some(tools::output_schema::<unfurl::Unfurled>()),
+                ..Tool::kagi(
+                    "kagi_unfurl",
+                    "Kagi Link Preview",
+                    "Get the title, site name, published date and a one-sentence description of a URL. Much cheaper than a full summary; use when you only need to label or identify a link.",
+                    tools::input_schema::<tools::UnfurlArgs>(),
+                )
+            },
+            Tool {
+                output_schema: Some(tools::output_schema::<references::Answer>()),
+                ..Tool::kagi(
+                    "kagi_fastgpt",
+                    "Kagi FastGPT",
+                    "Generate AI-powered answers to questions using the Kagi FastGPT API. This tool performs web searches automatically to provide well-referenced, up-to-date responses. Use for direct questions that need AI-generated answers with citations.",
+                    tools::input_schema::<tools::FastGptArgs>(),
+                )
+            },
+            Tool {
+                output_schema: Some(tools::output_schema::<references::Answer>()),
+                ..Tool::kagi(
+                    "kagi_fastgpt_followup",
+                    "Kagi FastGPT Follow-up",
+                    "Ask FastGPT a follow-up question about its earlier answers in this conversation. The recent kagi_fastgpt questions, answers and references are sent along with the question, so it can refer to them, e.g. 'which of those supports Windows?'. Billed like kagi_fastgpt.",
+                    tools::input_schema::<tools::FastGptFollowupArgs>(),
+                )
+            },
+            Tool::kagi(
+                "kagi_enrich",
+                "Kagi Enrichment",
+                "Find content that regular search results miss using Kagi's Enrichment API: with enrich_type 'web', non-commercial 'small web' sites and discussions; with 'news', non-mainstream news sources and alternative perspectives on current events.",
+                enrich_schema,
+            ),
+            Tool::kagi(
+                "kagi_research",
+                "Kagi Research",
+                format!(
+                    "Search a query and summarize each of the top results into one briefing with numbered sources. Use instead of kagi_search_fetch followed by several kagi_summarizer calls. Costs one search plus one summary per source, at most ${:.2} each. Clients that support sampling get an overview of the sources written by their own model.",
+                    kagiapi::pricing::estimate_cost(&kagiapi::SummarizeRequest::default()),
+                ),
+                tools::input_schema::<tools::ResearchArgs>(),
+            ),
+            Tool::kagi(
+                "kagi_smallweb_digest",
+                "Kagi Small Web Digest",
+                format!(
+                    "Summarize the latest posts from Kagi's Small Web feed of independent, non-commercial websites that mention a keyword, as bulleted key takeaways. Each summary is billed like kagi_summarizer; at most ${:.2} is spent per call, and posts beyond that are listed without a summary.",
+                    self.smallweb_budget
+                ),
+                tools::input_schema::<tools::SmallWebDigestArgs>(),
+            ),
+        ];
+
+        tools
+            .into_iter()
+            .filter(|tool| !self.disabled_tools.contains(&tool.name))
+            .filter(|tool| tool.name != "kagi_enrich" || !enrich_types.is_empty())
+            .map(|mut tool| {
+                let notes = self.deployment_notes(&tool.name);
+                if !notes.is_empty() {
+                    tool.description = format!("{} {}", tool.description, notes.join(" "));
+                }
+                if self.verbose {
+                    tools::set_description(
+                        &mut tool.input_schema,
+                        "debug",
+                        "Kagi request metadata is appended to every result on this server; pass false to leave it out.",
+                    );
+                }
+                tool
+            })
+            .collect()
+    }
+
+    /// Sentences describing the server options that change how `tool` behaves
+    ///
+    /// Appended to tool descriptions, so the model knows what this deployment does
+    /// with its arguments and results.
+    fn deployment_notes(&self, tool: &str) -> Vec<String> {
+        let mut notes = Vec::new();
+        if self.topic_context && matches!(tool, "kagi_search_fetch" | "kagi_fastgpt") {
+            notes.push(
+                "Vague follow-up queries such as 'its performance' are extended with the key terms of the previous query."
+                    .to_string(),
+            );
+        }
+        match self.secret_filter {
+            secrets::SecretFilter::Off => {}
+            secrets::SecretFilter::Mask => notes.push(
+                "Likely credentials in arguments are masked before they are sent to Kagi."
+                    .to_string(),
+            ),
+            secrets::SecretFilter::Refuse => notes
+                .push("Calls whose arguments contain likely credentials are refused.".to_string()),
+        }
+        if let Some(fallback) = self.summary_fallback_engine {
+            if tool == "kagi_summarizer" {
+                notes.push(format!(
+                    "Empty or unusually short summaries of long documents are regenerated once with the {} engine, which is billed as well.",
+                    fallback::engine_name(fallback)
+                ));
+            }
+        }
+        notes.extend(self.url_policy.describe());
+        notes
+    }
+
+    async fn handle_request(
+        &self,
+        request: McpRequest,
+        session: &Session,
+        notifier: &Notifier,
+        cancellation: &CancellationToken,
+    ) -> McpResponse {
+        match request.method.as_str() {
+            "initialize" => {
+                session.initialize(request.params.as_ref(), strict::PROTOCOL_VERSION);
+                let mut capabilities = json!({
+                    "tools": {"listChanged": true},
+                    "logging": {},
+                    "experimental": {
+                        output::SUGGESTED_CALLS_CAPABILITY: {}
+                    }
+                });
+                if self.handlers.has_resources() {
+                    capabilities["resources"] = json!({});
+                }
+                if self.handlers.has_prompts() {
+                    capabilities["prompts"] = json!({});
+                }
+                McpResponse::result(
+                    request.id,
+                    json!({
+                        "protocolVersion": strict::PROTOCOL_VERSION,
+                        "capabilities": capabilities,
+                        "serverInfo": {
+                            "name": "kagi-mcp-server",
+                            "version": env!("CARGO_PKG_VERSION")
+                        }
+                    }),
+                )
+            }
+            "ping" => McpResponse::result(request.id, json!({})),
+            "logging/setLevel" => {
+                let level = request
+                    .params
+                    .as_ref()
+                    .and_then(|params| params.get("level"))
+                    .and_then(|level| serde_json::from_value(level.clone()).ok());
+                match level {
+                    Some(level) => {
+                        session.set_log_level(level);
+                        McpResponse::result(request.id, json!({}))
+                    }
+                    None => McpResponse::error(
+                        request.id,
+                        ErrorCode::InvalidParams,
+                        "Missing or unknown log level",
+                    ),
+                }
+            }
+            "tools/list" => {
+                let outcome = self.list(self.registry.tools(), request.params.as_ref(), "tools");
+                McpResponse::from_outcome(request.id, outcome)
+            }
+            "tools/call" => {
+                let params = match tools::CallToolParams::parse(request.params) {
+                    Ok(params) => params,
+                    Err(e) => return McpResponse::from_outcome(request.id, Err(e)),
+                };
+                let outcome = self
+                    .handle_tool_call(&params, &request.id, session, notifier, cancellation)
+                    .await;
+                McpResponse::from_outcome(request.id, outcome)
+            }
+            "resources/list" if self.handlers.has_resources() => {
+                let outcome = self.list(
+                    self.handlers.resources(),
+                    request.params.as_ref(),
+                    "resources",
+                );
+                McpResponse::from_outcome(request.id, outcome)
+            }
+            "resources/read" if self.handlers.has_resources() => {
+                let Some(uri) = request
+                    .params
+                    .as_ref()
+                    .and_then(|params| params.get("uri"))
+                    .and_then(Value::as_str)
+                else {
+                    return McpResponse::error(
+                        request.id,
+                        ErrorCode::InvalidParams,
+                        "Missing uri parameter",
+                    );
+                };
+                let outcome = self
+                    .handlers
+                    .read_resource(uri)
+                    .await
+                    .map(|contents| json!({ "contents": contents }));
+                McpResponse::from_outcome(request.id, outcome)
+            }
+            "prompts/list" if self.handlers.has_prompts() => {
+                let outcome =
+                    self.list(self.handlers.prompts(), request.params.as_ref(), "prompts");
+                McpResponse::from_outcome(request.id, outcome)
+            }
+            "prompts/get" if self.handlers.has_prompts() => {
+                let params = request.params.unwrap_or_default();
+                let Some(name) = params.get("name").and_then(Value::as_str) else {
+                    return McpResponse::error(
+                        request.id,
+                        ErrorCode::InvalidParams,
+                        "Missing name parameter",
+                    );
+                };
+                let Ok(arguments) = serde_json::from_value(
+                    params
+                        .get("arguments")
+                        .cloned()
+                        .unwrap_or_else(|| json!({})),
+                ) else {
+                    return McpResponse::error(
+                        request.id,
+                        ErrorCode::InvalidParams,
+                        "Prompt arguments must be strings",
+                    );
+                };
+                let outcome = self
+                    .handlers
+                    .get_prompt(name, arguments)
+                    .await
+                    .and_then(|prompt| {
+                        serde_json::to_value(prompt).map_err(|e| {
+                            tools::ToolCallError::new(ErrorCode::InternalError, e.to_string())
+                        })
+                    });
+                McpResponse::from_outcome(request.id, outcome)
+            }
+            _ => McpResponse::error(
+                request.id,
+                ErrorCode::MethodNotFound,
+                format!("Unknown method: {}", request.method),
+            ),
+        }
+    }
+
+    /// The result of a list request with `params`: the page of `items` under `key`,
+    /// with the cursor of the next page
+    fn list<T: Serialize>(
+        &self,
+        items: Vec<T>,
+        params: Option<&Value>,
+        key: &str,
+    ) -> Result<Value, tools::ToolCallError> {
+        let (page, next_cursor) = pagination::paginate(items, params, self.list_page_size)?;
+        let mut result = json!({ key: page });
+        if let Some(next_cursor) = next_cursor {
+            result["nextCursor"] = json!(next_cursor);
+        }
+        Ok(result)
+    }
+
+    /// Run the `tools/call` with `params` and return its result
+    async fn handle_tool_call(
+        &self,
+        params: &tools::CallToolParams,
+        request_id: &Value,
+        session: &Session,
+        notifier: &Notifier,
+        cancellation: &CancellationToken,
+    ) -> Result<Value, tools::ToolCallError> {
+        let resolved = params.resolve_alias();
+        let params = resolved.as_ref().unwrap_or(params);
+        let name = params.name.as_str();
+        // A former name of kagi_enrich stays disabled, for its type, once resolved
+        if let Some(disabled) = std::iter::once(name)
+            .chain(params.enrich_alias())
+            .find(|tool| self.disabled_tools.iter().any(|disabled| disabled == tool))
+        {
+            return Err(tools::ToolCallError::new(
+                ErrorCode::MethodNotFound,
+                format!("Tool '{disabled}' is disabled"),
+            ));
+        }
+        if !self.registry.contains(name) {
+            return Err(tools::ToolCallError::not_found(name));
+        }
+        self.rate_limits.check(name, session)?;
+        if let Some((sub_server, tool)) = self.hub.route(name) {
+            let mut arguments = params.arguments.clone().unwrap_or(json!({}));
+            self.hooks.tool_call_start(name, &mut arguments);
+            let started = Instant::now();
+            let call = sub_server.call(tool, arguments);
+            let outcome = self.tool_timeouts.for_call(name).run(call).await;
+            self.hooks
+                .tool_call_end(name, started.elapsed(), tool_failure(&outcome));
+            return outcome;
+        }
+
+        let progress_token = params.progress_token();
+        let _permit = self
+            .tool_limits
+            .acquire(name, progress_token, notifier)
+            .await;
+        let timeout = self.tool_timeouts.for_call(name);
+        let mut context = ToolContext {
+            tool: name,
+            request_id,
+            meta: params.meta.as_ref(),
+            session,
+            notifier,
+            progress: Progress::new(progress_token, notifier),
+            cancellation: cancellation.clone(),
+            timeout: &timeout,
+            billed: None,
+        };
+        let Some(args) = &params.arguments else {
+            return Err(tools::ToolCallError::invalid_params(
+                "Missing arguments parameter".to_string(),
+            ));
+        };
+        let mut args = args.clone();
+        let secrets_found = match self.secret_filter {
+            secrets::SecretFilter::Off => Vec::new(),
+            filter => secrets::filter_args(&mut args, filter == secrets::SecretFilter::Mask),
+        };
+        if self.secret_filter == secrets::SecretFilter::Refuse && !secrets_found.is_empty() {
+            return Err(tools::ToolCallError::invalid_params(format!(
+                "Refusing to call {name}: {}. Remove them and try again.",
+                secrets::describe(&secrets_found)
+            )));
+        }
+        self.hooks.tool_call_start(name, &mut args);
+        let started = Instant::now();
+        let call = self.call_tool(name, args, &mut context);
+        let mut outcome = timeout
+            .run(call)
+            .await
+            .map(|output| output.into_result(session.accepts_suggestions()));
+        self.hooks
+            .tool_call_end(name, started.elapsed(), tool_failure(&outcome));
+        if let Ok(result) = outcome.as_mut() {
+            self.url_policy.apply_to_result(result);
+            if !secrets_found.is_empty() {
+                prepend_warning(
+                    result,
+                    &format!(
+                        "Warning: {}; they were masked before calling Kagi.",
+                        secrets::describe(&secrets_found)
+                    ),
+                );
+            }
+        }
+        outcome
+    }
+
+    /// Handle `request` as a cancellable in-flight request registered under `key`
+    ///
+    /// The request is registered before this returns, so a cancellation processed
+    /// right after finds it. The future resolves to `None` if it was cancelled, as
+    /// cancelled requests get no response.
+    fn process(
+        self: &Arc<Self>,
+        request: McpRequest,
+        key: &Value,
+        session: Arc<Session>,
+        notifier: Notifier,
+    ) -> impl Future<Output = Option<McpResponse>> + Send + 'static {
+        let server = Arc::clone(self);
+        let registration = self.in_flight_requests.register(key);
+        async move {
+            let _in_flight = server.stats.begin_request();
+            let method = request.method.clone();
+            server.hooks.request(&method, &request.id);
+            let audit = server.audit.as_ref().map(|audit| {
+                (
+                    audit,
+                    audit.start(&method, &request.id, request.params.as_ref()),
+                )
+            });
+            let response = tokio::select! {
+                response = server.handle_request(
+                    request,
+                    &session,
+                    &notifier,
+                    registration.token(),
+                ) => response,
+                () = registration.token().cancelled() => {
+                    if let Some((audit, pending)) = audit {
+                        audit.finish(pending, audit::Outcome::Cancelled);
+                    }
+                    return None;
+                }
+            };
+            if let Some(error) = &response.error {
+                server.hooks.error(&method, error.code, &error.message);
+            }
+            if let Some((audit, pending)) = audit {
+                let error = response
+                    .error
+                    .as_ref()
+                    .map(|error| (error.code, error.message.as_str()));
+                audit.finish(pending, audit::Outcome::of(error, response.result.as_ref()));
+            }
+            Some(response)
+        }
+    }
+
+    /// Serve the stdio transport until stdin closes or `shutdown` resolves
+    async fn run_until(
+        self: Arc<Self>,
+        framing: transport::Framing,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> McpResult<()> {
+        self.shut_down_on(shutdown);
+        let transport = transport::stdio(framing, self.max_message_size);
+        self.serve(transport, None).await
+    }
+
+    /// Shut down once `shutdown` resolves
+    ///
+    /// Sessions stop reading messages, give in-flight requests the shutdown deadline
+    /// to finish, answer the rest with an error and flush their output.
+    fn shut_down_on(&self, shutdown: impl Future<Output = ()> + Send + 'static) {
+        let token = self.shutdown.clone();
+        tokio::spawn(async move {
+            shutdown.await;
+            token.cancel();
+        });
+    }
+
+    /// Serve one session over `transport` until the peer closes it or the server
+    /// shuts down
+    ///
+    /// Connections sharing the server pass their own `session_id`, which keeps their
+    /// request ids apart.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from or writing to the transport fails.
+    pub async fn serve(
+        self: Arc<Self>,
+        mut transport: impl transport::Transport,
+        session_id: Option<String>,
+    ) -> McpResult<()> {
+        let (notifier, mut outgoing) = Notifier::channel();
+        let session = Arc::new(Session::new(notifier.clone()));
+        let validator = strict::Validator::new(self.strict, strict::PROTOCOL_VERSION);
+        let write = async |transport: &mut dyn transport::Transport, line: String| {
+            if let Some(validator) = &validator {
+                validator.check(&line);
+            }
+            transport.write_message(&line).await
+        };
+        let responses = dispatch::ResponseOrder::new(self.dispatch_mode, &notifier);
+        let mut in_flight = JoinSet::new();
+        let mut tools_changed = self.registry.subscribe();
+        let mut keepalive = self.keepalive_interval.map(|period| {
+            let mut interval =
+                tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            interval
+        });
+        let mut pings = 0u64;
+        let past_deadline = CancellationToken::new();
+
+        loop {
+            let message = tokio::select! {
+                message = transport.read_message() => match message {
+                    // The transport skipped a malformed message and can go on
+                    Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                        responses.reserve().send(McpResponse::error(
+                            Value::Null,
+                            ErrorCode::ParseError,
+                            format!("Parse error: {e}"),
+                        ));
+                        continue;
+                    }
+                    message => message?,
+                },
+                () = self.shutdown.cancelled() => break,
+                Some(line) = outgoing.recv() => {
+                    write(&mut transport, line).await?;
+                    continue;
+                }
+                Ok(()) = tools_changed.changed() => {
+                    notifier.notify("notifications/tools/list_changed", json!({}));
+                    continue;
+                }
+                Some(_) = OptionFuture::from(keepalive.as_mut().map(tokio::time::Interval::tick)) => {
+                    pings += 1;
+                    notifier.send(&json!({
+                        "jsonrpc": "2.0",
+                        "id": format!("keepalive-{pings}"),
+                        "method": "ping"
+                    }));
+                    continue;
+                }
+            };
+            // Any message shows the client is alive, so the next ping can wait
+            if let Some(keepalive) = &mut keepalive {
+                keepalive.reset();
+            }
+            let Some(message) = message else {
+                break; // EOF
+            };
+
+            // Reap finished requests so the set doesn't grow unbounded
+            while in_flight.try_join_next().is_some() {}
+
+            let value: Value = match serde_json::from_str(&message) {
+                Ok(value) => value,
+                Err(e) => {
+                    responses.reserve().send(McpResponse::error(
+                        Value::Null,
+                        ErrorCode::ParseError,
+                        format!("Parse error: {e}"),
+                    ));
+                    continue;
+                }
+            };
+
+            // Notifications are handled inline and never answered
+            if let Some(notification) = notification::parse(&message) {
+                session.handle_notification(&notification);
+                match &session_id {
+                    Some(session_id) => {
+                        self.handle_session_notification(session_id, &notification);
+                    }
+                    None => self.handle_notification(&notification),
+                }
+                continue;
+            }
+            // Responses to requests the server sent, or to keepalive pings, which are dropped
+            if value.get("method").is_none()
+                && (value.get("result").is_some() || value.get("error").is_some())
+            {
+                session.handle_response(&value);
+                continue;
+            }
+
+            let slot = responses.reserve();
+
+            match serde_json::from_value::<McpRequest>(value) {
+                Ok(request) => {
+                    // Register before spawning so a cancellation read next finds the request
+                    let key = match &session_id {
+                        Some(session_id) => cancellation::scoped_id(session_id, &request.id),
+                        None => request.id.clone(),
+                    };
+                    let id = request.id.clone();
+                    let response =
+                        self.process(request, &key, Arc::clone(&session), notifier.clone());
+                    let past_deadline = past_deadline.clone();
+                    in_flight.spawn(async move {
+                        tokio::select! {
+                            response = response => {
+                                if let Some(response) = response {
+                                    slot.send(response);
+                                }
+                            }
+                            () = past_deadline.cancelled() => slot.send(McpResponse::error(
+                                id,
+                                ErrorCode::InternalError,
+                                "The server shut down before the request completed",
+                            )),
+                        }
+                    });
+                }
+                Err(e) => slot.send(McpResponse::error(
+                    Value::Null,
+                    ErrorCode::InvalidRequest,
+                    format!("Invalid request: {e}"),
+                )),
+            }
+        }
+
+        // Let in-flight requests finish and flush their responses before exiting, also
+        // when the peer closed its end, as hosts restarting the server close stdin and
+        // wait for the process to exit
+        let finished = async { while in_flight.join_next().await.is_some() {} };
+        if tokio::time::timeout(self.shutdown_deadline, finished)
+            .await
+            .is_err()
+        {
+            past_deadline.cancel();
+            while in_flight.join_next().await.is_some() {}
+        }
+        session.close();
+        drop(session);
+        responses.finish().await;
+        drop(notifier);
+        while let Some(line) = outgoing.recv().await {
+            write(&mut transport, line).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl KagiMcpServer {
+    /// Keep the registered tools of sub-servers up to date until the process exits
+    async fn watch_sub_servers(self: Arc<Self>) {
+        loop {
+            let sub_server = self.hub.tools_changed().await;
+            let tools = match sub_server.list_tools().await {
+                Ok(tools) => tools,
+                Err(e) => {
+                    tracing::warn!(
+                        sub_server = sub_server.name(),
+                        "Failed to list the tools of the sub-server: {}",
+                        e.message
+                    );
+                    continue;
+                }
+            };
+            let prefix = format!("{}{}", sub_server.name(), hub::SEPARATOR);
+            self.registry.update(|registered| {
+                registered.retain(|tool| !tool.name.starts_with(&prefix));
+                registered.extend(
+                    tools
+                        .into_iter()
+                        .filter(|tool| !self.disabled_tools.contains(&tool.name)),
+                );
+            });
+        }
+    }
+
+    /// Handle a notification received in `session`
+    ///
+    /// Cancellations refer to request ids of the same session.
+    fn handle_session_notification(&self, session: &str, notification: &Notification) {
+        match cancellation::cancelled_request_id(&notification.method, notification.params.as_ref())
+        {
+            Some(id) => {
+                self.in_flight_requests
+                    .cancel(&cancellation::scoped_id(session, id));
+            }
+            None => self.handle_notification(notification),
+        }
+    }
+}
+
+impl NotificationHandler for KagiMcpServer {
+    fn handle_notification(&self, notification: &Notification) {
+        match notification.method.as_str() {
+            // The session is usable as soon as `initialize` is answered, and the session
+            // itself lists the client's roots
+            "notifications/initialized" | "notifications/roots/list_changed" => {}
+            method @ ("notifications/cancelled" | "$/cancelRequest") => {
+                let cancelled =
+                    cancellation::cancelled_request_id(method, notification.params.as_ref())
+                        .is_some_and(|id| self.in_flight_requests.cancel(id));
+                if !cancelled {
+                    tracing::debug!("Ignoring cancellation of a request that is not in flight");
+                }
+            }
+            method => tracing::debug!("Ignoring notification: {method}"),
+        }
+    }
+}
+
+/// Resolve on Ctrl-C, or on SIGTERM on Unix
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        Ok(()) = tokio::signal::ctrl_c() => {}
+        () = terminate => {}
+    }
+    tracing::info!("Shutting down");
+}
+
+/// Run the `kagi-mcp-server` command line
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    diagnostics::init(args.log_level, args.log_format, args.log_file.as_deref())
+        .map_err(|e| format!("failed to open log file: {e}"))?;
+
+    if let Some(Command::Digest { days }) = args.command {
+        let path = args
+            .ledger
+            .ok_or("--ledger or KAGI_LEDGER must point at the ledger to digest")?;
+        let entries = ledger::read(&path)
+            .map_err(|e| format!("failed to read ledger '{}': {e}", path.display()))?;
+        let until = ledger::now();
+        print!(
+            "{}",
+            digest::render(&entries, until.saturating_sub(days * 86_400), until)
+        );
+        return Ok(());
+    }
+
+    let ledger = args
+        .ledger
+        .as_deref()
+        .map(ledger::Ledger::open)
+        .transpose()
+        .map_err(|e| format!("failed to open ledger: {e}"))?;
+
+    let audit = args
+        .audit_log
+        .as_deref()
+        .map(audit::AuditLog::open)
+        .transpose()
+        .map_err(|e| format!("failed to open audit log: {e}"))?;
+
+    if args.proxy && args.sub_servers.is_empty() {
+        return Err("--proxy needs at least one --sub-server to front".into());
+    }
+    let prints_tools = matches!(
+        args.command,
+        Some(Command::ListTools { .. } | Command::DescribeTool { .. })
+    );
+    let api_key = match args.api_key.or_else(|| env::var("KAGI_API_KEY").ok()) {
+        Some(api_key) => api_key,
+        // The Kagi API is never called
+        None if args.proxy || prints_tools => String::new(),
+        None => {
+            return Err(
+                "KAGI_API_KEY must be provided via --api-key or environment variable".into(),
+            )
+        }
+    };
+
+    let default_engine = match args.summarizer_engine.as_str() {
+        "cecil" => SummarizerEngine::Cecil,
+        "agnes" => SummarizerEngine::Agnes,
+        "daphne" => SummarizerEngine::Daphne,
+        "muriel" => SummarizerEngine::Muriel,
+        _ => {
+            tracing::warn!(
+                "Unknown engine '{}', defaulting to 'cecil'",
+                args.summarizer_engine
+            );
+            SummarizerEngine::Cecil
+        }
+    };
+
+    let tool_limits =
+        concurrency::ToolLimits::parse(args.tool_concurrency.as_deref().unwrap_or(""))?;
+    let tool_timeouts = timeouts::ToolTimeouts::parse(args.tool_timeout.as_deref().unwrap_or(""))?
+        .with_max_requested(Duration::from_secs(args.max_call_timeout));
+    let rate_limits = ratelimit::RateLimits::parse(args.tool_rate_limit.as_deref().unwrap_or(""))?;
+
+    let client = KagiClient::with_api_versions(
+        api_key,
+        args.search_api_version,
+        args.summarizer_api_version,
+        args.fastgpt_api_version,
+        args.enrich_api_version,
+    );
+    if let Some(Command::Check) = args.command {
+        let (report, passed) = check::run(&client).await;
+        print!("{report}");
+        return if passed {
+            Ok(())
+        } else {
+            Err("the check failed".into())
+        };
+    }
+
+    let hub = hub::Hub::start(&args.sub_servers).await?;
+    #[cfg(feature = "http")]
+    let metrics = args.metrics.then(|| Arc::new(metrics::Metrics::default()));
+    #[cfg(feature = "http")]
+    let hooks = hooks::Hooks::new(
+        metrics
+            .iter()
+            .map(|metrics| Arc::clone(metrics) as Arc<dyn hooks::ServerHook>)
+            .collect(),
+    );
+    #[cfg(not(feature = "http"))]
+    let hooks = hooks::Hooks::default();
+
+    let disabled_tools: Vec<String> = args
+        .disabled_tools
+        .into_iter()
+        .map(|tool| tool.trim().to_string())
+        .filter(|tool| !tool.is_empty())
+        .collect();
+    let mut builder = KagiMcpServer::builder(
+        client,
+        ServerOptions {
+            default_engine,
+            tool_limits,
+            tool_timeouts,
+            rate_limits,
+            hooks,
+            dispatch_mode: args.dispatch_mode,
+            verbose: args.verbose,
+            secret_filter: args.secret_filter,
+            disabled_tools: disabled_tools.clone(),
+            output_language: args
+                .output_language
+                .map(|language| language.trim().to_ascii_uppercase())
+                .filter(|language| !language.is_empty()),
+            topic_context: args.topic_context,
+            url_policy: urls::UrlPolicy::new(args.url_policy),
+            ledger,
+            audit,
+            strict: args.strict,
+            smallweb_budget: args.smallweb_budget,
+            summary_fallback_engine: args.summary_fallback_engine.map(Into::into),
+            hub,
+            kagi_tools: !args.proxy,
+            keepalive_interval: (args.keepalive_interval > 0)
+                .then(|| Duration::from_secs(args.keepalive_interval)),
+            max_message_size: args.max_message_size,
+            list_page_size: args.list_page_size.map(std::num::NonZeroUsize::get),
+            cache: cache::ResultCache::new(
+                Duration::from_secs(args.cache_ttl),
+                args.cache_max_entries,
+            ),
+            text_style: args.format,
+            budget: budget::Budget {
+                max_snippet_chars: args.max_snippet_chars.map(std::num::NonZeroUsize::get),
+                max_response_chars: args.max_response_chars.map(std::num::NonZeroUsize::get),
+            },
+            retry: retry::RetryPolicy::new(args.retries),
+            shutdown_deadline: Duration::from_secs(args.shutdown_timeout),
+            cost_ceiling: spend::CostCeiling::new(args.max_session_cost),
+        },
+    );
+    if !args.proxy {
+        builder = builder.prompts(prompts::KagiPrompts::new(disabled_tools));
+    }
+    let server = Arc::new(builder.build()?);
+    match &args.command {
+        Some(Command::ListTools { json }) => {
+            print!("{}", catalog::list(&server.registry.tools(), *json));
+            return Ok(());
+        }
+        Some(Command::DescribeTool { name, json }) => {
+            let tools = server.registry.tools();
+            let tool = tools
+                .iter()
+                .find(|tool| tool.name == *name)
+                .ok_or_else(|| {
+                    format!("unknown tool '{name}'; `kagi-mcp-server list-tools` lists them")
+                })?;
+            print!("{}", catalog::describe(tool, *json));
+            return Ok(());
+        }
+        Some(Command::Repl) => {
+            repl::run(Arc::clone(&server)).await?;
+            return Ok(());
+        }
+        _ => {}
+    }
+    tokio::spawn(Arc::clone(&server).watch_sub_servers());
+
+    if args.heartbeat_interval > 0 && !args.proxy {
+        heartbeat::spawn(
+            Duration::from_secs(args.heartbeat_interval),
+            Arc::clone(&server.stats),
+            server.client.clone(),
+        );
+    }
+
+    #[cfg(feature = "http")]
+    let protect = {
+        let auth = args.auth.auth(&server.http)?;
+        |router: axum::Router| {
+            let router = match metrics {
+                Some(metrics) => metrics.route(router),
+                None => router,
+            };
+            match auth {
+                Some(auth) => auth.protect(router),
+                None => router,
+            }
+        }
+    };
+
+    match args.transport {
+        Transport::Stdio => {
+            Arc::clone(&server)
+                .run_until(args.stdio_framing, shutdown_signal())
+                .await?;
+            // A pending blocking read of stdin would keep the runtime from shutting down
+            if server.shutdown.is_cancelled() {
+                std::process::exit(0);
+            }
+        }
+        Transport::Tcp => {
+            server.shut_down_on(shutdown_signal());
+            server.run_tcp(args.tcp_addr).await?;
+        }
+        #[cfg(unix)]
+        Transport::Unix => {
+            let path = args
+                .socket_path
+                .ok_or("--socket-path or KAGI_SOCKET_PATH must be set for the unix transport")?;
+            server.shut_down_on(shutdown_signal());
+            server.run_unix(&path).await?;
+        }
+        #[cfg(not(unix))]
+        Transport::Unix => return Err("Unix sockets are not supported on this platform".into()),
+        #[cfg(feature = "http")]
+        Transport::StreamableHttp => {
+            http::serve(
+                protect(http::router(server)),
+                args.http_addr,
+                http::ENDPOINT,
+            )
+            .await?;
+        }
+        #[cfg(feature = "http")]
+        Transport::Sse => {
+            http::serve(
+                protect(sse::router(server)),
+                args.http_addr,
+                sse::SSE_ENDPOINT,
+            )
+            .await?;
+        }
+        #[cfg(feature = "http")]
+        Transport::WebSocket => {
+            http::serve(protect(ws::router(server)), args.http_addr, ws::ENDPOINT).await?;
+        }
+        #[cfg(not(feature = "http"))]
+        Transport::StreamableHttp | Transport::Sse | Transport::WebSocket => {
+            return Err(format!(
+                "cannot listen on {}: built without the `http` feature",
+                args.http_addr
+            )
+            .into())
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestClient;
+    use kagiapi::testing::MockKagi;
+
+    /// A server in strict mode backed by the mock Kagi API
+    pub fn test_server(mock: &MockKagi) -> Arc<KagiMcpServer> {
+        Arc::new(KagiMcpServer::new(
+            mock.client(),
+            ServerOptions {
+                strict: strict::StrictMode::Panic,
+                ..ServerOptions::default()
+            },
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_search_limit_offset() {
+        let mock = MockKagi::start().await;
+        let mut client = TestClient::start(test_server(&mock));
+        let result = client
+            .call_tool(
+                "kagi_search_fetch",
+                json!({"queries": ["rust"], "limit": 3, "offset": 1}),
+            )
+            .await
+            .unwrap();
+        let text = result.text();
+        assert!(!text.contains("https://www.rust-lang.org/"), "{text}");
+        assert!(text.contains("2: "), "{text}");
+        assert!(text.contains("Related searches"), "{text}");
+        let requests = mock.server().received_requests().await.unwrap();
+        assert!(requests[0].url.query().unwrap().contains("limit=4"));
+
+        let error = client
+            .call_tool(
+                "kagi_search_fetch",
+                json!({"queries": ["rust"], "limit": 0}),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, -32602);
+    }
+
+    #[tokio::test]
+    async fn test_summarize_text() {
+        let mock = MockKagi::start().await;
+        let mut client = TestClient::start(test_server(&mock));
+        let result = client
+            .call_tool(
+                "kagi_summarizer_text",
+                json!({"text": "Rust is a systems programming language.", "summary_type": "takeaway"}),
+            )
+            .await
+            .unwrap();
+        assert!(!result.is_error);
+        assert!(!result.text().is_empty());
+        let requests = mock.server().received_requests().await.unwrap();
+        let body: Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["text"], "Rust is a systems programming language.");
+        assert_eq!(body["summary_type"], "takeaway");
+        assert!(body.get("url").is_none());
+
+        let result = client
+            .call_tool("kagi_summarizer_text", json!({"text": "  "}))
+            .await
+            .unwrap();
+        assert!(result.is_error);
+    }
+
+    #[tokio::test]
+    async fn test_research() {
+        let mock = MockKagi::start().await;
+        let mut client = TestClient::start(test_server(&mock));
+        let result = client
+            .call_tool("kagi_research", json!({"query": "rust", "sources": 2}))
+            .await
+            .unwrap();
+        let text = result.text();
+        assert!(text.starts_with("# Research briefing: rust"), "{text}");
+        assert!(text.contains("## 2. "), "{text}");
+        assert!(text.contains("[1] "), "{text}");
+        assert!(!text.contains("Summary failed"), "{text}");
+        let requests = mock.server().received_requests().await.unwrap();
+        let summaries: Vec<Value> = requests
+            .iter()
+            .filter(|request| request.url.path().ends_with("/summarize"))
+            .map(|request| serde_json::from_slice(&request.body).unwrap())
+            .collect();
+        assert_eq!(summaries.len(), 2);
+        assert!(summaries
+            .iter()
+            .all(|body| body["summary_type"] == "takeaway"));
+        assert!(!text.contains("## Overview"), "{text}");
+
+        // Clients that support sampling write the overview
+        let mut client = TestClient::start(test_server(&mock));
+        client
+            .initialize_with(json!({"sampling": {}}))
+            .await
+            .unwrap();
+        client.respond_to(
+            "sampling/createMessage",
+            json!({
+                "role": "assistant",
+                "content": {"type": "text", "text": "Rust is fast and safe [1]."},
+                "model": "test-model"
+            }),
+        );
+        let result = client
+            .call_tool("kagi_research", json!({"query": "rust", "sources": 2}))
+            .await
+            .unwrap();
+        let text = result.text();
+        assert!(
+            text.starts_with(
+                "# Research briefing: rust\n\n## Overview\n\nRust is fast and safe [1].\n\n## 1. "
+            ),
+            "{text}"
+        );
+        let sampling = client.notification("sampling/createMessage").await;
+        let prompt = sampling["params"]["messages"][0]["content"]["text"]
+            .as_str()
+            .unwrap();
+        assert!(prompt.starts_with("Question: rust\n\n## 1. "), "{prompt}");
+    }
+}
@@ -0,0 +1,315 @@
+//! Summarization of pages that Kagi cannot reach
+//!
+//! URLs on loopback, private or link-local addresses (e.g. a docs server on
+//! `http://localhost:3000`) are fetched by the server itself, and their text is
+//! uploaded to the summarizer instead of the URL. So are `file://` URLs, as long as
+//! they are inside one of the workspace roots shared by the client.
+
+use crate::session::Root;
+use crate::unfurl;
+use std::net::{IpAddr, Ipv6Addr};
+use std::path::PathBuf;
+use tokio::io::AsyncReadExt;
+
+/// Maximum number of bytes read from a local page
+const MAX_LOCAL_BYTES: usize = 4 * 1024 * 1024;
+
+/// Whether `url` points at a host that is only reachable from this machine or network
+pub fn is_private_url(url: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return is_private_ip(ip);
+    }
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    host == "localhost"
+        || host.ends_with(".localhost")
+        || host.ends_with(".local")
+        || host.ends_with(".internal")
+}
+
+fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified()
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_private_ip(IpAddr::V4(ip));
+            }
+            ip.is_loopback() || ip.is_unspecified() || is_unique_local(ip) || is_link_local(ip)
+        }
+    }
+}
+
+fn is_unique_local(ip: Ipv6Addr) -> bool {
+    (ip.segments()[0] & 0xfe00) == 0xfc00
+}
+
+fn is_link_local(ip: Ipv6Addr) -> bool {
+    (ip.segments()[0] & 0xffc0) == 0xfe80
+}
+
+/// Whether `url` is a `file://` URL
+pub fn is_file_url(url: &str) -> bool {
+    url.get(..5)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("file:"))
+}
+
+/// Read a local file inside one of `roots` and return its readable text
+///
+/// HTML files are reduced to their visible text like pages; other files are read
+/// as text.
+pub async fn read_file(url: &str, roots: &[Root]) -> Result<String, String> {
+    let path = file_path(url).ok_or_else(|| format!("'{url}' is not a valid file URL"))?;
+    // Resolve symlinks and `..` so a path cannot escape the roots
+    let path = tokio::fs::canonicalize(&path)
+        .await
+        .map_err(|e| format!("failed to read '{url}': {e}"))?;
+    let mut allowed = false;
+    for root in roots {
+        let Some(root) = file_path(&root.uri) else {
+            continue;
+        };
+        if let Ok(root) = tokio::fs::canonicalize(root).await {
+            allowed |= path.starts_with(root);
+        }
+    }
+    if !allowed {
+        return Err(format!(
+            "'{url}' is outside the workspace roots shared by the client"
+        ));
+    }
+
+    let file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|e| format!("failed to read '{url}': {e}"))?;
+    let mut body = Vec::new();
+    file.take(MAX_LOCAL_BYTES as u64)
+        .read_to_end(&mut body)
+        .await
+        .map_err(|e| format!("failed to read '{url}': {e}"))?;
+
+    let body = String::from_utf8_lossy(&body);
+    let is_html = path.extension().is_some_and(|extension| {
+        extension.eq_ignore_ascii_case("html") || extension.eq_ignore_ascii_case("htm")
+    });
+    let text = if is_html {
+        html_to_text(&body)
+    } else {
+        body.into_owned()
+    };
+    if text.trim().is_empty() {
+        return Err(format!("'{url}' has no text content"));
+    }
+    Ok(text)
+}
+
+fn file_path(uri: &str) -> Option<PathBuf> {
+    reqwest::Url::parse(uri).ok()?.to_file_path().ok()
+}
+
+/// Fetch a page and return its readable text
+///
+/// HTML is reduced to its visible text; other textual content types are returned
+/// unchanged.
+pub async fn fetch_text(client: &reqwest::Client, url: &str) -> Result<String, String> {
+    let mut response = client
+        .get(url)
+        .timeout(unfurl::FETCH_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| format!("failed to fetch '{url}': {e}"))?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "failed to fetch '{url}': HTTP {}",
+            response.status().as_u16()
+        ));
+    }
+
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("text/html")
+        .to_ascii_lowercase();
+    let is_html = content_type.contains("html");
+    if !is_html
+        && !content_type.starts_with("text/")
+        && !content_type.contains("json")
+        && !content_type.contains("xml")
+    {
+        return Err(format!(
+            "'{url}' is not a text document ({content_type}); only text pages on local hosts can be summarized"
+        ));
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("failed to read '{url}': {e}"))?
+    {
+        body.extend_from_slice(&chunk);
+        if body.len() >= MAX_LOCAL_BYTES {
+            body.truncate(MAX_LOCAL_BYTES);
+            break;
+        }
+    }
+
+    let body = String::from_utf8_lossy(&body);
+    let text = if is_html {
+        html_to_text(&body)
+    } else {
+        body.into_owned()
+    };
+    if text.trim().is_empty() {
+        return Err(format!("'{url}' has no text content"));
+    }
+    Ok(text)
+}
+
+/// Reduce HTML to its visible text, keeping block elements on separate lines
+pub fn html_to_text(html: &str) -> String {
+    const BLOCK_TAGS: &[&str] = &[
+        "p",
+        "div",
+        "br",
+        "li",
+        "tr",
+        "h1",
+        "h2",
+        "h3",
+        "h4",
+        "h5",
+        "h6",
+        "pre",
+        "section",
+        "article",
+        "header",
+        "footer",
+        "blockquote",
+        "table",
+        "ul",
+        "ol",
+    ];
+
+    let lower = html.to_ascii_lowercase();
+    let mut text = String::with_capacity(html.len() / 2);
+    let mut pos = 0;
+    while let Some(open) = lower[pos..].find('<') {
+        let open = pos + open;
+        text.push_str(&html[pos..open]);
+
+        let Some(close) = lower[open..].find('>') else {
+            pos = html.len();
+            break;
+        };
+        let close = open + close;
+        let name: String = lower[open + 1..close]
+            .trim_start_matches('/')
+            .chars()
+            .take_while(char::is_ascii_alphanumeric)
+            .collect();
+        pos = close + 1;
+
+        if matches!(name.as_str(), "script" | "style" | "noscript" | "template")
+            && !lower[open + 1..].starts_with('/')
+        {
+            let end_tag = format!("</{name}");
+            pos = lower[pos..]
+                .find(&end_tag)
+                .and_then(|end| lower[pos + end..].find('>').map(|gt| pos + end + gt + 1))
+                .unwrap_or(html.len());
+        } else if BLOCK_TAGS.contains(&name.as_str()) {
+            text.push('\n');
+        }
+    }
+    text.push_str(&html[pos..]);
+
+    let text = unfurl::decode_entities(&text);
+    let mut output = String::with_capacity(text.len());
+    let mut blank = true;
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() {
+            if !blank {
+                output.push('\n');
+                blank = true;
+            }
+            continue;
+        }
+        output.push_str(&line);
+        output.push('\n');
+        blank = false;
+    }
+    output.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_private_urls() {
+        assert!(is_private_url("http://localhost:3000/docs"));
+        assert!(is_private_url("http://docs.localhost/"));
+        assert!(is_private_url("http://127.0.0.1:8080"));
+        assert!(is_private_url("http://192.168.1.20/page"));
+        assert!(is_private_url("http://[::1]:3000/"));
+        assert!(is_private_url("http://[fd00::1]/"));
+        assert!(!is_private_url("https://example.com/article"));
+        assert!(!is_private_url("https://8.8.8.8/"));
+        assert!(!is_private_url("not a url"));
+    }
+
+    #[test]
+    fn test_html_to_text() {
+        let html = r#"<html><head><title>Docs</title><style>p { color: red; }</style>
+            <script>var x = "<p>";</script></head>
+            <body><h1>Getting&nbsp;started</h1><p>Install the   tool.</p>
+            <ul><li>One</li><li>Two &amp; three</li></ul></body></html>"#;
+        assert_eq!(
+            html_to_text(html),
+            "Docs\n\nGetting started\n\nInstall the tool.\n\nOne\n\nTwo & three"
+        );
+    }
+    #[tokio::test]
+    async fn test_read_file() {
+        let dir = std::env::temp_dir().join(format!("kagi-mcp-local-{}", std::process::id()));
+        let workspace = dir.join("workspace");
+        std::fs::create_dir_all(&workspace).unwrap();
+        std::fs::write(workspace.join("notes.md"), "# Notes\nShip it.").unwrap();
+        std::fs::write(workspace.join("page.html"), "<p>Hello <b>there</b></p>").unwrap();
+        std::fs::write(dir.join("secret.txt"), "hunter2").unwrap();
+        let url = |path: PathBuf| reqwest::Url::from_file_path(path).unwrap().to_string();
+        let roots = [Root {
+            uri: url(workspace.clone()),
+            name: None,
+        }];
+
+        assert!(is_file_url(&url(workspace.join("notes.md"))));
+        assert_eq!(
+            read_file(&url(workspace.join("notes.md")), &roots).await,
+            Ok("# Notes\nShip it.".to_string())
+        );
+        assert_eq!(
+            read_file(&url(workspace.join("page.html")), &roots).await,
+            Ok("Hello there".to_string())
+        );
+        let escape = format!("{}/../secret.txt", url(workspace.clone()));
+        assert!(read_file(&escape, &roots)
+            .await
+            .unwrap_err()
+            .contains("outside"));
+        assert!(read_file(&url(dir.join("secret.txt")), &[]).await.is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
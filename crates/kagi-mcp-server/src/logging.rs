@@ -0,0 +1,57 @@
+//! Log messages sent to the client
+//!
+//! The server declares the `logging` capability: tool handlers can send log
+//! messages to the client as `notifications/message`, and the client sets the
+//! minimum level it wants with `logging/setLevel`. Until it does, messages from
+//! [`LogLevel::Info`] up are sent.
+
+use serde::{Deserialize, Serialize};
+
+/// Syslog severity of a log message, from least to most severe
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    #[default]
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestClient;
+    use crate::tests::test_server;
+    use kagiapi::testing::MockKagi;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_set_level() {
+        let mock = MockKagi::start().await;
+        let mut client = TestClient::start(test_server(&mock));
+        let init = client.initialize().await.unwrap();
+        assert!(init.capabilities["logging"].is_object());
+
+        let result = client
+            .request("logging/setLevel", json!({"level": "error"}))
+            .await
+            .unwrap();
+        assert_eq!(result, json!({}));
+        let error = client
+            .request("logging/setLevel", json!({"level": "loud"}))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, -32602);
+
+        assert!(LogLevel::Warning < LogLevel::Error);
+        assert_eq!(
+            serde_json::from_value::<LogLevel>(json!("critical")).unwrap(),
+            LogLevel::Critical
+        );
+    }
+}
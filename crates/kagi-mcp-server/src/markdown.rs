@@ -0,0 +1,117 @@
+//! Markdown rendering of tool results
+//!
+//! Most MCP clients render tool results as Markdown, where the plain text blocks
+//! read poorly. With `--format markdown`, search and enrichment results become
+//! numbered lists of links under a heading. Titles are escaped so they cannot
+//! break out of their links.
+
+use kagiapi::{EnrichResult, SearchResponse};
+use std::fmt::Write;
+
+/// How tool results are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TextStyle {
+    /// Plain text blocks
+    #[default]
+    Plain,
+    /// Markdown with headings, lists and links
+    Markdown,
+}
+
+/// A Markdown link to `url` labelled `title`
+pub fn link(title: &str, url: &str) -> String {
+    let title = title
+        .replace('\\', "\\\\")
+        .replace('[', "\\[")
+        .replace(']', "\\]");
+    let url = url
+        .replace('(', "%28")
+        .replace(')', "%29")
+        .replace(' ', "%20");
+    format!("[{title}]({url})")
+}
+
+/// Search results as a numbered list starting at `first_number`
+pub fn search_results(query: &str, response: &SearchResponse, first_number: usize) -> String {
+    let mut output = format!("## Results for \"{query}\"\n\n");
+    let mut result_number = first_number;
+    let mut related = Vec::new();
+    for result in &response.data {
+        match (result.result_type, &result.title, &result.url) {
+            (1, _, _) => related.extend(result.list.iter().flatten()),
+            (0, Some(_), None) | (_, None, _) => {}
+            (_, Some(title), url) => {
+                let _ = match url {
+                    Some(url) => writeln!(output, "{result_number}. {}", link(title, url)),
+                    None => writeln!(output, "{result_number}. {title}"),
+                };
+                if let Some(published) = &result.published {
+                    let _ = writeln!(output, "   *Published {published}*");
+                }
+                if let Some(snippet) = &result.snippet {
+                    let _ = writeln!(output, "   {snippet}");
+                }
+                output.push('\n');
+                result_number += 1;
+            }
+        }
+    }
+    if !related.is_empty() {
+        output.push_str("**Related searches:**\n");
+        for item in related {
+            let _ = writeln!(output, "- {item}");
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// Enrichment results as a numbered list
+pub fn enrich_results(type_name: &str, query: &str, results: &[EnrichResult]) -> String {
+    let mut output = format!("## Kagi {type_name} enrichment results for \"{query}\"\n\n");
+    for (i, result) in results.iter().enumerate() {
+        if result.result_type != 0 {
+            continue;
+        }
+        let title = result.title.as_deref().unwrap_or("[No Title]");
+        let _ = match &result.url {
+            Some(url) => writeln!(output, "{}. {}", i + 1, link(title, url)),
+            None => writeln!(output, "{}. {title}", i + 1),
+        };
+        if let Some(snippet) = result.snippet.as_deref().filter(|s| !s.is_empty()) {
+            let _ = writeln!(output, "   {snippet}");
+        }
+        if let Some(published) = result.published.as_deref().filter(|p| !p.is_empty()) {
+            let _ = writeln!(output, "   *Published {published}*");
+        }
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_search_results() {
+        let response: SearchResponse = serde_json::from_value(json!({
+            "meta": {"id": "1", "node": "test", "ms": 5},
+            "data": [
+                {"t": 0, "url": "https://www.rust-lang.org/", "title": "Rust [official]", "snippet": "A language"},
+                {"t": 1, "list": ["rust tutorial"]},
+                {"t": 0, "url": "https://en.wikipedia.org/wiki/Rust_(programming_language)", "title": "Rust", "published": "2024-01-01"}
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(
+            search_results("rust", &response, 1),
+            "## Results for \"rust\"\n\n\
+             1. [Rust \\[official\\]](https://www.rust-lang.org/)\n   A language\n\n\
+             2. [Rust](https://en.wikipedia.org/wiki/Rust_%28programming_language%29)\n   *Published 2024-01-01*\n\n\
+             **Related searches:**\n- rust tutorial\n\n"
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! Prometheus metrics
+//!
+//! [`Metrics`] is a [`ServerHook`] counting requests by method, errors by method
+//! and code, and tool calls by tool, with a histogram of their durations. With
+//! `--metrics`, the HTTP transports serve them in the Prometheus text format at
+//! `/metrics`, so the server can be monitored like any other service. The route
+//! sits behind the same authentication as the MCP endpoint.
+
+use crate::hooks::ServerHook;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Path the metrics are served at
+pub const ENDPOINT: &str = "/metrics";
+
+/// Upper bounds in seconds of the tool call duration buckets
+const DURATION_BUCKETS: [f64; 9] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+#[derive(Default)]
+pub struct Metrics(Mutex<Counts>);
+
+#[derive(Default)]
+struct Counts {
+    requests: BTreeMap<String, u64>,
+    errors: BTreeMap<(String, i32), u64>,
+    tool_calls: BTreeMap<String, Histogram>,
+    tool_failures: BTreeMap<String, u64>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Observations per bucket of [`DURATION_BUCKETS`], not cumulative
+    buckets: [u64; DURATION_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(bucket) = DURATION_BUCKETS.iter().position(|&le| seconds <= le) {
+            self.buckets[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+impl ServerHook for Metrics {
+    fn on_request(&self, method: &str, _id: &Value) {
+        *self
+            .counts()
+            .requests
+            .entry(method.to_string())
+            .or_default() += 1;
+    }
+
+    fn on_tool_call_end(&self, tool: &str, elapsed: Duration, error: Option<&str>) {
+        let mut counts = self.counts();
+        counts
+            .tool_calls
+            .entry(tool.to_string())
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+        if error.is_some() {
+            *counts.tool_failures.entry(tool.to_string()).or_default() += 1;
+        }
+    }
+
+    fn on_error(&self, method: &str, code: i32, _message: &str) {
+        *self
+            .counts()
+            .errors
+            .entry((method.to_string(), code))
+            .or_default() += 1;
+    }
+}
+
+impl Metrics {
+    fn counts(&self) -> std::sync::MutexGuard<'_, Counts> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Serve the metrics at [`ENDPOINT`] next to the routes of `router`
+    pub fn route(self: Arc<Self>, router: Router) -> Router {
+        router.route(
+            ENDPOINT,
+            get(move || async move {
+                (
+                    [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+                    self.render(),
+                )
+                    .into_response()
+            }),
+        )
+    }
+
+    /// The metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let counts = self.counts();
+        let mut out = String::new();
+        header(
+            &mut out,
+            "mcp_requests_total",
+            "counter",
+            "Requests received, by method",
+        );
+        for (method, count) in &counts.requests {
+            let _ = writeln!(
+                out,
+                "mcp_requests_total{{method=\"{}\"}} {count}",
+                escape(method)
+            );
+        }
+        header(
+            &mut out,
+            "mcp_errors_total",
+            "counter",
+            "Requests answered with a JSON-RPC error, by method and code",
+        );
+        for ((method, code), count) in &counts.errors {
+            let _ = writeln!(
+                out,
+                "mcp_errors_total{{method=\"{}\",code=\"{code}\"}} {count}",
+                escape(method)
+            );
+        }
+        header(
+            &mut out,
+            "mcp_tool_call_duration_seconds",
+            "histogram",
+            "Duration of tool calls, by tool",
+        );
+        for (tool, histogram) in &counts.tool_calls {
+            let tool = escape(tool);
+            let mut cumulative = 0;
+            for (le, count) in DURATION_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "mcp_tool_call_duration_seconds_bucket{{tool=\"{tool}\",le=\"{le}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "mcp_tool_call_duration_seconds_bucket{{tool=\"{tool}\",le=\"+Inf\"}} {}",
+                histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "mcp_tool_call_duration_seconds_sum{{tool=\"{tool}\"}} {}",
+                histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "mcp_tool_call_duration_seconds_count{{tool=\"{tool}\"}} {}",
+                histogram.count
+            );
+        }
+        header(
+            &mut out,
+            "mcp_tool_call_failures_total",
+            "counter",
+            "Tool calls that failed, with a JSON-RPC error or an isError result, by tool",
+        );
+        for (tool, count) in &counts.tool_failures {
+            let _ = writeln!(
+                out,
+                "mcp_tool_call_failures_total{{tool=\"{}\"}} {count}",
+                escape(tool)
+            );
+        }
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// Escape a label value
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hooks::Hooks;
+    use crate::testing::TestClient;
+    use crate::{http, strict, KagiMcpServer, ServerOptions};
+    use kagiapi::testing::MockKagi;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_metrics() {
+        let mock = MockKagi::start().await;
+        let metrics = Arc::new(Metrics::default());
+        let server = Arc::new(KagiMcpServer::new(
+            mock.client(),
+            ServerOptions {
+                strict: strict::StrictMode::Panic,
+                hooks: Hooks::new(vec![metrics.clone()]),
+                ..ServerOptions::default()
+            },
+        ));
+        let mut client = TestClient::start(Arc::clone(&server));
+        client.initialize().await.unwrap();
+        client
+            .call_tool("kagi_search_fetch", json!({"queries": ["rust"]}))
+            .await
+            .unwrap();
+        client
+            .call_tool("kagi_fastgpt", json!({}))
+            .await
+            .unwrap_err();
+
+        let router = Arc::clone(&metrics).route(http::router(server));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}{ENDPOINT}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        let text = reqwest::get(url).await.unwrap().text().await.unwrap();
+
+        assert!(text.contains("mcp_requests_total{method=\"initialize\"} 1\n"));
+        assert!(text.contains("mcp_requests_total{method=\"tools/call\"} 2\n"));
+        assert!(text.contains("mcp_errors_total{method=\"tools/call\",code=\"-32602\"} 1\n"));
+        assert!(text.contains(
+            "mcp_tool_call_duration_seconds_bucket{tool=\"kagi_search_fetch\",le=\"+Inf\"} 1\n"
+        ));
+        assert!(
+            text.contains("mcp_tool_call_duration_seconds_count{tool=\"kagi_search_fetch\"} 1\n")
+        );
+        assert!(text.contains("# TYPE mcp_tool_call_duration_seconds histogram\n"));
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::default();
+        histogram.observe(0.3);
+        histogram.observe(0.3);
+        histogram.observe(120.0);
+        assert_eq!(histogram.buckets[2], 2);
+        assert_eq!(histogram.buckets.iter().sum::<u64>(), 2);
+        assert_eq!(histogram.count, 3);
+        assert_eq!(escape("a\"b\\"), "a\\\"b\\\\");
+    }
+}
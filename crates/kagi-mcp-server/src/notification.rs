@@ -0,0 +1,64 @@
+//! Incoming JSON-RPC notifications
+//!
+//! Messages without an `id`, such as `notifications/initialized`, are
+//! notifications: they are passed to a [`NotificationHandler`] and never answered,
+//! not even with an error.
+
+use serde::Deserialize;
+use serde_json::Value;
+
+/// A message from the client that expects no response
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Notification {
+    pub method: String,
+    #[serde(default)]
+    pub params: Option<Value>,
+}
+
+/// Reacts to notifications from the client
+pub trait NotificationHandler {
+    fn handle_notification(&self, notification: &Notification);
+}
+
+/// The notification in `line`, or `None` if it is a request or not valid JSON-RPC
+pub fn parse(line: &str) -> Option<Notification> {
+    let message: Value = serde_json::from_str(line).ok()?;
+    if message.get("id").is_some() {
+        return None;
+    }
+    serde_json::from_value(message).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_notifications() {
+        assert_eq!(
+            parse(r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#),
+            Some(Notification {
+                method: "notifications/initialized".to_string(),
+                params: None,
+            })
+        );
+        assert_eq!(
+            parse(
+                r#"{"jsonrpc":"2.0","method":"notifications/cancelled","params":{"requestId":3}}"#
+            )
+            .and_then(|n| n.params),
+            Some(json!({"requestId": 3}))
+        );
+
+        assert_eq!(
+            parse(r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#),
+            None
+        );
+        assert_eq!(
+            parse(r#"{"jsonrpc":"2.0","id":null,"method":"ping"}"#),
+            None
+        );
+        assert_eq!(parse("not json"), None);
+    }
+}
@@ -0,0 +1,116 @@
+//! Outgoing message channel shared by request handlers
+//!
+//! Every outgoing message goes through a single channel, drained by the session's
+//! transport, so that responses and notifications produced by concurrently running
+//! handlers never interleave.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    tx: mpsc::UnboundedSender<String>,
+}
+
+impl Notifier {
+    /// Create a notifier and the receiving end consumed by the writer task
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<String>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Self { tx }, rx)
+    }
+
+    /// Whether the receiving end is gone, so messages are dropped
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    /// Queue a JSON-RPC message for writing
+    pub fn send(&self, message: &impl Serialize) {
+        match serde_json::to_string(message) {
+            Ok(line) => {
+                // The receiver only goes away once the server is shutting down
+                let _ = self.tx.send(line);
+            }
+            Err(e) => tracing::error!("Failed to serialize outgoing message: {e}"),
+        }
+    }
+
+    /// Send a JSON-RPC notification
+    pub fn notify(&self, method: &str, params: Value) {
+        self.send(&json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        }));
+    }
+
+    /// Send a `notifications/progress` message for a request that supplied a progress token
+    pub fn progress(&self, token: &Value, progress: f64, message: &str) {
+        self.notify(
+            "notifications/progress",
+            json!({
+                "progressToken": token,
+                "progress": progress,
+                "message": message,
+            }),
+        );
+    }
+
+    /// Send a `notifications/progress` message reporting `progress` of `total` steps
+    pub fn progress_with_total(&self, token: &Value, progress: usize, total: usize, message: &str) {
+        self.notify(
+            "notifications/progress",
+            json!({
+                "progressToken": token,
+                "progress": progress,
+                "total": total,
+                "message": message,
+            }),
+        );
+    }
+}
+
+/// Progress reporting for one request
+///
+/// Reports are only sent when the client supplied a progress token. Each report
+/// increments the progress value, as MCP requires it to increase monotonically.
+/// Concurrent steps of a request may report through a shared reference.
+pub struct Progress<'a> {
+    token: Option<&'a Value>,
+    notifier: &'a Notifier,
+    step: AtomicUsize,
+}
+
+impl<'a> Progress<'a> {
+    pub fn new(token: Option<&'a Value>, notifier: &'a Notifier) -> Self {
+        Self {
+            token,
+            notifier,
+            step: AtomicUsize::new(0),
+        }
+    }
+
+    /// Whether the client asked for progress notifications
+    pub fn is_enabled(&self) -> bool {
+        self.token.is_some()
+    }
+
+    /// Report the next step, optionally out of a known `total`
+    pub fn report(&self, total: Option<usize>, message: &str) {
+        let Some(token) = self.token else {
+            return;
+        };
+        let step = self.step.fetch_add(1, Ordering::Relaxed) + 1;
+        match total {
+            Some(total) => self
+                .notifier
+                .progress_with_total(token, step, total, message),
+            None => {
+                #[allow(clippy::cast_precision_loss)]
+                self.notifier.progress(token, step as f64, message);
+            }
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Tool results with typed content and optional follow-up call suggestions
+//!
+//! Tool results are made of [`Content`] blocks, which always serialize to valid MCP
+//! content, optionally with the same data as `structuredContent` for clients that
+//! consume it programmatically. Besides its content, a tool can suggest further tool
+//! calls with prefilled
+//! arguments, e.g. summarizing the top search results. Suggestions are an
+//! experimental extension: they are only included in results once the client has
+//! opted in by declaring the [`SUGGESTED_CALLS_CAPABILITY`] experimental capability
+//! in `initialize`.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Name of the experimental capability, under both client and server capabilities
+pub const SUGGESTED_CALLS_CAPABILITY: &str = "suggestedCalls";
+
+/// A tool invocation the assistant may want to make next
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SuggestedCall {
+    pub name: String,
+    pub arguments: Value,
+    pub reason: String,
+}
+
+/// A content block of a tool result
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Content {
+    Text {
+        text: String,
+    },
+    Image {
+        /// Base64-encoded image data
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    Resource {
+        resource: ResourceContents,
+    },
+}
+
+/// A resource embedded in a tool result
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResourceContents {
+    pub uri: String,
+    #[serde(rename = "mimeType", skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    pub text: String,
+}
+
+/// The result of a tool call
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolOutput {
+    pub content: Vec<Content>,
+    /// Whether the tool failed; the content then describes the failure
+    pub is_error: bool,
+    /// Machine-readable result, matching the tool's `outputSchema`
+    pub structured_content: Option<Value>,
+    pub suggested_calls: Vec<SuggestedCall>,
+}
+
+impl From<String> for ToolOutput {
+    fn from(text: String) -> Self {
+        Self {
+            content: vec![Content::Text { text }],
+            is_error: false,
+            structured_content: None,
+            suggested_calls: Vec::new(),
+        }
+    }
+}
+
+impl ToolOutput {
+    /// A failed tool call, reported to the model rather than as a protocol error
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            is_error: true,
+            ..Self::from(message.into())
+        }
+    }
+
+    /// The `tools/call` result object
+    pub fn into_result(self, include_suggestions: bool) -> Value {
+        let mut result = json!({ "content": self.content });
+        if self.is_error {
+            result["isError"] = Value::Bool(true);
+        }
+        if let Some(structured_content) = self.structured_content {
+            result["structuredContent"] = structured_content;
+        }
+        if include_suggestions && !self.suggested_calls.is_empty() {
+            result["_meta"] = json!({ SUGGESTED_CALLS_CAPABILITY: self.suggested_calls });
+        }
+        result
+    }
+}
+
+/// Whether the client's `initialize` params opt in to suggested calls
+pub fn client_accepts_suggestions(initialize_params: Option<&Value>) -> bool {
+    initialize_params
+        .and_then(|params| params.pointer("/capabilities/experimental"))
+        .and_then(|experimental| experimental.get(SUGGESTED_CALLS_CAPABILITY))
+        .is_some_and(|capability| !capability.is_null())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggestions_require_opt_in() {
+        let output = ToolOutput {
+            suggested_calls: vec![SuggestedCall {
+                name: "kagi_summarizer".to_string(),
+                arguments: json!({"url": "https://example.com"}),
+                reason: "Summarize the top result".to_string(),
+            }],
+            ..ToolOutput::from("results".to_string())
+        };
+
+        let result = output.clone().into_result(false);
+        assert!(result.get("_meta").is_none());
+
+        let result = output.into_result(true);
+        assert_eq!(result["content"][0]["text"], "results");
+        assert_eq!(
+            result["_meta"]["suggestedCalls"][0]["arguments"]["url"],
+            "https://example.com"
+        );
+
+        assert!(client_accepts_suggestions(Some(
+            &json!({"capabilities": {"experimental": {"suggestedCalls": {}}}})
+        )));
+        assert!(!client_accepts_suggestions(Some(
+            &json!({"capabilities": {}})
+        )));
+        assert!(!client_accepts_suggestions(None));
+    }
+
+    #[test]
+    fn test_content_serialization() {
+        let output = ToolOutput {
+            content: vec![
+                Content::Text {
+                    text: "A chart".to_string(),
+                },
+                Content::Image {
+                    data: "iVBORw0KGgo=".to_string(),
+                    mime_type: "image/png".to_string(),
+                },
+                Content::Resource {
+                    resource: ResourceContents {
+                        uri: "https://example.com/".to_string(),
+                        mime_type: None,
+                        text: "Example".to_string(),
+                    },
+                },
+            ],
+            ..ToolOutput::default()
+        };
+        assert_eq!(
+            output.into_result(false),
+            json!({"content": [
+                {"type": "text", "text": "A chart"},
+                {"type": "image", "data": "iVBORw0KGgo=", "mimeType": "image/png"},
+                {"type": "resource", "resource": {"uri": "https://example.com/", "text": "Example"}}
+            ]})
+        );
+
+        let output = ToolOutput {
+            structured_content: Some(json!({"title": "Example"})),
+            ..ToolOutput::from("Title: Example".to_string())
+        };
+        assert_eq!(
+            output.into_result(false)["structuredContent"]["title"],
+            "Example"
+        );
+
+        let result = ToolOutput::error("Search failed").into_result(false);
+        assert_eq!(result["isError"], true);
+        assert_eq!(result["content"][0]["text"], "Search failed");
+    }
+}
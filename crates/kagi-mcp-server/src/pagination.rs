@@ -0,0 +1,110 @@
+//! Cursor-based pagination of list results
+//!
+//! With `--list-page-size`, `tools/list`, `resources/list` and `prompts/list`
+//! return at most that many items, with a `nextCursor` clients send back to get the
+//! next page, so long listings stay within client message limits. Cursors are
+//! opaque to clients: they hold the offset of the next page. A listing that changes
+//! between pages may skip or repeat items, which clients are told about with
+//! `notifications/tools/list_changed` anyway.
+
+use crate::tools::ToolCallError;
+use serde_json::Value;
+
+/// Prefix of cursors, so offsets are not mistaken for cursors of other servers
+const CURSOR_PREFIX: &str = "offset-";
+
+/// The page of `items` starting at the `cursor` of the list request's `params`,
+/// and the cursor of the next page if there is one
+///
+/// Without a page size, all items are returned.
+pub fn paginate<T>(
+    mut items: Vec<T>,
+    params: Option<&Value>,
+    page_size: Option<usize>,
+) -> Result<(Vec<T>, Option<String>), ToolCallError> {
+    let offset = match params.and_then(|params| params.get("cursor")) {
+        None | Some(Value::Null) => 0,
+        Some(cursor) => cursor
+            .as_str()
+            .and_then(|cursor| cursor.strip_prefix(CURSOR_PREFIX))
+            .and_then(|offset| offset.parse::<usize>().ok())
+            .filter(|&offset| offset <= items.len())
+            .ok_or_else(|| ToolCallError::invalid_params(format!("Invalid cursor: {cursor}")))?,
+    };
+    items.drain(..offset);
+    let Some(page_size) = page_size else {
+        return Ok((items, None));
+    };
+    let next_cursor =
+        (items.len() > page_size).then(|| format!("{CURSOR_PREFIX}{}", offset + page_size));
+    items.truncate(page_size);
+    Ok((items, next_cursor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestClient;
+    use crate::{strict, KagiMcpServer, ServerOptions};
+    use kagiapi::testing::MockKagi;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_paginate() {
+        let items: Vec<u32> = (0..5).collect();
+        let (page, next) = paginate(items.clone(), None, Some(2)).unwrap();
+        assert_eq!(page, [0, 1]);
+        let params = json!({"cursor": next.unwrap()});
+        let (page, next) = paginate(items.clone(), Some(&params), Some(2)).unwrap();
+        assert_eq!(page, [2, 3]);
+        let params = json!({"cursor": next.unwrap()});
+        assert_eq!(
+            paginate(items.clone(), Some(&params), Some(2)).unwrap(),
+            (vec![4], None)
+        );
+        assert_eq!(
+            paginate(items.clone(), None, None).unwrap(),
+            (items.clone(), None)
+        );
+
+        for cursor in [json!("offset-9"), json!("2"), json!(2)] {
+            let params = json!({ "cursor": cursor });
+            assert!(paginate(items.clone(), Some(&params), Some(2)).is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_paginated_tools() {
+        let mock = MockKagi::start().await;
+        let server = KagiMcpServer::new(
+            mock.client(),
+            ServerOptions {
+                strict: strict::StrictMode::Panic,
+                list_page_size: Some(3),
+                ..ServerOptions::default()
+            },
+        );
+        let all = server.registry.tools().len();
+        let mut client = TestClient::start(Arc::new(server));
+        let mut names = Vec::new();
+        let mut params = json!({});
+        loop {
+            let page = client.request("tools/list", params).await.unwrap();
+            let tools = page["tools"].as_array().unwrap();
+            assert!(tools.len() <= 3);
+            names.extend(tools.iter().map(|tool| tool["name"].clone()));
+            match page.get("nextCursor") {
+                Some(cursor) => params = json!({ "cursor": cursor }),
+                None => break,
+            }
+        }
+        assert_eq!(names.len(), all);
+
+        let error = client
+            .request("tools/list", json!({"cursor": "bogus"}))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, crate::ErrorCode::InvalidParams.code());
+    }
+}
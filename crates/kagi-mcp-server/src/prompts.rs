@@ -0,0 +1,200 @@
+//! Prompts offered by the server
+//!
+//! Clients list them as ready-made commands, e.g. slash commands, so users get
+//! well-behaved research and summarization without writing instructions. Each
+//! prompt expands to a user message telling the assistant which of the server's
+//! tools to call and how to present the result. Prompts whose tools are disabled
+//! are not listed.
+
+use crate::handler::{Prompt, PromptArgument, PromptHandler, PromptMessages};
+use crate::sampling::SamplingMessage;
+use crate::tools::ToolCallError;
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+const RESEARCH_BRIEF: &str = "kagi_research_brief";
+const SUMMARIZE_SELECTION: &str = "summarize_selection";
+
+/// The built-in prompts, given the tools hidden from clients
+pub struct KagiPrompts {
+    disabled_tools: Vec<String>,
+}
+
+impl KagiPrompts {
+    pub fn new(disabled_tools: Vec<String>) -> Self {
+        Self { disabled_tools }
+    }
+
+    /// Whether the tools prompt `name` relies on are served
+    fn available(&self, name: &str) -> bool {
+        let tools: &[&str] = match name {
+            RESEARCH_BRIEF => &["kagi_search_fetch", "kagi_summarizer"],
+            SUMMARIZE_SELECTION => &["kagi_summarizer_text"],
+            _ => return false,
+        };
+        tools
+            .iter()
+            .all(|tool| !self.disabled_tools.iter().any(|disabled| disabled == tool))
+    }
+}
+
+#[async_trait]
+impl PromptHandler for KagiPrompts {
+    fn prompts(&self) -> Vec<Prompt> {
+        let prompts = [
+            Prompt {
+                name: RESEARCH_BRIEF.to_string(),
+                description: Some(
+                    "Research a topic with Kagi Search and the Summarizer, and write a short cited brief"
+                        .to_string(),
+                ),
+                arguments: vec![PromptArgument {
+                    name: "topic".to_string(),
+                    description: Some("The topic or question to research".to_string()),
+                    required: true,
+                }],
+            },
+            Prompt {
+                name: SUMMARIZE_SELECTION.to_string(),
+                description: Some(
+                    "Summarize the selected text with the Kagi Universal Summarizer".to_string(),
+                ),
+                arguments: vec![PromptArgument {
+                    name: "language".to_string(),
+                    description: Some(
+                        "Language code of the summary, e.g. EN or DE; defaults to the language of the text"
+                            .to_string(),
+                    ),
+                    required: false,
+                }],
+            },
+        ];
+        prompts
+            .into_iter()
+            .filter(|prompt| self.available(&prompt.name))
+            .collect()
+    }
+
+    async fn get(
+        &self,
+        name: &str,
+        arguments: HashMap<String, String>,
+    ) -> Result<PromptMessages, ToolCallError> {
+        let argument = |name: &str| {
+            arguments
+                .get(name)
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+        };
+        let (description, text) = match name {
+            RESEARCH_BRIEF => {
+                let topic = argument("topic").ok_or_else(|| {
+                    ToolCallError::invalid_params("Missing argument 'topic'".to_string())
+                })?;
+                (format!("Research brief on {topic}"), research_brief(topic))
+            }
+            SUMMARIZE_SELECTION => (
+                "Summary of the selected text".to_string(),
+                summarize_selection(argument("language")),
+            ),
+            _ => {
+                return Err(ToolCallError::invalid_params(format!(
+                    "Prompt '{name}' not found"
+                )))
+            }
+        };
+        Ok(PromptMessages {
+            description: Some(description),
+            messages: vec![SamplingMessage::user(text)],
+        })
+    }
+}
+
+fn research_brief(topic: &str) -> String {
+    format!(
+        "Research the following topic and write a brief: {topic}\n\
+         \n\
+         1. Call kagi_search_fetch once with 2-4 concise, complementary queries covering \
+         the topic from different angles.\n\
+         2. Pick the 2-3 most authoritative and recent results and call kagi_summarizer \
+         on each with summary_type \"takeaway\".\n\
+         3. Write a brief of at most 300 words: a one-sentence answer first, then the \
+         key points, then open questions or disagreements between sources.\n\
+         4. Cite every claim with the URL it comes from, and list the sources at the end.\n\
+         \n\
+         Only state what the sources support; say so if they don't settle a point."
+    )
+}
+
+fn summarize_selection(language: Option<&str>) -> String {
+    let language = match language {
+        Some(language) => format!(" and target_language \"{}\"", language.to_uppercase()),
+        None => String::new(),
+    };
+    format!(
+        "Summarize the text I have selected in the editor. Call kagi_summarizer_text with \
+         the selected text as `text`{language}, and reply with the summary only. If no \
+         text is selected, ask me to select some instead of guessing."
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestClient;
+    use crate::{strict, KagiMcpServer, ServerOptions};
+    use kagiapi::testing::MockKagi;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_kagi_prompts() {
+        let mock = MockKagi::start().await;
+        let options = ServerOptions {
+            strict: strict::StrictMode::Panic,
+            ..ServerOptions::default()
+        };
+        let server = KagiMcpServer::builder(mock.client(), options)
+            .prompts(KagiPrompts::new(Vec::new()))
+            .build()
+            .unwrap();
+        let mut client = TestClient::start(Arc::new(server));
+        let listed = client.request("prompts/list", json!({})).await.unwrap();
+        assert_eq!(listed["prompts"][0]["name"], RESEARCH_BRIEF);
+        assert_eq!(listed["prompts"][1]["arguments"][0]["required"], false);
+
+        let prompt = client
+            .request(
+                "prompts/get",
+                json!({"name": RESEARCH_BRIEF, "arguments": {"topic": "rust async runtimes"}}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            prompt["description"],
+            "Research brief on rust async runtimes"
+        );
+        let text = prompt["messages"][0]["content"]["text"].as_str().unwrap();
+        assert!(text
+            .starts_with("Research the following topic and write a brief: rust async runtimes\n"));
+        assert!(text.contains("kagi_summarizer"));
+        assert!(client
+            .request("prompts/get", json!({"name": RESEARCH_BRIEF}))
+            .await
+            .is_err());
+
+        let prompt = client
+            .request(
+                "prompts/get",
+                json!({"name": SUMMARIZE_SELECTION, "arguments": {"language": "de"}}),
+            )
+            .await
+            .unwrap();
+        let text = prompt["messages"][0]["content"]["text"].as_str().unwrap();
+        assert!(text.contains("as `text` and target_language \"DE\","));
+
+        let prompts = KagiPrompts::new(vec!["kagi_summarizer_text".to_string()]).prompts();
+        assert_eq!(prompts.len(), 1);
+        assert_eq!(prompts[0].name, RESEARCH_BRIEF);
+    }
+}
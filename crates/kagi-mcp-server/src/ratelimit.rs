@@ -0,0 +1,225 @@
+//! Per-session tool rate limits
+//!
+//! Tools can be limited to a number of calls per minute within each session, so a
+//! runaway agent loop cannot hammer the Kagi API, or a sub-server, behind them.
+//! Each session has a token bucket per tool: it may burst up to the bucket size at
+//! once, after which calls are admitted at the steady rate. Unlike calls over a
+//! concurrency limit, calls over a rate limit do not wait: they fail right away
+//! with a [`ErrorCode::RateLimited`] error whose data says when to retry.
+
+use crate::error_code::ErrorCode;
+use crate::session::Session;
+use crate::tools::ToolCallError;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Calls a session may make to a tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Steady rate in calls per minute
+    per_minute: u32,
+    /// Calls that may be made at once after an idle period
+    burst: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct RateLimits {
+    default: Option<RateLimit>,
+    overrides: HashMap<String, RateLimit>,
+}
+
+impl RateLimits {
+    /// Parse a limit specification in calls per minute such as
+    /// `60,kagi_summarizer=10:3`
+    ///
+    /// An entry without a tool name sets the default for all tools. A `:N` suffix
+    /// sets the burst size, which defaults to the calls per minute.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut limits = Self::default();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (tool, limit) = match entry.split_once('=') {
+                Some((tool, limit)) => (Some(tool.trim()), limit),
+                None => (None, entry),
+            };
+            let positive = |n: &str| n.trim().parse::<u32>().ok().filter(|&n| n > 0);
+            let limit = match limit.split_once(':') {
+                Some((per_minute, burst)) => positive(per_minute).zip(positive(burst)),
+                None => positive(limit).map(|per_minute| (per_minute, per_minute)),
+            }
+            .map(|(per_minute, burst)| RateLimit { per_minute, burst })
+            .ok_or_else(|| {
+                format!("invalid tool rate limit '{entry}', expected calls per minute as N or N:BURST with positive integers")
+            })?;
+            match tool {
+                Some(tool) => {
+                    limits.overrides.insert(tool.to_string(), limit);
+                }
+                None => limits.default = Some(limit),
+            }
+        }
+        Ok(limits)
+    }
+
+    /// The rate limit of `tool`, if it has one
+    pub fn get(&self, tool: &str) -> Option<RateLimit> {
+        self.overrides.get(tool).copied().or(self.default)
+    }
+
+    /// Count a call of `tool` in `session`, failing with [`ErrorCode::RateLimited`]
+    /// if the session has used up its calls
+    pub fn check(&self, tool: &str, session: &Session) -> Result<(), ToolCallError> {
+        let Some(limit) = self.get(tool) else {
+            return Ok(());
+        };
+        let buckets = session.data::<Buckets>();
+        let mut buckets = buckets.0.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let bucket = buckets
+            .entry(tool.to_string())
+            .or_insert_with(|| Bucket::full(limit, now));
+        let Err(retry_after) = bucket.take(limit, now) else {
+            return Ok(());
+        };
+        let retry_after = retry_after.as_secs_f64().ceil();
+        let error = ToolCallError::new(
+            ErrorCode::RateLimited,
+            format!(
+                "{tool} is limited to {} calls per minute; retry in {retry_after}s",
+                limit.per_minute
+            ),
+        );
+        Err(error.with_data(json!({
+            "tool": tool,
+            "callsPerMinute": limit.per_minute,
+            "burst": limit.burst,
+            "retryAfterSeconds": retry_after,
+        })))
+    }
+}
+
+/// A session's buckets, by tool
+#[derive(Default)]
+struct Buckets(Mutex<HashMap<String, Bucket>>);
+
+#[derive(Debug)]
+struct Bucket {
+    /// Calls left, refilled continuously at the steady rate
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(limit.burst),
+            updated: now,
+        }
+    }
+
+    /// Take a call from the bucket, or return how long until one is available
+    fn take(&mut self, limit: RateLimit, now: Instant) -> Result<(), Duration> {
+        let rate = f64::from(limit.per_minute) / 60.0;
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(f64::from(limit.burst));
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestClient;
+    use crate::{strict, KagiMcpServer, ServerOptions};
+    use kagiapi::testing::MockKagi;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse() {
+        let limits = RateLimits::parse("60, kagi_summarizer=10:3").unwrap();
+        assert_eq!(
+            limits.get("kagi_summarizer"),
+            Some(RateLimit {
+                per_minute: 10,
+                burst: 3
+            })
+        );
+        assert_eq!(
+            limits.get("kagi_fastgpt"),
+            Some(RateLimit {
+                per_minute: 60,
+                burst: 60
+            })
+        );
+        assert_eq!(
+            RateLimits::parse("kagi_fastgpt=5")
+                .unwrap()
+                .get("kagi_enrich_web"),
+            None
+        );
+
+        assert!(RateLimits::parse("kagi_fastgpt=0").is_err());
+        assert!(RateLimits::parse("kagi_fastgpt=5:").is_err());
+        assert!(RateLimits::parse("often").is_err());
+    }
+
+    #[test]
+    fn test_bucket() {
+        let limit = RateLimit {
+            per_minute: 6,
+            burst: 2,
+        };
+        let start = Instant::now();
+        let mut bucket = Bucket::full(limit, start);
+        assert_eq!(bucket.take(limit, start), Ok(()));
+        assert_eq!(bucket.take(limit, start), Ok(()));
+        assert_eq!(bucket.take(limit, start), Err(Duration::from_secs(10)));
+        // One call every 10 seconds
+        let later = start + Duration::from_secs(5);
+        assert_eq!(bucket.take(limit, later), Err(Duration::from_secs(5)));
+        assert_eq!(bucket.take(limit, later + Duration::from_secs(5)), Ok(()));
+        // Idle time refills no more than the burst
+        let much_later = later + Duration::from_secs(600);
+        assert_eq!(bucket.take(limit, much_later), Ok(()));
+        assert_eq!(bucket.take(limit, much_later), Ok(()));
+        assert!(bucket.take(limit, much_later).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_calls_fail() {
+        let mock = MockKagi::start().await;
+        let server = Arc::new(KagiMcpServer::new(
+            mock.client(),
+            ServerOptions {
+                strict: strict::StrictMode::Panic,
+                rate_limits: RateLimits::parse("kagi_fastgpt=1").unwrap(),
+                ..ServerOptions::default()
+            },
+        ));
+        let mut client = TestClient::start(Arc::clone(&server));
+        let ask = json!({"query": "rust"});
+
+        assert!(client.call_tool("kagi_fastgpt", ask.clone()).await.is_ok());
+        let error = client
+            .call_tool("kagi_fastgpt", ask.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, ErrorCode::RateLimited.code());
+        assert_eq!(error.data.unwrap()["retryAfterSeconds"], 60.0);
+        assert!(client
+            .call_tool("kagi_search_fetch", json!({"queries": ["rust"]}))
+            .await
+            .is_ok());
+
+        // Every session has its own buckets
+        let mut other = TestClient::start(server);
+        assert!(other.call_tool("kagi_fastgpt", ask).await.is_ok());
+    }
+}
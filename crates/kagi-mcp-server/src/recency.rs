@@ -0,0 +1,167 @@
+//! Recency filtering of news enrichment results
+//!
+//! The Enrichment API takes only a query, so news results of any age come back.
+//! `kagi_enrich` takes `since` and `max_age_days` for news, and results published
+//! before the later of the two dates are left out after retrieval. Results without
+//! a publication date are left out too, as they cannot be shown to be recent.
+
+use kagiapi::EnrichResult;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// The earliest day results may be published on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recency {
+    /// Days since the Unix epoch
+    earliest_day: i64,
+}
+
+impl Recency {
+    /// The recency asked for with `since` (`YYYY-MM-DD`) and `max_age_days`,
+    /// counted back from `now` (Unix seconds), if any
+    pub fn new(
+        since: Option<&str>,
+        max_age_days: Option<u32>,
+        now: u64,
+    ) -> Result<Option<Self>, String> {
+        let since = since
+            .map(|date| {
+                parse_day(date).ok_or_else(|| format!("Invalid date '{date}', expected YYYY-MM-DD"))
+            })
+            .transpose()?;
+        let today = i64::try_from(now / SECONDS_PER_DAY).unwrap_or(i64::MAX);
+        let max_age = max_age_days.map(|days| today - i64::from(days));
+        Ok(since
+            .into_iter()
+            .chain(max_age)
+            .max()
+            .map(|earliest_day| Self { earliest_day }))
+    }
+
+    /// Drop results published before the earliest day, and return how many were
+    pub fn retain(&self, results: &mut Vec<EnrichResult>) -> usize {
+        let before = results.len();
+        results.retain(|result| {
+            result.result_type != 0
+                || result
+                    .published
+                    .as_deref()
+                    .and_then(parse_day)
+                    .is_some_and(|day| day >= self.earliest_day)
+        });
+        before - results.len()
+    }
+
+    /// The line telling the assistant that `dropped` results were left out
+    pub fn marker(&self, dropped: usize) -> String {
+        let earliest_day = u64::try_from(self.earliest_day).unwrap_or(0);
+        let earliest = crate::digest::format_date(earliest_day * SECONDS_PER_DAY);
+        match dropped {
+            1 => format!("… 1 result published before {earliest} or undated left out\n"),
+            _ => format!("… {dropped} results published before {earliest} or undated left out\n"),
+        }
+    }
+}
+
+/// Days since the Unix epoch of a date starting with `YYYY-MM-DD`, such as an
+/// RFC 3339 timestamp
+fn parse_day(date: &str) -> Option<i64> {
+    let date = date.trim().get(..10)?;
+    let mut parts = date.splitn(3, '-');
+    let year: i64 = parts.next().filter(|year| year.len() == 4)?.parse().ok()?;
+    let month: i64 = parts
+        .next()
+        .filter(|month| month.len() == 2)?
+        .parse()
+        .ok()?;
+    let day: i64 = parts.next().filter(|day| day.len() == 2)?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Days-from-civil conversion for the proleptic Gregorian calendar
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_recency() {
+        assert_eq!(parse_day("1970-01-01"), Some(0));
+        assert_eq!(parse_day("2024-03-01T12:00:00Z"), Some(19_783));
+        assert_eq!(parse_day("2024-13-01"), None);
+        assert_eq!(parse_day("last week"), None);
+
+        // 2024-03-01 00:00:00 UTC
+        let now = 1_709_251_200;
+        assert_eq!(Recency::new(None, None, now), Ok(None));
+        assert!(Recency::new(Some("yesterday"), None, now).is_err());
+        let recency = Recency::new(Some("2024-01-01"), Some(7), now)
+            .unwrap()
+            .unwrap();
+        assert_eq!(recency.earliest_day, 19_783 - 7);
+
+        let mut results: Vec<EnrichResult> = serde_json::from_value(json!([
+            {"t": 0, "title": "Recent", "published": "2024-02-28T08:00:00Z"},
+            {"t": 0, "title": "Old", "published": "2019-05-01"},
+            {"t": 0, "title": "Undated"},
+            {"t": 1, "list": ["rust news"]}
+        ]))
+        .unwrap();
+        assert_eq!(recency.retain(&mut results), 2);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].title.as_deref(), Some("Recent"));
+        assert_eq!(
+            recency.marker(2),
+            "… 2 results published before 2024-02-23 or undated left out\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_enrich_news_since() {
+        let mock = kagiapi::testing::MockKagi::start().await;
+        let mut client = crate::testing::TestClient::start(crate::tests::test_server(&mock));
+        let result = client
+            .call_tool(
+                "kagi_enrich",
+                json!({"query": "rust", "enrich_type": "news", "since": "2024-09-01"}),
+            )
+            .await
+            .unwrap();
+        assert!(result.text().contains("Rust in the kernel"));
+
+        let result = client
+            .call_tool(
+                "kagi_enrich_news",
+                json!({"query": "rust", "since": "2024-10-01"}),
+            )
+            .await
+            .unwrap();
+        assert!(!result.text().contains("Rust in the kernel"));
+        assert!(result
+            .text()
+            .contains("… 1 result published before 2024-10-01"));
+
+        assert!(client
+            .call_tool(
+                "kagi_enrich_news",
+                json!({"query": "rust", "since": "last week"})
+            )
+            .await
+            .is_err());
+        assert!(client
+            .call_tool(
+                "kagi_enrich",
+                json!({"query": "rust", "enrich_type": "web", "max_age_days": 7})
+            )
+            .await
+            .is_err());
+    }
+}
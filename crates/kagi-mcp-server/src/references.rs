@@ -0,0 +1,171 @@
+//! `FastGPT` answers with their references as separate content
+//!
+//! Rather than appending a `References:` section to the answer text, each distinct
+//! referenced URL is an embedded resource block, so clients can render it as a
+//! clickable citation, and the answer is also returned as `structuredContent`.
+//! Duplicate URLs are merged, and each reference keeps the citation numbers used
+//! in the answer text (e.g. `[2]`) so claims stay traceable.
+
+use crate::output::{Content, ResourceContents, ToolOutput};
+use kagiapi::FastGptReference;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::fmt::Write;
+
+/// A `FastGPT` result, and its structured content
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct Answer {
+    pub answer: String,
+    /// Distinct referenced pages, in the order they are first cited
+    pub references: Vec<Reference>,
+    /// Number of referenced pages left out to stay within `max_references`
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub omitted: usize,
+}
+
+/// A distinct referenced URL with the citation numbers pointing at it
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct Reference {
+    /// Citation numbers used for the page in the answer
+    pub numbers: Vec<usize>,
+    pub title: String,
+    pub url: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub snippet: String,
+}
+
+fn is_zero(count: &usize) -> bool {
+    *count == 0
+}
+
+impl Answer {
+    /// The answer `output` citing `references`, of which at most `max_references`
+    /// distinct URLs are kept, preferring the ones cited first
+    pub fn new(
+        output: &str,
+        references: &[FastGptReference],
+        max_references: Option<usize>,
+    ) -> Self {
+        let mut distinct: Vec<Reference> = Vec::new();
+        for (index, reference) in references.iter().enumerate() {
+            match distinct.iter_mut().find(|seen| seen.url == reference.url) {
+                Some(seen) => seen.numbers.push(index + 1),
+                None => distinct.push(Reference {
+                    numbers: vec![index + 1],
+                    title: reference.title.clone(),
+                    url: reference.url.clone(),
+                    snippet: reference.snippet.clone(),
+                }),
+            }
+        }
+        let omitted = match max_references {
+            Some(max) if distinct.len() > max => {
+                let omitted = distinct.len() - max;
+                distinct.truncate(max);
+                omitted
+            }
+            _ => 0,
+        };
+        Self {
+            answer: output.to_string(),
+            references: distinct,
+            omitted,
+        }
+    }
+
+    /// The answer, noting how many references were left out
+    pub fn text(&self) -> String {
+        let mut text = self.answer.clone();
+        if self.omitted > 0 {
+            let _ = write!(text, "\n\n({} more references omitted)", self.omitted);
+        }
+        text
+    }
+
+    /// The result with `text` followed by a resource block per reference
+    pub fn into_output(self, text: String) -> ToolOutput {
+        let mut content = vec![Content::Text { text }];
+        content.extend(self.references.iter().map(|reference| Content::Resource {
+            resource: ResourceContents {
+                uri: reference.url.clone(),
+                mime_type: Some("text/plain".to_string()),
+                text: reference.text(),
+            },
+        }));
+        ToolOutput {
+            content,
+            structured_content: serde_json::to_value(&self).ok(),
+            ..ToolOutput::default()
+        }
+    }
+}
+
+impl Reference {
+    /// The citation numbers and title, followed by the snippet if there is one
+    fn text(&self) -> String {
+        let numbers: Vec<String> = self.numbers.iter().map(ToString::to_string).collect();
+        let mut text = format!("[{}] {}", numbers.join(", "), self.title);
+        if !self.snippet.is_empty() {
+            text.push('\n');
+            text.push_str(&self.snippet);
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn reference(title: &str, url: &str) -> FastGptReference {
+        FastGptReference {
+            title: title.to_string(),
+            snippet: String::new(),
+            url: url.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_answer_references() {
+        let references = [
+            reference("Ownership", "https://doc.rust-lang.org/book/ch04-01.html"),
+            reference(
+                "Rust 1.0",
+                "https://blog.rust-lang.org/2015/05/15/Rust-1.0.html",
+            ),
+            reference("Borrowing", "https://doc.rust-lang.org/book/ch04-02.html"),
+            reference("Ownership", "https://doc.rust-lang.org/book/ch04-01.html"),
+        ];
+
+        let answer = Answer::new("Rust has ownership [1][4].", &references, None);
+        assert_eq!(answer.references.len(), 3);
+        assert_eq!(answer.references[0].numbers, [1, 4]);
+        let result = answer.clone().into_output(answer.text()).into_result(false);
+        assert_eq!(result["content"][0]["text"], "Rust has ownership [1][4].");
+        assert_eq!(
+            result["content"][1],
+            json!({"type": "resource", "resource": {
+                "uri": "https://doc.rust-lang.org/book/ch04-01.html",
+                "mimeType": "text/plain",
+                "text": "[1, 4] Ownership"
+            }})
+        );
+        assert_eq!(
+            result["structuredContent"]["references"][1]["url"],
+            "https://blog.rust-lang.org/2015/05/15/Rust-1.0.html"
+        );
+
+        let capped = Answer::new("Rust has ownership.", &references, Some(2));
+        assert_eq!(capped.omitted, 1);
+        let result = capped.clone().into_output(capped.text()).into_result(false);
+        assert_eq!(result["content"].as_array().unwrap().len(), 3);
+        assert!(result["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .ends_with("(1 more references omitted)"));
+
+        let schema = crate::tools::output_schema::<Answer>();
+        assert_eq!(schema["required"], json!(["answer", "references"]));
+    }
+}
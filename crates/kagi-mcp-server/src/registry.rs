@@ -0,0 +1,150 @@
+//! The set of tools offered to clients
+//!
+//! Which tools exist can change while the server runs, e.g. when a sub-server
+//! reports a new toolset. Every change bumps a generation counter that sessions
+//! watch, so each connected client gets a `notifications/tools/list_changed` and can
+//! list the tools again.
+
+use crate::Tool;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tokio::sync::watch;
+
+pub struct ToolRegistry {
+    tools: RwLock<Vec<Tool>>,
+    generation: watch::Sender<u64>,
+}
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        Self {
+            tools: RwLock::default(),
+            generation: watch::Sender::new(0),
+        }
+    }
+}
+
+impl ToolRegistry {
+    /// Add `tool`, or replace the tool with the same name
+    pub fn register(&self, tool: Tool) {
+        self.update(
+            |tools| match tools.iter_mut().find(|t| t.name == tool.name) {
+                Some(existing) => *existing = tool,
+                None => tools.push(tool),
+            },
+        );
+    }
+
+    /// Remove the tool called `name`, returning whether it was registered
+    // Sub-server tools are replaced wholesale with `update`; only tests remove one
+    #[allow(dead_code)]
+    pub fn unregister(&self, name: &str) -> bool {
+        let mut removed = false;
+        self.update(|tools| {
+            let before = tools.len();
+            tools.retain(|tool| tool.name != name);
+            removed = tools.len() < before;
+        });
+        removed
+    }
+
+    /// Change the tools in one step, notifying sessions once if anything changed
+    pub fn update(&self, change: impl FnOnce(&mut Vec<Tool>)) {
+        let mut tools = self.write();
+        let before = tools.clone();
+        change(&mut tools);
+        if *tools != before {
+            self.generation.send_modify(|generation| *generation += 1);
+        }
+    }
+
+    /// The registered tools, in registration order
+    pub fn tools(&self) -> Vec<Tool> {
+        self.read().clone()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.read().iter().any(|tool| tool.name == name)
+    }
+
+    /// A receiver that sees every change made after this call
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.generation.subscribe()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Vec<Tool>> {
+        self.tools.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Vec<Tool>> {
+        self.tools.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestClient;
+    use crate::tests::test_server;
+    use kagiapi::testing::MockKagi;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn tool(name: &str, description: &str) -> Tool {
+        Tool {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: json!({"type": "object"}),
+            ..Tool::default()
+        }
+    }
+
+    #[test]
+    fn test_registry_changes() {
+        let registry = ToolRegistry::default();
+        let mut changes = registry.subscribe();
+
+        registry.register(tool("a", "first"));
+        registry.register(tool("b", "second"));
+        assert!(changes.has_changed().unwrap());
+        changes.mark_unchanged();
+
+        // Registering an identical tool or removing a missing one is not a change
+        registry.register(tool("a", "first"));
+        assert!(!registry.unregister("c"));
+        assert!(!changes.has_changed().unwrap());
+
+        registry.register(tool("a", "updated"));
+        assert!(registry.unregister("b"));
+        assert!(changes.has_changed().unwrap());
+        let tools = registry.tools();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].description, "updated");
+        assert!(registry.contains("a"));
+        assert!(!registry.contains("b"));
+    }
+
+    #[tokio::test]
+    async fn test_list_changed_notification() {
+        let mock = MockKagi::start().await;
+        let server = test_server(&mock);
+        let mut client = TestClient::start(Arc::clone(&server));
+        let init = client.initialize().await.unwrap();
+        assert_eq!(init.capabilities["tools"]["listChanged"], true);
+
+        server
+            .registry
+            .register(tool("extra", "Registered at runtime"));
+        client
+            .notification("notifications/tools/list_changed")
+            .await;
+        let tools = client.list_tools().await.unwrap();
+        assert_eq!(tools.last().unwrap().name, "extra");
+
+        assert!(server.registry.unregister("kagi_unfurl"));
+        let error = client
+            .call_tool("kagi_unfurl", json!({"url": "https://example.com"}))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, -32601);
+    }
+}
@@ -0,0 +1,392 @@
+//! Interactive tool calls run by the `repl` command
+//!
+//! Lets a developer try tools from a terminal, e.g. `search rust async` or
+//! `summarize https://example.com --engine muriel`, and see the exact result the
+//! assistant would receive. Words after the command fill the tool's first required
+//! argument, and `--name value` sets any other; values are read as JSON where the
+//! argument is not a string, so `--limit 3` is a number. Calls go through the same
+//! path as `tools/call` requests, and progress notices are printed to stderr.
+
+use crate::{strict, KagiMcpServer, Notifier, Session, Tool};
+use serde_json::{json, Map, Value};
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio_util::sync::CancellationToken;
+
+/// Short names for the tools, besides their full names and those without `kagi_`
+const ALIASES: &[(&str, &str)] = &[
+    ("search", "kagi_search_fetch"),
+    ("summarize", "kagi_summarizer"),
+    ("summarize-text", "kagi_summarizer_text"),
+    ("ask", "kagi_fastgpt"),
+    ("followup", "kagi_fastgpt_followup"),
+    ("web", "kagi_enrich_web"),
+    ("news", "kagi_enrich_news"),
+];
+
+const HELP: &str = "\
+Commands:
+  <tool> [text] [--argument value]...  call a tool, e.g. `search rust async`
+  tools                                list the tools
+  describe <tool>                      show a tool's arguments
+  json                                 toggle printing the raw tools/call result
+  quit                                 leave the REPL
+Tools can be named in full, without `kagi_`, or as search, summarize,
+summarize-text, ask, followup, web and news.
+";
+
+/// A line typed at the prompt
+#[derive(Debug, PartialEq)]
+enum Input {
+    Call { tool: String, arguments: Value },
+    Tools,
+    Describe(String),
+    Json,
+    Help,
+    Quit,
+}
+
+/// Read commands from stdin and print their results until `quit` or end of input
+pub async fn run(server: Arc<KagiMcpServer>) -> std::io::Result<()> {
+    let (notifier, mut outgoing) = Notifier::channel();
+    let session = Session::new(notifier.clone());
+    session.initialize(
+        Some(&json!({
+            "capabilities": {},
+            "clientInfo": {"name": "kagi-mcp-server repl", "version": env!("CARGO_PKG_VERSION")}
+        })),
+        strict::PROTOCOL_VERSION,
+    );
+    tokio::spawn(async move {
+        while let Some(message) = outgoing.recv().await {
+            if let Some(notice) = notice(&message) {
+                eprintln!("… {notice}");
+            }
+        }
+    });
+
+    let tools = server.registry.tools();
+    let mut raw = false;
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    println!("Type `help` for commands, `quit` to leave.");
+    loop {
+        print!("kagi> ");
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next_line().await? else {
+            println!();
+            return Ok(());
+        };
+        let output = match parse(&line, &tools) {
+            Ok(None) => continue,
+            Ok(Some(Input::Quit)) => return Ok(()),
+            Ok(Some(Input::Help)) => HELP.to_string(),
+            Ok(Some(Input::Tools)) => crate::catalog::list(&tools, false),
+            Ok(Some(Input::Describe(name))) => match resolve(&name, &tools) {
+                Some(tool) => crate::catalog::describe(tool, false),
+                None => unknown_tool(&name),
+            },
+            Ok(Some(Input::Json)) => {
+                raw = !raw;
+                format!("Raw results {}\n", if raw { "on" } else { "off" })
+            }
+            Ok(Some(Input::Call { tool, arguments })) => {
+                call(&server, &session, &notifier, &tool, arguments, raw).await
+            }
+            Err(e) => format!("{e}\n"),
+        };
+        print!("{output}");
+    }
+}
+
+/// Call `tool` as a `tools/call` request would, returning what the client gets
+async fn call(
+    server: &KagiMcpServer,
+    session: &Session,
+    notifier: &Notifier,
+    tool: &str,
+    arguments: Value,
+    raw: bool,
+) -> String {
+    let params = json!({
+        "name": tool,
+        "arguments": arguments,
+        "_meta": {"progressToken": "repl"}
+    });
+    let params = match crate::tools::CallToolParams::parse(Some(params)) {
+        Ok(params) => params,
+        Err(e) => return format!("Error {}: {}\n", e.code, e.message),
+    };
+    let outcome = server
+        .handle_tool_call(
+            &params,
+            &json!("repl"),
+            session,
+            notifier,
+            &CancellationToken::new(),
+        )
+        .await;
+    match outcome {
+        Ok(result) if raw => format!(
+            "{}\n",
+            serde_json::to_string_pretty(&result).unwrap_or_default()
+        ),
+        Ok(result) => format_result(&result),
+        Err(e) => format!("Error {}: {}\n", e.code, e.message),
+    }
+}
+
+/// The content blocks of a `tools/call` result, as the assistant reads them
+fn format_result(result: &Value) -> String {
+    let mut output = String::new();
+    if result["isError"] == true {
+        output.push_str("[tool error]\n");
+    }
+    let blocks = result["content"].as_array().map(Vec::as_slice);
+    for block in blocks.unwrap_or_default() {
+        match block["type"].as_str() {
+            Some("text") => output.push_str(block["text"].as_str().unwrap_or_default()),
+            Some("resource") => {
+                let resource = &block["resource"];
+                let _ = write!(
+                    output,
+                    "[resource {}]\n{}",
+                    resource["uri"].as_str().unwrap_or_default(),
+                    resource["text"].as_str().unwrap_or_default()
+                );
+            }
+            _ => output.push_str(&block.to_string()),
+        }
+        if !output.ends_with('\n') {
+            output.push('\n');
+        }
+    }
+    output
+}
+
+/// The message of a progress or log notification
+fn notice(message: &str) -> Option<String> {
+    let message: Value = serde_json::from_str(message).ok()?;
+    let params = &message["params"];
+    match message["method"].as_str()? {
+        "notifications/progress" => params["message"].as_str().map(str::to_string),
+        "notifications/message" => Some(match &params["data"] {
+            Value::String(data) => data.clone(),
+            data => data.to_string(),
+        }),
+        _ => None,
+    }
+}
+
+/// The input typed on `line`, or `None` for an empty line
+fn parse(line: &str, tools: &[Tool]) -> Result<Option<Input>, String> {
+    let words = split_words(line)?;
+    let Some((command, rest)) = words.split_first() else {
+        return Ok(None);
+    };
+    let input = match command.as_str() {
+        "quit" | "exit" => Input::Quit,
+        "help" | "?" => Input::Help,
+        "tools" => Input::Tools,
+        "json" => Input::Json,
+        "describe" => match rest {
+            [name] => Input::Describe(name.clone()),
+            _ => return Err("Usage: describe <tool>".to_string()),
+        },
+        name => {
+            let tool = resolve(name, tools).ok_or_else(|| unknown_tool(name).trim().to_string())?;
+            let mut arguments = arguments(tool, rest)?;
+            if let Some(enrich_type) = enrich_type(name) {
+                arguments["enrich_type"] = json!(enrich_type);
+            }
+            Input::Call {
+                tool: tool.name.clone(),
+                arguments,
+            }
+        }
+    };
+    Ok(Some(input))
+}
+
+/// The tool called `name`, by its full name, without `kagi_` or by an alias
+fn resolve<'a>(name: &str, tools: &'a [Tool]) -> Option<&'a Tool> {
+    let name = if enrich_type(name).is_some() {
+        "kagi_enrich"
+    } else {
+        name
+    };
+    let full_name = ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or_else(|| name.to_string(), |(_, tool)| (*tool).to_string());
+    tools
+        .iter()
+        .find(|tool| tool.name == full_name)
+        .or_else(|| {
+            tools
+                .iter()
+                .find(|tool| tool.name == format!("kagi_{name}"))
+        })
+}
+
+/// The `enrich_type` that `name` stands for, if it is a former name of `kagi_enrich`
+fn enrich_type(name: &str) -> Option<&'static str> {
+    let full_name = ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name, |(_, tool)| tool);
+    crate::tools::ENRICH_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == full_name || *alias == format!("kagi_{name}"))
+        .map(|(_, enrich_type)| *enrich_type)
+}
+
+fn unknown_tool(name: &str) -> String {
+    format!("Unknown tool '{name}'; `tools` lists them\n")
+}
+
+/// The arguments of a call to `tool` given as `words`
+fn arguments(tool: &Tool, words: &[String]) -> Result<Value, String> {
+    let properties = tool.input_schema["properties"].as_object();
+    let property_type = |name: &str| {
+        properties
+            .and_then(|properties| properties.get(name))
+            .and_then(|schema| schema["type"].as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let mut arguments = Map::new();
+    let mut text = Vec::new();
+    let mut words = words.iter().peekable();
+    while let Some(word) = words.next() {
+        let Some(flag) = word.strip_prefix("--") else {
+            text.push(word.as_str());
+            continue;
+        };
+        let name = flag.replace('-', "_");
+        let value = match words.next_if(|next| !next.starts_with("--")) {
+            Some(value) if property_type(&name) == "string" => Value::String(value.clone()),
+            Some(value) => {
+                serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.clone()))
+            }
+            // A flag on its own switches a boolean argument on
+            None => Value::Bool(true),
+        };
+        arguments.insert(name, value);
+    }
+    if !text.is_empty() {
+        let primary = tool.input_schema["required"][0]
+            .as_str()
+            .ok_or_else(|| format!("{} takes no text, only --arguments", tool.name))?;
+        let text = text.join(" ");
+        let value = if property_type(primary) == "array" {
+            json!([text])
+        } else {
+            Value::String(text)
+        };
+        arguments.insert(primary.to_string(), value);
+    }
+    Ok(Value::Object(arguments))
+}
+
+/// Split `line` into words at whitespace, keeping quoted text together
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err("Unterminated quote".to_string());
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kagiapi::testing::MockKagi;
+
+    #[tokio::test]
+    async fn test_repl_calls() {
+        let mock = MockKagi::start().await;
+        let server = crate::tests::test_server(&mock);
+        let tools = server.registry.tools();
+
+        assert_eq!(
+            split_words(r#"search "rust async" --since '2024-09-01'"#).unwrap(),
+            ["search", "rust async", "--since", "2024-09-01"]
+        );
+        assert!(split_words("search \"rust").is_err());
+        assert_eq!(parse("  ", &tools), Ok(None));
+        assert_eq!(
+            parse("search rust async --limit 3 --debug", &tools),
+            Ok(Some(Input::Call {
+                tool: "kagi_search_fetch".to_string(),
+                arguments: json!({"queries": ["rust async"], "limit": 3, "debug": true}),
+            }))
+        );
+        assert_eq!(
+            parse("summarize https://example.com --engine muriel", &tools),
+            Ok(Some(Input::Call {
+                tool: "kagi_summarizer".to_string(),
+                arguments: json!({"url": "https://example.com", "engine": "muriel"}),
+            }))
+        );
+        assert_eq!(
+            parse(
+                "enrich_news rust --since 2024-09-01 --max-age-days 7",
+                &tools
+            ),
+            Ok(Some(Input::Call {
+                tool: "kagi_enrich".to_string(),
+                arguments: json!({
+                    "query": "rust",
+                    "since": "2024-09-01",
+                    "max_age_days": 7,
+                    "enrich_type": "news"
+                }),
+            }))
+        );
+        assert!(parse("browse rust", &tools).is_err());
+
+        let (notifier, _outgoing) = Notifier::channel();
+        let session = Session::new(notifier.clone());
+        session.initialize(
+            Some(&json!({"capabilities": {}, "clientInfo": {"name": "repl", "version": "0"}})),
+            strict::PROTOCOL_VERSION,
+        );
+        let output = call(
+            &server,
+            &session,
+            &notifier,
+            "kagi_search_fetch",
+            json!({"queries": ["rust"]}),
+            false,
+        )
+        .await;
+        assert!(output.contains("https://www.rust-lang.org"), "{output}");
+        let output = call(
+            &server,
+            &session,
+            &notifier,
+            "kagi_search_fetch",
+            json!({}),
+            false,
+        )
+        .await;
+        assert!(output.starts_with("Error -32602: "), "{output}");
+    }
+}
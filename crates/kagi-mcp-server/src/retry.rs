@@ -0,0 +1,167 @@
+//! Retries of failed Kagi requests
+//!
+//! Rate limits (429), server errors (5xx) and timeouts are often over within
+//! seconds. Requests failing with them are retried up to `--retries` times with
+//! exponential backoff, and each retry is reported to the client as a progress
+//! notification, e.g. "Kagi rate limited the request, retrying in 2s", instead of
+//! the tool failing at once. Other failures, such as an exhausted balance, are
+//! returned immediately.
+
+use crate::notifier::Progress;
+use std::future::Future;
+use std::time::Duration;
+
+/// Number of retries when none is configured on the command line
+pub const DEFAULT_RETRIES: u32 = 2;
+
+/// Delay before the first retry, doubled for each further one
+const BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest delay between two attempts
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; zero disables retrying
+    pub max_retries: u32,
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            base_delay: BASE_DELAY,
+        }
+    }
+
+    /// How long to wait after attempt `retry` (0 for the first attempt) failed with
+    /// `error`, or `None` if the request is not retried
+    pub fn delay(&self, retry: u32, error: &kagiapi::Error) -> Option<Duration> {
+        (retry < self.max_retries && error.is_retryable()).then(|| {
+            self.base_delay
+                .saturating_mul(2u32.saturating_pow(retry))
+                .min(MAX_DELAY)
+        })
+    }
+
+    /// Run `request` until it succeeds, fails for good or runs out of retries,
+    /// reporting each retry to `progress`
+    pub async fn run<T, F, Fut>(
+        &self,
+        progress: &Progress<'_>,
+        mut request: F,
+    ) -> kagiapi::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = kagiapi::Result<T>>,
+    {
+        let mut retry = 0;
+        loop {
+            let error = match request().await {
+                Ok(response) => return Ok(response),
+                Err(error) => error,
+            };
+            let Some(delay) = self.delay(retry, &error) else {
+                return Err(error);
+            };
+            progress.report(None, &message(&error, delay));
+            tokio::time::sleep(delay).await;
+            retry += 1;
+        }
+    }
+}
+
+/// The notice telling the client why and when a request is retried
+pub fn message(error: &kagiapi::Error, delay: Duration) -> String {
+    let reason = match error.status() {
+        Some(429) => "Kagi rate limited the request".to_string(),
+        Some(status) => format!("Kagi failed with status {status}"),
+        None => "Kagi could not be reached in time".to_string(),
+    };
+    format!("{reason}, retrying in {}s", delay.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestClient;
+    use crate::{strict, KagiMcpServer, ServerOptions};
+    use kagiapi::testing::MockKagi;
+    use serde_json::json;
+    use std::sync::Arc;
+    use wiremock::matchers::path;
+    use wiremock::{Mock, ResponseTemplate};
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy::new(3);
+        let rate_limited = kagiapi::Error::from_status(429, "");
+        assert_eq!(policy.delay(0, &rate_limited), Some(Duration::from_secs(1)));
+        assert_eq!(policy.delay(2, &rate_limited), Some(Duration::from_secs(4)));
+        assert_eq!(policy.delay(3, &rate_limited), None);
+        assert_eq!(policy.delay(0, &kagiapi::Error::from_status(402, "")), None);
+        assert_eq!(RetryPolicy::default().delay(0, &rate_limited), None);
+        assert_eq!(
+            message(&rate_limited, Duration::from_secs(2)),
+            "Kagi rate limited the request, retrying in 2s"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retried_calls() {
+        let mock = MockKagi::start().await;
+        Mock::given(path("/api/v0/fastgpt"))
+            .respond_with(ResponseTemplate::new(429))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(mock.server())
+            .await;
+        Mock::given(path("/api/v0/search"))
+            .respond_with(ResponseTemplate::new(503))
+            .with_priority(1)
+            .mount(mock.server())
+            .await;
+        let server = KagiMcpServer::new(
+            mock.client(),
+            ServerOptions {
+                strict: strict::StrictMode::Panic,
+                retry: RetryPolicy {
+                    max_retries: 2,
+                    base_delay: Duration::ZERO,
+                },
+                ..ServerOptions::default()
+            },
+        );
+        let mut client = TestClient::start(Arc::new(server));
+
+        let result = client
+            .call_tool("kagi_fastgpt", json!({"query": "rust"}))
+            .await
+            .unwrap();
+        assert!(!result.is_error, "{}", result.text());
+        let notice = client.notification("notifications/progress").await;
+        assert_eq!(
+            notice["params"]["message"],
+            "Kagi rate limited the request, retrying in 0s"
+        );
+
+        let result = client
+            .call_tool("kagi_search_fetch", json!({"queries": ["rust"]}))
+            .await
+            .unwrap();
+        assert!(result.is_error);
+        let requests = mock.server().received_requests().await.unwrap();
+        let searches = requests
+            .iter()
+            .filter(|request| request.url.path().ends_with("/search"))
+            .count();
+        assert_eq!(searches, 3);
+    }
+}
@@ -0,0 +1,184 @@
+//! LLM completions requested from the client
+//!
+//! Clients that declare the `sampling` capability let the server ask their model
+//! for a completion with `sampling/createMessage`, so a tool can post-process
+//! results with the user's own model instead of bundling one. The client decides
+//! which model to use and may show the request to the user, who can reject it.
+
+use crate::session::Session;
+use serde::{Deserialize, Serialize};
+
+/// A message of a sampling conversation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SamplingMessage {
+    pub role: Role,
+    pub content: SamplingContent,
+}
+
+impl SamplingMessage {
+    /// A text message from the user
+    pub fn user(text: impl Into<String>) -> Self {
+        Self {
+            role: Role::User,
+            content: SamplingContent::Text { text: text.into() },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+/// Content of a sampling message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SamplingContent {
+    Text {
+        text: String,
+    },
+    Image {
+        /// Base64-encoded image data
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+}
+
+/// Hints for the client's model choice; priorities range from 0 to 1
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPreferences {
+    /// Model names to prefer, in order, e.g. `claude-3-5-sonnet` or `sonnet`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hints: Vec<ModelHint>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_priority: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed_priority: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub intelligence_priority: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModelHint {
+    pub name: String,
+}
+
+/// Parameters of `sampling/createMessage`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageParams {
+    pub messages: Vec<SamplingMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_preferences: Option<ModelPreferences>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    pub max_tokens: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+}
+
+/// The client's completion
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageResult {
+    pub role: Role,
+    pub content: SamplingContent,
+    /// The model that generated the message
+    pub model: String,
+    /// Why sampling stopped, e.g. `endTurn`, `stopSequence` or `maxTokens`
+    #[serde(default)]
+    pub stop_reason: Option<String>,
+}
+
+impl CreateMessageResult {
+    /// The text of the completion, if it is text
+    pub fn text(&self) -> Option<&str> {
+        match &self.content {
+            SamplingContent::Text { text } => Some(text),
+            SamplingContent::Image { .. } => None,
+        }
+    }
+}
+
+impl Session {
+    /// Ask the client's model for a completion
+    ///
+    /// Fails if the client does not support sampling, or rejects or fails the request.
+    pub async fn create_message(
+        &self,
+        params: CreateMessageParams,
+    ) -> Result<CreateMessageResult, String> {
+        if !self.client_supports("sampling") {
+            return Err("the client does not support sampling".to_string());
+        }
+        let params = serde_json::to_value(params)
+            .map_err(|e| format!("failed to serialize sampling/createMessage: {e}"))?;
+        let result = self.request("sampling/createMessage", params).await?;
+        serde_json::from_value(result)
+            .map_err(|e| format!("invalid sampling/createMessage result: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifier::Notifier;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_create_message() {
+        let (notifier, mut outgoing) = Notifier::channel();
+        let session = Arc::new(Session::new(notifier));
+        let params = CreateMessageParams {
+            messages: vec![SamplingMessage::user("Which result is the most recent?")],
+            system_prompt: Some("Answer with a number".to_string()),
+            max_tokens: 10,
+            ..CreateMessageParams::default()
+        };
+
+        let error = session.create_message(params.clone()).await.unwrap_err();
+        assert_eq!(error, "the client does not support sampling");
+
+        session.initialize(
+            Some(&json!({"capabilities": {"sampling": {}}})),
+            crate::strict::PROTOCOL_VERSION,
+        );
+        let completion = tokio::spawn({
+            let session = Arc::clone(&session);
+            async move { session.create_message(params).await }
+        });
+        let request: Value = serde_json::from_str(&outgoing.recv().await.unwrap()).unwrap();
+        assert_eq!(request["method"], "sampling/createMessage");
+        assert_eq!(
+            request["params"],
+            json!({
+                "messages": [{
+                    "role": "user",
+                    "content": {"type": "text", "text": "Which result is the most recent?"}
+                }],
+                "systemPrompt": "Answer with a number",
+                "maxTokens": 10
+            })
+        );
+        session.handle_response(&json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": {
+                "role": "assistant",
+                "content": {"type": "text", "text": "2"},
+                "model": "test-model",
+                "stopReason": "endTurn"
+            }
+        }));
+        let completion = completion.await.unwrap().unwrap();
+        assert_eq!(completion.text(), Some("2"));
+        assert_eq!(completion.model, "test-model");
+    }
+}
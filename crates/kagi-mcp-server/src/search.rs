@@ -0,0 +1,274 @@
+//! Structured search results
+//!
+//! `kagi_search_fetch` returns its results as `structuredContent` besides the
+//! formatted text, so clients that post-process results don't have to parse the
+//! text. With `output_format: json`, the text content is the same JSON.
+
+use kagiapi::{SearchResponse, SearchResult};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// A `kagi_search_fetch` result, and its structured content
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct SearchResults {
+    /// Results of each query, in query order
+    pub queries: Vec<QueryResults>,
+    /// Number of results left out to keep the response within its size limit
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub truncated: usize,
+}
+
+fn is_zero(count: &usize) -> bool {
+    *count == 0
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct QueryResults {
+    pub query: String,
+    pub results: Vec<SearchHit>,
+    /// Searches related to the query
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub related_searches: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct SearchHit {
+    /// Position of the result, counting skipped results
+    pub number: usize,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+    /// Publication date, as given by the Search API
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub published: Option<String>,
+}
+
+impl QueryResults {
+    /// The results of `response`, numbered from `first_number` like the text output
+    pub fn new(query: &str, response: &SearchResponse, first_number: usize) -> Self {
+        let mut results = Vec::new();
+        let mut related_searches = Vec::new();
+        for result in &response.data {
+            match (result.result_type, &result.title, &result.url) {
+                (0, Some(_), None) => {}
+                (1, _, _) => related_searches.extend(result.list.iter().flatten().cloned()),
+                (_, Some(title), url) => results.push(SearchHit {
+                    number: first_number + results.len(),
+                    title: title.clone(),
+                    url: url.clone(),
+                    snippet: result.snippet.clone(),
+                    published: result.published.clone(),
+                }),
+                _ => {}
+            }
+        }
+        Self {
+            query: query.to_string(),
+            results,
+            related_searches,
+        }
+    }
+}
+
+/// Whether a result is listed with a number: search results with a URL, and
+/// results of unknown types with a title
+fn is_numbered(result: &SearchResult) -> bool {
+    match result.result_type {
+        0 => result.title.is_some() && result.url.is_some(),
+        1 => false,
+        _ => result.title.is_some(),
+    }
+}
+
+/// Number of results of `response` listed with a number
+pub fn numbered_count(response: &SearchResponse) -> usize {
+    response
+        .data
+        .iter()
+        .filter(|result| is_numbered(result))
+        .count()
+}
+
+/// Drop search results whose page an earlier result already points at, adding
+/// their snippet to the earlier result
+pub fn merge_duplicates(responses: &mut [SearchResponse]) {
+    let mut seen: HashMap<String, (usize, usize)> = HashMap::new();
+    for query in 0..responses.len() {
+        let mut kept: Vec<SearchResult> = Vec::new();
+        for result in std::mem::take(&mut responses[query].data) {
+            let Some(key) = result
+                .url
+                .as_deref()
+                .filter(|_| result.result_type == 0)
+                .map(page_key)
+            else {
+                kept.push(result);
+                continue;
+            };
+            match seen.get(&key) {
+                Some(&(earlier_query, index)) => {
+                    let earlier = if earlier_query == query {
+                        &mut kept[index]
+                    } else {
+                        &mut responses[earlier_query].data[index]
+                    };
+                    merge_snippet(earlier, result.snippet);
+                }
+                None => {
+                    seen.insert(key, (query, kept.len()));
+                    kept.push(result);
+                }
+            }
+        }
+        responses[query].data = kept;
+    }
+}
+
+/// Drop the last search result of the last query that has one, returning
+/// whether there was one
+pub fn drop_last_result(responses: &mut [SearchResponse]) -> bool {
+    for response in responses.iter_mut().rev() {
+        if let Some(index) = response.data.iter().rposition(is_numbered) {
+            response.data.remove(index);
+            return true;
+        }
+    }
+    false
+}
+
+/// The page a URL points at, ignoring fragments and trailing slashes
+fn page_key(url: &str) -> String {
+    let key = match reqwest::Url::parse(url) {
+        Ok(mut url) => {
+            url.set_fragment(None);
+            url.to_string()
+        }
+        Err(_) => url.to_string(),
+    };
+    key.trim_end_matches('/').to_string()
+}
+
+fn merge_snippet(result: &mut SearchResult, snippet: Option<String>) {
+    let Some(snippet) = snippet.filter(|snippet| !snippet.trim().is_empty()) else {
+        return;
+    };
+    match &mut result.snippet {
+        Some(existing) if existing.contains(snippet.trim()) => {}
+        Some(existing) if !existing.trim().is_empty() => {
+            existing.push_str(" … ");
+            existing.push_str(snippet.trim());
+        }
+        _ => result.snippet = Some(snippet),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_query_results() {
+        let response: kagiapi::SearchResponse = serde_json::from_value(json!({
+            "meta": {"id": "1", "node": "test", "ms": 5},
+            "data": [
+                {"t": 0, "url": "https://www.rust-lang.org/", "title": "Rust", "snippet": "A language"},
+                {"t": 0, "title": "No URL"},
+                {"t": 1, "list": ["rust tutorial"]},
+                {"t": 0, "url": "https://doc.rust-lang.org/book/", "title": "The Book", "published": "2024-01-01"}
+            ]
+        }))
+        .unwrap();
+
+        let results = QueryResults::new("rust", &response, 3);
+        assert_eq!(results.results.len(), 2);
+        assert_eq!(results.results[1].number, 4);
+        assert_eq!(results.related_searches, ["rust tutorial"]);
+        assert_eq!(
+            serde_json::to_value(&results.results[0]).unwrap(),
+            json!({"number": 3, "title": "Rust", "url": "https://www.rust-lang.org/", "snippet": "A language"})
+        );
+
+        let schema = crate::tools::output_schema::<SearchResults>();
+        assert_eq!(schema["required"], json!(["queries"]));
+    }
+
+    #[test]
+    fn test_merge_duplicates() {
+        let response = |data| -> SearchResponse {
+            serde_json::from_value(json!({
+                "meta": {"id": "1", "node": "test", "ms": 5},
+                "data": data
+            }))
+            .unwrap()
+        };
+        let mut responses = [
+            response(json!([
+                {"t": 0, "url": "https://tokio.rs/", "title": "Tokio", "snippet": "A runtime"},
+                {"t": 0, "url": "https://docs.rs/tokio", "title": "tokio - docs.rs"}
+            ])),
+            response(json!([
+                {"t": 0, "url": "https://tokio.rs#start", "title": "Tokio", "snippet": "Async I/O"},
+                {"t": 1, "list": ["tokio tutorial"]},
+                {"t": 0, "url": "https://docs.rs/tokio/", "title": "tokio", "snippet": "Docs"},
+                {"t": 0, "url": "https://async.rs/", "title": "async-std"}
+            ])),
+        ];
+        merge_duplicates(&mut responses);
+
+        assert_eq!(
+            responses[0].data[0].snippet.as_deref(),
+            Some("A runtime … Async I/O")
+        );
+        assert_eq!(responses[0].data[1].snippet.as_deref(), Some("Docs"));
+        assert_eq!(responses[1].data.len(), 2);
+        assert_eq!(numbered_count(&responses[1]), 1);
+        let second = QueryResults::new("async", &responses[1], 1 + numbered_count(&responses[0]));
+        assert_eq!(second.results[0].number, 3);
+        assert_eq!(second.related_searches, ["tokio tutorial"]);
+    }
+
+    #[tokio::test]
+    async fn test_json_output() {
+        let mock = kagiapi::testing::MockKagi::start().await;
+        let mut client = crate::testing::TestClient::start(crate::tests::test_server(&mock));
+        let result = client
+            .call_tool("kagi_search_fetch", json!({"queries": ["rust"]}))
+            .await
+            .unwrap();
+        let structured = result.structured_content.clone().unwrap();
+        assert_eq!(structured["queries"][0]["query"], "rust");
+        assert_eq!(
+            structured["queries"][0]["results"][0]["url"],
+            "https://www.rust-lang.org/"
+        );
+        assert!(result.text().starts_with("-----\nResults for search query"));
+
+        let result = client
+            .call_tool(
+                "kagi_search_fetch",
+                json!({"queries": ["rust"], "output_format": "markdown"}),
+            )
+            .await
+            .unwrap();
+        assert!(result.text().starts_with("## Results for \"rust\""));
+
+        let result = client
+            .call_tool(
+                "kagi_search_fetch",
+                json!({"queries": ["rust"], "output_format": "json", "debug": true}),
+            )
+            .await
+            .unwrap();
+        let text: serde_json::Value =
+            serde_json::from_str(result.content[0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(Some(text), result.structured_content);
+        assert!(result.content[1]["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("-----\nDebug:"));
+    }
+}
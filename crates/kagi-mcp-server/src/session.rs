@@ -0,0 +1,321 @@
+//! Per-session client state and requests from the server to the client
+//!
+//! A [`Session`] is the context requests of one client session are handled in. It
+//! sends requests to the client through its [`ClientHandle`], and remembers what
+//! the client declared in `initialize`. Every session has its own
+//! initialization state and data, so clients sharing the server over a network
+//! transport don't see each other's.
+//!
+//! Tool handlers reach their session through their
+//! [`ToolContext`](crate::context::ToolContext).
+//!
+//! Over authenticated HTTP transports, a session belongs to the
+//! [`Principal`](crate::auth::Principal) that opened it.
+//!
+//! Clients that declare the `roots` capability are asked for their workspace roots
+//! with `roots/list` once initialized, and again whenever they send
+//! `notifications/roots/list_changed`.
+
+#[cfg(feature = "http")]
+use crate::auth::Principal;
+use crate::client_handle::ClientHandle;
+use crate::logging::LogLevel;
+use crate::notification::Notification;
+use crate::notifier::Notifier;
+use crate::output;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+
+/// A workspace root shared by the client
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Root {
+    /// A `file://` URI
+    pub uri: String,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Name and version of the client, from `initialize`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ClientInfo {
+    pub name: String,
+    pub version: String,
+}
+
+/// What was agreed in `initialize`
+#[derive(Debug)]
+struct Initialized {
+    /// The protocol version the server answered with
+    protocol_version: &'static str,
+    /// `capabilities` of the client's `initialize` params
+    capabilities: Value,
+    client_info: Option<ClientInfo>,
+    /// Whether the client opted in to suggested calls
+    accepts_suggestions: bool,
+}
+
+pub struct Session {
+    client: ClientHandle,
+    initialized: OnceLock<Initialized>,
+    /// Minimum level of log messages sent to the client
+    log_level: Mutex<LogLevel>,
+    /// Roots listed by the client, until they change
+    roots: RwLock<Option<Vec<Root>>>,
+    /// Incremented when the client's roots change, so stale listings are not cached
+    roots_generation: AtomicU64,
+    /// Session data of tool handlers, by type
+    data: Mutex<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>,
+    /// The authenticated client that opened the session
+    #[cfg(feature = "http")]
+    principal: OnceLock<Principal>,
+}
+
+impl Session {
+    pub fn new(notifier: Notifier) -> Self {
+        Self {
+            client: ClientHandle::new(notifier),
+            initialized: OnceLock::new(),
+            log_level: Mutex::default(),
+            roots: RwLock::default(),
+            roots_generation: AtomicU64::new(0),
+            data: Mutex::default(),
+            #[cfg(feature = "http")]
+            principal: OnceLock::new(),
+        }
+    }
+
+    /// A session whose client can only be reached in responses to its requests,
+    /// as over Streamable HTTP without a standalone event stream
+    #[cfg(feature = "http")]
+    pub fn detached() -> Self {
+        Self::new(Notifier::channel().0)
+    }
+
+    #[cfg(feature = "http")]
+    pub fn notifier(&self) -> &Notifier {
+        self.client.notifier()
+    }
+
+    /// The authenticated client that opened the session, if clients authenticate
+    #[cfg(feature = "http")]
+    pub fn principal(&self) -> Option<&Principal> {
+        self.principal.get()
+    }
+
+    /// Give the session to the client that opened it
+    #[cfg(feature = "http")]
+    pub fn set_principal(&self, principal: Option<Principal>) {
+        if let Some(principal) = principal {
+            let _ = self.principal.set(principal);
+        }
+    }
+
+    /// Whether a request authenticated as `principal` may use the session
+    #[cfg(feature = "http")]
+    pub fn is_owned_by(&self, principal: Option<&Principal>) -> bool {
+        self.principal().map(|owner| &owner.subject) == principal.map(|p| &p.subject)
+    }
+
+    /// Remember the client's `initialize` params, answered with `protocol_version`
+    pub fn initialize(&self, params: Option<&Value>, protocol_version: &'static str) {
+        let capabilities = params
+            .and_then(|params| params.get("capabilities"))
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+        let client_info = params
+            .and_then(|params| params.get("clientInfo"))
+            .and_then(|info| serde_json::from_value(info.clone()).ok());
+        // A repeated `initialize` cannot change what the session was set up with
+        let _ = self.initialized.set(Initialized {
+            protocol_version,
+            capabilities,
+            client_info,
+            accepts_suggestions: output::client_accepts_suggestions(params),
+        });
+    }
+
+    /// Whether the client declared `capability` in `initialize`
+    pub fn client_supports(&self, capability: &str) -> bool {
+        self.initialized
+            .get()
+            .and_then(|initialized| initialized.capabilities.get(capability))
+            .is_some_and(|capability| !capability.is_null())
+    }
+
+    /// The client's name and version, once initialized
+    pub fn client_info(&self) -> Option<&ClientInfo> {
+        self.initialized.get()?.client_info.as_ref()
+    }
+
+    /// The negotiated protocol version, once initialized
+    pub fn protocol_version(&self) -> Option<&'static str> {
+        Some(self.initialized.get()?.protocol_version)
+    }
+
+    /// Whether the client opted in to suggested calls
+    pub fn accepts_suggestions(&self) -> bool {
+        self.initialized
+            .get()
+            .is_some_and(|initialized| initialized.accepts_suggestions)
+    }
+
+    /// Minimum level of log messages the client wants
+    pub fn log_level(&self) -> LogLevel {
+        *self.log_level.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_log_level(&self, level: LogLevel) {
+        *self.log_level.lock().unwrap_or_else(|e| e.into_inner()) = level;
+    }
+
+    /// The session's instance of `T`, created on first use
+    pub fn data<T: Any + Default + Send + Sync>(&self) -> Arc<T> {
+        let data = Arc::clone(
+            self.data
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .entry(TypeId::of::<T>())
+                .or_insert_with(|| Arc::new(T::default())),
+        );
+        data.downcast()
+            .unwrap_or_else(|_| unreachable!("session data is keyed by its type"))
+    }
+
+    /// Update the session for a notification from the client
+    pub fn handle_notification(self: &Arc<Self>, notification: &Notification) {
+        if matches!(
+            notification.method.as_str(),
+            "notifications/initialized" | "notifications/roots/list_changed"
+        ) {
+            self.refresh_roots();
+        }
+    }
+
+    /// Send a request to the client and wait for its result, or until it times out
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        self.client.request(method, params).await
+    }
+
+    /// Deliver a response from the client to the request awaiting it
+    ///
+    /// Returns whether a request was waiting for it; responses to keepalive pings
+    /// are not.
+    pub fn handle_response(&self, response: &Value) -> bool {
+        self.client.handle_response(response)
+    }
+
+    /// Fail the requests still waiting for the client, as the session has ended
+    pub fn close(&self) {
+        self.client.close();
+    }
+
+    /// The client's workspace roots, empty if it shares none
+    ///
+    /// Roots are listed once and cached until the client reports a change.
+    pub async fn roots(&self) -> Vec<Root> {
+        if !self.client_supports("roots") || !self.client.is_connected() {
+            return Vec::new();
+        }
+        if let Some(roots) = self.cached_roots().clone() {
+            return roots;
+        }
+        let generation = self.roots_generation.load(Ordering::Acquire);
+        let roots = match self.request("roots/list", json!({})).await {
+            Ok(mut result) => serde_json::from_value::<Vec<Root>>(result["roots"].take())
+                .map_err(|e| format!("invalid roots/list result: {e}")),
+            Err(e) => Err(e),
+        };
+        match roots {
+            Ok(roots) => {
+                if self.roots_generation.load(Ordering::Acquire) == generation {
+                    *self.roots.write().unwrap_or_else(|e| e.into_inner()) = Some(roots.clone());
+                }
+                roots
+            }
+            Err(e) => {
+                tracing::warn!("Failed to list the client's roots: {e}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Forget the cached roots and list them again in the background
+    fn refresh_roots(self: &Arc<Self>) {
+        if !self.client_supports("roots") || !self.client.is_connected() {
+            return;
+        }
+        self.roots_generation.fetch_add(1, Ordering::AcqRel);
+        *self.roots.write().unwrap_or_else(|e| e.into_inner()) = None;
+        let session = Arc::clone(self);
+        tokio::spawn(async move {
+            session.roots().await;
+        });
+    }
+
+    fn cached_roots(&self) -> std::sync::RwLockReadGuard<'_, Option<Vec<Root>>> {
+        self.roots.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_roots() {
+        let (notifier, mut outgoing) = Notifier::channel();
+        let session = Arc::new(Session::new(notifier));
+        assert!(session.roots().await.is_empty());
+
+        session.initialize(
+            Some(&json!({"capabilities": {"roots": {"listChanged": true}}})),
+            crate::strict::PROTOCOL_VERSION,
+        );
+        let answer = |roots: Value| {
+            let session = Arc::clone(&session);
+            move |line: String| {
+                let request: Value = serde_json::from_str(&line).unwrap();
+                assert_eq!(request["method"], "roots/list");
+                assert!(session.handle_response(
+                    &json!({"jsonrpc": "2.0", "id": request["id"], "result": {"roots": roots}})
+                ));
+            }
+        };
+
+        let roots = tokio::spawn({
+            let session = Arc::clone(&session);
+            async move { session.roots().await }
+        });
+        answer(json!([{"uri": "file:///work/a", "name": "a"}]))(outgoing.recv().await.unwrap());
+        let roots = roots.await.unwrap();
+        assert_eq!(roots[0].uri, "file:///work/a");
+        // Listed roots are cached until they change
+        assert_eq!(session.roots().await, roots);
+
+        session.refresh_roots();
+        answer(json!([{"uri": "file:///work/b"}]))(outgoing.recv().await.unwrap());
+        while session.cached_roots().is_none() {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(session.roots().await[0].uri, "file:///work/b");
+
+        // Keepalive pings are answered too, but nothing awaits them
+        assert!(!session.handle_response(&json!({"id": "keepalive-1", "result": {}})));
+
+        let pending = tokio::spawn({
+            let session = Arc::clone(&session);
+            async move { session.request("sampling/createMessage", json!({})).await }
+        });
+        outgoing.recv().await.unwrap();
+        session.close();
+        assert!(pending
+            .await
+            .unwrap()
+            .unwrap_err()
+            .contains("session ended"));
+    }
+}
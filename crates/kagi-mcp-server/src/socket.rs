@@ -0,0 +1,146 @@
+//! TCP and Unix socket transports
+//!
+//! Supervisors that keep one long-lived server process can connect several clients
+//! to it over a local socket, without the overhead of HTTP. Each connection speaks
+//! newline-delimited JSON-RPC, exactly like the stdio transport, and is a session of
+//! its own: request ids and cancellations of one connection never affect another.
+//!
+//! When the server shuts down, it stops accepting connections and waits for the
+//! sessions of the open ones to finish. After handing its listener to an upgraded
+//! server (see [`handoff`](crate::handoff)), it also stops accepting, but lets the
+//! open sessions run until their clients close them.
+
+use crate::handoff;
+use crate::transport::StreamTransport;
+use crate::{KagiMcpServer, McpResult};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::task::JoinSet;
+
+impl KagiMcpServer {
+    /// Accept connections on `addr` until the server shuts down
+    pub async fn run_tcp(self: Arc<Self>, addr: SocketAddr) -> McpResult<()> {
+        let listener = handoff::tcp_listener(addr).await?;
+        tracing::info!("Serving MCP over TCP at {}", listener.local_addr()?);
+        let mut connections = JoinSet::new();
+        let mut handoff = std::pin::pin!(handoff::hand_off(&listener));
+        for connection in 1u64.. {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                () = self.shutdown.cancelled() => break,
+                () = &mut handoff => break,
+            };
+            match accepted {
+                Ok((stream, _)) => {
+                    let (input, output) = stream.into_split();
+                    self.spawn_connection(&mut connections, connection, input, output);
+                }
+                Err(e) => tracing::warn!("Failed to accept a TCP connection: {e}"),
+            }
+        }
+        connections.join_all().await;
+        Ok(())
+    }
+
+    /// Accept connections on a Unix socket at `path` until the server shuts down
+    ///
+    /// A socket left behind by a previous run is replaced; any other file at `path`
+    /// is an error.
+    #[cfg(unix)]
+    pub async fn run_unix(self: Arc<Self>, path: &std::path::Path) -> McpResult<()> {
+        let listener = handoff::unix_listener(path)?;
+        tracing::info!("Serving MCP over the Unix socket {}", path.display());
+        let mut connections = JoinSet::new();
+        let mut handoff = std::pin::pin!(handoff::hand_off(&listener));
+        for connection in 1u64.. {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                () = self.shutdown.cancelled() => break,
+                () = &mut handoff => break,
+            };
+            match accepted {
+                Ok((stream, _)) => {
+                    let (input, output) = stream.into_split();
+                    self.spawn_connection(&mut connections, connection, input, output);
+                }
+                Err(e) => tracing::warn!("Failed to accept a Unix socket connection: {e}"),
+            }
+        }
+        connections.join_all().await;
+        Ok(())
+    }
+
+    /// Serve one connection in the background as its own session
+    fn spawn_connection(
+        self: &Arc<Self>,
+        connections: &mut JoinSet<()>,
+        connection: u64,
+        input: impl AsyncRead + Unpin + Send + 'static,
+        output: impl AsyncWrite + Unpin + Send + 'static,
+    ) {
+        let server = Arc::clone(self);
+        // Reap finished connections so the set doesn't grow unbounded
+        while connections.try_join_next().is_some() {}
+        connections.spawn(async move {
+            let verbose = server.verbose;
+            let session = format!("connection-{connection}");
+            let transport =
+                StreamTransport::new(input, output).with_max_message_size(server.max_message_size);
+            if let Err(e) = server.serve(transport, Some(session)).await {
+                if verbose {
+                    tracing::warn!("Connection {connection} failed: {e}");
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::test_server;
+    use kagiapi::testing::MockKagi;
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpStream;
+
+    /// Send `request` on a new connection and read the response
+    async fn exchange(addr: &str, request: Value) -> Value {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (input, mut output) = stream.into_split();
+        output
+            .write_all(format!("{request}\n").as_bytes())
+            .await
+            .unwrap();
+        let line = BufReader::new(input).lines().next_line().await.unwrap();
+        serde_json::from_str(&line.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_tcp_connections() {
+        let mock = MockKagi::start().await;
+        // Reserve a free port, then let the server bind it
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        tokio::spawn(test_server(&mock).run_tcp(addr));
+        let addr = addr.to_string();
+        let mut connected = false;
+        for _ in 0..50 {
+            if TcpStream::connect(&addr).await.is_ok() {
+                connected = true;
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(connected);
+
+        // Both clients use id 1 without their requests getting mixed up
+        let initialize = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}});
+        let list = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"});
+        let (first, second) = tokio::join!(exchange(&addr, initialize), exchange(&addr, list));
+        assert!(first["result"]["protocolVersion"].is_string());
+        assert!(second["result"]["tools"].is_array());
+    }
+}
@@ -0,0 +1,387 @@
+//! Per-session cost ceiling
+//!
+//! With `--max-session-cost`, each session keeps a running total of the estimated
+//! cost of its successful tool calls. A call whose estimate would take the total
+//! past the ceiling fails with a [`ErrorCode::CostCeilingReached`] error telling
+//! the model to ask the user, so a runaway agent loop cannot spend without bound.
+//! Clients that support elicitation ask the user right away instead, and the call
+//! goes ahead if they approve. Free tools, such as those of sub-servers, are never
+//! refused.
+//!
+//! Estimates are upper bounds from `kagiapi::pricing`: a URL summary counts as the
+//! most its engine can bill, since the document's size is unknown beforehand.
+//! `kagi_unfurl` reserves the cheapest summary of such a document for its fallback,
+//! and keeps nothing when the page's own metadata sufficed. Results served from the
+//! cache cost nothing and are not counted.
+
+use crate::elicitation::Elicitation;
+use crate::error_code::ErrorCode;
+use crate::session::Session;
+use crate::tools::{self, ToolCallError};
+use kagiapi::pricing::{self, ENRICH_COST_PER_QUERY, FASTGPT_COST_PER_QUERY};
+use kagiapi::SummarizerEngine;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// Estimated spend of a session so far, in USD
+#[derive(Debug, Default)]
+struct Spent(Mutex<f64>);
+
+impl Spent {
+    fn lock(&self) -> std::sync::MutexGuard<'_, f64> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The user's answer when a call would pass the session's ceiling
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SpendApproval {
+    /// Allow this call, although it takes the session's Kagi spend past its limit
+    approve: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CostCeiling {
+    /// Most a session may spend in USD; `None` leaves spending unlimited
+    max: Option<f64>,
+}
+
+impl CostCeiling {
+    pub fn new(max: Option<f64>) -> Self {
+        Self { max }
+    }
+
+    /// Reserve `estimate` of `session`'s allowance for a call of `tool`
+    ///
+    /// If the ceiling would be passed, the user is asked to approve the call when the
+    /// client supports elicitation; the call fails otherwise.
+    pub async fn reserve(
+        &self,
+        tool: &str,
+        estimate: f64,
+        session: &Session,
+    ) -> Result<Reservation, ToolCallError> {
+        let Some(max) = self.max.filter(|_| estimate > 0.0) else {
+            return Ok(Reservation(None));
+        };
+        let data = session.data::<Spent>();
+        let spent = {
+            let mut spent = data.lock();
+            if *spent + estimate <= max {
+                *spent += estimate;
+                drop(spent);
+                return Ok(Reservation(Some((data, estimate))));
+            }
+            *spent
+        };
+        let passing = format!(
+            "{tool} would cost up to ${estimate:.3}, taking this session's Kagi spend of \
+             ${spent:.3} past its ${max:.2} ceiling."
+        );
+        let message = if !session.client_supports("elicitation") {
+            format!(
+                "{passing} Do not retry: ask the user whether to go on, which needs a higher \
+                 --max-session-cost."
+            )
+        } else if approved(&passing, session).await {
+            *data.lock() += estimate;
+            return Ok(Reservation(Some((data, estimate))));
+        } else {
+            format!("{passing} The user did not approve it, so do not retry.")
+        };
+        let error = ToolCallError::new(ErrorCode::CostCeilingReached, message);
+        Err(error.with_data(json!({
+            "tool": tool,
+            "estimatedCost": estimate,
+            "sessionSpend": spent,
+            "maxSessionCost": max,
+        })))
+    }
+}
+
+/// Whether the user approves a call that would pass the ceiling, as `passing` explains
+async fn approved(passing: &str, session: &Session) -> bool {
+    let message = format!("{passing} Allow this call?");
+    match session.elicit::<SpendApproval>(&message).await {
+        Ok(Elicitation::Accept(approval)) => approval.approve,
+        Ok(Elicitation::Decline | Elicitation::Cancel) => false,
+        Err(e) => {
+            tracing::warn!("Could not ask the user to approve a call: {e}");
+            false
+        }
+    }
+}
+
+/// Part of a session's allowance held for a call in progress
+///
+/// Dropping it gives the amount back, as failed, timed out and cancelled calls are
+/// not billed; [`Reservation::commit`] keeps it spent.
+#[must_use]
+pub struct Reservation(Option<(Arc<Spent>, f64)>);
+
+impl Reservation {
+    /// Count the reserved amount as spent, once the call succeeded
+    pub fn commit(mut self) {
+        self.0 = None;
+    }
+
+    /// Count `cost` of the reserved amount as spent and give back the rest, once a
+    /// call that cost less than its estimate succeeded
+    pub fn settle(mut self, cost: f64) {
+        if let Some((spent, estimate)) = self.0.take() {
+            let mut spent = spent.lock();
+            *spent = (*spent - estimate + cost.min(estimate)).max(0.0);
+        }
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let Some((spent, estimate)) = self.0.take() {
+            let mut spent = spent.lock();
+            *spent = (*spent - estimate).max(0.0);
+        }
+    }
+}
+
+/// Estimated upper bound of the cost of calling `tool` with `args`, in USD
+pub fn estimate(
+    tool: &str,
+    args: &Value,
+    default_engine: SummarizerEngine,
+    smallweb_budget: f64,
+) -> f64 {
+    let engine = args
+        .get("engine")
+        .and_then(|engine| serde_json::from_value::<tools::Engine>(engine.clone()).ok())
+        .map_or(default_engine, Into::into);
+    // Documents behind a URL are estimated at the billing cap
+    let summary = |text: Option<&str>| {
+        let tokens = text.map_or(
+            pricing::SUMMARIZER_MAX_BILLED_TOKENS,
+            pricing::estimate_tokens,
+        );
+        pricing::summary_cost(engine, tokens)
+    };
+    let count = |name: &str| args.get(name).and_then(Value::as_array).map_or(0, Vec::len);
+    match tool {
+        "kagi_search_fetch" => count("queries") as f64 * pricing::SEARCH_COST_PER_QUERY,
+        "kagi_research" => {
+            let sources = args
+                .get("sources")
+                .and_then(Value::as_u64)
+                .and_then(|sources| usize::try_from(sources).ok());
+            pricing::SEARCH_COST_PER_QUERY + crate::research_sources(sources) as f64 * summary(None)
+        }
+        "kagi_summarizer" => summary(None),
+        "kagi_unfurl" => pricing::summary_cost(
+            SummarizerEngine::Cecil,
+            pricing::SUMMARIZER_MAX_BILLED_TOKENS,
+        ),
+        "kagi_summarizer_text" => summary(args.get("text").and_then(Value::as_str)),
+        "kagi_fastgpt" | "kagi_fastgpt_followup" => FASTGPT_COST_PER_QUERY,
+        "kagi_enrich" => ENRICH_COST_PER_QUERY,
+        "kagi_smallweb_digest" => smallweb_budget,
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestClient;
+    use crate::{cache, strict, KagiMcpServer, ServerOptions, RESEARCH_MAX_SOURCES};
+    use kagiapi::testing::MockKagi;
+    use std::time::Duration;
+
+    #[test]
+    fn test_estimate() {
+        let cecil = SummarizerEngine::Cecil;
+        let search = json!({"queries": ["rust", "tokio"]});
+        assert!((estimate("kagi_search_fetch", &search, cecil, 1.0) - 0.05).abs() < 1e-9);
+        let muriel = json!({"url": "https://example.com", "engine": "muriel"});
+        assert_eq!(estimate("kagi_summarizer", &muriel, cecil, 1.0), 1.0);
+        assert_eq!(
+            estimate("kagi_smallweb_digest", &json!({}), cecil, 0.5),
+            0.5
+        );
+        let unfurl = estimate(
+            "kagi_unfurl",
+            &json!({"url": "https://example.com"}),
+            cecil,
+            1.0,
+        );
+        assert!((unfurl - 0.3).abs() < 1e-9);
+        assert_eq!(estimate("hub__tool", &json!({}), cecil, 1.0), 0.0);
+        let research =
+            |sources| estimate("kagi_research", &json!({"sources": sources}), cecil, 1.0);
+        assert_eq!(research(1000), research(RESEARCH_MAX_SOURCES));
+        assert_eq!(research(0), research(1));
+        assert!(research(0) > pricing::SEARCH_COST_PER_QUERY);
+    }
+
+    #[tokio::test]
+    async fn test_cost_ceiling() {
+        let mock = MockKagi::start().await;
+        let server = KagiMcpServer::new(
+            mock.client(),
+            ServerOptions {
+                strict: strict::StrictMode::Panic,
+                cost_ceiling: CostCeiling::new(Some(0.04)),
+                ..ServerOptions::default()
+            },
+        );
+        let mut client = TestClient::start(Arc::new(server));
+
+        let result = client
+            .call_tool("kagi_fastgpt", json!({"query": "rust"}))
+            .await
+            .unwrap();
+        assert!(!result.is_error);
+        let result = client
+            .call_tool("kagi_enrich_web", json!({"query": "rust"}))
+            .await
+            .unwrap();
+        assert!(!result.is_error);
+
+        // $0.017 spent, and a search costs $0.025
+        let error = client
+            .call_tool("kagi_search_fetch", json!({"queries": ["rust"]}))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, ErrorCode::CostCeilingReached.code());
+        assert!(error.message.contains("ask the user"), "{}", error.message);
+        assert_eq!(error.data.unwrap()["maxSessionCost"], 0.04);
+        let result = client
+            .call_tool("kagi_enrich_news", json!({"query": "rust"}))
+            .await
+            .unwrap();
+        assert!(!result.is_error);
+    }
+
+    #[tokio::test]
+    async fn test_cached_results_are_free() {
+        let mock = MockKagi::start().await;
+        let server = KagiMcpServer::new(
+            mock.client(),
+            ServerOptions {
+                strict: strict::StrictMode::Panic,
+                cache: cache::ResultCache::new(Duration::from_secs(60), 16),
+                cost_ceiling: CostCeiling::new(Some(0.03)),
+                ..ServerOptions::default()
+            },
+        );
+        let mut client = TestClient::start(Arc::new(server));
+
+        // A search costs $0.025, so only one fits below the ceiling
+        for _ in 0..3 {
+            let result = client
+                .call_tool("kagi_search_fetch", json!({"queries": ["rust"]}))
+                .await
+                .unwrap();
+            assert!(!result.is_error);
+        }
+        let error = client
+            .call_tool("kagi_search_fetch", json!({"queries": ["tokio"]}))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, ErrorCode::CostCeilingReached.code());
+    }
+
+    #[tokio::test]
+    async fn test_unfurl_spend() {
+        use wiremock::matchers::path;
+        use wiremock::{Mock, ResponseTemplate};
+
+        let mock = MockKagi::start().await;
+        for (page, html) in [
+            ("/titled", "<html><head><title>Rust</title></head></html>"),
+            ("/untitled", "<html><body></body></html>"),
+        ] {
+            Mock::given(path(page))
+                .respond_with(ResponseTemplate::new(200).set_body_raw(html, "text/html"))
+                .mount(mock.server())
+                .await;
+        }
+        let server = KagiMcpServer::new(
+            mock.client(),
+            ServerOptions {
+                strict: strict::StrictMode::Panic,
+                cost_ceiling: CostCeiling::new(Some(0.35)),
+                ..ServerOptions::default()
+            },
+        );
+        let mut client = TestClient::start(Arc::new(server));
+        let unfurl = |page: &str| json!({"url": format!("{}{page}", mock.server().uri())});
+
+        // The page's own title is free; the fallback summary of 1024 tokens is not
+        let result = client
+            .call_tool("kagi_unfurl", unfurl("/titled"))
+            .await
+            .unwrap();
+        assert_eq!(result.structured_content.unwrap()["title"], "Rust");
+        let result = client
+            .call_tool("kagi_unfurl", unfurl("/untitled"))
+            .await
+            .unwrap();
+        assert!(result.structured_content.unwrap()["description"].is_string());
+        let result = client
+            .call_tool("kagi_search_fetch", json!({"queries": ["rust"]}))
+            .await
+            .unwrap();
+        assert!(!result.is_error);
+
+        // $0.056 spent, and another fallback might cost $0.30
+        let error = client
+            .call_tool("kagi_unfurl", unfurl("/untitled"))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, ErrorCode::CostCeilingReached.code());
+    }
+
+    #[tokio::test]
+    async fn test_approved_spend() {
+        let mock = MockKagi::start().await;
+        let server = KagiMcpServer::new(
+            mock.client(),
+            ServerOptions {
+                strict: strict::StrictMode::Panic,
+                cost_ceiling: CostCeiling::new(Some(0.03)),
+                ..ServerOptions::default()
+            },
+        );
+        let mut client = TestClient::start(Arc::new(server));
+        client
+            .initialize_with(json!({"elicitation": {}}))
+            .await
+            .unwrap();
+        client.respond_to(
+            "elicitation/create",
+            json!({"action": "accept", "content": {"approve": true}}),
+        );
+        for query in ["rust", "tokio"] {
+            let result = client
+                .call_tool("kagi_search_fetch", json!({ "queries": [query] }))
+                .await
+                .unwrap();
+            assert!(!result.is_error);
+        }
+        let elicitation = client.notification("elicitation/create").await;
+        let message = elicitation["params"]["message"].as_str().unwrap();
+        assert!(message.ends_with("ceiling. Allow this call?"), "{message}");
+
+        client.respond_to("elicitation/create", json!({"action": "decline"}));
+        let error = client
+            .call_tool("kagi_search_fetch", json!({"queries": ["serde"]}))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, ErrorCode::CostCeilingReached.code());
+        assert!(
+            error.message.contains("did not approve"),
+            "{}",
+            error.message
+        );
+    }
+}
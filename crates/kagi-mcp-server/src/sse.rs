@@ -0,0 +1,287 @@
+//! HTTP with Server-Sent Events transport
+//!
+//! The transport of the 2024-11-05 specification, which several MCP clients still
+//! speak instead of Streamable HTTP:
+//!
+//! - `GET /sse` opens an event stream. Its first `endpoint` event gives the path,
+//!   including a session id, to which the client posts its messages.
+//! - `POST /messages?sessionId=…` carries one JSON-RPC message and is answered with
+//!   `202 Accepted`. Responses and notifications are sent as `message` events on
+//!   the session's event stream.
+//!
+//! A session ends when the client closes its event stream. Like a stdio session, it
+//! can send requests to the client on its event stream, such as `roots/list`, and
+//! the client posts its responses. When clients authenticate, only the client that
+//! opened the event stream can post to its session.
+
+use crate::auth::Principal;
+use crate::cancellation::scoped_id;
+use crate::error_code::ErrorCode;
+use crate::http::{check_origin, new_session_id, rpc_error};
+use crate::notification;
+use crate::notifier::Notifier;
+use crate::session::Session;
+use crate::{strict, KagiMcpServer, McpRequest};
+use axum::extract::{Extension, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use futures::stream::{self, StreamExt};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+
+/// Path of the event stream
+pub const SSE_ENDPOINT: &str = "/sse";
+
+/// Path clients post their messages to
+pub const MESSAGES_ENDPOINT: &str = "/messages";
+
+/// State shared by the SSE handlers
+struct LegacySse {
+    server: Arc<KagiMcpServer>,
+    /// Open sessions, which send their messages on their event stream, by id
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+    validator: Option<strict::Validator>,
+}
+
+impl LegacySse {
+    fn sessions(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<Session>>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Ends a session when its event stream is dropped
+struct SessionGuard {
+    transport: Arc<LegacySse>,
+    session: String,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        if let Some(session) = self.transport.sessions().remove(&self.session) {
+            session.close();
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct MessageParams {
+    #[serde(rename = "sessionId")]
+    session_id: String,
+}
+
+/// Routes of the SSE transport
+pub fn router(server: Arc<KagiMcpServer>) -> Router {
+    let transport = LegacySse {
+        validator: strict::Validator::new(server.strict, strict::PROTOCOL_VERSION),
+        server,
+        sessions: Mutex::default(),
+    };
+    Router::new()
+        .route(SSE_ENDPOINT, get(handle_connect))
+        .route(MESSAGES_ENDPOINT, post(handle_message))
+        .with_state(Arc::new(transport))
+}
+
+async fn handle_connect(
+    State(transport): State<Arc<LegacySse>>,
+    principal: Option<Extension<Principal>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(rejection) = check_origin(&headers) {
+        return rejection.into_response();
+    }
+    let session = new_session_id();
+    let (notifier, mut messages) = Notifier::channel();
+    let state = Session::new(notifier);
+    state.set_principal(principal.map(|Extension(principal)| principal));
+    transport
+        .sessions()
+        .insert(session.clone(), Arc::new(state));
+
+    let endpoint = Event::default()
+        .event("endpoint")
+        .data(format!("{MESSAGES_ENDPOINT}?sessionId={session}"));
+    let guard = SessionGuard {
+        transport: Arc::clone(&transport),
+        session,
+    };
+    let messages = stream::poll_fn(move |cx| {
+        let _session = &guard;
+        messages.poll_recv(cx)
+    })
+    .map(move |line| {
+        if let Some(validator) = &transport.validator {
+            validator.check(&line);
+        }
+        Event::default().event("message").data(line)
+    });
+    let events = stream::once(async { endpoint })
+        .chain(messages)
+        .map(Ok::<_, Infallible>);
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+async fn handle_message(
+    State(transport): State<Arc<LegacySse>>,
+    Query(params): Query<MessageParams>,
+    principal: Option<Extension<Principal>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    if let Err(rejection) = check_origin(&headers) {
+        return rejection.into_response();
+    }
+    let principal = principal.map(|Extension(principal)| principal);
+    let session = transport.sessions().get(&params.session_id).cloned();
+    // Other clients' sessions are as unknown as closed ones
+    let Some(session) = session.filter(|session| session.is_owned_by(principal.as_ref())) else {
+        return (StatusCode::NOT_FOUND, "Unknown or closed session").into_response();
+    };
+    let message: Value = match serde_json::from_str(&body) {
+        Ok(message) => message,
+        Err(e) => {
+            return rpc_error(
+                StatusCode::BAD_REQUEST,
+                ErrorCode::ParseError,
+                format!("Parse error: {e}"),
+            )
+        }
+    };
+
+    // Notifications and responses to server requests need no answer
+    if message.get("method").is_none() || message.get("id").is_none() {
+        if let Some(notification) = notification::parse(&body) {
+            session.handle_notification(&notification);
+            transport
+                .server
+                .handle_session_notification(&params.session_id, &notification);
+        } else {
+            session.handle_response(&message);
+        }
+        return StatusCode::ACCEPTED.into_response();
+    }
+
+    let request: McpRequest = match serde_json::from_value(message) {
+        Ok(request) => request,
+        Err(e) => {
+            return rpc_error(
+                StatusCode::BAD_REQUEST,
+                ErrorCode::InvalidRequest,
+                format!("Invalid request: {e}"),
+            )
+        }
+    };
+    let key = scoped_id(&params.session_id, &request.id);
+    let notifier = session.notifier().clone();
+    let response = transport
+        .server
+        .process(request, &key, session, notifier.clone());
+    tokio::spawn(async move {
+        if let Some(response) = response.await {
+            notifier.send(&response);
+        }
+    });
+    StatusCode::ACCEPTED.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_server;
+    use kagiapi::testing::MockKagi;
+    use serde_json::json;
+
+    /// Read the next `(event, data)` pair from an event stream
+    async fn next_event(response: &mut reqwest::Response, buffer: &mut String) -> (String, String) {
+        loop {
+            if let Some(end) = buffer.find("\n\n") {
+                let block: String = buffer.drain(..end + 2).collect();
+                let field = |name: &str| {
+                    block
+                        .lines()
+                        .find_map(|line| line.strip_prefix(name))
+                        .unwrap_or_default()
+                        .to_string()
+                };
+                let data = field("data: ");
+                if !data.is_empty() {
+                    return (field("event: "), data);
+                }
+                continue;
+            }
+            let chunk = response.chunk().await.unwrap().expect("event stream ended");
+            buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sse_session() {
+        let mock = MockKagi::start().await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let server = test_server(&mock);
+        tokio::spawn(async move { axum::serve(listener, router(server)).await });
+
+        let client = reqwest::Client::new();
+        let mut events = client
+            .get(format!("{base}{SSE_ENDPOINT}"))
+            .send()
+            .await
+            .unwrap();
+        let mut buffer = String::new();
+        let (event, endpoint) = next_event(&mut events, &mut buffer).await;
+        assert_eq!(event, "endpoint");
+        assert!(endpoint.starts_with("/messages?sessionId="));
+
+        let response = client
+            .post(format!("{base}{endpoint}"))
+            .json(&json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 202);
+        let (event, data) = next_event(&mut events, &mut buffer).await;
+        assert_eq!(event, "message");
+        let message: Value = serde_json::from_str(&data).unwrap();
+        assert_eq!(message["id"], 1);
+        assert_eq!(
+            message["result"]["protocolVersion"],
+            strict::PROTOCOL_VERSION
+        );
+
+        let response = client
+            .post(format!("{base}{endpoint}"))
+            .json(&json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 202);
+
+        let response = client
+            .post(format!("{base}{MESSAGES_ENDPOINT}?sessionId=unknown"))
+            .json(&json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        // Closing the stream ends the session
+        drop(events);
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let response = client
+            .post(format!("{base}{endpoint}"))
+            .json(&json!({"jsonrpc": "2.0", "id": 3, "method": "tools/list"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+    }
+}
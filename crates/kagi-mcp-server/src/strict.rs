@@ -0,0 +1,110 @@
+//! Validation of outgoing messages against the MCP schema
+//!
+//! In strict mode every line is checked against the bundled JSON Schema of the
+//! protocol version the server speaks before it is written, so protocol
+//! regressions surface in tests and CI instead of being silently rejected by hosts.
+
+use serde_json::Value;
+
+/// Protocol version announced in `initialize`, and the schema strict mode uses
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Schemas of server-to-client messages, by protocol version
+const SCHEMAS: &[(&str, &str)] = &[("2024-11-05", include_str!("../schemas/2024-11-05.json"))];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum StrictMode {
+    /// Write messages without validating them
+    #[default]
+    Off,
+    /// Report invalid messages on stderr and write them anyway
+    Log,
+    /// Panic on the first invalid message
+    Panic,
+}
+
+/// Validator for outgoing messages
+pub struct Validator {
+    mode: StrictMode,
+    schema: jsonschema::Validator,
+}
+
+impl Validator {
+    /// A validator for `version`, or `None` when strict mode is off
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no bundled schema for `version` or it fails to compile.
+    pub fn new(mode: StrictMode, version: &str) -> Option<Self> {
+        if mode == StrictMode::Off {
+            return None;
+        }
+        let (_, schema) = SCHEMAS
+            .iter()
+            .find(|(schema_version, _)| *schema_version == version)
+            .unwrap_or_else(|| panic!("no MCP schema bundled for protocol version {version}"));
+        let schema: Value = serde_json::from_str(schema).expect("bundled MCP schema is valid JSON");
+        let schema = jsonschema::draft7::new(&schema).expect("bundled MCP schema compiles");
+        Some(Self { mode, schema })
+    }
+
+    /// Check a serialized message, reporting violations according to the mode
+    pub fn check(&self, line: &str) {
+        let violations = self.violations(line);
+        if violations.is_empty() {
+            return;
+        }
+        let report = format!(
+            "Invalid outgoing MCP message: {}\n  {line}",
+            violations.join("; ")
+        );
+        match self.mode {
+            StrictMode::Off => {}
+            StrictMode::Log => tracing::error!("{report}"),
+            StrictMode::Panic => panic!("{report}"),
+        }
+    }
+
+    fn violations(&self, line: &str) -> Vec<String> {
+        let message: Value = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(e) => return vec![format!("not JSON: {e}")],
+        };
+        self.schema
+            .iter_errors(&message)
+            .map(|error| format!("{} at '{}'", error, error.instance_path()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_messages() {
+        let validator = Validator::new(StrictMode::Log, PROTOCOL_VERSION).unwrap();
+        let valid = [
+            r#"{"jsonrpc":"2.0","id":1,"result":{"content":[{"type":"text","text":"ok"}],"_meta":{"suggestedCalls":[]}}}"#,
+            r#"{"jsonrpc":"2.0","id":"a","result":{"tools":[{"name":"kagi_search_fetch","inputSchema":{"type":"object"}}]}}"#,
+            r#"{"jsonrpc":"2.0","id":null,"error":{"code":-32700,"message":"Parse error"}}"#,
+            r#"{"jsonrpc":"2.0","method":"notifications/progress","params":{"progressToken":"t","progress":1,"message":"1/2"}}"#,
+        ];
+        for line in valid {
+            assert!(validator.violations(line).is_empty(), "{line}");
+        }
+
+        let invalid = [
+            r#"{"jsonrpc":"2.0","id":1,"result":{"content":[{"type":"text"}]}}"#,
+            r#"{"jsonrpc":"2.0","id":1,"result":{"content":[]},"error":{"code":-1,"message":"x"}}"#,
+            r#"{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05","capabilities":{}}}"#,
+            r#"{"jsonrpc":"2.0","method":"notifications/progress","params":{"progress":1}}"#,
+            "not json",
+        ];
+        for line in invalid {
+            assert!(!validator.violations(line).is_empty(), "{line}");
+        }
+
+        assert!(Validator::new(StrictMode::Off, PROTOCOL_VERSION).is_none());
+    }
+}
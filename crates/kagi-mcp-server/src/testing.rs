@@ -0,0 +1,285 @@
+//! In-process protocol test harness
+//!
+//! [`TestClient`] drives a server through a real MCP session over a
+//! [`MemoryTransport`]: requests are serialized, dispatched and answered exactly as
+//! they would be over stdio, so tests cover the protocol without spawning a process.
+//!
+//! Other crates get the harness with the `testing` feature, to test servers built
+//! with their own handlers.
+
+use crate::transport::{MemoryPeer, MemoryTransport};
+use crate::{KagiMcpServer, McpResult, Tool};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+/// A JSON-RPC error returned by the server
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct RpcError {
+    pub code: i32,
+    pub message: String,
+    #[serde(default)]
+    pub data: Option<Value>,
+}
+
+/// Result of `initialize`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeResult {
+    pub protocol_version: String,
+    pub capabilities: Value,
+    pub server_info: Value,
+}
+
+/// Result of `tools/call`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallToolResult {
+    pub content: Vec<Value>,
+    #[serde(default)]
+    pub is_error: bool,
+    pub structured_content: Option<Value>,
+}
+
+impl CallToolResult {
+    /// The text of all text content blocks
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|block| block["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// A client session with a server running in the same process
+pub struct TestClient {
+    peer: MemoryPeer,
+    session: JoinHandle<McpResult<()>>,
+    next_id: i64,
+    notifications: Vec<Value>,
+    /// Results sent back to requests from the server, by method
+    responses: HashMap<String, Value>,
+}
+
+impl TestClient {
+    /// Start a session with `server`
+    pub fn start(server: Arc<KagiMcpServer>) -> Self {
+        let (transport, peer) = MemoryTransport::pair();
+        Self {
+            peer,
+            session: tokio::spawn(server.serve(transport, None)),
+            next_id: 1,
+            notifications: Vec::new(),
+            responses: HashMap::new(),
+        }
+    }
+
+    /// Answer every request from the server with `method`, e.g.
+    /// `sampling/createMessage`, with `result`
+    pub fn respond_to(&mut self, method: &str, result: Value) {
+        self.responses.insert(method.to_string(), result);
+    }
+
+    /// Notifications and requests from the server received so far, in order
+    pub fn notifications(&self) -> &[Value] {
+        &self.notifications
+    }
+
+    /// Wait for a notification, or a request from the server, with `method`, unless
+    /// one was already received
+    ///
+    /// # Panics
+    ///
+    /// Panics if the session ends first.
+    pub async fn notification(&mut self, method: &str) -> Value {
+        loop {
+            if let Some(notification) = self.notifications.iter().find(|n| n["method"] == method) {
+                return notification.clone();
+            }
+            let message = self.receive().await;
+            if message.get("method").is_some() {
+                self.collect(message);
+            }
+        }
+    }
+
+    /// Send a notification
+    pub fn notify(&self, method: &str, params: Value) {
+        self.send(&json!({"jsonrpc": "2.0", "method": method, "params": params}));
+    }
+
+    /// Send a request and wait for its response
+    ///
+    /// Notifications received in the meantime are collected in [`Self::notifications`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the session ends before the response arrives.
+    pub async fn request(&mut self, method: &str, params: Value) -> Result<Value, RpcError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(&json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}));
+        loop {
+            let mut message = self.receive().await;
+            if message.get("method").is_some() {
+                self.collect(message);
+            } else if message["id"] == id {
+                return match message.get_mut("error") {
+                    Some(error) => Err(serde_json::from_value(error.take())
+                        .expect("the server sent a malformed error")),
+                    None => Ok(message["result"].take()),
+                };
+            }
+        }
+    }
+
+    /// Initialize the session, as a client with no special capabilities
+    pub async fn initialize(&mut self) -> Result<InitializeResult, RpcError> {
+        self.initialize_with(json!({})).await
+    }
+
+    /// Initialize the session, as a client declaring `capabilities`
+    pub async fn initialize_with(
+        &mut self,
+        capabilities: Value,
+    ) -> Result<InitializeResult, RpcError> {
+        let result = self
+            .typed_request(
+                "initialize",
+                json!({
+                    "protocolVersion": crate::strict::PROTOCOL_VERSION,
+                    "capabilities": capabilities,
+                    "clientInfo": {"name": "test-client", "version": "0"}
+                }),
+            )
+            .await?;
+        self.notify("notifications/initialized", json!({}));
+        Ok(result)
+    }
+
+    pub async fn list_tools(&mut self) -> Result<Vec<Tool>, RpcError> {
+        let mut result = self.request("tools/list", json!({})).await?;
+        Ok(serde_json::from_value(result["tools"].take()).expect("malformed tools/list result"))
+    }
+
+    /// Call a tool, asking for progress notifications
+    pub async fn call_tool(
+        &mut self,
+        name: &str,
+        arguments: Value,
+    ) -> Result<CallToolResult, RpcError> {
+        let progress_token = format!("progress-{}", self.next_id);
+        self.typed_request(
+            "tools/call",
+            json!({
+                "name": name,
+                "arguments": arguments,
+                "_meta": {"progressToken": progress_token}
+            }),
+        )
+        .await
+    }
+
+    /// End the session and wait for the server to finish it
+    pub async fn close(self) -> McpResult<()> {
+        drop(self.peer);
+        self.session.await.expect("the session task panicked")
+    }
+
+    async fn typed_request<T: DeserializeOwned>(
+        &mut self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, RpcError> {
+        let result = self.request(method, params).await?;
+        Ok(serde_json::from_value(result)
+            .unwrap_or_else(|e| panic!("malformed {method} result: {e}")))
+    }
+
+    /// Keep a notification or request from the server, answering requests that
+    /// have a result set with [`Self::respond_to`]
+    fn collect(&mut self, message: Value) {
+        let response = message["method"]
+            .as_str()
+            .and_then(|method| self.responses.get(method));
+        if let (Some(result), Some(id)) = (response, message.get("id")) {
+            self.send(&json!({"jsonrpc": "2.0", "id": id, "result": result}));
+        }
+        self.notifications.push(message);
+    }
+
+    async fn receive(&mut self) -> Value {
+        let line = self
+            .peer
+            .incoming
+            .recv()
+            .await
+            .expect("the session ended unexpectedly");
+        serde_json::from_str(&line).expect("the server sent invalid JSON")
+    }
+
+    fn send(&self, message: &Value) {
+        self.peer
+            .outgoing
+            .send(message.to_string())
+            .expect("the session has ended");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_server;
+    use kagiapi::testing::MockKagi;
+
+    #[tokio::test]
+    async fn test_session_lifecycle() {
+        let mock = MockKagi::start().await;
+        let mut client = TestClient::start(test_server(&mock));
+
+        let init = client.initialize().await.unwrap();
+        assert_eq!(init.protocol_version, crate::strict::PROTOCOL_VERSION);
+        assert_eq!(init.server_info["name"], "kagi-mcp-server");
+        assert!(init.capabilities["tools"].is_object());
+
+        let tools = client.list_tools().await.unwrap();
+        let search = tools
+            .iter()
+            .find(|tool| tool.name == "kagi_search_fetch")
+            .unwrap();
+        assert_eq!(search.title.as_deref(), Some("Kagi Search"));
+        assert_eq!(
+            search.annotations.as_ref().unwrap().read_only_hint,
+            Some(true)
+        );
+
+        let result = client
+            .call_tool(
+                "kagi_search_fetch",
+                json!({"queries": ["rust", "rust async"]}),
+            )
+            .await
+            .unwrap();
+        assert!(!result.is_error);
+        assert!(result.text().contains("The Rust Programming Language"));
+        assert_eq!(client.notifications().len(), 2);
+
+        let error = client
+            .call_tool("kagi_search_fetch", json!({"queries": "rust"}))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, -32602);
+
+        let error = client
+            .request("resources/list", json!({}))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, -32601);
+
+        client.close().await.unwrap();
+    }
+}
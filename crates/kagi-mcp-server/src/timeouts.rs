@@ -0,0 +1,226 @@
+//! Per-tool execution timeouts
+//!
+//! A tool call that runs longer than its timeout is abandoned and answered with a
+//! [`ErrorCode::ToolTimedOut`] error, so a hung upstream request cannot hold a request slot, or
+//! the client, forever. Time spent queued for a concurrency slot does not count.
+//!
+//! Tools whose calls can take very long, like Muriel summaries of long videos, take
+//! a `timeout_seconds` argument that replaces the configured timeout for one call,
+//! capped at `--max-call-timeout`. Their handlers pass it on with
+//! [`CallTimeout::request`] once the arguments are parsed.
+
+use crate::error_code::ErrorCode;
+use crate::tools::{CallerTimeout, ToolCallError};
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Longest timeout callers may ask for when none is configured on the command line,
+/// in seconds
+pub const DEFAULT_MAX_CALL_TIMEOUT: u64 = 600;
+
+#[derive(Debug, Default)]
+pub struct ToolTimeouts {
+    default: Option<Duration>,
+    overrides: HashMap<String, Duration>,
+    /// Cap on the timeouts callers ask for; `None` leaves them uncapped
+    max_requested: Option<Duration>,
+}
+
+impl ToolTimeouts {
+    /// Parse a timeout specification in seconds such as `60,kagi_summarizer=180`
+    ///
+    /// An entry without a tool name sets the default for all tools.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut timeouts = Self::default();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (tool, seconds) = match entry.split_once('=') {
+                Some((tool, seconds)) => (Some(tool.trim()), seconds),
+                None => (None, entry),
+            };
+            let seconds: u64 = seconds
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&seconds| seconds > 0)
+                .ok_or_else(|| {
+                    format!("invalid tool timeout '{entry}', seconds must be a positive integer")
+                })?;
+            let timeout = Duration::from_secs(seconds);
+            match tool {
+                Some(tool) => {
+                    timeouts.overrides.insert(tool.to_string(), timeout);
+                }
+                None => timeouts.default = Some(timeout),
+            }
+        }
+        Ok(timeouts)
+    }
+
+    /// Cap the timeouts callers ask for at `max`
+    pub fn with_max_requested(mut self, max: Duration) -> Self {
+        self.max_requested = Some(max);
+        self
+    }
+
+    /// The timeout of `tool`, if it has one
+    pub fn get(&self, tool: &str) -> Option<Duration> {
+        self.overrides.get(tool).copied().or(self.default)
+    }
+
+    /// The timeout of a call of `tool`, starting out as the configured one
+    pub fn for_call(&self, tool: &str) -> CallTimeout {
+        CallTimeout {
+            tool: tool.to_string(),
+            timeout: watch::Sender::new(self.get(tool)),
+            max_requested: self.max_requested,
+        }
+    }
+}
+
+/// The timeout of one tool call, which its handler may replace while it runs
+#[derive(Debug)]
+pub struct CallTimeout {
+    tool: String,
+    timeout: watch::Sender<Option<Duration>>,
+    max_requested: Option<Duration>,
+}
+
+impl CallTimeout {
+    /// Replace the timeout with the one the caller asked for in `args`, up to the cap
+    pub fn request(&self, args: &impl CallerTimeout) {
+        let Some(seconds) = args.timeout_seconds().filter(|&seconds| seconds > 0) else {
+            return;
+        };
+        let requested = Duration::from_secs(seconds);
+        let timeout = self
+            .max_requested
+            .map_or(requested, |max| requested.min(max));
+        self.timeout.send_replace(Some(timeout));
+    }
+
+    /// Run `call`, failing with [`ErrorCode::ToolTimedOut`] if it exceeds the
+    /// timeout, counted from the start of the call
+    pub async fn run<T>(
+        &self,
+        call: impl Future<Output = Result<T, ToolCallError>>,
+    ) -> Result<T, ToolCallError> {
+        let started = tokio::time::Instant::now();
+        let mut changes = self.timeout.subscribe();
+        let mut call = std::pin::pin!(call);
+        loop {
+            let timeout = *changes.borrow_and_update();
+            let expired = async {
+                match timeout {
+                    Some(timeout) => tokio::time::sleep_until(started + timeout).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                result = &mut call => return result,
+                () = expired => break,
+                // The sender outlives the call, so this only fires on a new timeout
+                _ = changes.changed() => {}
+            }
+        }
+        let timeout = self.timeout.borrow().unwrap_or_default();
+        Err(ToolCallError::new(
+            ErrorCode::ToolTimedOut,
+            format!(
+                "{} did not finish within {}s and was abandoned",
+                self.tool,
+                timeout.as_secs_f64()
+            ),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let timeouts = ToolTimeouts::parse("60, kagi_summarizer=180").unwrap();
+        assert_eq!(
+            timeouts.get("kagi_summarizer"),
+            Some(Duration::from_secs(180))
+        );
+        assert_eq!(timeouts.get("kagi_fastgpt"), Some(Duration::from_secs(60)));
+
+        let timeouts = ToolTimeouts::parse("kagi_fastgpt=30").unwrap();
+        assert_eq!(timeouts.get("kagi_search_fetch"), None);
+
+        assert!(ToolTimeouts::parse("kagi_fastgpt=0").is_err());
+        assert!(ToolTimeouts::parse("soon").is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run() {
+        let timeouts = ToolTimeouts::parse("kagi_fastgpt=1").unwrap();
+        let hang = std::future::pending::<Result<(), ToolCallError>>;
+
+        let error = timeouts
+            .for_call("kagi_fastgpt")
+            .run(hang())
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, ErrorCode::ToolTimedOut.code());
+        assert_eq!(
+            error.message,
+            "kagi_fastgpt did not finish within 1s and was abandoned"
+        );
+
+        let result = timeouts
+            .for_call("kagi_search_fetch")
+            .run(async { Ok(1) })
+            .await;
+        assert_eq!(result, Ok(1));
+    }
+
+    /// Arguments asking for a timeout
+    struct Args(Option<u64>);
+
+    impl CallerTimeout for Args {
+        fn timeout_seconds(&self) -> Option<u64> {
+            self.0
+        }
+    }
+
+    /// How long `timeout` lets a call that never finishes run
+    async fn limit(timeout: &CallTimeout, args: Args) -> String {
+        let hang = async {
+            timeout.request(&args);
+            std::future::pending::<Result<(), ToolCallError>>().await
+        };
+        timeout.run(hang).await.unwrap_err().message
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_request() {
+        let timeouts = ToolTimeouts::parse("60")
+            .unwrap()
+            .with_max_requested(Duration::from_secs(300));
+        let within =
+            |seconds| format!("kagi_summarizer did not finish within {seconds}s and was abandoned");
+        let timeout = || timeouts.for_call("kagi_summarizer");
+        assert_eq!(limit(&timeout(), Args(Some(200))).await, within(200));
+        assert_eq!(limit(&timeout(), Args(Some(3600))).await, within(300));
+        assert_eq!(limit(&timeout(), Args(Some(0))).await, within(60));
+        assert_eq!(limit(&timeout(), Args(None)).await, within(60));
+
+        // The time before the request counts towards the requested timeout
+        for (seconds, expected) in [(80, Ok(())), (100, Err(within(120)))] {
+            let timeout = timeout();
+            let call = async {
+                tokio::time::sleep(Duration::from_secs(30)).await;
+                timeout.request(&Args(Some(120)));
+                tokio::time::sleep(Duration::from_secs(seconds)).await;
+                Ok(())
+            };
+            let result = timeout.run(call).await.map_err(|error| error.message);
+            assert_eq!(result, expected);
+        }
+    }
+}
@@ -0,0 +1,576 @@
+//! Typed tool arguments
+//!
+//! Each tool's arguments are a struct deriving [`JsonSchema`] and [`Deserialize`].
+//! The struct generates the tool's `inputSchema` and `tools/call` arguments are
+//! deserialized into it before the handler runs, so handlers never pick through
+//! raw JSON. Arguments are first validated against the schema, so callers get
+//! every field-level problem at once.
+
+use crate::budget::BudgetArgs;
+use crate::error_code::ErrorCode;
+use kagiapi::{SummarizerEngine, SummaryType};
+use schemars::generate::SchemaSettings;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Description of the `debug` argument accepted by tools that call the Kagi API
+const DEBUG_DESCRIPTION: &str = "Append Kagi request metadata (request id, node, latency, tokens) to the result. Only use when the user is reporting a problem.";
+
+const TIMEOUT_DESCRIPTION: &str = "Seconds to wait for the result before giving up, e.g. 900 for a Muriel summary of a long video. Capped by the server's maximum; defaults to the server's timeout for the tool.";
+
+/// A `tools/call` that could not run, reported as a JSON-RPC error
+///
+/// Tools that run and fail return an `isError` result instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolCallError {
+    pub code: i32,
+    pub message: String,
+    /// Details for clients to act on, sent as the error's `data`
+    pub data: Option<Value>,
+}
+
+impl ToolCallError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code: code.code(),
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    /// The arguments do not match the tool's input schema
+    pub fn invalid_params(message: String) -> Self {
+        Self::new(ErrorCode::InvalidParams, message)
+    }
+
+    pub fn not_found(name: &str) -> Self {
+        Self::new(
+            ErrorCode::MethodNotFound,
+            format!("Tool '{name}' not found"),
+        )
+    }
+}
+
+/// Params of a `tools/call` request
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CallToolParams {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: Option<Value>,
+    #[serde(rename = "_meta", default)]
+    pub meta: Option<RequestMeta>,
+}
+
+impl CallToolParams {
+    /// Parse the params of a `tools/call` request
+    pub fn parse(params: Option<Value>) -> Result<Self, ToolCallError> {
+        let params = params
+            .ok_or_else(|| ToolCallError::invalid_params("Missing parameters".to_string()))?;
+        serde_json::from_value(params).map_err(|e| {
+            ToolCallError::invalid_params(format!("Invalid tools/call parameters: {e}"))
+        })
+    }
+
+    /// Token to report the call's progress with, if the client wants progress
+    pub fn progress_token(&self) -> Option<&Value> {
+        self.meta.as_ref()?.progress_token.as_ref()
+    }
+
+    /// This call made to `kagi_enrich`, if it names one of the tool's former names
+    pub fn resolve_alias(&self) -> Option<Self> {
+        let (_, enrich_type) = ENRICH_ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == self.name)?;
+        let mut arguments = self.arguments.clone();
+        if let Some(Value::Object(arguments)) = &mut arguments {
+            arguments.insert("enrich_type".to_string(), Value::from(*enrich_type));
+        }
+        Some(Self {
+            name: "kagi_enrich".to_string(),
+            arguments,
+            meta: self.meta.clone(),
+        })
+    }
+
+    /// The former name standing for the `enrich_type` of a `kagi_enrich` call
+    pub fn enrich_alias(&self) -> Option<&'static str> {
+        if self.name != "kagi_enrich" {
+            return None;
+        }
+        let enrich_type = self.arguments.as_ref()?.get("enrich_type")?.as_str()?;
+        ENRICH_ALIASES
+            .iter()
+            .find(|(_, alias_type)| *alias_type == enrich_type)
+            .map(|(alias, _)| *alias)
+    }
+}
+
+/// `_meta` of a request
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct RequestMeta {
+    #[serde(rename = "progressToken", default)]
+    pub progress_token: Option<Value>,
+    /// Metadata of protocol extensions
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
+}
+
+/// Hints about a tool's behaviour that clients may show; they are not guarantees
+///
+/// Every field is optional and only serialized when set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolAnnotations {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// The tool does not modify its environment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only_hint: Option<bool>,
+    /// The tool may perform destructive updates; only meaningful if not read-only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destructive_hint: Option<bool>,
+    /// Repeated calls with the same arguments have no additional effect
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotent_hint: Option<bool>,
+    /// The tool interacts with external entities, such as the web
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_world_hint: Option<bool>,
+}
+
+/// The `outputSchema` of a tool returning `O` as structured content
+///
+/// Optional fields are omitted rather than null, as with arguments.
+pub fn output_schema<O: JsonSchema>() -> Value {
+    input_schema::<O>()
+}
+
+/// The `inputSchema` of a tool taking `A` as arguments
+pub fn input_schema<A: JsonSchema>() -> Value {
+    let mut settings = SchemaSettings::draft07();
+    settings.inline_subschemas = true;
+    settings.meta_schema = None;
+    let schema = settings.into_generator().into_root_schema_for::<A>();
+    let mut schema = schema.to_value();
+
+    if let Some(schema) = schema.as_object_mut() {
+        schema.remove("title");
+        schema.remove("description");
+    }
+    // Optional arguments are omitted rather than null, so don't advertise null
+    if let Some(Value::Object(properties)) = schema.get_mut("properties") {
+        for property in properties.values_mut() {
+            if let Some(Value::Array(types)) = property.get_mut("type") {
+                types.retain(|t| t != "null");
+                if let [single] = types.as_slice() {
+                    property["type"] = single.clone();
+                }
+            }
+            if let Some(Value::Array(values)) = property.get_mut("enum") {
+                values.retain(|v| !v.is_null());
+            }
+        }
+    }
+    schema
+}
+
+/// Replace the description of an argument whose wording depends on configuration
+pub fn set_description(schema: &mut Value, property: &str, description: impl Into<String>) {
+    if let Some(property) = schema.pointer_mut(&format!("/properties/{property}")) {
+        property["description"] = Value::String(description.into());
+    }
+}
+
+/// Validators compiled from the tools' input schemas
+pub struct ArgsValidators {
+    validators: HashMap<String, jsonschema::Validator>,
+}
+
+impl ArgsValidators {
+    /// Compile the input schema of each `(tool name, schema)` pair
+    ///
+    /// # Panics
+    ///
+    /// Panics if a schema does not compile, which would be a bug in an argument struct.
+    pub fn new<'a>(schemas: impl IntoIterator<Item = (&'a str, &'a Value)>) -> Self {
+        let validators = schemas
+            .into_iter()
+            .map(|(name, schema)| {
+                let validator = jsonschema::draft7::new(schema)
+                    .unwrap_or_else(|e| panic!("input schema of {name} does not compile: {e}"));
+                (name.to_string(), validator)
+            })
+            .collect();
+        Self { validators }
+    }
+
+    /// Check `args` against the input schema of tool `name`
+    ///
+    /// Unknown tools pass, so that dispatch can report them as not found.
+    pub fn validate(&self, name: &str, args: &Value) -> Result<(), ToolCallError> {
+        let Some(validator) = self.validators.get(name) else {
+            return Ok(());
+        };
+        let violations: Vec<String> = validator
+            .iter_errors(args)
+            .map(|error| {
+                let path = error.instance_path().to_string();
+                if path.is_empty() {
+                    error.to_string()
+                } else {
+                    format!("{path}: {error}")
+                }
+            })
+            .collect();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(ToolCallError::invalid_params(format!(
+                "Invalid arguments: {}",
+                violations.join("; ")
+            )))
+        }
+    }
+}
+
+/// Deserialize `tools/call` arguments
+pub fn parse_args<A: DeserializeOwned>(args: Value) -> Result<A, ToolCallError> {
+    serde_json::from_value(args)
+        .map_err(|e| ToolCallError::invalid_params(format!("Invalid arguments: {e}")))
+}
+
+/// Arguments with a `timeout_seconds` the caller may set, see `timeouts`
+pub trait CallerTimeout {
+    fn timeout_seconds(&self) -> Option<u64>;
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SearchArgs {
+    /// One or more concise, keyword-focused search queries. Include essential context within each query for standalone use.
+    pub queries: Vec<String>,
+    /// Maximum number of results per query. Defaults to 10; ask for a few for quick lookups.
+    #[schemars(range(min = 1, max = 50))]
+    pub limit: Option<u32>,
+    /// Number of results to skip per query, to page past results already seen. Defaults to 0.
+    #[schemars(range(max = 100))]
+    pub offset: Option<u32>,
+    /// Format of the text content: 'text' for readable results, 'markdown' for Markdown with links, 'json' for the structured results as JSON. Defaults to the server's configured format.
+    pub output_format: Option<OutputFormat>,
+    #[serde(flatten)]
+    pub budget: BudgetArgs,
+    #[schemars(description = DEBUG_DESCRIPTION)]
+    pub debug: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    Cecil,
+    Agnes,
+    Daphne,
+    Muriel,
+}
+
+impl From<Engine> for SummarizerEngine {
+    fn from(engine: Engine) -> Self {
+        match engine {
+            Engine::Cecil => SummarizerEngine::Cecil,
+            Engine::Agnes => SummarizerEngine::Agnes,
+            Engine::Daphne => SummarizerEngine::Daphne,
+            Engine::Muriel => SummarizerEngine::Muriel,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Markdown,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryKind {
+    #[default]
+    Summary,
+    Takeaway,
+}
+
+impl From<SummaryKind> for SummaryType {
+    fn from(kind: SummaryKind) -> Self {
+        match kind {
+            SummaryKind::Summary => SummaryType::Summary,
+            SummaryKind::Takeaway => SummaryType::Takeaway,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SummarizerArgs {
+    /// A URL to a document to summarize. Pages on localhost or private networks, and file:// URLs inside the client's workspace roots, are read locally and their text is summarized.
+    pub url: String,
+    /// Type of summary to produce. Options are 'summary' for paragraph prose and 'takeaway' for a bulleted list of key points.
+    #[serde(default)]
+    pub summary_type: SummaryKind,
+    /// Summarization engine to use. Defaults to configured engine.
+    pub engine: Option<Engine>,
+    /// Desired output language using language codes (e.g., 'EN' for English).
+    pub target_language: Option<String>,
+    /// Applied to the whole call, see `timeouts`
+    #[schemars(description = TIMEOUT_DESCRIPTION, range(min = 1))]
+    pub timeout_seconds: Option<u64>,
+    #[schemars(description = DEBUG_DESCRIPTION)]
+    pub debug: Option<bool>,
+}
+
+impl CallerTimeout for SummarizerArgs {
+    fn timeout_seconds(&self) -> Option<u64> {
+        self.timeout_seconds
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SummarizeTextArgs {
+    /// The text to summarize, e.g. pasted content or an editor selection.
+    pub text: String,
+    /// Type of summary to produce. Options are 'summary' for paragraph prose and 'takeaway' for a bulleted list of key points.
+    #[serde(default)]
+    pub summary_type: SummaryKind,
+    /// Summarization engine to use. Defaults to configured engine.
+    pub engine: Option<Engine>,
+    /// Desired output language using language codes (e.g., 'EN' for English).
+    pub target_language: Option<String>,
+    #[schemars(description = DEBUG_DESCRIPTION)]
+    pub debug: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ResearchArgs {
+    /// The question or topic to research, as a concise search query.
+    pub query: String,
+    /// Number of top search results to summarize. Defaults to 3.
+    #[schemars(range(min = 1, max = 5))]
+    pub sources: Option<usize>,
+    /// Type of summary per source. Defaults to 'takeaway', a bulleted list of key points.
+    #[serde(default = "takeaway")]
+    #[schemars(default = "takeaway")]
+    pub summary_type: SummaryKind,
+    /// Summarization engine to use. Defaults to configured engine.
+    pub engine: Option<Engine>,
+    #[schemars(description = DEBUG_DESCRIPTION)]
+    pub debug: Option<bool>,
+}
+
+fn takeaway() -> SummaryKind {
+    SummaryKind::Takeaway
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct UnfurlArgs {
+    /// The URL to unfurl.
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FastGptArgs {
+    /// The question or query to be answered by the AI.
+    pub query: String,
+    /// Whether to allow cached requests & responses. Defaults to true.
+    pub cache: Option<bool>,
+    /// Whether to perform web searches to enrich answers. Currently, must be set to true.
+    pub web_search: Option<bool>,
+    /// Maximum number of distinct reference links to list. References from the same site are grouped either way.
+    #[schemars(range(min = 1))]
+    pub max_references: Option<usize>,
+    /// Applied to the whole call, see `timeouts`
+    #[schemars(description = TIMEOUT_DESCRIPTION, range(min = 1))]
+    pub timeout_seconds: Option<u64>,
+    #[schemars(description = DEBUG_DESCRIPTION)]
+    pub debug: Option<bool>,
+}
+
+impl CallerTimeout for FastGptArgs {
+    fn timeout_seconds(&self) -> Option<u64> {
+        self.timeout_seconds
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FastGptFollowupArgs {
+    /// The follow-up question, which may refer to earlier answers.
+    pub query: String,
+    /// Maximum number of distinct reference links to list. References from the same site are grouped either way.
+    #[schemars(range(min = 1))]
+    pub max_references: Option<usize>,
+    #[schemars(description = DEBUG_DESCRIPTION)]
+    pub debug: Option<bool>,
+}
+
+/// Former names of `kagi_enrich`, each standing for one `enrich_type`
+///
+/// Calls to them are still accepted, but only `kagi_enrich` is listed.
+pub const ENRICH_ALIASES: &[(&str, &str)] =
+    &[("kagi_enrich_web", "web"), ("kagi_enrich_news", "news")];
+
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EnrichType {
+    Web,
+    News,
+}
+
+impl From<EnrichType> for kagiapi::EnrichType {
+    fn from(enrich_type: EnrichType) -> Self {
+        match enrich_type {
+            EnrichType::Web => kagiapi::EnrichType::Web,
+            EnrichType::News => kagiapi::EnrichType::News,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct EnrichArgs {
+    /// The search query.
+    pub query: String,
+    /// Index to search: 'web' for non-commercial "small web" content and discussions, 'news' for non-mainstream news sources.
+    pub enrich_type: EnrichType,
+    /// Only return news published on or after this date, as YYYY-MM-DD. Only for 'news'.
+    pub since: Option<String>,
+    /// Only return news published within this many days. Use for questions about recent events. Only for 'news'.
+    #[schemars(range(min = 1))]
+    pub max_age_days: Option<u32>,
+    #[serde(flatten)]
+    pub budget: BudgetArgs,
+    #[schemars(description = DEBUG_DESCRIPTION)]
+    pub debug: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SmallWebDigestArgs {
+    /// Keyword the posts must mention in their title, author, address or excerpt.
+    pub keyword: String,
+    /// Maximum number of matching posts to summarize, newest first. Defaults to 3.
+    #[schemars(range(min = 1, max = 10))]
+    pub limit: Option<usize>,
+    #[schemars(description = DEBUG_DESCRIPTION)]
+    pub debug: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_input_schema() {
+        let schema = input_schema::<SummarizerArgs>();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["required"], json!(["url"]));
+        assert_eq!(schema["properties"]["url"]["type"], "string");
+        assert_eq!(schema["properties"]["debug"]["type"], "boolean");
+        assert_eq!(
+            schema["properties"]["engine"]["enum"],
+            json!(["cecil", "agnes", "daphne", "muriel"])
+        );
+        assert_eq!(schema["properties"]["summary_type"]["default"], "summary");
+        assert!(schema.get("title").is_none());
+
+        let schema = input_schema::<FastGptArgs>();
+        assert_eq!(schema["properties"]["max_references"]["minimum"], 1);
+    }
+
+    #[test]
+    fn test_validate_args() {
+        let schema = input_schema::<FastGptArgs>();
+        let validators = ArgsValidators::new([("kagi_fastgpt", &schema)]);
+
+        assert!(validators
+            .validate(
+                "kagi_fastgpt",
+                &json!({"query": "rust", "max_references": 3})
+            )
+            .is_ok());
+
+        let error = validators
+            .validate("kagi_fastgpt", &json!({"query": 1, "max_references": 0}))
+            .unwrap_err();
+        assert_eq!(error.code, -32602);
+        assert!(error
+            .message
+            .contains("/query: 1 is not of type \"string\""));
+        assert!(error
+            .message
+            .contains("/max_references: 0 is less than the minimum of 1"));
+
+        let error = validators.validate("kagi_fastgpt", &json!({})).unwrap_err();
+        assert!(error.message.contains("\"query\" is a required property"));
+
+        assert!(validators.validate("kagi_unknown", &json!({})).is_ok());
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args: SummarizerArgs =
+            parse_args(json!({"url": "https://example.com", "engine": "muriel"})).unwrap();
+        assert!(matches!(args.engine, Some(Engine::Muriel)));
+        assert!(matches!(args.summary_type, SummaryKind::Summary));
+
+        let error = parse_args::<SearchArgs>(json!({"queries": "not a list"})).unwrap_err();
+        assert_eq!(error.code, -32602);
+        assert!(error.message.starts_with("Invalid arguments"));
+    }
+
+    #[test]
+    fn test_call_tool_params() {
+        let params = CallToolParams::parse(Some(json!({
+            "name": "kagi_fastgpt",
+            "arguments": {"query": "rust"},
+            "_meta": {"progressToken": 3, "io.zed/origin": "assistant"}
+        })))
+        .unwrap();
+        assert_eq!(params.name, "kagi_fastgpt");
+        assert_eq!(params.progress_token(), Some(&json!(3)));
+        assert_eq!(
+            params.meta.unwrap().other["io.zed/origin"],
+            json!("assistant")
+        );
+
+        let params = CallToolParams::parse(Some(json!({"name": "kagi_fastgpt"}))).unwrap();
+        assert_eq!(params.progress_token(), None);
+        assert_eq!(params.arguments, None);
+
+        let error = CallToolParams::parse(Some(json!({"arguments": {}}))).unwrap_err();
+        assert_eq!(error.code, -32602);
+        assert!(
+            error.message.contains("missing field `name`"),
+            "{}",
+            error.message
+        );
+        assert!(CallToolParams::parse(None).is_err());
+
+        let params = CallToolParams::parse(Some(json!({
+            "name": "kagi_enrich_news",
+            "arguments": {"query": "rust"}
+        })))
+        .unwrap();
+        let resolved = params.resolve_alias().unwrap();
+        assert_eq!(resolved.name, "kagi_enrich");
+        assert_eq!(
+            resolved.arguments,
+            Some(json!({"query": "rust", "enrich_type": "news"}))
+        );
+        assert_eq!(resolved.enrich_alias(), Some("kagi_enrich_news"));
+        assert!(resolved.resolve_alias().is_none());
+        assert_eq!(params.enrich_alias(), None);
+    }
+}
@@ -0,0 +1,136 @@
+//! Conversation topic tracking for vague follow-up queries
+//!
+//! Assistants often phrase follow-ups as if Kagi shared the conversation, e.g.
+//! "its performance" after searching for "tokio runtime". When enabled, the key
+//! terms of recent queries are remembered for the session and added to short
+//! queries that only refer back to an earlier topic. This changes what is sent to
+//! Kagi, so it is opt-in.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Number of recent topics remembered
+const MAX_TOPICS: usize = 5;
+
+/// Maximum number of key terms kept per topic
+const MAX_TERMS: usize = 4;
+
+/// Words that refer back to something mentioned earlier
+const REFERENCES: &[&str] = &[
+    "it", "its", "it's", "they", "them", "their", "this", "that", "these", "those", "he", "she",
+    "his", "her", "him",
+];
+
+/// Words that carry no topic on their own
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "best", "by", "can", "do", "does", "for", "from",
+    "get", "has", "have", "how", "in", "is", "of", "on", "or", "the", "to", "use", "vs", "was",
+    "what", "when", "where", "which", "who", "why", "with",
+];
+
+/// Key terms of recent queries in this session, most recent last
+#[derive(Debug, Default)]
+pub struct TopicCache {
+    topics: Mutex<VecDeque<Vec<String>>>,
+}
+
+impl TopicCache {
+    /// Remember the key terms of a query that names its own topic
+    pub fn record(&self, query: &str) {
+        if is_vague(query) {
+            return;
+        }
+        let terms: Vec<String> = key_terms(query).into_iter().take(MAX_TERMS).collect();
+        if terms.is_empty() {
+            return;
+        }
+        let mut topics = self.topics.lock().unwrap_or_else(|e| e.into_inner());
+        topics.retain(|topic| *topic != terms);
+        if topics.len() == MAX_TOPICS {
+            topics.pop_front();
+        }
+        topics.push_back(terms);
+    }
+
+    /// The query with the most recent topic appended if it only refers back to it
+    pub fn contextualize(&self, query: &str) -> String {
+        if !is_vague(query) {
+            return query.to_string();
+        }
+        let topics = self.topics.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(topic) = topics.back() else {
+            return query.to_string();
+        };
+        let own_terms = key_terms(query);
+        let missing: Vec<&str> = topic
+            .iter()
+            .filter(|term| !own_terms.contains(term))
+            .map(String::as_str)
+            .collect();
+        if missing.is_empty() {
+            return query.to_string();
+        }
+        format!("{query} {}", missing.join(" "))
+    }
+}
+
+/// Lowercased words of `query` that are neither stopwords nor references
+fn key_terms(query: &str) -> Vec<String> {
+    words(query)
+        .filter(|word| !STOPWORDS.contains(&word.as_str()) && !REFERENCES.contains(&word.as_str()))
+        .fold(Vec::new(), |mut terms, word| {
+            if !terms.contains(&word) {
+                terms.push(word);
+            }
+            terms
+        })
+}
+
+/// Whether `query` refers back to an earlier topic without naming much of its own
+fn is_vague(query: &str) -> bool {
+    words(query).any(|word| REFERENCES.contains(&word.as_str())) && key_terms(query).len() <= 2
+}
+
+fn words(query: &str) -> impl Iterator<Item = String> + '_ {
+    query
+        .split(|c: char| !(c.is_alphanumeric() || matches!(c, '\'' | '-' | '.' | '+' | '#')))
+        .map(|word| word.trim_matches(|c: char| matches!(c, '\'' | '-' | '.')))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contextualize_vague_queries() {
+        let topics = TopicCache::default();
+        assert_eq!(topics.contextualize("its performance"), "its performance");
+
+        topics.record("what is the tokio runtime");
+        topics.record("its license");
+        assert_eq!(
+            topics.contextualize("its performance"),
+            "its performance tokio runtime"
+        );
+        assert_eq!(
+            topics.contextualize("How does it compare to async-std?"),
+            "How does it compare to async-std? tokio runtime"
+        );
+        assert_eq!(
+            topics.contextualize("tokio runtime benchmarks"),
+            "tokio runtime benchmarks"
+        );
+        assert_eq!(
+            topics.contextualize("is it faster than the glommio io_uring executor"),
+            "is it faster than the glommio io_uring executor"
+        );
+
+        topics.record("Zig allocators");
+        assert_eq!(
+            topics.contextualize("their API"),
+            "their API zig allocators"
+        );
+    }
+}
@@ -0,0 +1,619 @@
+//! Message transports
+//!
+//! [`KagiMcpServer::serve`](crate::KagiMcpServer) runs one MCP session over any
+//! [`Transport`]: it reads JSON-RPC messages, dispatches them, and writes responses
+//! and notifications back, without knowing how the messages travel. Adding a
+//! transport only takes an implementation of the trait:
+//!
+//! - [`StreamTransport`] speaks JSON-RPC over a byte stream, for stdio, TCP and Unix
+//!   sockets. Messages are framed as newline-delimited JSON, or with LSP-style
+//!   `Content-Length` headers as some clients send them; see [`Framing`].
+//! - `MemoryTransport` passes messages over channels, for tests; it is built with
+//!   the `testing` feature.
+//! - The WebSocket transport sends one message per text frame.
+//!
+//! Streamable HTTP and SSE are not sessions over a single connection, so they drive
+//! requests through the server directly instead.
+
+use async_trait::async_trait;
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+#[cfg(any(test, feature = "testing"))]
+use tokio::sync::mpsc;
+
+/// Header that precedes each message with `Content-Length` framing
+const CONTENT_LENGTH: &[u8] = b"content-length";
+
+/// Maximum size in bytes of a message read from a byte stream, unless configured
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// A bidirectional channel of JSON-RPC messages
+#[async_trait]
+pub trait Transport: Send {
+    /// Receive the next message, or `None` once the peer has closed the connection
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] for a malformed message that was
+    /// skipped, after which the transport is still usable.
+    ///
+    /// Must be cancel safe: the server polls it concurrently with writes, and a
+    /// message must not be lost when the read is dropped before it completes.
+    async fn read_message(&mut self) -> io::Result<Option<String>>;
+
+    /// Send one message
+    async fn write_message(&mut self, message: &str) -> io::Result<()>;
+}
+
+/// How messages are delimited on a byte stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Framing {
+    /// Detect the framing from the client's first message and answer in kind
+    #[default]
+    Auto,
+    /// One JSON message per line
+    Newline,
+    /// Each message preceded by `Content-Length: N` and a blank line, as in LSP
+    ContentLength,
+}
+
+/// JSON-RPC over a byte stream
+///
+/// A message that is larger than the maximum size or is not UTF-8 is skipped, and
+/// reading it fails with [`io::ErrorKind::InvalidData`]; the next read continues
+/// with the message after it.
+pub struct StreamTransport<R, W> {
+    input: R,
+    /// Bytes read but not yet taken as messages
+    buffer: Vec<u8>,
+    /// Whether the input has been read to its end
+    eof: bool,
+    /// What is left of a skipped message
+    skipping: Skip,
+    output: W,
+    framing: Framing,
+    max_message_size: usize,
+}
+
+/// The rest of a skipped message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Skip {
+    Nothing,
+    /// Everything up to the next newline
+    Line,
+    /// A number of bytes of a `Content-Length` framed message
+    Bytes(usize),
+}
+
+impl<R: AsyncRead + Unpin, W: AsyncWrite + Unpin> StreamTransport<R, W> {
+    /// A transport that detects the framing of the input
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            buffer: Vec::new(),
+            eof: false,
+            skipping: Skip::Nothing,
+            output,
+            framing: Framing::Auto,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+
+    pub fn with_framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    pub fn with_max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = max_message_size;
+        self
+    }
+
+    /// Take the next complete message out of the buffer, if there is one
+    fn take_message(&mut self) -> io::Result<Option<String>> {
+        loop {
+            if !self.skip() {
+                return Ok(None);
+            }
+            // Blank lines between messages are ignored under either framing
+            let blank = self
+                .buffer
+                .iter()
+                .take_while(|byte| byte.is_ascii_whitespace())
+                .count();
+            self.buffer.drain(..blank);
+            if self.buffer.is_empty() {
+                return Ok(None);
+            }
+            if self.framing == Framing::Auto {
+                match detect_framing(&self.buffer) {
+                    Some(framing) => self.framing = framing,
+                    None if self.eof => self.framing = Framing::Newline,
+                    None => return Ok(None),
+                }
+            }
+            let message = match self.framing {
+                Framing::ContentLength => self.take_content_length_message()?,
+                _ => self.take_line()?,
+            };
+            match message {
+                Some(message) if message.is_empty() => {}
+                message => return message.map(into_string).transpose(),
+            }
+        }
+    }
+
+    /// Drop what is buffered of a skipped message, returning whether all of it is gone
+    fn skip(&mut self) -> bool {
+        match self.skipping {
+            Skip::Nothing => return true,
+            Skip::Line => match self.buffer.iter().position(|&byte| byte == b'\n') {
+                Some(newline) => {
+                    self.buffer.drain(..=newline);
+                    self.skipping = Skip::Nothing;
+                }
+                None => self.buffer.clear(),
+            },
+            Skip::Bytes(remaining) => {
+                let skipped = remaining.min(self.buffer.len());
+                self.buffer.drain(..skipped);
+                self.skipping = match remaining - skipped {
+                    0 => Skip::Nothing,
+                    remaining => Skip::Bytes(remaining),
+                };
+            }
+        }
+        self.skipping == Skip::Nothing
+    }
+
+    /// Skip a message that is larger than the maximum size
+    fn skip_oversized(&mut self, skip: Skip) -> io::Error {
+        self.skipping = skip;
+        self.skip();
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "message larger than {} bytes skipped",
+                self.max_message_size
+            ),
+        )
+    }
+
+    fn take_line(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let end = match self.buffer.iter().position(|&byte| byte == b'\n') {
+            Some(newline) if newline > self.max_message_size => {
+                return Err(self.skip_oversized(Skip::Line));
+            }
+            Some(newline) => newline + 1,
+            None if self.buffer.len() > self.max_message_size => {
+                return Err(self.skip_oversized(Skip::Line));
+            }
+            // The last line may lack a newline
+            None if self.eof => self.buffer.len(),
+            None => return Ok(None),
+        };
+        let mut line: Vec<u8> = self.buffer.drain(..end).collect();
+        while line
+            .last()
+            .is_some_and(|byte| matches!(byte, b'\n' | b'\r'))
+        {
+            line.pop();
+        }
+        Ok(Some(line))
+    }
+
+    fn take_content_length_message(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let Some(header_end) = find(&self.buffer, b"\r\n\r\n") else {
+            if self.buffer.len() > self.max_message_size {
+                return Err(self.skip_oversized(Skip::Bytes(self.buffer.len())));
+            }
+            return Ok(None);
+        };
+        let start = header_end + 4;
+        let headers = String::from_utf8_lossy(&self.buffer[..header_end]);
+        let length = headers
+            .lines()
+            .filter_map(|header| header.split_once(':'))
+            .find(|(name, _)| name.trim().as_bytes().eq_ignore_ascii_case(CONTENT_LENGTH))
+            .and_then(|(_, length)| length.trim().parse::<usize>().ok());
+        let Some(length) = length else {
+            let error = io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("missing or invalid Content-Length header in '{headers}'"),
+            );
+            self.buffer.drain(..start);
+            return Err(error);
+        };
+        if length > self.max_message_size {
+            return Err(self.skip_oversized(Skip::Bytes(start + length)));
+        }
+        if self.buffer.len() < start + length {
+            return Ok(None);
+        }
+        let message = self.buffer[start..start + length].to_vec();
+        self.buffer.drain(..start + length);
+        Ok(Some(message))
+    }
+}
+
+/// The framing of a stream starting with `buffer`, or `None` if it can't be told yet
+fn detect_framing(buffer: &[u8]) -> Option<Framing> {
+    let prefix = &buffer[..buffer.len().min(CONTENT_LENGTH.len())];
+    if !prefix.eq_ignore_ascii_case(&CONTENT_LENGTH[..prefix.len()]) {
+        Some(Framing::Newline)
+    } else if prefix.len() == CONTENT_LENGTH.len() {
+        Some(Framing::ContentLength)
+    } else {
+        None
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn into_string(message: Vec<u8>) -> io::Result<String> {
+    String::from_utf8(message).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("message skipped as it is not UTF-8: {e}"),
+        )
+    })
+}
+
+/// The stdio transport
+pub fn stdio(
+    framing: Framing,
+    max_message_size: usize,
+) -> StreamTransport<tokio::io::Stdin, tokio::io::Stdout> {
+    StreamTransport::new(tokio::io::stdin(), tokio::io::stdout())
+        .with_framing(framing)
+        .with_max_message_size(max_message_size)
+}
+
+#[async_trait]
+impl<R, W> Transport for StreamTransport<R, W>
+where
+    R: AsyncRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    async fn read_message(&mut self) -> io::Result<Option<String>> {
+        loop {
+            if let Some(message) = self.take_message()? {
+                return Ok(Some(message));
+            }
+            if self.eof {
+                return Ok(None);
+            }
+            // `read_buf` is cancel safe: bytes are only appended once read
+            if self.input.read_buf(&mut self.buffer).await? == 0 {
+                self.eof = true;
+            }
+        }
+    }
+
+    async fn write_message(&mut self, message: &str) -> io::Result<()> {
+        if self.framing == Framing::ContentLength {
+            let header = format!("Content-Length: {}\r\n\r\n", message.len());
+            self.output.write_all(header.as_bytes()).await?;
+            self.output.write_all(message.as_bytes()).await?;
+        } else {
+            self.output.write_all(message.as_bytes()).await?;
+            self.output.write_all(b"\n").await?;
+        }
+        self.output.flush().await
+    }
+}
+
+/// Messages passed over in-process channels, for running the server in tests
+#[cfg(any(test, feature = "testing"))]
+pub struct MemoryTransport {
+    incoming: mpsc::UnboundedReceiver<String>,
+    outgoing: mpsc::UnboundedSender<String>,
+}
+
+/// The client end of a [`MemoryTransport`]
+#[cfg(any(test, feature = "testing"))]
+pub struct MemoryPeer {
+    pub outgoing: mpsc::UnboundedSender<String>,
+    pub incoming: mpsc::UnboundedReceiver<String>,
+}
+
+#[cfg(any(test, feature = "testing"))]
+impl MemoryTransport {
+    /// A transport for the server and the peer that talks to it
+    ///
+    /// The session ends when the peer's sender is dropped.
+    pub fn pair() -> (Self, MemoryPeer) {
+        let (client_tx, server_rx) = mpsc::unbounded_channel();
+        let (server_tx, client_rx) = mpsc::unbounded_channel();
+        let transport = Self {
+            incoming: server_rx,
+            outgoing: server_tx,
+        };
+        let peer = MemoryPeer {
+            outgoing: client_tx,
+            incoming: client_rx,
+        };
+        (transport, peer)
+    }
+}
+
+#[cfg(any(test, feature = "testing"))]
+#[async_trait]
+impl Transport for MemoryTransport {
+    async fn read_message(&mut self) -> io::Result<Option<String>> {
+        Ok(self.incoming.recv().await)
+    }
+
+    async fn write_message(&mut self, message: &str) -> io::Result<()> {
+        self.outgoing
+            .send(message.to_string())
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestClient;
+    use crate::tests::test_server;
+    use crate::{strict, KagiMcpServer, ServerOptions};
+    use kagiapi::testing::{fixtures, MockKagi};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+    use wiremock::matchers::path;
+    use wiremock::{Mock, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_line_transport() {
+        let (ours, theirs) = tokio::io::duplex(1024);
+        let (input, output) = tokio::io::split(ours);
+        let mut transport = StreamTransport::new(input, output);
+        let (peer_input, mut peer_output) = tokio::io::split(theirs);
+
+        peer_output.write_all(b"\n{\"a\":1}\n  \n").await.unwrap();
+        assert_eq!(
+            transport.read_message().await.unwrap().as_deref(),
+            Some("{\"a\":1}")
+        );
+        transport.write_message("{\"b\":2}").await.unwrap();
+        let mut lines = BufReader::new(peer_input).lines();
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "{\"b\":2}");
+
+        drop((lines, peer_output));
+        assert_eq!(transport.read_message().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_content_length_framing() {
+        let (ours, theirs) = tokio::io::duplex(1024);
+        let (input, output) = tokio::io::split(ours);
+        let mut transport = StreamTransport::new(input, output);
+        let (mut peer_input, mut peer_output) = tokio::io::split(theirs);
+
+        // Detected even when the header arrives in pieces
+        peer_output.write_all(b"Cont").await.unwrap();
+        let read = tokio::spawn(async move {
+            let first = transport.read_message().await.unwrap();
+            let second = transport.read_message().await.unwrap();
+            (transport, first, second)
+        });
+        peer_output
+            .write_all(b"ent-Length: 7\r\n\r\n{\"a\":1}\r\ncontent-length:7\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{\"a\":2}")
+            .await
+            .unwrap();
+        let (mut transport, first, second) = read.await.unwrap();
+        assert_eq!(first.as_deref(), Some("{\"a\":1}"));
+        assert_eq!(second.as_deref(), Some("{\"a\":2}"));
+
+        // Answered in kind
+        transport.write_message("{\"b\":\"é\"}").await.unwrap();
+        let mut written = vec![0; 32];
+        peer_input.read_exact(&mut written).await.unwrap();
+        assert_eq!(written, b"Content-Length: 10\r\n\r\n{\"b\":\"\xc3\xa9\"}");
+
+        peer_output
+            .write_all(b"Content-Length: 2\r\n\r\n")
+            .await
+            .unwrap();
+        // A message cut short by the end of the stream is dropped
+        drop((peer_input, peer_output));
+        assert_eq!(transport.read_message().await.unwrap(), None);
+
+        let (_, input) = tokio::io::duplex(64);
+        let mut transport =
+            StreamTransport::new(input, tokio::io::sink()).with_framing(Framing::ContentLength);
+        transport.buffer = b"Content-Type: json\r\n\r\n{}".to_vec();
+        assert!(transport.read_message().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_malformed_messages_are_skipped() {
+        let input = [
+            &b"{\"id\":\"too long for the limit\"}\n{\"a\":1}\n"[..],
+            b"{\"a\":\"\xff\"}\n{\"a\":2}\n",
+        ]
+        .concat();
+        let mut transport =
+            StreamTransport::new(&input[..], tokio::io::sink()).with_max_message_size(16);
+        let mut read = async || transport.read_message().await.map_err(|e| e.kind());
+        assert_eq!(read().await, Err(io::ErrorKind::InvalidData));
+        assert_eq!(read().await.unwrap().as_deref(), Some("{\"a\":1}"));
+        assert_eq!(read().await, Err(io::ErrorKind::InvalidData));
+        assert_eq!(read().await.unwrap().as_deref(), Some("{\"a\":2}"));
+        assert_eq!(read().await, Ok(None));
+
+        let input = [
+            &b"Content-Length: 40\r\n\r\n"[..],
+            &[b' '; 40],
+            b"Content-Length: 7\r\n\r\n{\"a\":3}",
+        ]
+        .concat();
+        let mut transport =
+            StreamTransport::new(&input[..], tokio::io::sink()).with_max_message_size(16);
+        let error = transport.read_message().await.unwrap_err();
+        assert_eq!(error.to_string(), "message larger than 16 bytes skipped");
+        assert_eq!(
+            transport.read_message().await.unwrap().as_deref(),
+            Some("{\"a\":3}")
+        );
+
+        // The server answers with a parse error and carries on
+        let mock = MockKagi::start().await;
+        let (ours, theirs) = tokio::io::duplex(1024);
+        let (input, output) = tokio::io::split(ours);
+        let transport = StreamTransport::new(input, output);
+        let session = tokio::spawn(test_server(&mock).serve(transport, None));
+        let (peer_input, mut peer_output) = tokio::io::split(theirs);
+        peer_output
+            .write_all(b"\xff\xfe\n{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\"}\n")
+            .await
+            .unwrap();
+        let mut lines = BufReader::new(peer_input).lines();
+        let error: Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(error["error"]["code"], -32700);
+        let pong: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(pong["id"], 1);
+        drop((lines, peer_output));
+        session.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_memory_transport() {
+        let mock = MockKagi::start().await;
+        let (transport, mut peer) = MemoryTransport::pair();
+        let session = tokio::spawn(test_server(&mock).serve(transport, None));
+
+        let send = |message: Value| peer.outgoing.send(message.to_string()).unwrap();
+        send(json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}));
+        send(json!({"jsonrpc": "2.0", "method": "notifications/initialized"}));
+        send(json!({"jsonrpc": "2.0", "id": 2, "method": 5}));
+        send(json!({"jsonrpc": "2.0", "id": 3, "method": "tools/list"}));
+        let mut responses = Vec::new();
+        for _ in 0..3 {
+            let message = peer.incoming.recv().await.unwrap();
+            responses.push(serde_json::from_str::<Value>(&message).unwrap());
+        }
+        responses.sort_by_key(|response| response["id"].as_i64());
+        assert!(responses[0]["id"].is_null());
+        assert_eq!(responses[0]["error"]["code"], -32600);
+        assert!(responses[1]["result"]["protocolVersion"].is_string());
+        assert!(responses[2]["result"]["tools"].is_array());
+
+        drop(peer);
+        session.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ping() {
+        let mock = MockKagi::start().await;
+        let server = Arc::new(KagiMcpServer::new(
+            mock.client(),
+            ServerOptions {
+                strict: strict::StrictMode::Panic,
+                keepalive_interval: Some(Duration::from_millis(50)),
+                ..ServerOptions::default()
+            },
+        ));
+        let mut client = TestClient::start(server);
+        client.initialize().await.unwrap();
+        assert_eq!(client.request("ping", json!({})).await.unwrap(), json!({}));
+
+        let ping = client.notification("ping").await;
+        assert_eq!(ping["id"], "keepalive-1");
+        client.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_finishes_in_flight_requests() {
+        let mock = MockKagi::start().await;
+        Mock::given(path("/api/v0/search"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(fixtures::SEARCH, "application/json")
+                    .set_delay(Duration::from_millis(200)),
+            )
+            .mount(mock.server())
+            .await;
+        let server = test_server(&mock);
+        let (transport, mut peer) = MemoryTransport::pair();
+        let session = tokio::spawn(Arc::clone(&server).serve(transport, None));
+
+        peer.outgoing
+            .send(
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "method": "tools/call",
+                    "params": {"name": "kagi_search_fetch", "arguments": {"queries": ["rust"]}}
+                })
+                .to_string(),
+            )
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        server.shut_down_on(async {});
+
+        // The session ends although the peer is still connected, after answering
+        session.await.unwrap().unwrap();
+        let response: Value = serde_json::from_str(&peer.incoming.recv().await.unwrap()).unwrap();
+        assert_eq!(response["id"], 1);
+        assert!(response["result"]["content"].is_array());
+        assert!(peer.incoming.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_closed_input_cancels_slow_requests() {
+        let mock = MockKagi::start().await;
+        Mock::given(path("/api/v0/search"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(fixtures::SEARCH, "application/json")
+                    .set_delay(Duration::from_secs(30)),
+            )
+            .mount(mock.server())
+            .await;
+        let server = KagiMcpServer::new(
+            mock.client(),
+            ServerOptions {
+                strict: strict::StrictMode::Panic,
+                shutdown_deadline: Duration::from_millis(100),
+                ..ServerOptions::default()
+            },
+        );
+        let (transport, peer) = MemoryTransport::pair();
+        let session = tokio::spawn(Arc::new(server).serve(transport, None));
+        let MemoryPeer {
+            outgoing,
+            mut incoming,
+        } = peer;
+        outgoing
+            .send(
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "method": "tools/call",
+                    "params": {"name": "kagi_search_fetch", "arguments": {"queries": ["rust"]}}
+                })
+                .to_string(),
+            )
+            .unwrap();
+        drop(outgoing);
+
+        // The session ends at the deadline rather than when Kagi answers
+        tokio::time::timeout(Duration::from_secs(5), session)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let response: Value = serde_json::from_str(&incoming.recv().await.unwrap()).unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(
+            response["error"]["message"],
+            "The server shut down before the request completed"
+        );
+    }
+}
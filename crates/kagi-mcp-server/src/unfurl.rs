@@ -0,0 +1,317 @@
+//! Lightweight link unfurling
+//!
+//! Fetches the head of an HTML page and extracts the title, site name, published
+//! date and description from `<title>` and `<meta>` tags. This avoids a paid
+//! summarizer call when the assistant only needs to label a link.
+
+use crate::output::ToolOutput;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Maximum number of bytes read from a page when looking for metadata
+const MAX_HEAD_BYTES: usize = 512 * 1024;
+
+/// Timeout for fetching a page locally
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct PageMeta {
+    /// Title of the page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Name of the site the page belongs to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub site_name: Option<String>,
+    /// Publication date, as given by the page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub published: Option<String>,
+    /// One-sentence description
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// A `kagi_unfurl` result, and its structured content
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Unfurled {
+    /// The unfurled URL
+    pub url: String,
+    #[serde(flatten)]
+    pub meta: PageMeta,
+}
+
+impl Unfurled {
+    pub fn into_output(self) -> ToolOutput {
+        ToolOutput {
+            structured_content: serde_json::to_value(&self).ok(),
+            ..ToolOutput::from(self.meta.format(&self.url))
+        }
+    }
+}
+
+impl PageMeta {
+    /// Format the metadata as the text block returned by the `kagi_unfurl` tool
+    pub fn format(&self, url: &str) -> String {
+        let mut output = String::new();
+        let _ = writeln!(
+            output,
+            "Title: {}",
+            self.title.as_deref().unwrap_or("Not Available")
+        );
+        if let Some(site_name) = &self.site_name {
+            let _ = writeln!(output, "Site: {site_name}");
+        }
+        let _ = writeln!(
+            output,
+            "Published Date: {}",
+            self.published.as_deref().unwrap_or("Not Available")
+        );
+        if let Some(description) = &self.description {
+            let _ = writeln!(output, "Description: {description}");
+        }
+        let _ = writeln!(output, "URL: {url}");
+        output
+    }
+}
+
+/// Fetch a page and parse its metadata
+///
+/// Only the first [`MAX_HEAD_BYTES`] of the body are read, which is enough to
+/// cover the `<head>` of practically every page.
+pub async fn fetch_meta(client: &reqwest::Client, url: &str) -> Result<PageMeta, String> {
+    let mut response = client
+        .get(url)
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| format!("failed to fetch '{url}': {e}"))?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "failed to fetch '{url}': HTTP {}",
+            response.status().as_u16()
+        ));
+    }
+
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_none_or(|v| v.contains("html"));
+    if !is_html {
+        return Err(format!("'{url}' is not an HTML page"));
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("failed to read '{url}': {e}"))?
+    {
+        body.extend_from_slice(&chunk);
+        if body.len() >= MAX_HEAD_BYTES {
+            body.truncate(MAX_HEAD_BYTES);
+            break;
+        }
+    }
+
+    Ok(parse_meta(&String::from_utf8_lossy(&body)))
+}
+
+/// Extract page metadata from raw HTML
+pub fn parse_meta(html: &str) -> PageMeta {
+    let mut meta = PageMeta::default();
+    let lower = html.to_ascii_lowercase();
+
+    if let Some(start) = lower.find("<title") {
+        if let Some(open_end) = lower[start..].find('>') {
+            let content_start = start + open_end + 1;
+            if let Some(close) = lower[content_start..].find("</title>") {
+                let title = collapse_whitespace(&decode_entities(
+                    &html[content_start..content_start + close],
+                ));
+                if !title.is_empty() {
+                    meta.title = Some(title);
+                }
+            }
+        }
+    }
+
+    let mut og_title = None;
+    let mut og_description = None;
+    let mut description = None;
+    let mut search_from = 0;
+    while let Some(pos) = lower[search_from..].find("<meta") {
+        let tag_start = search_from + pos;
+        let Some(tag_len) = lower[tag_start..].find('>') else {
+            break;
+        };
+        let tag = &html[tag_start..tag_start + tag_len];
+        search_from = tag_start + tag_len;
+
+        let key = attribute(tag, "property")
+            .or_else(|| attribute(tag, "name"))
+            .or_else(|| attribute(tag, "itemprop"))
+            .map(|k| k.to_ascii_lowercase());
+        let Some(key) = key else { continue };
+        let Some(content) = attribute(tag, "content").map(|c| collapse_whitespace(&c)) else {
+            continue;
+        };
+        if content.is_empty() {
+            continue;
+        }
+
+        match key.as_str() {
+            "og:title" | "twitter:title" => {
+                og_title.get_or_insert(content);
+            }
+            "og:site_name" | "application-name" => {
+                meta.site_name.get_or_insert(content);
+            }
+            "article:published_time" | "datepublished" | "date" | "dc.date" | "pubdate" => {
+                meta.published.get_or_insert(content);
+            }
+            "og:description" | "twitter:description" => {
+                og_description.get_or_insert(content);
+            }
+            "description" => {
+                description.get_or_insert(content);
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(og_title) = og_title {
+        meta.title = Some(og_title);
+    }
+    meta.description = og_description
+        .or(description)
+        .map(|d| first_sentence(&d).to_string());
+    meta
+}
+
+/// Return the first sentence of `text`, or the whole text if it has no sentence break
+pub fn first_sentence(text: &str) -> &str {
+    let text = text.trim();
+    for (i, c) in text.char_indices() {
+        if matches!(c, '.' | '!' | '?') {
+            let end = i + c.len_utf8();
+            if text[end..].starts_with(char::is_whitespace) {
+                return &text[..end];
+            }
+        }
+    }
+    text
+}
+
+/// Read the value of an attribute from the inside of a tag
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut search_from = 0;
+    while let Some(pos) = lower[search_from..].find(name) {
+        let start = search_from + pos;
+        search_from = start + name.len();
+
+        // Make sure we matched a whole attribute name
+        let preceded_by_space = lower[..start]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_whitespace);
+        let rest = lower[search_from..].trim_start();
+        if !preceded_by_space || !rest.starts_with('=') {
+            continue;
+        }
+
+        let value_start = tag.len() - rest.len() + 1;
+        let value = tag[value_start..].trim_start();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let inner = &value[1..];
+                &inner[..inner.find(quote).unwrap_or(inner.len())]
+            }
+            _ => {
+                let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                &value[..end]
+            }
+        };
+        return Some(decode_entities(value));
+    }
+    None
+}
+
+pub fn decode_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_meta() {
+        let html = r#"<html><head>
+            <TITLE>Fallback &amp; Title</TITLE>
+            <meta property="og:title" content="Rust 2024 Edition">
+            <meta property="og:site_name" content='The Rust Blog'>
+            <meta name="description" content="The 2024 edition is out. It brings many changes.">
+            <meta property="article:published_time" content="2025-02-20T00:00:00Z" />
+        </head><body></body></html>"#;
+
+        let meta = parse_meta(html);
+        assert_eq!(meta.title.as_deref(), Some("Rust 2024 Edition"));
+        assert_eq!(meta.site_name.as_deref(), Some("The Rust Blog"));
+        assert_eq!(meta.published.as_deref(), Some("2025-02-20T00:00:00Z"));
+        assert_eq!(
+            meta.description.as_deref(),
+            Some("The 2024 edition is out.")
+        );
+    }
+
+    #[test]
+    fn test_parse_meta_title_only() {
+        let meta = parse_meta("<title>\n  Just a   title\n</title>");
+        assert_eq!(meta.title.as_deref(), Some("Just a title"));
+        assert_eq!(meta.description, None);
+    }
+
+    #[test]
+    fn test_structured_output() {
+        let output = Unfurled {
+            url: "https://blog.rust-lang.org/".to_string(),
+            meta: PageMeta {
+                title: Some("Rust Blog".to_string()),
+                ..PageMeta::default()
+            },
+        }
+        .into_output();
+        assert_eq!(
+            output.structured_content,
+            Some(serde_json::json!({"url": "https://blog.rust-lang.org/", "title": "Rust Blog"}))
+        );
+
+        let schema = crate::tools::output_schema::<Unfurled>();
+        assert_eq!(schema["required"], serde_json::json!(["url"]));
+        assert!(schema["properties"]["site_name"].is_object());
+    }
+
+    #[test]
+    fn test_first_sentence() {
+        assert_eq!(
+            first_sentence("Version 1.2 is out. More soon."),
+            "Version 1.2 is out."
+        );
+        assert_eq!(first_sentence("No terminator"), "No terminator");
+    }
+}
@@ -0,0 +1,278 @@
+//! Post-processing of URLs in tool results
+//!
+//! Every URL in the text of a tool result can be rewritten or annotated before it
+//! reaches the host, according to the configured [`UrlRule`]s. URLs that no rule
+//! changes are left exactly as Kagi returned them.
+
+use crate::local;
+use serde_json::Value;
+
+/// Query parameters that only track where a visitor came from
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "yclid", "igshid", "mc_cid",
+    "mc_eid", "_hsenc", "_hsmkt", "ref_src", "si",
+];
+
+/// Hosts of common link shorteners
+const SHORTENERS: &[&str] = &[
+    "bit.ly",
+    "buff.ly",
+    "cutt.ly",
+    "goo.gl",
+    "is.gd",
+    "lnkd.in",
+    "ow.ly",
+    "rebrand.ly",
+    "t.co",
+    "t.ly",
+    "tinyurl.com",
+];
+
+/// Note appended after shortened links
+const SHORTENED_TAG: &str = " [shortened link]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum UrlRule {
+    /// Remove tracking query parameters such as `utm_source` and `fbclid`
+    StripTracking,
+    /// Rewrite `http` links to public hosts to `https`
+    ForceHttps,
+    /// Mark links from URL shorteners, whose destination is hidden
+    TagShortened,
+}
+
+/// The URL rules applied to tool results
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UrlPolicy {
+    rules: Vec<UrlRule>,
+}
+
+impl UrlPolicy {
+    pub fn new(rules: Vec<UrlRule>) -> Self {
+        Self { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// A sentence telling the model how URLs in results are rewritten, if at all
+    pub fn describe(&self) -> Option<String> {
+        let changes: Vec<&str> = self
+            .rules
+            .iter()
+            .map(|rule| match rule {
+                UrlRule::StripTracking => "tracking parameters are removed from URLs",
+                UrlRule::ForceHttps => "http links are upgraded to https",
+                UrlRule::TagShortened => "links from URL shorteners are marked",
+            })
+            .collect();
+        match changes.as_slice() {
+            [] => None,
+            [change] => Some(format!("In results, {change}.")),
+            [init @ .., last] => Some(format!("In results, {} and {last}.", init.join(", "))),
+        }
+    }
+
+    /// Apply the policy to the text content and structured content of a
+    /// `tools/call` result
+    pub fn apply_to_result(&self, result: &mut Value) {
+        if self.is_empty() {
+            return;
+        }
+        if let Some(Value::Array(content)) = result.get_mut("content") {
+            for block in content {
+                if let Some(Value::String(text)) = block.get_mut("text") {
+                    *text = self.apply(text);
+                }
+            }
+        }
+        if let Some(structured) = result.get_mut("structuredContent") {
+            self.apply_to_strings(structured);
+        }
+    }
+
+    /// Apply the policy to every string nested in `value`
+    fn apply_to_strings(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => *text = self.apply(text),
+            Value::Array(items) => items
+                .iter_mut()
+                .for_each(|item| self.apply_to_strings(item)),
+            Value::Object(fields) => fields
+                .values_mut()
+                .for_each(|field| self.apply_to_strings(field)),
+            _ => {}
+        }
+    }
+
+    /// Apply the policy to every URL in `text`
+    pub fn apply(&self, text: &str) -> String {
+        let mut output = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = find_url_start(rest) {
+            output.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = url_len(rest);
+            output.push_str(&self.rewrite(&rest[..end]));
+            rest = &rest[end..];
+        }
+        output.push_str(rest);
+        output
+    }
+
+    fn rewrite(&self, original: &str) -> String {
+        let Ok(mut url) = reqwest::Url::parse(original) else {
+            return original.to_string();
+        };
+        let mut changed = false;
+
+        if self.rules.contains(&UrlRule::StripTracking) && url.query().is_some() {
+            let pairs: Vec<(String, String)> = url
+                .query_pairs()
+                .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                .collect();
+            let kept: Vec<&(String, String)> =
+                pairs.iter().filter(|(key, _)| !is_tracking(key)).collect();
+            if kept.len() != pairs.len() {
+                if kept.is_empty() {
+                    url.set_query(None);
+                } else {
+                    url.query_pairs_mut().clear().extend_pairs(kept);
+                }
+                changed = true;
+            }
+        }
+
+        if self.rules.contains(&UrlRule::ForceHttps)
+            && url.scheme() == "http"
+            && !local::is_private_url(original)
+            && url.set_scheme("https").is_ok()
+        {
+            changed = true;
+        }
+
+        let mut rewritten = if changed {
+            url.to_string()
+        } else {
+            original.to_string()
+        };
+        if self.rules.contains(&UrlRule::TagShortened)
+            && url
+                .host_str()
+                .is_some_and(|host| SHORTENERS.contains(&host.trim_start_matches("www.")))
+        {
+            rewritten.push_str(SHORTENED_TAG);
+        }
+        rewritten
+    }
+}
+
+fn is_tracking(key: &str) -> bool {
+    key.starts_with("utm_") || TRACKING_PARAMS.contains(&key)
+}
+
+fn find_url_start(text: &str) -> Option<usize> {
+    match (text.find("http://"), text.find("https://")) {
+        (Some(http), Some(https)) => Some(http.min(https)),
+        (http, https) => http.or(https),
+    }
+}
+
+/// Length of the URL at the start of `text`, without trailing punctuation
+fn url_len(text: &str) -> usize {
+    let end = text
+        .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"' | '\'' | '`'))
+        .unwrap_or(text.len());
+    let mut url = &text[..end];
+    loop {
+        let trimmed = url.trim_end_matches(['.', ',', ';', ':', '!', '?']);
+        // Keep closing brackets that belong to the URL, e.g. Wikipedia links
+        let trimmed = match trimmed.chars().last() {
+            Some(')') if trimmed.matches('(').count() < trimmed.matches(')').count() => {
+                &trimmed[..trimmed.len() - 1]
+            }
+            Some(']') if trimmed.matches('[').count() < trimmed.matches(']').count() => {
+                &trimmed[..trimmed.len() - 1]
+            }
+            _ => trimmed,
+        };
+        if trimmed.len() == url.len() {
+            return url.len();
+        }
+        url = trimmed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_apply_rules() {
+        let policy = UrlPolicy::new(vec![
+            UrlRule::StripTracking,
+            UrlRule::ForceHttps,
+            UrlRule::TagShortened,
+        ]);
+
+        assert_eq!(
+            policy.apply("See http://example.com/a?id=3&utm_source=x&fbclid=y."),
+            "See https://example.com/a?id=3."
+        );
+        assert_eq!(
+            policy.apply("[docs](https://example.com/guide?utm_medium=mail)"),
+            "[docs](https://example.com/guide)"
+        );
+        assert_eq!(
+            policy.apply("https://en.wikipedia.org/wiki/Rust_(programming_language), more"),
+            "https://en.wikipedia.org/wiki/Rust_(programming_language), more"
+        );
+        assert_eq!(
+            policy.apply("via https://bit.ly/3abc"),
+            "via https://bit.ly/3abc [shortened link]"
+        );
+        assert_eq!(
+            policy.apply("local http://localhost:3000/docs stays"),
+            "local http://localhost:3000/docs stays"
+        );
+        assert_eq!(
+            UrlPolicy::new(vec![UrlRule::TagShortened]).apply("http://example.com?utm_id=1"),
+            "http://example.com?utm_id=1"
+        );
+
+        let mut result = json!({
+            "content": [{"type": "text", "text": "http://example.com/"}],
+            "structuredContent": {"url": "http://example.com/", "links": ["http://example.org/"]}
+        });
+        policy.apply_to_result(&mut result);
+        assert_eq!(result["content"][0]["text"], "https://example.com/");
+        assert_eq!(result["structuredContent"]["url"], "https://example.com/");
+        assert_eq!(
+            result["structuredContent"]["links"][0],
+            "https://example.org/"
+        );
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(UrlPolicy::default().describe(), None);
+        assert_eq!(
+            UrlPolicy::new(vec![UrlRule::ForceHttps])
+                .describe()
+                .unwrap(),
+            "In results, http links are upgraded to https."
+        );
+        assert_eq!(
+            UrlPolicy::new(vec![
+                UrlRule::StripTracking,
+                UrlRule::ForceHttps,
+                UrlRule::TagShortened
+            ])
+            .describe()
+            .unwrap(),
+            "In results, tracking parameters are removed from URLs, http links are upgraded to https and links from URL shorteners are marked."
+        );
+    }
+}
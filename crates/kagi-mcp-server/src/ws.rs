@@ -0,0 +1,147 @@
+//! WebSocket transport
+//!
+//! Serves MCP over a WebSocket at `/ws`, for gateways and embedders where stdio is
+//! not available and pairing HTTP requests with responses is awkward. Each
+//! connection is one session: every text frame carries one JSON-RPC message in
+//! either direction, like a line of the stdio transport, and requests run
+//! concurrently with their progress notifications sent as they happen.
+
+use crate::http::{check_origin, new_session_id};
+use crate::transport::Transport;
+use crate::KagiMcpServer;
+use async_trait::async_trait;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use std::io;
+use std::sync::Arc;
+
+/// Path of the WebSocket endpoint
+pub const ENDPOINT: &str = "/ws";
+
+/// Routes of the WebSocket transport
+pub fn router(server: Arc<KagiMcpServer>) -> Router {
+    Router::new()
+        .route(ENDPOINT, get(handle_upgrade))
+        .with_state(server)
+}
+
+async fn handle_upgrade(
+    State(server): State<Arc<KagiMcpServer>>,
+    headers: HeaderMap,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    if let Err(rejection) = check_origin(&headers) {
+        return rejection.into_response();
+    }
+    upgrade.on_upgrade(move |socket| run_session(server, socket))
+}
+
+/// A WebSocket carrying one JSON-RPC message per text frame
+struct WebSocketTransport {
+    socket: WebSocket,
+}
+
+#[async_trait]
+impl Transport for WebSocketTransport {
+    async fn read_message(&mut self) -> io::Result<Option<String>> {
+        while let Some(message) = self.socket.recv().await {
+            match message.map_err(io::Error::other)? {
+                Message::Text(text) => return Ok(Some(text.to_string())),
+                Message::Close(_) => break,
+                // Pings are answered by axum; binary frames carry no JSON-RPC
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    async fn write_message(&mut self, message: &str) -> io::Result<()> {
+        self.socket
+            .send(Message::Text(message.into()))
+            .await
+            .map_err(io::Error::other)
+    }
+}
+
+/// Serve one connection until the client closes it
+async fn run_session(server: Arc<KagiMcpServer>, socket: WebSocket) {
+    let verbose = server.verbose;
+    let transport = WebSocketTransport { socket };
+    if let Err(e) = server.serve(transport, Some(new_session_id())).await {
+        if verbose {
+            tracing::warn!("WebSocket session failed: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strict;
+    use crate::tests::test_server;
+    use futures::{SinkExt, StreamExt};
+    use kagiapi::testing::MockKagi;
+    use serde_json::{json, Value};
+    use tokio_tungstenite::tungstenite;
+
+    #[tokio::test]
+    async fn test_websocket_session() {
+        let mock = MockKagi::start().await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}{ENDPOINT}", listener.local_addr().unwrap());
+        let server = test_server(&mock);
+        tokio::spawn(async move { axum::serve(listener, router(server)).await });
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let mut send = async |message: Value| {
+            socket
+                .send(tungstenite::Message::text(message.to_string()))
+                .await
+                .unwrap();
+        };
+        send(json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}})).await;
+        send(json!({"jsonrpc": "2.0", "method": "notifications/initialized"})).await;
+        send(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": {
+                "name": "kagi_search_fetch",
+                "arguments": {"queries": ["rust", "rust async"]},
+                "_meta": {"progressToken": "search"}
+            }
+        }))
+        .await;
+        send(json!({"jsonrpc": "2.0", "id": 3, "method": 5})).await;
+
+        let mut messages = Vec::new();
+        while messages.len() < 5 {
+            let frame = socket.next().await.unwrap().unwrap();
+            let message: Value = serde_json::from_str(frame.to_text().unwrap()).unwrap();
+            messages.push(message);
+        }
+        let response = |id: i64| messages.iter().find(|m| m["id"] == id).unwrap();
+        assert_eq!(
+            response(1)["result"]["protocolVersion"],
+            strict::PROTOCOL_VERSION
+        );
+        assert!(response(2)["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("The Rust Programming Language"));
+        assert_eq!(
+            messages
+                .iter()
+                .filter(|m| m["method"] == "notifications/progress")
+                .count(),
+            2
+        );
+        assert!(messages
+            .iter()
+            .any(|m| m["id"].is_null() && m["error"]["code"] == -32600));
+    }
+}
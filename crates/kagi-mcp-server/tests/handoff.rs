@@ -0,0 +1,102 @@
+//! Hands the TCP listener of the built binary over to an upgraded one
+
+#![cfg(unix)]
+
+use serde_json::{json, Value};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::TcpStream;
+use tokio::process::{ChildStderr, Command};
+
+/// The text after `prefix` in the next log line containing it
+async fn log_line(stderr: &mut Lines<BufReader<ChildStderr>>, prefix: &str) -> String {
+    loop {
+        let line = stderr
+            .next_line()
+            .await
+            .unwrap()
+            .expect("the server exited");
+        if let Some((_, rest)) = line.split_once(prefix) {
+            break rest.trim().to_string();
+        }
+    }
+}
+
+/// A connection speaking newline-delimited JSON-RPC
+struct Connection(
+    Lines<BufReader<tokio::net::tcp::OwnedReadHalf>>,
+    tokio::net::tcp::OwnedWriteHalf,
+);
+
+impl Connection {
+    async fn open(addr: &str) -> Self {
+        let (input, output) = TcpStream::connect(addr).await.unwrap().into_split();
+        Self(BufReader::new(input).lines(), output)
+    }
+
+    async fn request(&mut self, id: u64, method: &str) -> Value {
+        let request = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": {}});
+        self.1
+            .write_all(format!("{request}\n").as_bytes())
+            .await
+            .unwrap();
+        let line = self.0.next_line().await.unwrap().unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+}
+
+/// The upgraded server, which is not a child of the test, stopped when dropped
+struct Successor(i32);
+
+impl Drop for Successor {
+    fn drop(&mut self) {
+        // SAFETY: signalling the server started by the test's child
+        unsafe { libc::kill(self.0, libc::SIGTERM) };
+    }
+}
+
+#[tokio::test]
+async fn test_upgrade_keeps_open_sessions() {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_kagi-mcp-server"))
+        .args(["--transport", "tcp", "--tcp-addr", "127.0.0.1:0"])
+        .env("KAGI_API_KEY", "test-key")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .unwrap();
+    let mut stderr = BufReader::new(server.stderr.take().unwrap()).lines();
+    let addr = log_line(&mut stderr, "Serving MCP over TCP at ").await;
+
+    let mut open = Connection::open(&addr).await;
+    let response = open.request(1, "initialize").await;
+    assert!(response["result"]["protocolVersion"].is_string());
+
+    let pid = i32::try_from(server.id().unwrap()).unwrap();
+    // SAFETY: signalling a child process of this test
+    assert_eq!(unsafe { libc::kill(pid, libc::SIGUSR2) }, 0);
+    let successor = log_line(&mut stderr, "Handed the listener over to process ").await;
+    let _successor = Successor(successor.split(';').next().unwrap().parse().unwrap());
+    assert_eq!(
+        log_line(&mut stderr, "Serving MCP over TCP at ").await,
+        addr
+    );
+
+    // The open session stays with the old server, new ones go to the upgraded one
+    let response = open.request(2, "tools/list").await;
+    assert!(response["result"]["tools"].is_array());
+    let response = Connection::open(&addr).await.request(1, "initialize").await;
+    assert!(response["result"]["protocolVersion"].is_string());
+
+    // The old server exits once its last session is closed
+    drop(open);
+    let status = tokio::time::timeout(Duration::from_secs(10), server.wait())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(status.success());
+    let response = Connection::open(&addr).await.request(1, "initialize").await;
+    assert!(response["result"]["protocolVersion"].is_string());
+}
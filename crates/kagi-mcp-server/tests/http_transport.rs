@@ -0,0 +1,43 @@
+//! Runs the built binary with `--transport http`, as the released binary is run
+
+#![cfg(feature = "http")]
+
+use serde_json::{json, Value};
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+
+#[tokio::test]
+async fn test_serves_streamable_http() {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_kagi-mcp-server"))
+        .args(["--transport", "http", "--http-addr", "127.0.0.1:0"])
+        .env("KAGI_API_KEY", "test-key")
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .unwrap();
+
+    // The address the OS picked is only known from the startup log line
+    let mut stderr = BufReader::new(server.stderr.take().unwrap()).lines();
+    let url = loop {
+        let line = stderr
+            .next_line()
+            .await
+            .unwrap()
+            .expect("the server exited before listening");
+        if let Some((_, url)) = line.split_once("Serving MCP over HTTP at ") {
+            break url.trim().to_string();
+        }
+    };
+
+    let response = reqwest::Client::new()
+        .post(&url)
+        .json(&json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["result"]["serverInfo"]["name"], "kagi-mcp-server");
+}
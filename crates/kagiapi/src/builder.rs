@@ -0,0 +1,253 @@
+//! Builder for [`KagiClient`] with HTTP transport tuning
+
+use crate::{ClientConfig, Error, KagiClient, Result, API_BASE_URL_PREFIX};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Configures and creates a [`KagiClient`]
+///
+/// Response compression (gzip, brotli and deflate) is enabled by default, which noticeably
+/// speeds up large search responses over slow links.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let client = kagiapi::KagiClient::builder("your-api-key")
+///     .pool_idle_timeout(Some(Duration::from_secs(30)))
+///     .pool_max_idle_per_host(4)
+///     .http2(false)
+///     .build()
+///     .unwrap();
+/// # let _ = client;
+/// ```
+#[derive(Debug, Clone)]
+pub struct KagiClientBuilder {
+    api_key: String,
+    base_url_prefix: String,
+    search_api_version: String,
+    summarizer_api_version: String,
+    fastgpt_api_version: String,
+    enrich_api_version: String,
+    compression: bool,
+    pool_idle_timeout: Option<Option<Duration>>,
+    pool_max_idle_per_host: Option<usize>,
+    tcp_keepalive: Option<Duration>,
+    http2: bool,
+    http2_keep_alive_interval: Option<Duration>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+}
+
+impl KagiClientBuilder {
+    pub(crate) fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            base_url_prefix: API_BASE_URL_PREFIX.to_string(),
+            search_api_version: "v0".to_string(),
+            summarizer_api_version: "v0".to_string(),
+            fastgpt_api_version: "v0".to_string(),
+            enrich_api_version: "v0".to_string(),
+            compression: true,
+            pool_idle_timeout: None,
+            pool_max_idle_per_host: None,
+            tcp_keepalive: None,
+            http2: true,
+            http2_keep_alive_interval: None,
+            timeout: None,
+            connect_timeout: None,
+        }
+    }
+
+    /// Base URL prefix of the API (useful for testing)
+    #[must_use]
+    pub fn base_url_prefix(mut self, base_url_prefix: impl Into<String>) -> Self {
+        self.base_url_prefix = base_url_prefix.into();
+        self
+    }
+
+    /// API version for the search endpoint
+    #[must_use]
+    pub fn search_api_version(mut self, version: impl Into<String>) -> Self {
+        self.search_api_version = version.into();
+        self
+    }
+
+    /// API version for the summarizer endpoint
+    #[must_use]
+    pub fn summarizer_api_version(mut self, version: impl Into<String>) -> Self {
+        self.summarizer_api_version = version.into();
+        self
+    }
+
+    /// API version for the `FastGPT` endpoint
+    #[must_use]
+    pub fn fastgpt_api_version(mut self, version: impl Into<String>) -> Self {
+        self.fastgpt_api_version = version.into();
+        self
+    }
+
+    /// API version for the enrichment endpoints
+    #[must_use]
+    pub fn enrich_api_version(mut self, version: impl Into<String>) -> Self {
+        self.enrich_api_version = version.into();
+        self
+    }
+
+    /// Request gzip, brotli and deflate compressed responses (default: enabled)
+    #[must_use]
+    pub fn compression(mut self, enabled: bool) -> Self {
+        self.compression = enabled;
+        self
+    }
+
+    /// How long idle pooled connections are kept open; `None` keeps them indefinitely
+    #[must_use]
+    pub fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Maximum number of idle pooled connections per host
+    #[must_use]
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Interval of TCP keepalive probes on open connections
+    #[must_use]
+    pub fn tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Allow HTTP/2 to be negotiated (default: enabled); disabling forces HTTP/1.1
+    #[must_use]
+    pub fn http2(mut self, enabled: bool) -> Self {
+        self.http2 = enabled;
+        self
+    }
+
+    /// Interval of HTTP/2 keepalive pings on open connections
+    #[must_use]
+    pub fn http2_keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.http2_keep_alive_interval = Some(interval);
+        self
+    }
+
+    /// Total time allowed for a request, including reading the response
+    ///
+    /// Exceeding it fails the request with [`Error::ReadTimeout`], or
+    /// [`Error::ConnectTimeout`] if no connection was established.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Time allowed for establishing a connection
+    #[must_use]
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Create the client
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client cannot be initialized, e.g. because the
+    /// TLS backend fails to load.
+    pub fn build(self) -> Result<KagiClient> {
+        let mut http = reqwest::Client::builder()
+            .gzip(self.compression)
+            .brotli(self.compression)
+            .deflate(self.compression);
+        if let Some(timeout) = self.pool_idle_timeout {
+            http = http.pool_idle_timeout(timeout);
+        }
+        if let Some(timeout) = self.timeout {
+            http = http.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            http = http.connect_timeout(timeout);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            http = http.pool_max_idle_per_host(max);
+        }
+        if let Some(interval) = self.tcp_keepalive {
+            http = http.tcp_keepalive(interval);
+        }
+        if self.http2 {
+            if let Some(interval) = self.http2_keep_alive_interval {
+                http = http
+                    .http2_keep_alive_interval(interval)
+                    .http2_keep_alive_while_idle(true);
+            }
+        } else {
+            http = http.http1_only();
+        }
+
+        Ok(KagiClient {
+            client: http.build().map_err(Error::Request)?,
+            config: Arc::new(ClientConfig {
+                api_key: self.api_key,
+                search_api_version: self.search_api_version,
+                summarizer_api_version: self.summarizer_api_version,
+                fastgpt_api_version: self.fastgpt_api_version,
+                enrich_api_version: self.enrich_api_version,
+                base_url_prefix: self.base_url_prefix,
+            }),
+            correlation_id: None,
+            usage: Arc::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_settings() {
+        let client = KagiClient::builder("key")
+            .base_url_prefix("http://localhost:8080/api")
+            .summarizer_api_version("v1")
+            .compression(false)
+            .pool_idle_timeout(None)
+            .pool_max_idle_per_host(2)
+            .tcp_keepalive(Duration::from_secs(60))
+            .http2_keep_alive_interval(Duration::from_secs(30))
+            .build()
+            .unwrap();
+        assert_eq!(client.config.base_url_prefix, "http://localhost:8080/api");
+        assert_eq!(client.config.summarizer_api_version, "v1");
+        assert_eq!(client.config.search_api_version, "v0");
+    }
+
+    #[tokio::test]
+    async fn test_compression() {
+        let mock = crate::testing::MockKagi::start().await;
+        for compression in [true, false] {
+            let client = KagiClient::builder("key")
+                .base_url_prefix(mock.base_url_prefix())
+                .compression(compression)
+                .build()
+                .unwrap();
+            client.search("rust", None).await.unwrap();
+        }
+
+        let requests = mock.server().received_requests().await.unwrap();
+        let accept_encoding = |index: usize| {
+            requests[index]
+                .headers
+                .get("accept-encoding")
+                .map(|value| value.to_str().unwrap().to_string())
+        };
+        let compressed = accept_encoding(0).unwrap();
+        for encoding in ["gzip", "br", "deflate"] {
+            assert!(compressed.contains(encoding), "{compressed}");
+        }
+        assert_eq!(accept_encoding(1), None);
+    }
+}
@@ -0,0 +1,200 @@
+//! Stable, canonical serialization of request parameters
+//!
+//! Two requests that would produce the same API response serialize to the same
+//! key: object keys are sorted, whitespace in queries is collapsed, language codes
+//! are upper-cased and omitted parameters are replaced by the API's defaults.
+//! The endpoint name is part of the key so requests to different endpoints never
+//! collide. Keys are suitable for response caches and recorded test fixtures.
+//!
+//! # Example
+//!
+//! ```
+//! use kagiapi::canonical::CanonicalRequest;
+//! use kagiapi::{SummarizeRequest, SummarizerEngine};
+//!
+//! let implicit = SummarizeRequest {
+//!     url: Some("https://example.com".to_string()),
+//!     ..SummarizeRequest::default()
+//! };
+//! let explicit = SummarizeRequest {
+//!     url: Some(" https://example.com ".to_string()),
+//!     engine: Some(SummarizerEngine::Cecil),
+//!     ..SummarizeRequest::default()
+//! };
+//! assert_eq!(implicit.canonical_key(), explicit.canonical_key());
+//! ```
+
+use crate::{
+    EnrichRequest, EnrichType, FastGptRequest, SearchRequest, SummarizeRequest, SummaryType,
+};
+use serde_json::{json, Value};
+use std::fmt::Write;
+
+/// A request with a canonical, order-independent serialization
+pub trait CanonicalRequest {
+    /// Name of the API endpoint the request is sent to
+    const ENDPOINT: &'static str;
+
+    /// The request parameters with defaults applied and values normalized
+    fn canonical_params(&self) -> Value;
+
+    /// Canonical JSON serialization of the request, including its endpoint
+    fn canonical_key(&self) -> String {
+        let value = json!({
+            "endpoint": Self::ENDPOINT,
+            "params": self.canonical_params(),
+        });
+        let mut key = String::new();
+        write_canonical(&value, &mut key);
+        key
+    }
+}
+
+impl CanonicalRequest for SearchRequest {
+    const ENDPOINT: &'static str = "search";
+
+    fn canonical_params(&self) -> Value {
+        json!({
+            "query": normalize_query(&self.query),
+            "limit": self.limit,
+        })
+    }
+}
+
+impl CanonicalRequest for SummarizeRequest {
+    const ENDPOINT: &'static str = "summarize";
+
+    fn canonical_params(&self) -> Value {
+        json!({
+            "url": self.url.as_deref().map(str::trim),
+            "text": self.text,
+            "engine": self.engine.unwrap_or_default(),
+            "summary_type": self.summary_type.unwrap_or(SummaryType::Summary),
+            "target_language": self
+                .target_language
+                .as_deref()
+                .map(|language| language.trim().to_ascii_uppercase()),
+        })
+    }
+}
+
+impl CanonicalRequest for FastGptRequest {
+    const ENDPOINT: &'static str = "fastgpt";
+
+    fn canonical_params(&self) -> Value {
+        json!({
+            "query": normalize_query(&self.query),
+            "cache": self.cache.unwrap_or(true),
+            "web_search": self.web_search.unwrap_or(true),
+        })
+    }
+}
+
+impl CanonicalRequest for EnrichRequest {
+    const ENDPOINT: &'static str = "enrich";
+
+    fn canonical_params(&self) -> Value {
+        json!({
+            "query": normalize_query(&self.query),
+            "enrich_type": match self.enrich_type {
+                EnrichType::Web => "web",
+                EnrichType::News => "news",
+            },
+        })
+    }
+}
+
+/// Trim a query and collapse internal runs of whitespace
+pub fn normalize_query(query: &str) -> String {
+    query.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Serialize a JSON value with object keys sorted at every level
+///
+/// This does not rely on `serde_json`'s map ordering, which changes when the
+/// `preserve_order` feature is enabled anywhere in the dependency graph.
+pub fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                let _ = write!(out, "{}:", Value::String(key.clone()));
+                write_canonical(value, out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        other => {
+            let _ = write!(out, "{other}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_normalization() {
+        let a = SearchRequest {
+            query: "  rust   async ".to_string(),
+            limit: Some(5),
+        };
+        let b = SearchRequest {
+            query: "rust async".to_string(),
+            limit: Some(5),
+        };
+        assert_eq!(a.canonical_key(), b.canonical_key());
+        assert_eq!(
+            a.canonical_key(),
+            r#"{"endpoint":"search","params":{"limit":5,"query":"rust async"}}"#
+        );
+    }
+
+    #[test]
+    fn test_defaults_are_applied() {
+        let implicit = FastGptRequest {
+            query: "rust".to_string(),
+            cache: None,
+            web_search: None,
+        };
+        let explicit = FastGptRequest {
+            query: "rust".to_string(),
+            cache: Some(true),
+            web_search: Some(true),
+        };
+        assert_eq!(implicit.canonical_key(), explicit.canonical_key());
+    }
+
+    #[test]
+    fn test_endpoints_do_not_collide() {
+        let web = EnrichRequest {
+            query: "rust".to_string(),
+            enrich_type: EnrichType::Web,
+        };
+        let news = EnrichRequest {
+            query: "rust".to_string(),
+            enrich_type: EnrichType::News,
+        };
+        let search = SearchRequest {
+            query: "rust".to_string(),
+            limit: None,
+        };
+        assert_ne!(web.canonical_key(), news.canonical_key());
+        assert_ne!(web.canonical_key(), search.canonical_key());
+    }
+}
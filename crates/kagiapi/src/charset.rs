@@ -0,0 +1,101 @@
+//! Transcoding of response bodies to UTF-8
+//!
+//! The API answers in UTF-8, but error pages from proxies and load balancers in
+//! front of it are sometimes Latin-1 or another legacy encoding. Bodies are decoded
+//! with the charset of their `Content-Type` before they are parsed, so such pages
+//! surface as API errors rather than UTF-8 or JSON decoding errors.
+
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use std::borrow::Cow;
+
+/// Decode `body` to UTF-8 according to its `Content-Type` header
+///
+/// A byte order mark takes precedence over the declared charset. Bodies without a
+/// known charset are read as UTF-8, falling back to Windows-1252 (a superset of
+/// Latin-1) when they are not valid UTF-8. Undecodable bytes become U+FFFD.
+pub(crate) fn decode<'a>(body: &'a [u8], content_type: Option<&str>) -> Cow<'a, str> {
+    let declared = content_type
+        .and_then(charset)
+        .and_then(|label| Encoding::for_label(label.as_bytes()));
+    let encoding = match declared {
+        Some(encoding) => encoding,
+        None if std::str::from_utf8(body).is_ok() => UTF_8,
+        None => WINDOWS_1252,
+    };
+    let (text, _, _) = encoding.decode(body);
+    text
+}
+
+/// The `charset` parameter of a `Content-Type` header value
+fn charset(content_type: &str) -> Option<&str> {
+    content_type.split(';').skip(1).find_map(|parameter| {
+        let (name, value) = parameter.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches('"'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockKagi;
+    use crate::Error;
+    use wiremock::matchers::path;
+    use wiremock::{Mock, ResponseTemplate};
+
+    #[test]
+    fn test_decode() {
+        // "Café" in Latin-1
+        let latin1 = b"Caf\xe9";
+        assert_eq!(
+            decode(latin1, Some("text/html; charset=ISO-8859-1")),
+            "Café"
+        );
+        assert_eq!(decode(latin1, Some("text/html")), "Café");
+        assert_eq!(decode(latin1, None), "Café");
+        assert_eq!(decode("Café".as_bytes(), Some("application/json")), "Café");
+        assert_eq!(
+            decode(b"\xff\xfeC\x00a\x00f\x00\xe9\x00", Some("text/plain")),
+            "Café"
+        );
+        assert_eq!(
+            decode(
+                b"\x82\xb1\x82\xf1",
+                Some(r#"text/plain; charset="shift_jis""#)
+            ),
+            "こん"
+        );
+        assert_eq!(charset("text/html;Charset=utf-8"), Some("utf-8"));
+        assert_eq!(charset("text/html"), None);
+    }
+
+    #[tokio::test]
+    async fn test_non_utf8_responses() {
+        let mock = MockKagi::start().await;
+        Mock::given(path("/api/v0/search"))
+            .respond_with(ResponseTemplate::new(502).set_body_raw(
+                b"<h1>Passerelle d\xe9faillante</h1>".to_vec(),
+                "text/html; charset=iso-8859-1",
+            ))
+            .mount(mock.server())
+            .await;
+        Mock::given(path("/api/v0/enrich/web"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                b"{\"meta\": {\"id\": \"1\", \"node\": \"mock\", \"ms\": 1}, \
+                  \"data\": [{\"t\": 0, \"url\": \"https://example.com/\", \"title\": \"Caf\xe9\"}]}"
+                    .to_vec(),
+                "application/json; charset=latin1",
+            ))
+            .mount(mock.server())
+            .await;
+
+        let client = mock.client();
+        let error = client.search("rust", None).await.unwrap_err();
+        assert!(matches!(error, Error::ServerError { status: 502, .. }));
+        assert!(error.to_string().contains("Passerelle défaillante"));
+
+        let response = client.enrich("café", crate::EnrichType::Web).await.unwrap();
+        assert_eq!(response.data[0].title.as_deref(), Some("Café"));
+    }
+}
@@ -0,0 +1,79 @@
+//! Splitting of long text into summarizer-sized chunks
+
+/// Split `text` into chunks of at most `max_bytes` bytes
+///
+/// Chunks are split on paragraph boundaries where possible, falling back to line
+/// breaks, then whitespace, and finally to any character boundary for text with no
+/// natural break points. Empty chunks are never returned.
+pub(crate) fn chunk_text(text: &str, max_bytes: usize) -> Vec<&str> {
+    let max_bytes = max_bytes.max(1);
+    let mut chunks = Vec::new();
+    let mut rest = text.trim();
+
+    while !rest.is_empty() {
+        if rest.len() <= max_bytes {
+            chunks.push(rest);
+            break;
+        }
+
+        let window = &rest[..floor_char_boundary(rest, max_bytes)];
+        let split_at = ["\n\n", "\n", " "]
+            .iter()
+            .find_map(|separator| window.rfind(separator).filter(|&pos| pos > 0))
+            .unwrap_or(
+                window
+                    .len()
+                    .max(rest.chars().next().map_or(1, char::len_utf8)),
+            );
+
+        let chunk = rest[..split_at].trim();
+        if !chunk.is_empty() {
+            chunks.push(chunk);
+        }
+        rest = rest[split_at..].trim_start();
+    }
+
+    chunks
+}
+
+/// Largest index not greater than `index` that lies on a character boundary
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_text_is_one_chunk() {
+        assert_eq!(chunk_text("  hello world  ", 100), vec!["hello world"]);
+        assert!(chunk_text("   ", 100).is_empty());
+    }
+
+    #[test]
+    fn test_prefers_paragraph_boundaries() {
+        let text = "first paragraph here\n\nsecond paragraph\nwith two lines\n\nthird";
+        let chunks = chunk_text(text, 35);
+        assert_eq!(
+            chunks,
+            vec![
+                "first paragraph here",
+                "second paragraph\nwith two lines",
+                "third"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_splits_unbroken_text_on_char_boundaries() {
+        let text = "é".repeat(10);
+        let chunks = chunk_text(&text, 5);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 5));
+        assert_eq!(chunks.concat(), text);
+    }
+}
@@ -0,0 +1,274 @@
+//! Error types returned by the Kagi API client
+
+use serde::Deserialize;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("HTTP request failed: {0}")]
+    Request(reqwest::Error),
+    /// No connection to the API could be established in time
+    #[error("Connecting to Kagi timed out after {elapsed:?}: {message}")]
+    ConnectTimeout { elapsed: Duration, message: String },
+    /// The connection was established but the response did not arrive in time
+    #[error("Kagi response timed out after {elapsed:?}: {message}")]
+    ReadTimeout { elapsed: Duration, message: String },
+    /// The API itself reported a timeout (HTTP 408, 504 or 524)
+    #[error("Kagi timed out processing the request ({status}) after {elapsed:?}: {message}")]
+    ServerTimeout {
+        status: u16,
+        elapsed: Duration,
+        message: String,
+    },
+    #[error("Unauthorized: {message}")]
+    Unauthorized { message: String },
+    #[error("API quota exceeded: {message}")]
+    QuotaExceeded { message: String },
+    #[error("Rate limited: {message}")]
+    RateLimited { message: String },
+    #[error("Bad request{}: {message}", field.as_ref().map(|f| format!(" ({f})")).unwrap_or_default())]
+    BadRequest {
+        /// HTTP status, 400 or 422
+        status: u16,
+        field: Option<String>,
+        message: String,
+    },
+    #[error("Kagi server error: {status} - {message}")]
+    ServerError { status: u16, message: String },
+    #[error("API error: {status} - {message}")]
+    Api { status: u16, message: String },
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    /// A feed could not be parsed
+    #[error("Invalid feed: {0}")]
+    InvalidFeed(String),
+    #[error("{source} (correlation id: {correlation_id})")]
+    Correlated {
+        correlation_id: String,
+        #[source]
+        source: Box<Error>,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Error body returned by the Kagi API, e.g.
+/// `{"meta": {...}, "data": null, "error": [{"code": 1, "msg": "...", "ref": null}]}`
+#[derive(Deserialize)]
+struct ErrorBody {
+    #[serde(default)]
+    error: Vec<ErrorDetail>,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail {
+    #[serde(default)]
+    msg: Option<String>,
+    #[serde(default, rename = "ref")]
+    reference: Option<serde_json::Value>,
+}
+
+impl Error {
+    /// Attach the correlation ID of the request that failed
+    #[must_use]
+    pub fn with_correlation_id(self, correlation_id: impl Into<String>) -> Self {
+        Self::Correlated {
+            correlation_id: correlation_id.into(),
+            source: Box::new(self.into_inner()),
+        }
+    }
+
+    /// Correlation ID of the request that failed, if the client had one
+    pub fn correlation_id(&self) -> Option<&str> {
+        match self {
+            Self::Correlated { correlation_id, .. } => Some(correlation_id),
+            _ => None,
+        }
+    }
+
+    /// The underlying error, without its correlation ID
+    pub fn kind(&self) -> &Self {
+        match self {
+            Self::Correlated { source, .. } => source.kind(),
+            other => other,
+        }
+    }
+
+    /// Record how long the failed request ran on timeout errors
+    #[must_use]
+    pub(crate) fn with_elapsed(mut self, duration: Duration) -> Self {
+        if let Self::ConnectTimeout { elapsed, .. }
+        | Self::ReadTimeout { elapsed, .. }
+        | Self::ServerTimeout { elapsed, .. } = &mut self
+        {
+            *elapsed = duration;
+        }
+        self
+    }
+
+    fn into_inner(self) -> Self {
+        match self {
+            Self::Correlated { source, .. } => source.into_inner(),
+            other => other,
+        }
+    }
+
+    /// Classify a non-success HTTP response
+    pub fn from_status(status: u16, body: &str) -> Self {
+        let detail = serde_json::from_str::<ErrorBody>(body)
+            .ok()
+            .and_then(|body| body.error.into_iter().next());
+        let field = detail
+            .as_ref()
+            .and_then(|d| d.reference.as_ref())
+            .and_then(|r| r.as_str())
+            .map(str::to_string);
+        let message = detail
+            .and_then(|d| d.msg)
+            .unwrap_or_else(|| body.trim().to_string());
+
+        match status {
+            400 | 422 => Self::BadRequest {
+                status,
+                field,
+                message,
+            },
+            401 | 403 => Self::Unauthorized { message },
+            402 => Self::QuotaExceeded { message },
+            429 => Self::RateLimited { message },
+            408 | 504 | 524 => Self::ServerTimeout {
+                status,
+                elapsed: Duration::ZERO,
+                message,
+            },
+            500..=599 => Self::ServerError { status, message },
+            _ => Self::Api { status, message },
+        }
+    }
+
+    /// Whether the same request may succeed if retried later
+    pub fn is_retryable(&self) -> bool {
+        match self.kind() {
+            Self::ConnectTimeout { .. }
+            | Self::ReadTimeout { .. }
+            | Self::ServerTimeout { .. }
+            | Self::RateLimited { .. }
+            | Self::ServerError { .. } => true,
+            Self::Request(e) => e.is_connect() || e.is_timeout(),
+            _ => false,
+        }
+    }
+
+    /// HTTP status code of the failed response, if the API responded
+    pub fn status(&self) -> Option<u16> {
+        match self.kind() {
+            Self::Unauthorized { .. } => Some(401),
+            Self::QuotaExceeded { .. } => Some(402),
+            Self::RateLimited { .. } => Some(429),
+            Self::BadRequest { status, .. }
+            | Self::ServerError { status, .. }
+            | Self::ServerTimeout { status, .. }
+            | Self::Api { status, .. } => Some(*status),
+            Self::Request(e) => e.status().map(|s| s.as_u16()),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() && e.is_connect() {
+            Self::ConnectTimeout {
+                elapsed: Duration::ZERO,
+                message: e.to_string(),
+            }
+        } else if e.is_timeout() {
+            Self::ReadTimeout {
+                elapsed: Duration::ZERO,
+                message: e.to_string(),
+            }
+        } else {
+            Self::Request(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_classification() {
+        assert!(matches!(
+            Error::from_status(401, ""),
+            Error::Unauthorized { .. }
+        ));
+        assert!(matches!(
+            Error::from_status(402, "Insufficient credit"),
+            Error::QuotaExceeded { message } if message == "Insufficient credit"
+        ));
+        assert!(matches!(
+            Error::from_status(503, ""),
+            Error::ServerError { status: 503, .. }
+        ));
+        assert!(matches!(Error::from_status(418, ""), Error::Api { .. }));
+    }
+
+    #[test]
+    fn test_bad_request_field() {
+        let body =
+            r#"{"meta":{},"data":null,"error":[{"code":1,"msg":"Invalid engine","ref":"engine"}]}"#;
+        match Error::from_status(400, body) {
+            Error::BadRequest {
+                status,
+                field,
+                message,
+            } => {
+                assert_eq!(status, 400);
+                assert_eq!(field.as_deref(), Some("engine"));
+                assert_eq!(message, "Invalid engine");
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+        assert_eq!(Error::from_status(400, "").status(), Some(400));
+        assert_eq!(Error::from_status(422, "").status(), Some(422));
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(Error::from_status(429, "").is_retryable());
+        assert!(Error::from_status(500, "").is_retryable());
+        assert!(Error::from_status(524, "").is_retryable());
+        assert!(!Error::from_status(401, "").is_retryable());
+        assert!(!Error::from_status(402, "").is_retryable());
+        assert!(!Error::from_status(400, "").is_retryable());
+    }
+
+    #[test]
+    fn test_server_timeout() {
+        let error = Error::from_status(524, "").with_elapsed(Duration::from_secs(100));
+        assert!(matches!(
+            error,
+            Error::ServerTimeout { status: 524, elapsed, .. } if elapsed == Duration::from_secs(100)
+        ));
+        assert_eq!(error.status(), Some(524));
+    }
+
+    #[test]
+    fn test_correlation_id() {
+        let error = Error::from_status(429, "Slow down").with_correlation_id("step-3");
+        assert_eq!(error.correlation_id(), Some("step-3"));
+        assert!(matches!(error.kind(), Error::RateLimited { .. }));
+        assert!(error.is_retryable());
+        assert_eq!(error.status(), Some(429));
+        assert_eq!(
+            error.to_string(),
+            "Rate limited: Slow down (correlation id: step-3)"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! HTTP response headers of interest to API clients
+
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+
+/// Headers checked, in order, for the number of requests left in the rate limit window
+const RATE_LIMIT_REMAINING_HEADERS: &[&str] = &["x-ratelimit-remaining", "ratelimit-remaining"];
+
+/// Headers checked, in order, for the request ID assigned by the server
+const REQUEST_ID_HEADERS: &[&str] = &["x-request-id", "x-kagi-request-id"];
+
+/// Selected headers of an API response
+///
+/// Available on the `meta` of every response, e.g. [`SearchMeta::headers`](crate::SearchMeta::headers),
+/// for throttling and diagnostics. Fields are `None` when the API did not send the header.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResponseHeaders {
+    /// Requests left before the rate limit applies
+    pub rate_limit_remaining: Option<u64>,
+    /// Request ID assigned by the server or a proxy in front of it
+    pub request_id: Option<String>,
+    pub content_type: Option<String>,
+}
+
+impl ResponseHeaders {
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        let first = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| headers.get(*name)?.to_str().ok())
+                .map(str::trim)
+        };
+        Self {
+            rate_limit_remaining: first(RATE_LIMIT_REMAINING_HEADERS)
+                .and_then(|value| value.parse().ok()),
+            request_id: first(REQUEST_ID_HEADERS).map(str::to_string),
+            content_type: first(&[CONTENT_TYPE.as_str()]).map(str::to_string),
+        }
+    }
+}
+
+/// Responses whose `meta` carries [`ResponseHeaders`]
+pub(crate) trait WithHeaders {
+    fn set_headers(&mut self, headers: ResponseHeaders);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fixtures, MockKagi};
+    use wiremock::matchers::path;
+    use wiremock::{Mock, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_response_headers() {
+        let mock = MockKagi::start().await;
+        Mock::given(path("/api/v0/search"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(fixtures::SEARCH, "application/json")
+                    .insert_header("X-RateLimit-Remaining", "41")
+                    .insert_header("X-Request-Id", "req-123"),
+            )
+            .mount(mock.server())
+            .await;
+
+        let response = mock.client().search("rust", None).await.unwrap();
+        assert_eq!(
+            response.meta.headers,
+            ResponseHeaders {
+                rate_limit_remaining: Some(41),
+                request_id: Some("req-123".to_string()),
+                content_type: Some("application/json".to_string()),
+            }
+        );
+
+        let response = mock
+            .client()
+            .fastgpt("rust", crate::FastGptOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(response.meta.headers.rate_limit_remaining, None);
+    }
+}
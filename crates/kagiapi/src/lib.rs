@@ -9,12 +9,13 @@
 //! - <https://help.kagi.com/kagi/api/summarizer.html>
 //! - <https://help.kagi.com/kagi/api/fastgpt.html>
 //! - <https://help.kagi.com/kagi/api/enrich.html>
+//! - <https://kagi.com/smallweb>
 //!
 //!
 //! # Example
 //!
 //! ```no_run
-//! use kagiapi::{KagiClient, SummaryType, SummarizerEngine};
+//! use kagiapi::{KagiClient, SummarizeOptions, SummaryType, SummarizerEngine};
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), kagiapi::Error> {
@@ -31,56 +32,156 @@
 //!     }
 //!
 //!     // Summarize content
-//!     let summary = client.summarize(
-//!         "https://example.com/article",
-//!         Some(SummarizerEngine::Cecil),
-//!         Some(SummaryType::Summary),
-//!         None
-//!     ).await?;
-//!     println!("Summary: {}", summary.output);
+//!     let options = SummarizeOptions {
+//!         engine: Some(SummarizerEngine::Cecil),
+//!         summary_type: Some(SummaryType::Summary),
+//!         ..SummarizeOptions::default()
+//!     };
+//!     let summary = client.summarize("https://example.com/article", options).await?;
+//!     println!("Summary: {}", summary.data.output);
 //!
 //!     Ok(())
 //! }
 //! ```
 
+use futures::{stream, Stream, StreamExt, TryStreamExt};
 use reqwest::Client;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use thiserror::Error;
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+mod builder;
+pub mod canonical;
+mod charset;
+mod chunking;
+mod error;
+mod headers;
+pub mod pricing;
+mod smallweb;
+#[cfg(feature = "testing")]
+pub mod testing;
+
+pub use builder::KagiClientBuilder;
+pub use error::{Error, Result};
+pub use headers::ResponseHeaders;
+use headers::WithHeaders;
+pub use smallweb::{parse_feed, SmallWebEntry};
 
 pub const API_BASE_URL_PREFIX: &str = "https://kagi.com/api";
 
-#[derive(Error, Debug)]
-pub enum Error {
-    #[error("HTTP request failed: {0}")]
-    Request(#[from] reqwest::Error),
-    #[error("API error: {status} - {message}")]
-    Api { status: u16, message: String },
-    #[error("Serialization error: {0}")]
-    Serialization(#[from] serde_json::Error),
-    #[error("Invalid API key")]
-    InvalidApiKey,
-}
+/// Path of the Small Web feed, relative to the base URL prefix
+const SMALLWEB_FEED_PATH: &str = "v1/smallweb/feed/";
+
+/// Header carrying the caller's correlation ID, see [`KagiClient::with_correlation_id`]
+pub const CORRELATION_ID_HEADER: &str = "X-Correlation-ID";
+
+/// Conservative maximum size of text sent to the summarizer in a single request
+pub const MAX_SUMMARIZE_TEXT_BYTES: usize = 64 * 1024;
 
-pub type Result<T> = std::result::Result<T, Error>;
+/// Number of chunks summarized concurrently by [`KagiClient::summarize_text_chunked`]
+const CHUNK_CONCURRENCY: usize = 4;
+
+/// Maximum number of summarize-and-merge rounds for very long text
+const MAX_MERGE_PASSES: usize = 4;
 
 #[derive(Debug, Clone)]
 pub struct KagiClient {
     client: Client,
-    api_key: String,
-    search_api_version: String,
-    summarizer_api_version: String,
-    fastgpt_api_version: String,
-    enrich_api_version: String,
-    base_url_prefix: String,
+    config: Arc<ClientConfig>,
+    correlation_id: Option<Arc<str>>,
+    usage: Arc<Mutex<Usage>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+/// Settings shared by a client and its clones
+#[derive(Debug, Clone)]
+pub(crate) struct ClientConfig {
+    pub(crate) api_key: String,
+    pub(crate) search_api_version: String,
+    pub(crate) summarizer_api_version: String,
+    pub(crate) fastgpt_api_version: String,
+    pub(crate) enrich_api_version: String,
+    pub(crate) base_url_prefix: String,
+}
+
+/// Cumulative API usage recorded by a [`KagiClient`] and all of its clones
+///
+/// Only successful requests are counted, as failed requests are not billed.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Usage {
+    pub search_requests: u64,
+    pub summarizer_requests: u64,
+    pub fastgpt_requests: u64,
+    pub enrich_requests: u64,
+    pub summarizer_tokens: u64,
+    pub fastgpt_tokens: u64,
+    /// Most recent account balance reported by the API, if any
+    pub api_balance: Option<f64>,
+    /// Round-trip time of the most recent request, whether or not it succeeded
+    pub last_latency: Option<Duration>,
+}
+
+impl Usage {
+    /// Total number of successful requests across all endpoints
+    pub fn total_requests(&self) -> u64 {
+        self.search_requests
+            + self.summarizer_requests
+            + self.fastgpt_requests
+            + self.enrich_requests
+    }
+
+    /// Total number of tokens processed across the summarizer and `FastGPT`
+    pub fn total_tokens(&self) -> u64 {
+        self.summarizer_tokens + self.fastgpt_tokens
+    }
+
+    fn update_balance(&mut self, api_balance: Option<f64>) {
+        if api_balance.is_some() {
+            self.api_balance = api_balance;
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum EnrichType {
     Web,
     News,
 }
 
+/// An API endpoint, see [`KagiClient::probe`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endpoint {
+    Search,
+    Summarizer,
+    FastGpt,
+    EnrichWeb,
+    EnrichNews,
+}
+
+impl Endpoint {
+    pub const ALL: [Self; 5] = [
+        Self::Search,
+        Self::Summarizer,
+        Self::FastGpt,
+        Self::EnrichWeb,
+        Self::EnrichNews,
+    ];
+
+    /// Display name of the endpoint
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Search => "Search",
+            Self::Summarizer => "Summarizer",
+            Self::FastGpt => "FastGPT",
+            Self::EnrichWeb => "Web Enrichment",
+            Self::EnrichNews => "News Enrichment",
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SearchResponse {
     pub meta: SearchMeta,
@@ -94,6 +195,8 @@ pub struct SearchMeta {
     pub ms: u64,
     #[serde(default)]
     pub api_balance: Option<f64>,
+    #[serde(skip)]
+    pub headers: ResponseHeaders,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -135,6 +238,8 @@ pub struct SummaryMeta {
     pub node: String,
     pub ms: u64,
     pub api_balance: f64,
+    #[serde(skip)]
+    pub headers: ResponseHeaders,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -142,6 +247,22 @@ pub struct SummaryData {
     pub output: String,
     #[serde(default)]
     pub tokens: Option<u32>,
+    /// Kind of document that was summarized, when the summarizer reports it
+    #[serde(default, alias = "content_type", alias = "type")]
+    pub kind: Option<DocumentKind>,
+}
+
+/// Kind of document summarized by the Universal Summarizer
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentKind {
+    Webpage,
+    Video,
+    Audio,
+    Pdf,
+    /// A kind this version of the crate does not know about
+    #[serde(other)]
+    Other,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -155,6 +276,10 @@ pub struct FastGptMeta {
     pub id: String,
     pub node: String,
     pub ms: u64,
+    #[serde(default)]
+    pub api_balance: Option<f64>,
+    #[serde(skip)]
+    pub headers: ResponseHeaders,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -175,10 +300,53 @@ pub struct FastGptReference {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct EnrichResponse {
     pub meta: SearchMeta,
-    pub data: Vec<SearchResult>,
+    pub data: Vec<EnrichResult>,
+}
+
+/// A result from the web or news Enrichment API
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EnrichResult {
+    #[serde(rename = "t")]
+    pub result_type: i32, // 0 = result, 1 = related searches
+    #[serde(default)]
+    pub rank: Option<i32>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub snippet: Option<String>,
+    /// Publication date of news results
+    #[serde(default)]
+    pub published: Option<String>,
+    #[serde(default)]
+    pub thumbnail: Option<Thumbnail>,
+    #[serde(default)]
+    pub list: Option<Vec<String>>,
+    /// Any further fields the API returns, such as additional publication metadata
+    /// on news results
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy)]
+/// Web and news enrichment results returned by [`KagiClient::enrich_all`]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CombinedEnrichResponse {
+    pub web_meta: SearchMeta,
+    pub news_meta: SearchMeta,
+    /// Web results followed by news results, without duplicate URLs
+    pub data: Vec<LabeledEnrichResult>,
+}
+
+/// An enrichment result labeled with the endpoint it came from
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LabeledEnrichResult {
+    pub enrich_type: EnrichType,
+    #[serde(flatten)]
+    pub result: EnrichResult,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum SummarizerEngine {
     #[default]
@@ -188,7 +356,20 @@ pub enum SummarizerEngine {
     Muriel,
 }
 
-#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy)]
+/// Output of [`KagiClient::summarize_stream`] and [`KagiClient::summarize_text_stream`]
+#[derive(Debug, Clone)]
+pub enum SummaryEvent {
+    /// Summary of one chunk of a long text, available before the final summary
+    Partial {
+        index: usize,
+        total: usize,
+        output: String,
+    },
+    /// The final summary; always the last event of a successful stream
+    Done(SummaryResponse),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum SummaryType {
     #[default]
@@ -196,37 +377,167 @@ pub enum SummaryType {
     Takeaway,
 }
 
+/// Optional parameters for [`KagiClient::search`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Maximum number of results
+    pub limit: Option<u32>,
+}
+
+impl From<Option<u32>> for SearchOptions {
+    fn from(limit: Option<u32>) -> Self {
+        Self { limit }
+    }
+}
+
+impl From<u32> for SearchOptions {
+    fn from(limit: u32) -> Self {
+        Self { limit: Some(limit) }
+    }
+}
+
+/// Optional parameters for [`KagiClient::summarize`] and [`KagiClient::summarize_text`]
+#[derive(Debug, Default, Serialize, Clone, PartialEq, Eq)]
+pub struct SummarizeOptions {
+    /// Summarization engine (defaults to Cecil)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine: Option<SummarizerEngine>,
+    /// Type of summary (defaults to Summary)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary_type: Option<SummaryType>,
+    /// Target language code, e.g. `EN`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_language: Option<String>,
+}
+
+impl From<SummarizerEngine> for SummarizeOptions {
+    fn from(engine: SummarizerEngine) -> Self {
+        Self {
+            engine: Some(engine),
+            ..Self::default()
+        }
+    }
+}
+
+impl From<SummaryType> for SummarizeOptions {
+    fn from(summary_type: SummaryType) -> Self {
+        Self {
+            summary_type: Some(summary_type),
+            ..Self::default()
+        }
+    }
+}
+
+/// Optional parameters for [`KagiClient::fastgpt`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FastGptOptions {
+    /// Whether to allow cached requests & responses (defaults to true)
+    pub cache: Option<bool>,
+    /// Whether to perform web searches to enrich answers (defaults to true)
+    pub web_search: Option<bool>,
+}
+
+/// Borrowed summarizer request body, avoiding a copy of large text inputs
+#[derive(Serialize)]
+struct SummarizeBody<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<&'a str>,
+    #[serde(flatten)]
+    options: &'a SummarizeOptions,
+}
+
+/// Borrowed `FastGPT` request body
+#[derive(Serialize)]
+struct FastGptBody<'a> {
+    query: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    web_search: Option<bool>,
+}
+
+/// Parameters of a Search API request
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SearchRequest {
+    pub query: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+/// Parameters of a Universal Summarizer API request
+///
+/// Exactly one of `url` or `text` should be set.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SummarizeRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engine: Option<SummarizerEngine>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary_type: Option<SummaryType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_language: Option<String>,
+}
+
+/// Parameters of a `FastGPT` API request
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FastGptRequest {
+    pub query: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web_search: Option<bool>,
+}
+
+/// Parameters of an Enrichment API request
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct EnrichRequest {
+    pub query: String,
+    pub enrich_type: EnrichType,
+}
+
 impl KagiClient {
     /// Create a new Kagi API client with the given API key
+    ///
+    /// # Panics
+    ///
+    /// Panics if the HTTP client cannot be initialized; use [`KagiClient::builder`]
+    /// to handle that error instead.
     pub fn new(api_key: impl Into<String>) -> Self {
-        Self {
-            client: Client::new(),
-            api_key: api_key.into(),
-            search_api_version: "v0".to_string(),
-            summarizer_api_version: "v0".to_string(),
-            fastgpt_api_version: "v0".to_string(),
-            enrich_api_version: "v0".to_string(),
-            base_url_prefix: API_BASE_URL_PREFIX.to_string(),
-        }
+        Self::builder(api_key)
+            .build()
+            .expect("failed to initialize HTTP client")
+    }
+
+    /// Start configuring a client with HTTP transport options
+    pub fn builder(api_key: impl Into<String>) -> KagiClientBuilder {
+        KagiClientBuilder::new(api_key)
     }
 
     /// Create a new client with a custom base URL prefix (useful for testing)
+    ///
+    /// # Panics
+    ///
+    /// Panics if the HTTP client cannot be initialized.
     pub fn with_base_url_prefix(
         api_key: impl Into<String>,
         base_url_prefix: impl Into<String>,
     ) -> Self {
-        Self {
-            client: Client::new(),
-            api_key: api_key.into(),
-            search_api_version: "v0".to_string(),
-            summarizer_api_version: "v0".to_string(),
-            fastgpt_api_version: "v0".to_string(),
-            enrich_api_version: "v0".to_string(),
-            base_url_prefix: base_url_prefix.into(),
-        }
+        Self::builder(api_key)
+            .base_url_prefix(base_url_prefix)
+            .build()
+            .expect("failed to initialize HTTP client")
     }
 
     /// Create a new client with specific API versions for each endpoint
+    ///
+    /// # Panics
+    ///
+    /// Panics if the HTTP client cannot be initialized.
     pub fn with_api_versions(
         api_key: impl Into<String>,
         search_version: impl Into<String>,
@@ -234,14 +545,122 @@ impl KagiClient {
         fastgpt_version: impl Into<String>,
         enrich_version: impl Into<String>,
     ) -> Self {
+        Self::builder(api_key)
+            .search_api_version(search_version)
+            .summarizer_api_version(summarizer_version)
+            .fastgpt_api_version(fastgpt_version)
+            .enrich_api_version(enrich_version)
+            .build()
+            .expect("failed to initialize HTTP client")
+    }
+
+    /// Snapshot of the usage recorded since the client was created (or last reset)
+    ///
+    /// Clones of a client share the same counters.
+    pub fn usage(&self) -> Usage {
+        self.usage.lock().map(|usage| *usage).unwrap_or_default()
+    }
+
+    /// Reset the recorded usage counters
+    pub fn reset_usage(&self) {
+        self.record_usage(|usage| *usage = Usage::default());
+    }
+
+    /// A client that sends `correlation_id` with every request
+    ///
+    /// The ID is sent in the [`CORRELATION_ID_HEADER`] header and included in any
+    /// error returned, so that a multi-step workflow can tie Kagi API requests back
+    /// to the step that made them. The returned client shares usage counters and
+    /// the connection pool with `self`.
+    #[must_use]
+    pub fn with_correlation_id(&self, correlation_id: impl Into<String>) -> Self {
         Self {
-            client: Client::new(),
-            api_key: api_key.into(),
-            search_api_version: search_version.into(),
-            summarizer_api_version: summarizer_version.into(),
-            fastgpt_api_version: fastgpt_version.into(),
-            enrich_api_version: enrich_version.into(),
-            base_url_prefix: API_BASE_URL_PREFIX.to_string(),
+            correlation_id: Some(Arc::from(correlation_id.into())),
+            ..self.clone()
+        }
+    }
+
+    /// A client for another API key that shares this client's connection pool
+    ///
+    /// Useful for servers that act on behalf of several accounts. The returned
+    /// client keeps the API versions and base URL, but has its own usage counters
+    /// and no correlation ID.
+    #[must_use]
+    pub fn with_api_key(&self, api_key: impl Into<String>) -> Self {
+        Self {
+            client: self.client.clone(),
+            config: Arc::new(ClientConfig {
+                api_key: api_key.into(),
+                ..ClientConfig::clone(&self.config)
+            }),
+            correlation_id: None,
+            usage: Arc::default(),
+        }
+    }
+
+    /// A client using different API versions that shares this client's connection
+    /// pool and usage counters
+    #[must_use]
+    pub fn with_versions(
+        &self,
+        search_version: impl Into<String>,
+        summarizer_version: impl Into<String>,
+        fastgpt_version: impl Into<String>,
+        enrich_version: impl Into<String>,
+    ) -> Self {
+        Self {
+            config: Arc::new(ClientConfig {
+                search_api_version: search_version.into(),
+                summarizer_api_version: summarizer_version.into(),
+                fastgpt_api_version: fastgpt_version.into(),
+                enrich_api_version: enrich_version.into(),
+                ..ClientConfig::clone(&self.config)
+            }),
+            ..self.clone()
+        }
+    }
+
+    /// The correlation ID sent with every request, if any
+    pub fn correlation_id(&self) -> Option<&str> {
+        self.correlation_id.as_deref()
+    }
+
+    /// Authorize and send a request, then decode its JSON response body
+    async fn send<T: DeserializeOwned + WithHeaders>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T> {
+        let mut request = request.header("Authorization", format!("Bot {}", self.config.api_key));
+        if let Some(correlation_id) = &self.correlation_id {
+            request = request.header(CORRELATION_ID_HEADER, correlation_id.as_ref());
+        }
+
+        let started = Instant::now();
+        let result: Result<T> = async {
+            let response = check_response(request.send().await?).await?;
+            let headers = ResponseHeaders::from_headers(response.headers());
+            let bytes = response.bytes().await?;
+            let mut body: T =
+                serde_json::from_str(&charset::decode(&bytes, headers.content_type.as_deref()))?;
+            body.set_headers(headers);
+            Ok(body)
+        }
+        .await;
+        let elapsed = started.elapsed();
+        self.record_usage(|usage| usage.last_latency = Some(elapsed));
+        let result = result.map_err(|e| e.with_elapsed(elapsed));
+
+        match &self.correlation_id {
+            Some(correlation_id) => {
+                result.map_err(|e| e.with_correlation_id(correlation_id.as_ref()))
+            }
+            None => result,
+        }
+    }
+
+    fn record_usage(&self, f: impl FnOnce(&mut Usage)) {
+        if let Ok(mut usage) = self.usage.lock() {
+            f(&mut usage);
         }
     }
 
@@ -249,46 +668,38 @@ impl KagiClient {
     ///
     /// # Arguments
     /// * `query` - The search query
-    /// * `limit` - Maximum number of results (optional, defaults to 10)
+    /// * `options` - Search options; an `Option<u32>` result limit converts directly
     ///
     /// # Errors
     ///
     /// Returns an error if the API request fails or the response cannot be parsed.
-    pub async fn search(&self, query: &str, limit: Option<u32>) -> Result<SearchResponse> {
+    pub async fn search(
+        &self,
+        query: impl AsRef<str>,
+        options: impl Into<SearchOptions>,
+    ) -> Result<SearchResponse> {
+        let query = query.as_ref();
+        let options = options.into();
+
         // Use URL parameters instead of JSON body for search API
         let mut url = url::Url::parse(&format!(
             "{}/{}/search",
-            self.base_url_prefix, self.search_api_version
+            self.config.base_url_prefix, self.config.search_api_version
         ))
-        .map_err(|_| Error::Api {
-            status: 400,
-            message: "Invalid URL".to_string(),
-        })?;
+        .map_err(|e| Error::InvalidInput(format!("invalid API URL: {e}")))?;
 
         // Add query parameters to URL
         url.query_pairs_mut().append_pair("q", query);
-        if let Some(limit) = limit {
+        if let Some(limit) = options.limit {
             url.query_pairs_mut()
                 .append_pair("limit", &limit.to_string());
         }
 
-        let response = self
-            .client
-            .get(url)
-            .header("Authorization", format!("Bot {}", self.api_key))
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status().as_u16();
-            let text = response.text().await.unwrap_or_default();
-            return Err(Error::Api {
-                status,
-                message: text,
-            });
-        }
-
-        let search_response: SearchResponse = response.json().await?;
+        let search_response: SearchResponse = self.send(self.client.get(url)).await?;
+        self.record_usage(|usage| {
+            usage.search_requests += 1;
+            usage.update_balance(search_response.meta.api_balance);
+        });
         Ok(search_response)
     }
 
@@ -296,202 +707,322 @@ impl KagiClient {
     ///
     /// # Arguments
     /// * `url` - URL of the content to summarize
-    /// * `engine` - Summarization engine to use (optional, defaults to Cecil)
-    /// * `summary_type` - Type of summary (optional, defaults to Summary)
-    /// * `target_language` - Target language code (optional)
+    /// * `options` - Engine, summary type and target language (all optional)
     /// # Errors
     ///
     /// Returns an error if the API request fails or the response cannot be parsed.
     pub async fn summarize(
         &self,
-        url: &str,
-        engine: Option<SummarizerEngine>,
-        summary_type: Option<SummaryType>,
-        target_language: Option<&str>,
-    ) -> Result<SummaryData> {
-        let mut params = serde_json::Map::new();
-        params.insert(
-            "url".to_string(),
-            serde_json::Value::String(url.to_string()),
-        );
-
-        if let Some(engine) = engine {
-            let engine_str = serde_json::to_string(&engine)?
-                .trim_matches('"')
-                .to_string();
-            params.insert("engine".to_string(), serde_json::Value::String(engine_str));
-        }
-
-        if let Some(summary_type) = summary_type {
-            let summary_type_str = serde_json::to_string(&summary_type)?
-                .trim_matches('"')
-                .to_string();
-            params.insert(
-                "summary_type".to_string(),
-                serde_json::Value::String(summary_type_str),
-            );
-        }
-
-        if let Some(target_language) = target_language {
-            params.insert(
-                "target_language".to_string(),
-                serde_json::Value::String(target_language.to_string()),
-            );
-        }
-
-        let url = format!(
-            "{}/{}/summarize",
-            self.base_url_prefix, self.summarizer_api_version
-        );
-        let response = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bot {}", self.api_key))
-            .json(&serde_json::Value::Object(params))
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status().as_u16();
-            let text = response.text().await.unwrap_or_default();
-            return Err(Error::Api {
-                status,
-                message: text,
-            });
-        }
-
-        let summary_response: SummaryResponse = response.json().await?;
-        Ok(summary_response.data)
+        url: impl AsRef<str>,
+        options: impl Into<SummarizeOptions>,
+    ) -> Result<SummaryResponse> {
+        let options = options.into();
+        self.send_summarize(&SummarizeBody {
+            url: Some(url.as_ref()),
+            text: None,
+            options: &options,
+        })
+        .await
     }
 
     /// Summarize text content directly (not from URL)
     ///
     /// # Arguments
     /// * `text` - The text content to summarize
-    /// * `engine` - Summarization engine to use (optional, defaults to Cecil)
-    /// * `summary_type` - Type of summary (optional, defaults to Summary)
-    /// * `target_language` - Target language code (optional)
+    /// * `options` - Engine, summary type and target language (all optional)
     /// # Errors
     ///
     /// Returns an error if the API request fails or the response cannot be parsed.
     pub async fn summarize_text(
         &self,
-        text: &str,
-        engine: Option<SummarizerEngine>,
-        summary_type: Option<SummaryType>,
-        target_language: Option<&str>,
-    ) -> Result<SummaryData> {
-        let mut params = serde_json::Map::new();
-        params.insert(
-            "text".to_string(),
-            serde_json::Value::String(text.to_string()),
-        );
+        text: impl AsRef<str>,
+        options: impl Into<SummarizeOptions>,
+    ) -> Result<SummaryResponse> {
+        let options = options.into();
+        self.send_summarize(&SummarizeBody {
+            url: None,
+            text: Some(text.as_ref()),
+            options: &options,
+        })
+        .await
+    }
 
-        if let Some(engine) = engine {
-            let engine_str = serde_json::to_string(&engine)?
-                .trim_matches('"')
-                .to_string();
-            params.insert("engine".to_string(), serde_json::Value::String(engine_str));
+    /// Summarize text of any length by splitting it into chunks
+    ///
+    /// Text that fits in a single request is summarized directly. Longer text is
+    /// split on paragraph boundaries into chunks of at most
+    /// [`MAX_SUMMARIZE_TEXT_BYTES`], the chunks are summarized concurrently, and the
+    /// concatenated chunk summaries are summarized again into a single output. The
+    /// returned token count covers every request made; the meta is that of the final
+    /// request.
+    ///
+    /// # Arguments
+    /// * `text` - The text content to summarize
+    /// * `options` - Engine, summary type and target language (all optional)
+    /// # Errors
+    ///
+    /// Returns an error if the text is empty or any of the API requests fail.
+    pub async fn summarize_text_chunked(
+        &self,
+        text: impl AsRef<str>,
+        options: impl Into<SummarizeOptions>,
+    ) -> Result<SummaryResponse> {
+        let options = options.into();
+        let mut text = Cow::Borrowed(text.as_ref().trim());
+        if text.is_empty() {
+            return Err(Error::InvalidInput(
+                "text to summarize is empty".to_string(),
+            ));
         }
 
-        if let Some(summary_type) = summary_type {
-            let summary_type_str = serde_json::to_string(&summary_type)?
-                .trim_matches('"')
-                .to_string();
-            params.insert(
-                "summary_type".to_string(),
-                serde_json::Value::String(summary_type_str),
-            );
+        let mut tokens = None;
+        let mut meta = None;
+        for _ in 0..MAX_MERGE_PASSES {
+            if text.len() <= MAX_SUMMARIZE_TEXT_BYTES {
+                let summary = self.summarize_text(text.as_ref(), options.clone()).await?;
+                return Ok(SummaryResponse {
+                    meta: summary.meta,
+                    data: SummaryData {
+                        output: summary.data.output,
+                        tokens: add_tokens(tokens, summary.data.tokens),
+                        kind: summary.data.kind,
+                    },
+                });
+            }
+
+            // Owned chunks keep the returned future `Send` for callers that spawn it
+            let chunks: Vec<String> = chunking::chunk_text(&text, MAX_SUMMARIZE_TEXT_BYTES)
+                .into_iter()
+                .map(str::to_string)
+                .collect();
+            let summaries: Vec<SummaryResponse> = stream::iter(chunks)
+                .map(|chunk| self.summarize_text(chunk, options.clone()))
+                .buffered(CHUNK_CONCURRENCY)
+                .try_collect()
+                .await?;
+
+            let mut merged = String::new();
+            for summary in summaries {
+                tokens = add_tokens(tokens, summary.data.tokens);
+                if !merged.is_empty() {
+                    merged.push_str("\n\n");
+                }
+                merged.push_str(&summary.data.output);
+                meta = Some(summary.meta);
+            }
+            text = Cow::Owned(merged);
         }
 
-        if let Some(target_language) = target_language {
-            params.insert(
-                "target_language".to_string(),
-                serde_json::Value::String(target_language.to_string()),
-            );
+        // The chunk summaries are still too long to merge; return them as they are
+        let meta =
+            meta.ok_or_else(|| Error::InvalidInput("text to summarize is empty".to_string()))?;
+        Ok(SummaryResponse {
+            meta,
+            data: SummaryData {
+                output: text.into_owned(),
+                tokens,
+                kind: None,
+            },
+        })
+    }
+
+    /// Summarize content from a URL as a stream of [`SummaryEvent`]s
+    ///
+    /// The Universal Summarizer returns URL summaries in one piece, so the stream
+    /// yields a single [`SummaryEvent::Done`]. It exists so callers can handle URL
+    /// and text summaries (see [`KagiClient::summarize_text_stream`]) uniformly.
+    pub fn summarize_stream<'a>(
+        &'a self,
+        url: &'a str,
+        options: impl Into<SummarizeOptions>,
+    ) -> impl Stream<Item = Result<SummaryEvent>> + Send + 'a {
+        let options = options.into();
+        stream::once(async move { self.summarize(url, options).await.map(SummaryEvent::Done) })
+    }
+
+    /// Summarize text of any length, yielding chunk summaries as they complete
+    ///
+    /// Text longer than [`MAX_SUMMARIZE_TEXT_BYTES`] is chunked as in
+    /// [`KagiClient::summarize_text_chunked`]; each chunk summary is yielded as a
+    /// [`SummaryEvent::Partial`] in order, followed by the merged
+    /// [`SummaryEvent::Done`]. Short text yields only the final summary.
+    pub fn summarize_text_stream<'a>(
+        &'a self,
+        text: &'a str,
+        options: impl Into<SummarizeOptions>,
+    ) -> impl Stream<Item = Result<SummaryEvent>> + Send + 'a {
+        enum State<'a> {
+            Chunks {
+                summaries: stream::BoxStream<'a, Result<SummaryResponse>>,
+                outputs: Vec<String>,
+                total: usize,
+                tokens: Option<u32>,
+            },
+            Single(&'a str),
+            Empty,
         }
 
+        let options = options.into();
+        let text = text.trim();
+        let state = if text.is_empty() {
+            State::Empty
+        } else if text.len() <= MAX_SUMMARIZE_TEXT_BYTES {
+            State::Single(text)
+        } else {
+            let chunks: Vec<String> = chunking::chunk_text(text, MAX_SUMMARIZE_TEXT_BYTES)
+                .into_iter()
+                .map(str::to_string)
+                .collect();
+            let total = chunks.len();
+            let chunk_options = options.clone();
+            State::Chunks {
+                summaries: stream::iter(chunks)
+                    .map(move |chunk| self.summarize_text(chunk, chunk_options.clone()))
+                    .buffered(CHUNK_CONCURRENCY)
+                    .boxed(),
+                outputs: Vec::with_capacity(total),
+                total,
+                tokens: None,
+            }
+        };
+
+        stream::unfold(Some(state), move |state| {
+            let options = options.clone();
+            async move {
+                match state? {
+                    State::Empty => Some((
+                        Err(Error::InvalidInput(
+                            "text to summarize is empty".to_string(),
+                        )),
+                        None,
+                    )),
+                    State::Single(text) => Some((
+                        self.summarize_text(text, options)
+                            .await
+                            .map(SummaryEvent::Done),
+                        None,
+                    )),
+                    State::Chunks {
+                        mut summaries,
+                        mut outputs,
+                        total,
+                        tokens,
+                    } => match summaries.next().await {
+                        Some(Ok(summary)) => {
+                            let event = SummaryEvent::Partial {
+                                index: outputs.len(),
+                                total,
+                                output: summary.data.output.clone(),
+                            };
+                            outputs.push(summary.data.output);
+                            let state = State::Chunks {
+                                summaries,
+                                outputs,
+                                total,
+                                tokens: add_tokens(tokens, summary.data.tokens),
+                            };
+                            Some((Ok(event), Some(state)))
+                        }
+                        Some(Err(e)) => Some((Err(e), None)),
+                        None => {
+                            let merged = self
+                                .summarize_text_chunked(outputs.join("\n\n"), options)
+                                .await
+                                .map(|mut summary| {
+                                    summary.data.tokens = add_tokens(tokens, summary.data.tokens);
+                                    SummaryEvent::Done(summary)
+                                });
+                            Some((merged, None))
+                        }
+                    },
+                }
+            }
+        })
+    }
+
+    /// Summarize a local text file
+    ///
+    /// Plain text and Markdown files are supported; extract the text from other
+    /// formats (e.g. PDF) first. Files larger than [`MAX_SUMMARIZE_TEXT_BYTES`] are
+    /// summarized in chunks, see [`KagiClient::summarize_text_chunked`].
+    ///
+    /// # Arguments
+    /// * `path` - Path of the file to summarize
+    /// * `options` - Engine, summary type and target language (all optional)
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read as UTF-8 text, or if any of the
+    /// API requests fail.
+    pub async fn summarize_file(
+        &self,
+        path: impl AsRef<Path>,
+        options: impl Into<SummarizeOptions>,
+    ) -> Result<SummaryResponse> {
+        let path = path.as_ref();
+        let bytes = tokio::fs::read(path).await?;
+        let text = String::from_utf8(bytes).map_err(|_| {
+            Error::InvalidInput(format!(
+                "'{}' is not a UTF-8 text file; extract its text before summarizing",
+                path.display()
+            ))
+        })?;
+
+        self.summarize_text_chunked(&text, options).await
+    }
+
+    async fn send_summarize(&self, body: &SummarizeBody<'_>) -> Result<SummaryResponse> {
         let url = format!(
             "{}/{}/summarize",
-            self.base_url_prefix, self.summarizer_api_version
+            self.config.base_url_prefix, self.config.summarizer_api_version
         );
-        let response = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bot {}", self.api_key))
-            .json(&serde_json::Value::Object(params))
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status().as_u16();
-            let text = response.text().await.unwrap_or_default();
-            return Err(Error::Api {
-                status,
-                message: text,
-            });
-        }
-
-        let summary_response: SummaryResponse = response.json().await?;
-        Ok(summary_response.data)
+        let summary_response: SummaryResponse =
+            self.send(self.client.post(&url).json(body)).await?;
+        self.record_usage(|usage| {
+            usage.summarizer_requests += 1;
+            usage.summarizer_tokens += u64::from(summary_response.data.tokens.unwrap_or(0));
+            usage.update_balance(Some(summary_response.meta.api_balance));
+        });
+        Ok(summary_response)
     }
 
     /// Use `FastGPT` to answer a query
     ///
     /// # Arguments
     /// * `query` - The query to be answered
-    /// * `cache` - Whether to allow cached requests & responses (optional, defaults to true)
-    /// * `web_search` - Whether to perform web searches to enrich answers (optional, defaults to true)
+    /// * `options` - Cache and web search toggles (both optional, default to true)
     /// # Errors
     ///
     /// Returns an error if the API request fails or the response cannot be parsed.
     pub async fn fastgpt(
         &self,
-        query: &str,
-        cache: Option<bool>,
-        web_search: Option<bool>,
-    ) -> Result<FastGptData> {
-        let mut params = serde_json::Map::new();
-        params.insert(
-            "query".to_string(),
-            serde_json::Value::String(query.to_string()),
-        );
-
-        if let Some(cache) = cache {
-            params.insert("cache".to_string(), serde_json::Value::Bool(cache));
-        }
-
-        if let Some(web_search) = web_search {
-            params.insert(
-                "web_search".to_string(),
-                serde_json::Value::Bool(web_search),
-            );
-        }
+        query: impl AsRef<str>,
+        options: impl Into<FastGptOptions>,
+    ) -> Result<FastGptResponse> {
+        let options = options.into();
+        let body = FastGptBody {
+            query: query.as_ref(),
+            cache: options.cache,
+            web_search: options.web_search,
+        };
 
         let url = format!(
             "{}/{}/fastgpt",
-            self.base_url_prefix, self.fastgpt_api_version
+            self.config.base_url_prefix, self.config.fastgpt_api_version
         );
-        let response = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bot {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&params)
-            .send()
+        let fastgpt_response: FastGptResponse = self
+            .send(
+                self.client
+                    .post(&url)
+                    .header("Content-Type", "application/json")
+                    .json(&body),
+            )
             .await?;
-
-        if !response.status().is_success() {
-            let status = response.status().as_u16();
-            let text = response.text().await.unwrap_or_default();
-            return Err(Error::Api {
-                status,
-                message: text,
-            });
-        }
-
-        let fastgpt_response: FastGptResponse = response.json().await?;
-        Ok(fastgpt_response.data)
+        self.record_usage(|usage| {
+            usage.fastgpt_requests += 1;
+            usage.fastgpt_tokens += u64::from(fastgpt_response.data.tokens);
+            usage.update_balance(fastgpt_response.meta.api_balance);
+        });
+        Ok(fastgpt_response)
     }
 
     /// Use Kagi's Enrichment API to get non-commercial content
@@ -502,7 +1033,13 @@ impl KagiClient {
     /// # Errors
     ///
     /// Returns an error if the API request fails or the response cannot be parsed.
-    pub async fn enrich(&self, query: &str, enrich_type: EnrichType) -> Result<Vec<SearchResult>> {
+    pub async fn enrich(
+        &self,
+        query: impl AsRef<str>,
+        enrich_type: EnrichType,
+    ) -> Result<EnrichResponse> {
+        let query = query.as_ref();
+
         // Build the URL with query parameters
         let endpoint = match enrich_type {
             EnrichType::Web => "web",
@@ -512,33 +1049,169 @@ impl KagiClient {
         // Construct the URL with parameters
         let mut url = url::Url::parse(&format!(
             "{}/{}/enrich/{}",
-            self.base_url_prefix, self.enrich_api_version, endpoint
+            self.config.base_url_prefix, self.config.enrich_api_version, endpoint
         ))
-        .map_err(|_| Error::Api {
-            status: 400,
-            message: "Invalid URL".to_string(),
-        })?;
+        .map_err(|e| Error::InvalidInput(format!("invalid API URL: {e}")))?;
 
         url.query_pairs_mut().append_pair("q", query);
 
-        let response = self
-            .client
-            .get(url)
-            .header("Authorization", format!("Bot {}", self.api_key))
-            .send()
-            .await?;
+        let enrich_response: EnrichResponse = self.send(self.client.get(url)).await?;
+        self.record_usage(|usage| {
+            usage.enrich_requests += 1;
+            usage.update_balance(enrich_response.meta.api_balance);
+        });
+        Ok(enrich_response)
+    }
+
+    /// Query the web and news Enrichment APIs concurrently
+    ///
+    /// Results are merged into a single list labeled with their source. Web results
+    /// come first; news results whose URL already appeared in the web results are
+    /// dropped.
+    ///
+    /// # Arguments
+    /// * `query` - The search query
+    /// # Errors
+    ///
+    /// Returns an error if either API request fails or a response cannot be parsed.
+    pub async fn enrich_all(&self, query: impl AsRef<str>) -> Result<CombinedEnrichResponse> {
+        let query = query.as_ref();
+        let (web, news) = futures::try_join!(
+            self.enrich(query, EnrichType::Web),
+            self.enrich(query, EnrichType::News)
+        )?;
 
-        if !response.status().is_success() {
-            let status = response.status().as_u16();
-            let text = response.text().await.unwrap_or_default();
-            return Err(Error::Api {
-                status,
-                message: text,
-            });
+        let mut data: Vec<LabeledEnrichResult> =
+            Vec::with_capacity(web.data.len() + news.data.len());
+        for (enrich_type, results) in [(EnrichType::Web, web.data), (EnrichType::News, news.data)] {
+            for result in results {
+                let duplicate =
+                    result.url.is_some() && data.iter().any(|seen| seen.result.url == result.url);
+                if !duplicate {
+                    data.push(LabeledEnrichResult {
+                        enrich_type,
+                        result,
+                    });
+                }
+            }
         }
 
-        let enrich_response: EnrichResponse = response.json().await?;
-        Ok(enrich_response.data)
+        Ok(CombinedEnrichResponse {
+            web_meta: web.meta,
+            news_meta: news.meta,
+            data,
+        })
+    }
+
+    /// Fetch the latest posts of the Kagi Small Web feed, newest first
+    ///
+    /// The feed is public and free, so the request is neither authorized nor
+    /// counted in [`KagiClient::usage`].
+    ///
+    /// # Arguments
+    /// * `limit` - Maximum number of entries to return
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the feed cannot be parsed.
+    pub async fn smallweb_feed(&self, limit: Option<usize>) -> Result<Vec<SmallWebEntry>> {
+        let mut url = url::Url::parse(&format!(
+            "{}/{SMALLWEB_FEED_PATH}",
+            self.config.base_url_prefix
+        ))
+        .map_err(|e| Error::InvalidInput(format!("invalid API URL: {e}")))?;
+        if let Some(limit) = limit {
+            url.query_pairs_mut()
+                .append_pair("limit", &limit.to_string());
+        }
+
+        let mut request = self.client.get(url);
+        if let Some(correlation_id) = &self.correlation_id {
+            request = request.header(CORRELATION_ID_HEADER, correlation_id.as_ref());
+        }
+        let response = check_response(request.send().await?).await?;
+        let headers = ResponseHeaders::from_headers(response.headers());
+        let bytes = response.bytes().await?;
+        let mut entries = parse_feed(&charset::decode(&bytes, headers.content_type.as_deref()))?;
+        if let Some(limit) = limit {
+            entries.truncate(limit);
+        }
+        Ok(entries)
+    }
+
+    /// URL of `endpoint`, with the configured API version
+    pub fn endpoint_url(&self, endpoint: Endpoint) -> String {
+        let config = &self.config;
+        let (version, path) = match endpoint {
+            Endpoint::Search => (&config.search_api_version, "search"),
+            Endpoint::Summarizer => (&config.summarizer_api_version, "summarize"),
+            Endpoint::FastGpt => (&config.fastgpt_api_version, "fastgpt"),
+            Endpoint::EnrichWeb => (&config.enrich_api_version, "enrich/web"),
+            Endpoint::EnrichNews => (&config.enrich_api_version, "enrich/news"),
+        };
+        format!("{}/{version}/{path}", config.base_url_prefix)
+    }
+
+    /// Send `endpoint` a request without the API key, which Kagi rejects without
+    /// billing it, and return the HTTP status of the response
+    ///
+    /// Any response shows the endpoint can be reached; a 404 suggests that the
+    /// configured API version does not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no response arrives.
+    pub async fn probe(&self, endpoint: Endpoint) -> Result<u16> {
+        let url = self.endpoint_url(endpoint);
+        let request = match endpoint {
+            Endpoint::Summarizer | Endpoint::FastGpt => {
+                self.client.post(url).json(&serde_json::json!({}))
+            }
+            _ => self.client.get(url),
+        };
+        Ok(request.send().await?.status().as_u16())
+    }
+}
+
+impl WithHeaders for SearchResponse {
+    fn set_headers(&mut self, headers: ResponseHeaders) {
+        self.meta.headers = headers;
+    }
+}
+
+impl WithHeaders for SummaryResponse {
+    fn set_headers(&mut self, headers: ResponseHeaders) {
+        self.meta.headers = headers;
+    }
+}
+
+impl WithHeaders for FastGptResponse {
+    fn set_headers(&mut self, headers: ResponseHeaders) {
+        self.meta.headers = headers;
+    }
+}
+
+impl WithHeaders for EnrichResponse {
+    fn set_headers(&mut self, headers: ResponseHeaders) {
+        self.meta.headers = headers;
+    }
+}
+
+/// Turn a non-success HTTP response into a classified [`Error`]
+async fn check_response(response: reqwest::Response) -> Result<reqwest::Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status().as_u16();
+    let content_type = ResponseHeaders::from_headers(response.headers()).content_type;
+    let body = response.bytes().await.unwrap_or_default();
+    let text = charset::decode(&body, content_type.as_deref());
+    Err(Error::from_status(status, &text))
+}
+
+fn add_tokens(total: Option<u32>, tokens: Option<u32>) -> Option<u32> {
+    match (total, tokens) {
+        (Some(total), Some(tokens)) => Some(total + tokens),
+        (total, tokens) => total.or(tokens),
     }
 }
 
@@ -549,29 +1222,70 @@ mod tests {
     #[test]
     fn test_client_creation() {
         let client = KagiClient::new("test-key");
-        assert_eq!(client.api_key, "test-key");
-        assert_eq!(client.base_url_prefix, API_BASE_URL_PREFIX);
-        assert_eq!(client.search_api_version, "v0");
-        assert_eq!(client.summarizer_api_version, "v0");
-        assert_eq!(client.fastgpt_api_version, "v0");
-        assert_eq!(client.enrich_api_version, "v0");
+        assert_eq!(client.config.api_key, "test-key");
+        assert_eq!(client.config.base_url_prefix, API_BASE_URL_PREFIX);
+        assert_eq!(client.config.search_api_version, "v0");
+        assert_eq!(client.config.summarizer_api_version, "v0");
+        assert_eq!(client.config.fastgpt_api_version, "v0");
+        assert_eq!(client.config.enrich_api_version, "v0");
     }
 
     #[test]
     fn test_client_with_custom_url() {
         let client = KagiClient::with_base_url_prefix("test-key", "https://custom.api.com");
-        assert_eq!(client.api_key, "test-key");
-        assert_eq!(client.base_url_prefix, "https://custom.api.com");
+        assert_eq!(client.config.api_key, "test-key");
+        assert_eq!(client.config.base_url_prefix, "https://custom.api.com");
     }
 
     #[test]
     fn test_client_with_api_versions() {
         let client = KagiClient::with_api_versions("test-key", "v1", "v2", "v3", "v4");
-        assert_eq!(client.api_key, "test-key");
-        assert_eq!(client.search_api_version, "v1");
-        assert_eq!(client.summarizer_api_version, "v2");
-        assert_eq!(client.fastgpt_api_version, "v3");
-        assert_eq!(client.enrich_api_version, "v4");
+        assert_eq!(client.config.api_key, "test-key");
+        assert_eq!(client.config.search_api_version, "v1");
+        assert_eq!(client.config.summarizer_api_version, "v2");
+        assert_eq!(client.config.fastgpt_api_version, "v3");
+        assert_eq!(client.config.enrich_api_version, "v4");
+    }
+
+    #[test]
+    fn test_derived_clients() {
+        let client = KagiClient::new("tenant-a");
+        client.record_usage(|usage| usage.search_requests += 1);
+
+        let tenant_b = client.with_api_key("tenant-b");
+        assert_eq!(tenant_b.config.api_key, "tenant-b");
+        assert_eq!(tenant_b.usage().search_requests, 0);
+
+        let versioned = client.with_versions("v1", "v1", "v0", "v0");
+        assert_eq!(versioned.config.api_key, "tenant-a");
+        assert_eq!(versioned.config.search_api_version, "v1");
+        assert_eq!(versioned.usage().search_requests, 1);
+        assert_eq!(client.config.search_api_version, "v0");
+    }
+
+    #[test]
+    fn test_usage_shared_between_clones() {
+        let client = KagiClient::new("test-key");
+        let clone = client.clone();
+        assert_eq!(client.usage(), Usage::default());
+
+        clone.record_usage(|usage| {
+            usage.summarizer_requests += 1;
+            usage.summarizer_tokens += 120;
+            usage.update_balance(Some(9.5));
+        });
+        client.record_usage(|usage| {
+            usage.search_requests += 1;
+            usage.update_balance(None);
+        });
+
+        let usage = client.usage();
+        assert_eq!(usage.total_requests(), 2);
+        assert_eq!(usage.total_tokens(), 120);
+        assert_eq!(usage.api_balance, Some(9.5));
+
+        client.reset_usage();
+        assert_eq!(clone.usage(), Usage::default());
     }
 
     #[test]
@@ -585,18 +1299,54 @@ mod tests {
         assert_eq!(json, "\"takeaway\"");
     }
 
+    #[test]
+    fn test_enrich_news_deserialization() {
+        let json = r#"{
+            "meta": {"id": "abc", "node": "us-east", "ms": 42},
+            "data": [{
+                "t": 0,
+                "rank": 1,
+                "url": "https://example.com/story",
+                "title": "Story",
+                "snippet": "A story",
+                "published": "2024-01-01T00:00:00Z",
+                "source": "Example News"
+            }]
+        }"#;
+        let response: EnrichResponse = serde_json::from_str(json).unwrap();
+        let result = &response.data[0];
+        assert_eq!(response.meta.id, "abc");
+        assert_eq!(result.published.as_deref(), Some("2024-01-01T00:00:00Z"));
+        assert_eq!(result.extra["source"], "Example News");
+    }
+
+    #[test]
+    fn test_summary_document_kind() {
+        let json = r#"{
+            "meta": {"id": "abc", "node": "us-east", "ms": 42, "api_balance": 1.0},
+            "data": {"output": "A talk about Rust", "tokens": 10, "kind": "video"}
+        }"#;
+        let response: SummaryResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(response.data.kind, Some(DocumentKind::Video));
+
+        let data: SummaryData =
+            serde_json::from_str(r#"{"output": "x", "content_type": "hologram"}"#).unwrap();
+        assert_eq!(data.kind, Some(DocumentKind::Other));
+
+        let data: SummaryData = serde_json::from_str(r#"{"output": "x"}"#).unwrap();
+        assert_eq!(data.kind, None);
+    }
+
     #[test]
     fn test_fastgpt_params_serialization() {
         // Test that boolean parameters are serialized as JSON booleans, not strings
-        let mut params = serde_json::Map::new();
-        params.insert(
-            "query".to_string(),
-            serde_json::Value::String("test query".to_string()),
-        );
-        params.insert("web_search".to_string(), serde_json::Value::Bool(true));
-        params.insert("cache".to_string(), serde_json::Value::Bool(false));
+        let body = FastGptBody {
+            query: "test query",
+            cache: Some(false),
+            web_search: Some(true),
+        };
 
-        let json = serde_json::to_string(&serde_json::Value::Object(params)).unwrap();
+        let json = serde_json::to_string(&body).unwrap();
 
         // Verify that booleans are not quoted in the JSON
         assert!(json.contains("\"web_search\":true"));
@@ -604,4 +1354,42 @@ mod tests {
         assert!(!json.contains("\"web_search\":\"true\""));
         assert!(!json.contains("\"cache\":\"false\""));
     }
+
+    #[test]
+    fn test_summarize_params_serialization() {
+        let options = SummarizeOptions {
+            engine: Some(SummarizerEngine::Muriel),
+            summary_type: Some(SummaryType::Takeaway),
+            target_language: None,
+        };
+        let body = SummarizeBody {
+            url: Some("https://example.com"),
+            text: None,
+            options: &options,
+        };
+
+        let json = serde_json::to_value(&body).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "url": "https://example.com",
+                "engine": "muriel",
+                "summary_type": "takeaway"
+            })
+        );
+    }
+
+    #[test]
+    fn test_options_conversions() {
+        assert_eq!(SearchOptions::from(Some(5)).limit, Some(5));
+        assert_eq!(SearchOptions::from(None).limit, None);
+        assert_eq!(
+            SummarizeOptions::from(SummarizerEngine::Agnes).engine,
+            Some(SummarizerEngine::Agnes)
+        );
+        assert_eq!(
+            SummarizeOptions::from(SummaryType::Takeaway).summary_type,
+            Some(SummaryType::Takeaway)
+        );
+    }
 }
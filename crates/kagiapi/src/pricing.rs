@@ -0,0 +1,168 @@
+//! Cost estimation for Kagi API requests
+//!
+//! Prices are taken from the public Kagi API documentation and are expressed in USD.
+//! Estimates are upper bounds: when the billed amount depends on the size of a
+//! document that has not been fetched yet, the maximum billable amount is assumed.
+//!
+//! References:
+//! - <https://help.kagi.com/kagi/api/search.html#pricing>
+//! - <https://help.kagi.com/kagi/api/summarizer.html#pricing>
+//! - <https://help.kagi.com/kagi/api/fastgpt.html#pricing>
+//! - <https://help.kagi.com/kagi/api/enrich.html#pricing>
+//!
+//! # Example
+//!
+//! ```
+//! use kagiapi::pricing::estimate_cost;
+//! use kagiapi::{SummarizeRequest, SummarizerEngine};
+//!
+//! let request = SummarizeRequest {
+//!     url: Some("https://example.com/talk.mp4".to_string()),
+//!     engine: Some(SummarizerEngine::Muriel),
+//!     ..SummarizeRequest::default()
+//! };
+//! assert!(estimate_cost(&request) >= 1.0);
+//! ```
+
+use crate::{EnrichRequest, FastGptRequest, SearchRequest, SummarizeRequest, SummarizerEngine};
+
+/// Cost of a single search query ($25 per 1000 queries)
+pub const SEARCH_COST_PER_QUERY: f64 = 0.025;
+
+/// Cost of a single `FastGPT` query ($15 per 1000 queries)
+pub const FASTGPT_COST_PER_QUERY: f64 = 0.015;
+
+/// Cost of a single enrichment query ($2 per 1000 queries)
+///
+/// Enrichment queries that return no results are not billed.
+pub const ENRICH_COST_PER_QUERY: f64 = 0.002;
+
+/// Cost per 1000 tokens processed by the consumer summarizer engines
+pub const SUMMARIZER_COST_PER_1K_TOKENS: f64 = 0.030;
+
+/// Maximum number of tokens billed for a single consumer engine summary
+pub const SUMMARIZER_MAX_BILLED_TOKENS: u64 = 10_000;
+
+/// Flat cost of a single summary produced by the enterprise Muriel engine
+pub const MURIEL_COST_PER_SUMMARY: f64 = 1.0;
+
+/// Rough number of characters per token, used to estimate text summaries
+const CHARS_PER_TOKEN: u64 = 4;
+
+/// A request whose cost can be estimated before it is sent
+pub trait EstimateCost {
+    /// Estimated upper bound of the cost of the request, in USD
+    fn estimate_cost(&self) -> f64;
+}
+
+/// Estimate the cost of a request in USD
+pub fn estimate_cost<R: EstimateCost + ?Sized>(request: &R) -> f64 {
+    request.estimate_cost()
+}
+
+impl EstimateCost for SearchRequest {
+    fn estimate_cost(&self) -> f64 {
+        SEARCH_COST_PER_QUERY
+    }
+}
+
+impl EstimateCost for FastGptRequest {
+    fn estimate_cost(&self) -> f64 {
+        FASTGPT_COST_PER_QUERY
+    }
+}
+
+impl EstimateCost for EnrichRequest {
+    fn estimate_cost(&self) -> f64 {
+        ENRICH_COST_PER_QUERY
+    }
+}
+
+impl EstimateCost for SummarizeRequest {
+    fn estimate_cost(&self) -> f64 {
+        // The size of a URL's document is unknown until Kagi fetches it, so assume the cap
+        let tokens = self
+            .text
+            .as_ref()
+            .map_or(SUMMARIZER_MAX_BILLED_TOKENS, |text| estimate_tokens(text));
+        summary_cost(self.engine.unwrap_or_default(), tokens)
+    }
+}
+
+/// Billed cost of a summary by `engine` of a document of `tokens` tokens, in USD
+///
+/// Consumer engines bill at most [`SUMMARIZER_MAX_BILLED_TOKENS`]; Muriel bills a flat rate.
+pub fn summary_cost(engine: SummarizerEngine, tokens: u64) -> f64 {
+    if engine == SummarizerEngine::Muriel {
+        MURIEL_COST_PER_SUMMARY
+    } else {
+        tokens_cost(tokens.min(SUMMARIZER_MAX_BILLED_TOKENS))
+    }
+}
+
+/// Estimate the number of tokens in a piece of text
+pub fn estimate_tokens(text: &str) -> u64 {
+    let chars = u64::try_from(text.chars().count()).unwrap_or(u64::MAX);
+    chars.div_ceil(CHARS_PER_TOKEN)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn tokens_cost(tokens: u64) -> f64 {
+    tokens as f64 / 1000.0 * SUMMARIZER_COST_PER_1K_TOKENS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flat_rate_costs() {
+        let search = SearchRequest {
+            query: "rust".to_string(),
+            limit: Some(10),
+        };
+        assert!((estimate_cost(&search) - SEARCH_COST_PER_QUERY).abs() < f64::EPSILON);
+
+        let fastgpt = FastGptRequest {
+            query: "rust".to_string(),
+            cache: None,
+            web_search: None,
+        };
+        assert!((estimate_cost(&fastgpt) - FASTGPT_COST_PER_QUERY).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_summarizer_costs() {
+        let muriel = SummarizeRequest {
+            text: Some("short".to_string()),
+            engine: Some(SummarizerEngine::Muriel),
+            ..SummarizeRequest::default()
+        };
+        assert!((estimate_cost(&muriel) - MURIEL_COST_PER_SUMMARY).abs() < f64::EPSILON);
+
+        // 4000 characters is roughly 1000 tokens
+        let text = SummarizeRequest {
+            text: Some("a".repeat(4000)),
+            ..SummarizeRequest::default()
+        };
+        assert!((estimate_cost(&text) - SUMMARIZER_COST_PER_1K_TOKENS).abs() < 1e-9);
+
+        // Unknown document size is estimated at the billing cap
+        let url = SummarizeRequest {
+            url: Some("https://example.com".to_string()),
+            ..SummarizeRequest::default()
+        };
+        assert!((estimate_cost(&url) - 0.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_summary_cost() {
+        assert!((summary_cost(SummarizerEngine::Cecil, 2000) - 0.06).abs() < 1e-9);
+        assert!((summary_cost(SummarizerEngine::Agnes, 50_000) - 0.3).abs() < 1e-9);
+        assert!(summary_cost(SummarizerEngine::Daphne, 0).abs() < f64::EPSILON);
+        assert!(
+            (summary_cost(SummarizerEngine::Muriel, 10) - MURIEL_COST_PER_SUMMARY).abs()
+                < f64::EPSILON
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! Kagi Small Web feed
+//!
+//! The Small Web feed lists recent posts from independent, non-commercial
+//! websites. It is an Atom feed that does not require an API key and is not billed.
+//!
+//! References:
+//! - <https://kagi.com/smallweb>
+//! - <https://github.com/kagisearch/smallweb>
+
+use crate::{Error, Result};
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// A post listed in the Small Web feed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SmallWebEntry {
+    pub title: String,
+    pub url: String,
+    /// Author or website name
+    pub author: Option<String>,
+    /// Publication (or last update) time, as given in the feed
+    pub published: Option<String>,
+    /// Excerpt of the post, when the feed includes one
+    pub summary: Option<String>,
+}
+
+impl SmallWebEntry {
+    /// Whether the title, author, URL or excerpt contains `keyword`, ignoring case
+    pub fn matches(&self, keyword: &str) -> bool {
+        let keyword = keyword.trim().to_lowercase();
+        [
+            Some(&self.title),
+            Some(&self.url),
+            self.author.as_ref(),
+            self.summary.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|field| field.to_lowercase().contains(&keyword))
+    }
+}
+
+/// Parse the entries of an Atom feed, in feed order
+///
+/// Entries without a link are skipped.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidFeed`] if the document is not well-formed XML.
+pub fn parse_feed(xml: &str) -> Result<Vec<SmallWebEntry>> {
+    let mut reader = Reader::from_str(xml);
+    let mut entries = Vec::new();
+    let mut entry: Option<SmallWebEntry> = None;
+    // Local names of the elements enclosing the current position
+    let mut path: Vec<String> = Vec::new();
+    let mut text = String::new();
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| Error::InvalidFeed(format!("{e} at byte {}", reader.error_position())))?;
+        match event {
+            Event::Start(element) => {
+                let name = local_name(&element);
+                if name == "entry" {
+                    entry = Some(SmallWebEntry::default());
+                } else if name == "link" {
+                    set_link(entry.as_mut(), &element)?;
+                }
+                path.push(name);
+                text.clear();
+            }
+            Event::Empty(element) if local_name(&element) == "link" => {
+                set_link(entry.as_mut(), &element)?;
+            }
+            Event::Text(content) => {
+                let content = content
+                    .decode()
+                    .map_err(|e| Error::InvalidFeed(e.to_string()))?;
+                text.push_str(&content);
+            }
+            Event::CData(content) => {
+                text.push_str(&String::from_utf8_lossy(&content));
+            }
+            Event::GeneralRef(reference) => {
+                if let Some(c) = reference
+                    .resolve_char_ref()
+                    .map_err(|e| Error::InvalidFeed(e.to_string()))?
+                {
+                    text.push(c);
+                } else {
+                    let name = reference
+                        .decode()
+                        .map_err(|e| Error::InvalidFeed(e.to_string()))?;
+                    text.push_str(resolve_predefined_entity(&name).unwrap_or_default());
+                }
+            }
+            Event::End(_) => {
+                let name = path.pop().unwrap_or_default();
+                let parent = path.last().map(String::as_str);
+                if let Some(current) = entry.as_mut() {
+                    let value = text.trim().to_string();
+                    match (parent, name.as_str()) {
+                        (Some("entry"), "title") => current.title = value,
+                        // Prefer the publication time over the last update
+                        (Some("entry"), "updated" | "published")
+                            if current.published.is_none() || name == "published" =>
+                        {
+                            current.published = Some(value).filter(|v| !v.is_empty());
+                        }
+                        (Some("entry"), "summary" | "content") if current.summary.is_none() => {
+                            current.summary = Some(value).filter(|v| !v.is_empty());
+                        }
+                        (Some("author"), "name") => {
+                            current.author = Some(value).filter(|v| !v.is_empty());
+                        }
+                        (_, "entry") => {
+                            if let Some(done) = entry.take().filter(|e| !e.url.is_empty()) {
+                                entries.push(done);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                text.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(entries)
+}
+
+fn local_name(element: &BytesStart<'_>) -> String {
+    String::from_utf8_lossy(element.local_name().as_ref()).into_owned()
+}
+
+/// Record the URL of an entry's `<link>`, preferring the alternate link
+fn set_link(entry: Option<&mut SmallWebEntry>, element: &BytesStart<'_>) -> Result<()> {
+    let Some(entry) = entry else {
+        return Ok(());
+    };
+    let mut href = None;
+    let mut rel = None;
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(|e| Error::InvalidFeed(e.to_string()))?;
+        let value = attribute
+            .unescape_value()
+            .map_err(|e| Error::InvalidFeed(e.to_string()))?
+            .into_owned();
+        match attribute.key.local_name().as_ref() {
+            b"href" => href = Some(value),
+            b"rel" => rel = Some(value),
+            _ => {}
+        }
+    }
+    let alternate = rel.as_deref().is_none_or(|rel| rel == "alternate");
+    if let Some(href) = href {
+        if entry.url.is_empty() || alternate {
+            entry.url = href;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures;
+
+    #[test]
+    fn test_parse_feed() {
+        let entries = parse_feed(fixtures::SMALLWEB).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[0],
+            SmallWebEntry {
+                title: "Writing a tiny async runtime in Rust".to_string(),
+                url: "https://example.org/posts/tiny-runtime".to_string(),
+                author: Some("Ferris & Friends".to_string()),
+                published: Some("2024-09-20T08:00:00Z".to_string()),
+                summary: Some("Futures, wakers & executors from scratch.".to_string()),
+            }
+        );
+        assert_eq!(entries[1].url, "https://garden.example.net/sourdough");
+        assert_eq!(entries[1].summary, None);
+        assert_eq!(entries[2].title, "Why I still write Rust <by hand>");
+        assert_eq!(
+            entries[2].published.as_deref(),
+            Some("2024-09-18T12:00:00Z")
+        );
+
+        assert!(entries[0].matches("RUST"));
+        assert!(entries[0].matches("wakers"));
+        assert!(!entries[1].matches("rust"));
+
+        assert!(matches!(
+            parse_feed("<feed><entry></feed>"),
+            Err(Error::InvalidFeed(_))
+        ));
+    }
+}
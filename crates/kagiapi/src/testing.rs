@@ -0,0 +1,276 @@
+//! Mock Kagi API for integration tests
+//!
+//! Enabled with the `testing` feature. [`MockKagi`] runs a local HTTP server
+//! preloaded with realistic responses for every endpoint, so code built on
+//! [`KagiClient`] can be tested without an API key or network access.
+//!
+//! ```
+//! use kagiapi::testing::MockKagi;
+//! use kagiapi::SummarizeOptions;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), kagiapi::Error> {
+//!     let mock = MockKagi::start().await;
+//!     let client = mock.client();
+//!
+//!     let results = client.search("rust programming", Some(5)).await?;
+//!     assert_eq!(results.data[0].title.as_deref(), Some("The Rust Programming Language"));
+//!
+//!     let summary = client
+//!         .summarize("https://www.rust-lang.org/", SummarizeOptions::default())
+//!         .await?;
+//!     assert!(summary.data.output.contains("Rust"));
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Fixtures are mounted with a low priority, so mocks added through
+//! [`MockKagi::server`] take precedence, e.g. to simulate errors.
+
+use crate::KagiClient;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Canned response bodies in the format returned by the Kagi API
+pub mod fixtures {
+    pub const SEARCH: &str = r#"{
+  "meta": {"id": "mock-search-1", "node": "mock", "ms": 12, "api_balance": 9.975},
+  "data": [
+    {
+      "t": 0,
+      "rank": 1,
+      "url": "https://www.rust-lang.org/",
+      "title": "The Rust Programming Language",
+      "snippet": "A language empowering everyone to build reliable and efficient software.",
+      "published": "2024-01-01T00:00:00Z"
+    },
+    {
+      "t": 0,
+      "rank": 2,
+      "url": "https://doc.rust-lang.org/book/",
+      "title": "The Rust Programming Language - The Book",
+      "snippet": "An introductory book about Rust."
+    },
+    {"t": 1, "list": ["rust tutorial", "rust vs go", "rust async"]}
+  ]
+}"#;
+
+    pub const SUMMARIZE: &str = r#"{
+  "meta": {"id": "mock-summarize-1", "node": "mock", "ms": 840, "api_balance": 9.945},
+  "data": {
+    "output": "Rust is a systems programming language focused on safety, speed and concurrency.",
+    "tokens": 1024
+  }
+}"#;
+
+    pub const FASTGPT: &str = r#"{
+  "meta": {"id": "mock-fastgpt-1", "node": "mock", "ms": 1520, "api_balance": 9.930},
+  "data": {
+    "output": "Rust 1.0 was released on May 15, 2015 [1].",
+    "tokens": 812,
+    "references": [
+      {
+        "title": "Announcing Rust 1.0",
+        "snippet": "Today we are very proud to announce the 1.0 release of Rust.",
+        "url": "https://blog.rust-lang.org/2015/05/15/Rust-1.0.html"
+      }
+    ]
+  }
+}"#;
+
+    pub const ENRICH_WEB: &str = r#"{
+  "meta": {"id": "mock-enrich-web-1", "node": "mock", "ms": 30, "api_balance": 9.928},
+  "data": [
+    {
+      "t": 0,
+      "rank": 1,
+      "url": "https://fasterthanli.me/articles/a-half-hour-to-learn-rust",
+      "title": "A half-hour to learn Rust",
+      "snippet": "In order to increase fluency in a programming language, one has to read a lot of it."
+    }
+  ]
+}"#;
+
+    pub const ENRICH_NEWS: &str = r#"{
+  "meta": {"id": "mock-enrich-news-1", "node": "mock", "ms": 28, "api_balance": 9.926},
+  "data": [
+    {
+      "t": 0,
+      "rank": 1,
+      "url": "https://lwn.net/Articles/rust-in-the-kernel/",
+      "title": "Rust in the kernel",
+      "snippet": "The state of Rust support in the Linux kernel.",
+      "published": "2024-09-18T00:00:00Z"
+    }
+  ]
+}"#;
+
+    pub const SMALLWEB: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Kagi Small Web</title>
+  <link href="https://kagi.com/smallweb" rel="alternate"/>
+  <updated>2024-09-20T08:00:00Z</updated>
+  <entry>
+    <title>Writing a tiny async runtime in Rust</title>
+    <link href="https://example.org/posts/tiny-runtime"/>
+    <id>https://example.org/posts/tiny-runtime</id>
+    <updated>2024-09-20T08:00:00Z</updated>
+    <author><name>Ferris &amp; Friends</name></author>
+    <summary>Futures, wakers &#38; executors from scratch.</summary>
+  </entry>
+  <entry>
+    <title>My sourdough starter, one year on</title>
+    <link rel="self" href="https://garden.example.net/feed.xml"/>
+    <link rel="alternate" href="https://garden.example.net/sourdough"/>
+    <updated>2024-09-19T17:30:00Z</updated>
+    <author><name>Digital Garden</name></author>
+  </entry>
+  <entry>
+    <title>Untitled draft</title>
+  </entry>
+  <entry>
+    <title><![CDATA[Why I still write Rust <by hand>]]></title>
+    <link href="https://blog.example.com/rust-by-hand"/>
+    <published>2024-09-18T12:00:00Z</published>
+    <updated>2024-09-19T09:00:00Z</updated>
+  </entry>
+</feed>"#;
+}
+
+/// Priority of the preloaded fixtures; lower than the wiremock default of 5
+const FIXTURE_PRIORITY: u8 = 10;
+
+/// A local mock of the Kagi API
+pub struct MockKagi {
+    server: MockServer,
+}
+
+impl MockKagi {
+    /// Start a mock server with fixtures mounted for every endpoint
+    pub async fn start() -> Self {
+        let server = MockServer::start().await;
+        let json = "application/json";
+        let endpoints = [
+            ("GET", "/api/v0/search", fixtures::SEARCH, json),
+            ("POST", "/api/v0/summarize", fixtures::SUMMARIZE, json),
+            ("POST", "/api/v0/fastgpt", fixtures::FASTGPT, json),
+            ("GET", "/api/v0/enrich/web", fixtures::ENRICH_WEB, json),
+            ("GET", "/api/v0/enrich/news", fixtures::ENRICH_NEWS, json),
+            (
+                "GET",
+                "/api/v1/smallweb/feed/",
+                fixtures::SMALLWEB,
+                "application/atom+xml",
+            ),
+        ];
+        for (http_method, endpoint, body, content_type) in endpoints {
+            Mock::given(method(http_method))
+                .and(path(endpoint))
+                .respond_with(ResponseTemplate::new(200).set_body_raw(body, content_type))
+                .with_priority(FIXTURE_PRIORITY)
+                .mount(&server)
+                .await;
+        }
+        Self { server }
+    }
+
+    /// The underlying server, for mounting extra mocks or inspecting requests
+    pub fn server(&self) -> &MockServer {
+        &self.server
+    }
+
+    /// Base URL prefix to pass to [`KagiClient::with_base_url_prefix`]
+    pub fn base_url_prefix(&self) -> String {
+        format!("{}/api", self.server.uri())
+    }
+
+    /// A client pointed at the mock server
+    pub fn client(&self) -> KagiClient {
+        KagiClient::with_base_url_prefix("mock-api-key", self.base_url_prefix())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Endpoint, EnrichType, Error, FastGptOptions};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_fixtures_cover_every_endpoint() {
+        let mock = MockKagi::start().await;
+        let client = mock.client();
+
+        assert_eq!(client.search("rust", None).await.unwrap().data.len(), 3);
+        assert_eq!(
+            client
+                .fastgpt("when was rust 1.0 released", FastGptOptions::default())
+                .await
+                .unwrap()
+                .data
+                .references
+                .len(),
+            1
+        );
+        assert!(client.enrich("rust", EnrichType::Web).await.is_ok());
+        assert!(client.enrich("rust", EnrichType::News).await.is_ok());
+        assert_eq!(client.usage().total_requests(), 4);
+        assert!(client.usage().last_latency.is_some());
+        assert_eq!(client.smallweb_feed(None).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_probe() {
+        let mock = MockKagi::start().await;
+        let client = mock.client();
+        assert_eq!(
+            client.endpoint_url(Endpoint::EnrichNews),
+            format!("{}/v0/enrich/news", mock.base_url_prefix())
+        );
+        for endpoint in Endpoint::ALL {
+            assert_eq!(client.probe(endpoint).await.unwrap(), 200, "{endpoint:?}");
+        }
+        let requests = mock.server().received_requests().await.unwrap();
+        assert!(requests
+            .iter()
+            .all(|request| !request.headers.contains_key("authorization")));
+        assert_eq!(client.usage().total_requests(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_custom_mocks_take_precedence() {
+        let mock = MockKagi::start().await;
+        Mock::given(path("/api/v0/search"))
+            .respond_with(ResponseTemplate::new(429).set_body_string("Slow down"))
+            .mount(mock.server())
+            .await;
+
+        let error = mock.client().search("rust", None).await.unwrap_err();
+        assert!(matches!(error, Error::RateLimited { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_read_timeout() {
+        let mock = MockKagi::start().await;
+        Mock::given(path("/api/v0/search"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(fixtures::SEARCH, "application/json")
+                    .set_delay(Duration::from_millis(500)),
+            )
+            .mount(mock.server())
+            .await;
+
+        let client = KagiClient::builder("mock-api-key")
+            .base_url_prefix(mock.base_url_prefix())
+            .timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        let error = client.search("rust", None).await.unwrap_err();
+        assert!(
+            matches!(error, Error::ReadTimeout { elapsed, .. } if elapsed >= Duration::from_millis(50)),
+            "unexpected error: {error:?}"
+        );
+        assert!(error.is_retryable());
+    }
+}
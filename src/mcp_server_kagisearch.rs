@@ -9,6 +9,34 @@ use zed_extension_api::{
 const REPO_NAME: &str = "jmylchreest/kagimcp-zed";
 const BINARY_NAME: &str = "kagi-mcp-server";
 
+/// Tools provided by the server, with the description shown in the configuration panel
+const TOOLS: &[(&str, &str)] = &[
+    ("kagi_search_fetch", "Kagi web search results"),
+    (
+        "kagi_summarizer",
+        "Summaries of web pages, videos and documents",
+    ),
+    ("kagi_summarizer_text", "Summaries of pasted text"),
+    (
+        "kagi_research",
+        "Briefings summarizing the top search results",
+    ),
+    ("kagi_unfurl", "Title and one-line description of a link"),
+    ("kagi_fastgpt", "AI-generated answers with references"),
+    (
+        "kagi_fastgpt_followup",
+        "Follow-up questions about earlier FastGPT answers",
+    ),
+    (
+        "kagi_enrich",
+        "Non-commercial \"small web\" content and non-mainstream news",
+    ),
+    (
+        "kagi_smallweb_digest",
+        "Summaries of recent Small Web posts mentioning a keyword",
+    ),
+];
+
 #[derive(Debug, Deserialize, JsonSchema)]
 #[allow(clippy::struct_field_names)]
 struct KagiContextServerSettings {
@@ -23,6 +51,12 @@ struct KagiContextServerSettings {
     kagi_fastgpt_api_version: String,
     #[serde(default = "default_enrich_api_version")]
     kagi_enrich_api_version: String,
+    /// Default language of summaries and `FastGPT` answers, e.g. `"DE"`
+    #[serde(default)]
+    kagi_output_language: Option<String>,
+    /// Tools to hide from the assistant, e.g. `["kagi_fastgpt"]`
+    #[serde(default)]
+    kagi_disabled_tools: Vec<String>,
 }
 
 // Default API versions
@@ -171,6 +205,17 @@ impl zed::Extension for KagiModelContextExtension {
             settings.kagi_enrich_api_version,
         ));
 
+        if let Some(language) = settings.kagi_output_language {
+            env.push(("KAGI_OUTPUT_LANGUAGE".into(), language));
+        }
+
+        if !settings.kagi_disabled_tools.is_empty() {
+            env.push((
+                "KAGI_DISABLED_TOOLS".into(),
+                settings.kagi_disabled_tools.join(","),
+            ));
+        }
+
         Ok(Command {
             command: self.context_server_binary_path(context_server_id)?,
             args: vec![],
@@ -181,10 +226,21 @@ impl zed::Extension for KagiModelContextExtension {
     fn context_server_configuration(
         &mut self,
         _context_server_id: &ContextServerId,
-        _project: &Project,
+        project: &Project,
     ) -> Result<Option<ContextServerConfiguration>> {
-        let installation_instructions =
-            include_str!("../configuration/installation_instructions.md").to_string();
+        // Read the toggles leniently so the panel still renders before the API key is set
+        let disabled_tools: Vec<String> = ContextServerSettings::for_project("kagimcp", project)
+            .ok()
+            .and_then(|settings| settings.settings)
+            .and_then(|settings| settings.get("kagi_disabled_tools").cloned())
+            .and_then(|tools| serde_json::from_value(tools).ok())
+            .unwrap_or_default();
+
+        let installation_instructions = format!(
+            "{}\n{}",
+            include_str!("../configuration/installation_instructions.md"),
+            tool_list_instructions(&disabled_tools)
+        );
         let default_settings = include_str!("../configuration/default_settings.jsonc").to_string();
         let settings_schema =
             serde_json::to_string(&schemars::schema_for!(KagiContextServerSettings))
@@ -198,4 +254,30 @@ impl zed::Extension for KagiModelContextExtension {
     }
 }
 
+/// Markdown listing which tools the assistant will be able to use
+fn tool_list_instructions(disabled_tools: &[String]) -> String {
+    let (enabled, disabled): (Vec<_>, Vec<_>) = TOOLS
+        .iter()
+        .partition(|(name, _)| !disabled_tools.iter().any(|tool| tool == name));
+
+    let mut instructions = String::from("### Enabled tools\n\n");
+    if enabled.is_empty() {
+        instructions.push_str("None - every tool is listed in `kagi_disabled_tools`.\n");
+    }
+    for (name, description) in &enabled {
+        instructions.push_str(&format!("- `{name}`: {description}\n"));
+    }
+    if !disabled.is_empty() {
+        let names: Vec<_> = disabled
+            .iter()
+            .map(|(name, _)| format!("`{name}`"))
+            .collect();
+        instructions.push_str(&format!(
+            "\nDisabled by `kagi_disabled_tools`: {}\n",
+            names.join(", ")
+        ));
+    }
+    instructions
+}
+
 zed::register_extension!(KagiModelContextExtension);